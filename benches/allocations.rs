@@ -0,0 +1,123 @@
+use criterion::*;
+use criterion::measurement::{Measurement, ValueFormatter};
+use apocalypse::{Hell, Demon};
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+// Allocator that counts allocations from all threads, broker and demons included
+struct CountingAllocator;
+
+static ALLOCATIONS: AtomicUsize = AtomicUsize::new(0);
+
+unsafe impl GlobalAlloc for CountingAllocator {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        ALLOCATIONS.fetch_add(1, Ordering::Relaxed);
+        System.alloc(layout)
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        System.dealloc(ptr, layout)
+    }
+}
+
+#[global_allocator]
+static GLOBAL: CountingAllocator = CountingAllocator;
+
+// Measurement of allocations, instead of time
+struct Allocations;
+
+impl Measurement for Allocations {
+    type Intermediate = usize;
+    type Value = usize;
+
+    fn start(&self) -> Self::Intermediate {
+        ALLOCATIONS.load(Ordering::Relaxed)
+    }
+
+    fn end(&self, start: Self::Intermediate) -> Self::Value {
+        ALLOCATIONS.load(Ordering::Relaxed) - start
+    }
+
+    fn add(&self, v1: &Self::Value, v2: &Self::Value) -> Self::Value {
+        v1 + v2
+    }
+
+    fn zero(&self) -> Self::Value {
+        0
+    }
+
+    fn to_f64(&self, value: &Self::Value) -> f64 {
+        *value as f64
+    }
+
+    fn formatter(&self) -> &dyn ValueFormatter {
+        &AllocationsFormatter
+    }
+}
+
+struct AllocationsFormatter;
+
+impl ValueFormatter for AllocationsFormatter {
+    fn scale_values(&self, _typical_value: f64, _values: &mut [f64]) -> &'static str {
+        "allocs"
+    }
+
+    fn scale_throughputs(&self, _typical_value: f64, throughput: &Throughput, values: &mut [f64]) -> &'static str {
+        if let Throughput::Elements(elements) = throughput {
+            for value in values {
+                *value /= *elements as f64;
+            }
+        }
+        "allocs/msg"
+    }
+
+    fn scale_for_machines(&self, _values: &mut [f64]) -> &'static str {
+        "allocs"
+    }
+}
+
+// Demon with non zero-sized messages, which need to be boxed
+struct Echo {}
+
+impl Demon for Echo {
+    type Input = u64;
+    type Output = u64;
+    async fn handle(&mut self, message: Self::Input) -> Self::Output {
+        message
+    }
+}
+
+fn bench(c: &mut Criterion<Allocations>) {
+    let rt = tokio::runtime::Runtime::new().unwrap();
+    let (gate, location) = rt.block_on(async {
+        let (gate, _) = Hell::new().ignite().await.unwrap();
+        let location = gate.spawn(Echo{}).await.unwrap();
+        (gate, location)
+    });
+
+    let mut group = c.benchmark_group("Allocations per message");
+    group.throughput(Throughput::Elements(1));
+    group.bench_function("send", |b| {
+        b.to_async(&rt).iter(|| async {
+            gate.send(&location, 1).await.unwrap();
+        });
+    });
+    group.bench_function("send_and_ignore", |b| {
+        b.to_async(&rt).iter(|| async {
+            gate.send_and_ignore(&location, 1).await.unwrap();
+        });
+    });
+    group.finish();
+}
+
+// Allocation counts barely vary between samples, which the plots cannot deal with
+fn allocations() -> Criterion<Allocations> {
+    Criterion::default().with_measurement(Allocations).without_plots()
+}
+
+criterion_group!{
+    name = benches;
+    config = allocations();
+    targets = bench
+}
+criterion_main!(benches);
@@ -0,0 +1,42 @@
+use criterion::*;
+use apocalypse::{Hell, Demon, Gate};
+use std::time::Duration;
+
+// Short-lived demon, as in one demon per request
+struct Request;
+
+impl Demon for Request {
+    type Input = ();
+    type Output = ();
+    async fn handle(&mut self, _message: Self::Input) -> Self::Output {}
+}
+
+// Spawns a demon and vanquishes it right away, a given number of times
+async fn churn(gate: &Gate, number: usize) {
+    for _ in 0..number {
+        let location = gate.spawn(Request).await.unwrap();
+        gate.vanquish(&location).await.unwrap();
+    }
+}
+
+fn bench(c: &mut Criterion) {
+    let rt = tokio::runtime::Runtime::new().unwrap();
+    let (gate, timed_gate) = rt.block_on(async {
+        let (gate, _) = Hell::new().ignite().await.unwrap();
+        // With a timeout, each vanquish also arms a killswitch
+        let (timed_gate, _) = Hell::builder().timeout(Duration::from_secs(5)).build().ignite().await.unwrap();
+        (gate, timed_gate)
+    });
+
+    for number in [100, 1_000, 10_000] {
+        let mut group = c.benchmark_group(format!("Spawn and vanquish {} demons", number));
+        group.throughput(Throughput::Elements(number as u64));
+        group.sample_size(10);
+        group.bench_function("without timeout", |b| b.to_async(&rt).iter(|| churn(&gate, number)));
+        group.bench_function("with timeout", |b| b.to_async(&rt).iter(|| churn(&timed_gate, number)));
+        group.finish();
+    }
+}
+
+criterion_group!(benches, bench);
+criterion_main!(benches);
@@ -0,0 +1,69 @@
+use criterion::*;
+use apocalypse::{Hell, Demon};
+use std::time::{Duration, Instant};
+
+// Stateless worker, as in a fan-out over many connections
+#[derive(Default)]
+struct Worker;
+
+impl Demon for Worker {
+    type Input = ();
+    type Output = ();
+    async fn handle(&mut self, _message: Self::Input) -> Self::Output {}
+}
+
+fn bench(c: &mut Criterion) {
+    let rt = tokio::runtime::Runtime::new().unwrap();
+    let gate = rt.block_on(async {
+        let (gate, _) = Hell::new().ignite().await.unwrap();
+        gate
+    });
+
+    for number in [100, 1_000, 10_000] {
+        let mut group = c.benchmark_group(format!("Spawn {} zero-sized demons", number));
+        group.throughput(Throughput::Elements(number as u64));
+        group.sample_size(10);
+        // Only the spawns are measured, the demons are vanquished after each iteration
+        group.bench_function("spawn loop", |b| {
+            b.to_async(&rt).iter_custom(|iters| {
+                let gate = gate.clone();
+                async move {
+                    let mut elapsed = Duration::ZERO;
+                    for _ in 0..iters {
+                        let start = Instant::now();
+                        let mut locations = Vec::with_capacity(number);
+                        for _ in 0..number {
+                            locations.push(gate.spawn(Worker).await.unwrap());
+                        }
+                        elapsed += start.elapsed();
+                        for location in locations {
+                            gate.vanquish_and_ignore(&location).await.unwrap();
+                        }
+                    }
+                    elapsed
+                }
+            });
+        });
+        group.bench_function("spawn_many_zst", |b| {
+            b.to_async(&rt).iter_custom(|iters| {
+                let gate = gate.clone();
+                async move {
+                    let mut elapsed = Duration::ZERO;
+                    for _ in 0..iters {
+                        let start = Instant::now();
+                        let locations = gate.spawn_many_zst::<Worker, _, _>(number).await.unwrap();
+                        elapsed += start.elapsed();
+                        for location in locations {
+                            gate.vanquish_and_ignore(&location).await.unwrap();
+                        }
+                    }
+                    elapsed
+                }
+            });
+        });
+        group.finish();
+    }
+}
+
+criterion_group!(benches, bench);
+criterion_main!(benches);
@@ -34,11 +34,8 @@ impl Demon for EchoBot {
 #[tokio::main]
 async fn main() {
     SimpleLogger::new().with_level(log::LevelFilter::Debug).init().unwrap();
-    // We create one demon
-    let mut counter = 0;
-    let echo_bot_factory = || {
-        let id = counter;
-        counter += 1;
+    // Each replica receives its own index
+    let echo_bot_factory = |id| {
         EchoBot {
             id
         }
@@ -54,7 +51,7 @@ async fn main() {
         };
         
         // We spawn the demon in the running hell through the gate
-        let location = match gate.spawn_multiple(echo_bot_factory, 3).await {
+        let location = match gate.spawn_multiple_indexed(echo_bot_factory, 3).await {
             Ok(v) => v,
             Err(e) => panic!("Could not spawn the demon, {}", e)
         };
@@ -0,0 +1,106 @@
+use self::misc::SimpleLogger;
+mod misc;
+
+use apocalypse::{Hell, Demon, Gate, Location, WiringBuilder, Wire};
+
+// Same system as in the many example, wired through a builder
+struct ReplaceBot;
+
+// Demon implementation for the replace bot
+impl Demon for ReplaceBot {
+    type Input = String;
+    type Output = String;
+    async fn handle(&mut self, message: Self::Input) -> Self::Output {
+        message.replace("a", "e").replace("o", "e")
+    }
+}
+
+// Human demon that emphasizes a message
+struct EmphasisBot;
+
+// Demon implementation for the emphasis bot
+impl Demon for EmphasisBot {
+    type Input = String;
+    type Output = String;
+    async fn handle(&mut self, message: Self::Input) -> Self::Output {
+        message + "!!!"
+    }
+}
+
+// Human demon that chains both bots, whose locations arrive through the builder
+struct NiceStringBot {
+    gate: Gate,
+    rb_location: Option<Location<ReplaceBot>>,
+    eb_location: Option<Location<EmphasisBot>>
+}
+
+impl NiceStringBot {
+    fn new(gate: Gate) -> NiceStringBot {
+        NiceStringBot{gate, rb_location: None, eb_location: None}
+    }
+}
+
+// Demon implementation for the nice string bot, which requires the location of both bots
+impl Demon for NiceStringBot {
+    type Input = String;
+    type Output = String;
+    async fn handle(&mut self, message: Self::Input) -> Self::Output {
+        let (rb_location, eb_location) = match (&self.rb_location, &self.eb_location) {
+            (Some(rb_location), Some(eb_location)) => (rb_location, eb_location),
+            _ => panic!("The nice string bot was not wired")
+        };
+        let replaced = self.gate.send(rb_location, message).await.unwrap();
+        self.gate.send(eb_location, replaced).await.unwrap()
+    }
+}
+
+// The nice string bot talks to both bots, and to nothing else
+impl Wire<ReplaceBot> for NiceStringBot {
+    fn wire(&mut self, producer: Location<ReplaceBot>) {
+        self.rb_location = Some(producer);
+    }
+}
+
+impl Wire<EmphasisBot> for NiceStringBot {
+    fn wire(&mut self, producer: Location<EmphasisBot>) {
+        self.eb_location = Some(producer);
+    }
+}
+
+#[tokio::main]
+async fn main() {
+    SimpleLogger::new().with_level(log::LevelFilter::Debug).init().unwrap();
+    // We create a hell for this
+    let hell = Hell::new();
+    let (gate, jh) = match hell.ignite().await {
+        Ok(v) => v,
+        Err(e) => panic!("Could not light up hell, {}", e)
+    };
+
+    // All the demons get their locations before being spawned
+    let mut wiring = WiringBuilder::new(&gate);
+    let nsb_location = wiring.add(NiceStringBot::new(gate.clone())).await.unwrap();
+    let rb_location = wiring.add(ReplaceBot).await.unwrap();
+    let eb_location = wiring.add(EmphasisBot).await.unwrap();
+
+    // Wrong connections, like giving the emphasis bot to the replace bot, do not compile
+    wiring.connect(&nsb_location, &rb_location).unwrap();
+    wiring.connect(&nsb_location, &eb_location).unwrap();
+
+    // And they are spawned together
+    if let Err(e) = wiring.build().await {
+        panic!("Could not spawn the demons, {}", e)
+    }
+
+    tokio::spawn(async move {
+        let m1 = gate.send(&nsb_location, "hello world".to_string()).await.unwrap();
+        // And check that it is correct
+        log::info!("Received chain {}", m1);
+        assert_eq!("helle werld!!!", &m1);
+        // And we kill the nice string bot to vanquish all gates
+        gate.vanquish_and_ignore(&nsb_location).await.unwrap();
+    });
+
+    // We wait for all messages to be processed.
+    jh.await.unwrap();
+}
@@ -0,0 +1,68 @@
+use std::sync::{Arc, Mutex};
+use tokio::time::{Duration, Instant};
+
+/// Source of the current time for demons
+///
+/// Demons read the time through their [context](crate::DemonContext::now), so that time-dependent logic can be tested with a [MockClock](MockClock) instead of real sleeps. The clock of hell is set with [HellBuilder::clock](crate::HellBuilder::clock), and defaults to the [SystemClock](SystemClock).
+pub trait Clock: Send + Sync {
+    /// Current time
+    fn now(&self) -> Instant;
+}
+
+/// Clock that returns the real time, as seen by tokio
+///
+/// Tokio's own time controls, like a paused runtime in tests, apply to this clock as well.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+    fn now(&self) -> Instant {
+        Instant::now()
+    }
+}
+
+/// Clock that only moves forward when told to
+///
+/// Clones share the same time, so a clone can be kept by the test to move the clock that hell uses. It starts at the real time of its creation.
+///
+/// ```rust
+/// use apocalypse::{Clock, MockClock};
+/// use std::time::Duration;
+///
+/// let clock = MockClock::new();
+/// let start = clock.now();
+/// clock.advance(Duration::from_secs(60));
+/// assert_eq!(Duration::from_secs(60), clock.now() - start);
+/// ```
+#[derive(Clone, Debug)]
+pub struct MockClock {
+    now: Arc<Mutex<Instant>>
+}
+
+impl MockClock {
+    /// Creates a clock stopped at the current time
+    pub fn new() -> MockClock {
+        MockClock {
+            now: Arc::new(Mutex::new(Instant::now()))
+        }
+    }
+
+    /// Moves the clock forward
+    pub fn advance(&self, duration: Duration) {
+        if let Ok(mut now) = self.now.lock() {
+            *now += duration;
+        }
+    }
+}
+
+impl Default for MockClock {
+    fn default() -> MockClock {
+        MockClock::new()
+    }
+}
+
+impl Clock for MockClock {
+    fn now(&self) -> Instant {
+        self.now.lock().map(|now| *now).unwrap_or_else(|poisoned| *poisoned.into_inner())
+    }
+}
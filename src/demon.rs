@@ -1,14 +1,127 @@
 use std::future::Future;
+use futures::future::BoxFuture;
+use crate::Error;
 pub use self::location::Location;
 mod location;
+pub use self::demon_map::DemonMap;
+mod demon_map;
+pub use self::demon_context::DemonContext;
+mod demon_context;
+pub use self::blocking_demon::{BlockingDemon, Blocking};
+mod blocking_demon;
+pub use self::message_size::MessageSize;
+mod message_size;
+pub use self::handler_outcome::HandlerOutcome;
+mod handler_outcome;
+pub use self::rate_limited::{RateLimited, RateLimitError};
+mod rate_limited;
+pub use self::broadcaster::{Broadcaster, BroadcasterInput};
+mod broadcaster;
+pub use self::io_thread::IoThread;
+mod io_thread;
+pub use self::shutdown_reason::ShutdownReason;
+mod shutdown_reason;
+#[cfg(feature = "record")]
+pub use self::replay::replay;
+#[cfg(feature = "record")]
+mod replay;
+#[cfg(feature = "remote")]
+pub use self::remote_demon::RemoteDemon;
+#[cfg(feature = "remote")]
+mod remote_demon;
+#[cfg(feature = "ws")]
+pub use self::web_socket_stream_thread::{WebSocketStreamThread, WebSocketFrames};
+#[cfg(feature = "ws")]
+mod web_socket_stream_thread;
+#[cfg(feature = "ws")]
+pub use self::web_socket_reconnect::WebSocketReconnect;
+#[cfg(feature = "ws")]
+pub(crate) use self::web_socket_reconnect::{ReconnectHook, reconnect_hook};
+#[cfg(feature = "ws")]
+mod web_socket_reconnect;
 
 /// Demon trait
 ///
 /// Demons are actors in the apocalypse framework. Implement this trait in your actors to allow them to reply to messages.
+///
+/// ## Trait object outputs
+///
+/// Demons can reply with trait objects, so that the caller does not need to know the concrete type of the reply. Use `Output = Box<dyn MyTrait + Send>`, as replies have to be sent across tasks. With [send](crate::Gate::send) the reply arrives already typed. With [send_any](crate::Gate::send_any), the reply is boxed once more, and the downcast target has to be exactly `Box<dyn MyTrait + Send>`, not the concrete type inside of it.
+///
+/// ```rust
+/// use apocalypse::{Hell, Demon};
+///
+/// trait Shape {
+///     fn area(&self) -> f64;
+/// }
+///
+/// struct Square(f64);
+///
+/// impl Shape for Square {
+///     fn area(&self) -> f64 {
+///         self.0 * self.0
+///     }
+/// }
+///
+/// struct ShapeFactory;
+///
+/// impl Demon for ShapeFactory {
+///     type Input = f64;
+///     type Output = Box<dyn Shape + Send>;
+///     async fn handle(&mut self, side: Self::Input) -> Self::Output {
+///         Box::new(Square(side))
+///     }
+/// }
+///
+/// # #[tokio::main]
+/// # async fn main() {
+/// let (gate, _join_handle) = Hell::new().ignite().await.unwrap();
+/// let location = gate.spawn(ShapeFactory).await.unwrap();
+///
+/// // Typed send, the reply is the trait object itself
+/// let shape = gate.send(&location, 2.0).await.unwrap();
+/// assert_eq!(4.0, shape.area());
+///
+/// // Type-erased send, the downcast target is the boxed trait object
+/// let reply = gate.send_any(location.address(), Box::new(3.0f64)).await.unwrap();
+/// assert!(reply.is::<Box<dyn Shape + Send>>());
+/// assert!(!reply.is::<Square>());
+/// let shape = reply.downcast::<Box<dyn Shape + Send>>().unwrap();
+/// assert_eq!(9.0, shape.area());
+/// # }
+/// ```
 pub trait Demon: Sized + std::marker::Send + 'static{
     type Input;
     type Output;
 
+    /// Stable identifier of this demon type
+    ///
+    /// Unlike `std::any::type_name`, the tag does not change between compiler versions or when the type is moved around, so it can be used to identify demons in wire protocols and in persisted data. Hell records it at registration, and it appears in the [stats](crate::Gate::stats), in type mismatch errors and in the envelopes of the remote gate. When left empty, which is the default, the type name is used, as it cannot be obtained in a constant.
+    ///
+    /// ```rust
+    /// use apocalypse::{Hell, Demon};
+    ///
+    /// struct PaymentWorker;
+    ///
+    /// impl Demon for PaymentWorker {
+    ///     type Input = u64;
+    ///     type Output = bool;
+    ///     const TYPE_TAG: &'static str = "payment-worker-v1";
+    ///     async fn handle(&mut self, amount: Self::Input) -> Self::Output {
+    ///         amount < 1000
+    ///     }
+    /// }
+    ///
+    /// # #[tokio::main]
+    /// # async fn main() {
+    /// let (gate, _join_handle) = Hell::new().ignite().await.unwrap();
+    /// let _location = gate.spawn(PaymentWorker).await.unwrap();
+    /// let stats = gate.stats().await.unwrap();
+    /// assert_eq!(Some(&1), stats.demons_by_tag.get("payment-worker-v1"));
+    /// # }
+    /// ```
+    const TYPE_TAG: &'static str = "";
+
     /// Function that is called when a demon is spawned
     ///
     /// By default, the function does nothing.
@@ -37,6 +150,34 @@ pub trait Demon: Sized + std::marker::Send + 'static{
         async {}
     }
 
+    /// Function that is called when a demon is spawned, with access to its context
+    ///
+    /// By default, it calls [spawned](Demon::spawned) with the location of the demon. Implement this function instead when the demon needs its [DemonContext](crate::DemonContext), which can be stored in the demon for later use.
+    ///
+    /// ```rust,no_run
+    /// use apocalypse::{Demon, DemonContext};
+    ///
+    /// struct EchoBot;
+    ///
+    /// impl Demon for EchoBot {
+    ///     type Input = String;
+    ///     type Output = String;
+    ///     
+    ///     // Callback for demon spawning
+    ///     async fn spawned_with_context(&mut self, ctx: DemonContext<Self>) {
+    ///         log::debug!("Spawned echo bot {}", ctx);
+    ///     }
+    ///     
+    ///     // Basic implementation of an echo handle function
+    ///     async fn handle(&mut self, message: Self::Input) -> Self::Output {
+    ///         message
+    ///     }
+    /// }
+    /// ```
+    fn spawned_with_context(&mut self, ctx: DemonContext<Self>) -> impl Future<Output = ()> + Send {
+        self.spawned(ctx.location().clone())
+    }
+
     /// Handler function for messages
     ///
     /// This is the main function, called for every message that the broker receives.
@@ -58,6 +199,57 @@ pub trait Demon: Sized + std::marker::Send + 'static{
     /// ```
     fn handle(&mut self, message: Self::Input) -> impl Future<Output = Self::Output> + Send;
 
+    /// Handler function for messages that leave work behind
+    ///
+    /// By default, it calls [handle](Demon::handle), with no follow-up. When implemented, hell calls this function instead of `handle`. The output goes to the caller right away, and the follow-up future, if any, is spawned as a [linked](crate::DemonContext::spawn_linked) task, so the demon takes its next message while the follow-up keeps working, and the follow-up is aborted once the demon leaves hell. Messages sent from the follow-up keep the [trace](crate::current_trace) of the message that started it. `handle` is still required, and should give the same output, as it is used wherever a follow-up cannot be driven.
+    ///
+    /// ```rust
+    /// use apocalypse::{Hell, Demon};
+    /// use futures::future::BoxFuture;
+    /// use std::sync::{Arc, atomic::{AtomicUsize, Ordering}};
+    /// use std::time::Duration;
+    ///
+    /// struct Uploader {
+    ///     uploaded: Arc<AtomicUsize>
+    /// }
+    ///
+    /// impl Demon for Uploader {
+    ///     type Input = usize;
+    ///     type Output = &'static str;
+    ///
+    ///     async fn handle(&mut self, size: Self::Input) -> Self::Output {
+    ///         self.handle_with_followup(size).await.0
+    ///     }
+    ///
+    ///     // The upload is accepted right away, and happens in the background
+    ///     async fn handle_with_followup(&mut self, size: Self::Input) -> (Self::Output, Option<BoxFuture<'static, ()>>) {
+    ///         let uploaded = self.uploaded.clone();
+    ///         ("accepted", Some(Box::pin(async move {
+    ///             tokio::time::sleep(Duration::from_millis(100)).await;
+    ///             uploaded.fetch_add(size, Ordering::SeqCst);
+    ///         })))
+    ///     }
+    /// }
+    ///
+    /// # #[tokio::main]
+    /// # async fn main() {
+    /// let (gate, _join_handle) = Hell::new().ignite().await.unwrap();
+    /// let uploaded = Arc::new(AtomicUsize::new(0));
+    /// let location = gate.spawn(Uploader{uploaded: uploaded.clone()}).await.unwrap();
+    /// assert_eq!("accepted", gate.send(&location, 42).await.unwrap());
+    /// assert_eq!(0, uploaded.load(Ordering::SeqCst));
+    /// tokio::time::sleep(Duration::from_millis(300)).await;
+    /// assert_eq!(42, uploaded.load(Ordering::SeqCst));
+    /// # }
+    /// ```
+    fn handle_with_followup(&mut self, message: Self::Input) -> impl Future<Output = (Self::Output, Option<BoxFuture<'static, ()>>)> + Send {
+        // Inputs are not required to be `Send`, so the message cannot be held across the await
+        let handled = self.handle(message);
+        async move {
+            (handled.await, None)
+        }
+    }
+
     /// Function that is called when a demon is removed
     ///
     /// By default, the function does nothing.
@@ -86,6 +278,366 @@ pub trait Demon: Sized + std::marker::Send + 'static{
         async {}
     }
 
+    /// Function that is called when a demon is removed, with access to its context
+    ///
+    /// By default, it calls [vanquished](Demon::vanquished). Implement this function instead when the demon needs to coordinate with other demons before disappearing, for example to hand over its remaining state. The gate of the context does not keep hell alive, and messages cannot be delivered anymore once hell is being extinguished.
+    ///
+    /// ```rust,no_run
+    /// use apocalypse::{Demon, DemonContext, Location};
+    ///
+    /// struct Coordinator;
+    ///
+    /// impl Demon for Coordinator {
+    ///     type Input = usize;
+    ///     type Output = ();
+    ///     async fn handle(&mut self, message: Self::Input) -> Self::Output {
+    ///         log::info!("worker left with {} pending items", message);
+    ///     }
+    /// }
+    ///
+    /// struct Worker {
+    ///     coordinator: Location<Coordinator>,
+    ///     pending: usize
+    /// }
+    ///
+    /// impl Demon for Worker {
+    ///     type Input = ();
+    ///     type Output = ();
+    ///
+    ///     async fn handle(&mut self, _message: Self::Input) -> Self::Output {
+    ///         self.pending += 1;
+    ///     }
+    ///
+    ///     // Goodbye message for the coordinator
+    ///     async fn vanquished_with_context(self, ctx: DemonContext<Self>) {
+    ///         if let Some(gate) = ctx.gate().upgrade() {
+    ///             let _ = gate.send_and_ignore(&self.coordinator, self.pending).await;
+    ///         }
+    ///     }
+    /// }
+    /// ```
+    fn vanquished_with_context(self, _ctx: DemonContext<Self>) -> impl Future<Output = ()> + Send {
+        self.vanquished()
+    }
+
+    /// Function that is called when a demon is removed, with the reason why it leaves
+    ///
+    /// By default, it calls [vanquished_with_context](Demon::vanquished_with_context). Implement this function instead when the cleanup depends on why the demon leaves, for example to skip a final flush to a connection that is already gone. Demons forced out by a killswitch do not get here, see [aborted](Demon::aborted).
+    ///
+    /// ```rust
+    /// use apocalypse::{Hell, Demon, DemonContext, ShutdownReason};
+    /// use std::sync::{Arc, Mutex};
+    ///
+    /// struct Witness {
+    ///     reason: Arc<Mutex<Option<ShutdownReason>>>,
+    ///     child: Option<Arc<Mutex<Option<ShutdownReason>>>>
+    /// }
+    ///
+    /// impl Demon for Witness {
+    ///     type Input = ();
+    ///     type Output = ();
+    ///     async fn handle(&mut self, _message: Self::Input) -> Self::Output {}
+    ///
+    ///     async fn spawned_with_context(&mut self, ctx: DemonContext<Self>) {
+    ///         if let Some(reason) = self.child.take() {
+    ///             ctx.spawn_child(Witness{reason, child: None}).await.unwrap();
+    ///         }
+    ///     }
+    ///
+    ///     async fn vanquished_with_reason(self, _ctx: DemonContext<Self>, reason: ShutdownReason) {
+    ///         *self.reason.lock().unwrap() = Some(reason);
+    ///     }
+    /// }
+    ///
+    /// # #[tokio::main]
+    /// # async fn main() {
+    /// let (gate, _join_handle) = Hell::new().ignite().await.unwrap();
+    /// let parent_reason = Arc::new(Mutex::new(None));
+    /// let child_reason = Arc::new(Mutex::new(None));
+    /// let parent = gate.spawn(Witness{reason: parent_reason.clone(), child: Some(child_reason.clone())}).await.unwrap();
+    /// // Messages are handled after the spawned function, so the child is in hell once this returns
+    /// gate.send(&parent, ()).await.unwrap();
+    /// gate.vanquish(&parent).await.unwrap();
+    /// assert_eq!(Some(ShutdownReason::Vanquished), *parent_reason.lock().unwrap());
+    /// assert_eq!(Some(ShutdownReason::ParentVanquished), *child_reason.lock().unwrap());
+    /// # }
+    /// ```
+    fn vanquished_with_reason(self, ctx: DemonContext<Self>, _reason: ShutdownReason) -> impl Future<Output = ()> + Send {
+        self.vanquished_with_context(ctx)
+    }
+
+    /// Function that is called when the demon could not be spawned, right before it gets dropped
+    ///
+    /// Demons that acquire resources when they are built, like an open connection, can release them here asynchronously. The reason why the spawn failed is given, for example `Error::TooManyDemons`. It is not called by [try_spawn](crate::Gate::try_spawn), which gives the demon back instead, nor by the spawn functions for websockets and for replicas.
+    ///
+    /// ```rust
+    /// use apocalypse::{HellBuilder, Demon, Error};
+    /// use std::sync::{Arc, atomic::{AtomicBool, Ordering}};
+    ///
+    /// struct Connected {
+    ///     released: Arc<AtomicBool>
+    /// }
+    ///
+    /// impl Demon for Connected {
+    ///     type Input = ();
+    ///     type Output = ();
+    ///     async fn handle(&mut self, _message: Self::Input) -> Self::Output {}
+    ///
+    ///     async fn on_spawn_rejected(self, reason: &Error) {
+    ///         // ... close the connection here
+    ///         self.released.store(true, Ordering::SeqCst);
+    ///     }
+    /// }
+    ///
+    /// # #[tokio::main]
+    /// # async fn main() {
+    /// let (gate, _join_handle) = HellBuilder::new().max_total_demons(1).build().ignite().await.unwrap();
+    /// let released = Arc::new(AtomicBool::new(false));
+    /// gate.spawn(Connected{released: Arc::new(AtomicBool::new(false))}).await.unwrap();
+    /// assert!(matches!(gate.spawn(Connected{released: released.clone()}).await, Err(Error::TooManyDemons)));
+    /// assert!(released.load(Ordering::SeqCst));
+    /// # }
+    /// ```
+    fn on_spawn_rejected(self, _reason: &Error) -> impl Future<Output = ()> + Send {
+        async {}
+    }
+
+    /// Function that is called when the demon gets dropped without being vanquished, as a last chance to save its state
+    ///
+    /// This happens when the demon is forced out by a killswitch, when the task of hell is aborted through its `JoinHandle`, and when the tokio runtime shuts down while the demon is still alive. It is not called once the [vanquished](Demon::vanquished) function started, even if it did not finish, nor for demons that were never spawned, nor for demons that panicked, as their state cannot be trusted. By default, the function does nothing.
+    ///
+    /// As there is no asynchronous drop, this function is synchronous, and it runs wherever the demon is dropped, possibly inside of the runtime that is shutting down. It cannot await, nor use `Handle::block_on`, which panics inside of a runtime, so it should stick to synchronous operations, like writing to a file with `std::fs`, and be quick. The demon may have been stopped in the middle of its handle function. All of this makes it a best-effort mechanism, that complements the vanquished function instead of replacing it.
+    ///
+    /// ```rust
+    /// use apocalypse::{Hell, Demon};
+    /// use std::sync::{Arc, Mutex};
+    ///
+    /// struct Journal {
+    ///     unsaved: Vec<String>,
+    ///     disk: Arc<Mutex<Vec<String>>>
+    /// }
+    ///
+    /// impl Demon for Journal {
+    ///     type Input = String;
+    ///     type Output = ();
+    ///     async fn handle(&mut self, entry: Self::Input) -> Self::Output {
+    ///         self.unsaved.push(entry);
+    ///     }
+    ///
+    ///     fn aborted(&mut self) {
+    ///         // ... a synchronous write to disk here
+    ///         self.disk.lock().unwrap().append(&mut self.unsaved);
+    ///     }
+    /// }
+    ///
+    /// # #[tokio::main]
+    /// # async fn main() {
+    /// let disk = Arc::new(Mutex::new(Vec::new()));
+    /// let (gate, join_handle) = Hell::new().ignite().await.unwrap();
+    /// let location = gate.spawn(Journal{unsaved: Vec::new(), disk: disk.clone()}).await.unwrap();
+    /// gate.send_and_ignore(&location, "entry".to_string()).await.unwrap();
+    /// gate.await_idle().await.unwrap();
+    /// // Hell is killed, so the demon is never vanquished
+    /// join_handle.abort();
+    /// while disk.lock().unwrap().is_empty() {
+    ///     tokio::task::yield_now().await;
+    /// }
+    /// assert_eq!(vec!["entry".to_string()], *disk.lock().unwrap());
+    /// # }
+    /// ```
+    ///
+    /// Neither single demons nor replicas are aborted after a panic.
+    ///
+    /// ```rust
+    /// use apocalypse::{Hell, Demon, Error};
+    /// use std::sync::Arc;
+    /// use std::sync::atomic::{AtomicBool, Ordering};
+    ///
+    /// struct Brittle {
+    ///     aborted: Arc<AtomicBool>
+    /// }
+    ///
+    /// impl Demon for Brittle {
+    ///     type Input = ();
+    ///     type Output = ();
+    ///     async fn handle(&mut self, _message: Self::Input) -> Self::Output {
+    ///         panic!("broken state");
+    ///     }
+    ///
+    ///     fn aborted(&mut self) {
+    ///         self.aborted.store(true, Ordering::SeqCst);
+    ///     }
+    /// }
+    ///
+    /// # #[tokio::main]
+    /// # async fn main() {
+    /// let aborted = Arc::new(AtomicBool::new(false));
+    /// let (gate, _join_handle) = Hell::new().ignite().await.unwrap();
+    /// let single = gate.spawn(Brittle{aborted: aborted.clone()}).await.unwrap();
+    /// assert!(gate.send(&single, ()).await.is_err());
+    /// let pool = gate.spawn_multiple(|| Brittle{aborted: aborted.clone()}, 1).await.unwrap();
+    /// assert!(matches!(gate.send(&pool, ()).await, Err(Error::Panicked)));
+    /// tokio::task::yield_now().await;
+    /// assert!(!aborted.load(Ordering::SeqCst));
+    /// # }
+    /// ```
+    fn aborted(&mut self) {}
+
+    /// Indicates if the reply means that the demon cannot keep serving
+    ///
+    /// By default, no reply is fatal. When a reply is fatal, the caller still gets it, and then the demon leaves hell through its [vanquished](Demon::vanquished) function, instead of handling the next message. Messages that were waiting for the demon get `Error::InvalidLocation`. For demons spawned with replicas, only the replica that gave the fatal reply leaves, and the pool leaves once no replica is left. It pairs well with [HandlerOutcome](crate::HandlerOutcome).
+    ///
+    /// ```rust
+    /// use apocalypse::{Hell, Demon, HandlerOutcome, Error};
+    ///
+    /// struct Storage {
+    ///     capacity: usize
+    /// }
+    ///
+    /// impl Demon for Storage {
+    ///     type Input = usize;
+    ///     type Output = HandlerOutcome<usize, String>;
+    ///
+    ///     async fn handle(&mut self, size: Self::Input) -> Self::Output {
+    ///         if size > self.capacity {
+    ///             HandlerOutcome::Fatal("out of space".to_string())
+    ///         } else {
+    ///             self.capacity -= size;
+    ///             HandlerOutcome::Ok(self.capacity)
+    ///         }
+    ///     }
+    ///
+    ///     fn is_fatal(output: &Self::Output) -> bool {
+    ///         output.is_fatal()
+    ///     }
+    /// }
+    ///
+    /// # #[tokio::main]
+    /// # async fn main() {
+    /// let (gate, _join_handle) = Hell::new().ignite().await.unwrap();
+    /// let location = gate.spawn(Storage{capacity: 10}).await.unwrap();
+    /// assert_eq!(HandlerOutcome::Ok(2), gate.send(&location, 8).await.unwrap());
+    /// assert!(gate.send(&location, 5).await.unwrap().is_fatal());
+    /// // The demon is gone
+    /// assert!(matches!(gate.send(&location, 1).await, Err(Error::InvalidLocation)));
+    /// # }
+    /// ```
+    fn is_fatal(_output: &Self::Output) -> bool {
+        false
+    }
+
+    /// Indicates if the reply means that the demon declined the message
+    ///
+    /// By default, no reply declines the message. A declined message is answered with `Error::Declined` instead of the reply, which tells the caller to retry or back off, as a cooperative backpressure signal. The demon keeps serving as usual. Messages sent with [send_and_ignore](crate::Gate::send_and_ignore) are silently dropped when declined. It pairs well with [HandlerOutcome](crate::HandlerOutcome).
+    ///
+    /// ```rust
+    /// use apocalypse::{Hell, Demon, HandlerOutcome, Error};
+    ///
+    /// struct Batcher {
+    ///     batch: Vec<u64>
+    /// }
+    ///
+    /// impl Demon for Batcher {
+    ///     type Input = u64;
+    ///     type Output = HandlerOutcome<usize, String>;
+    ///     async fn handle(&mut self, message: Self::Input) -> Self::Output {
+    ///         if self.batch.len() >= 2 {
+    ///             // Busy with a full batch, try again in a bit
+    ///             HandlerOutcome::Decline
+    ///         } else {
+    ///             self.batch.push(message);
+    ///             HandlerOutcome::Ok(self.batch.len())
+    ///         }
+    ///     }
+    ///
+    ///     fn is_declined(output: &Self::Output) -> bool {
+    ///         output.is_declined()
+    ///     }
+    /// }
+    ///
+    /// # #[tokio::main]
+    /// # async fn main() {
+    /// let (gate, _join_handle) = Hell::new().ignite().await.unwrap();
+    /// let location = gate.spawn(Batcher{batch: Vec::new()}).await.unwrap();
+    /// assert_eq!(HandlerOutcome::Ok(1), gate.send(&location, 1).await.unwrap());
+    /// assert_eq!(HandlerOutcome::Ok(2), gate.send(&location, 2).await.unwrap());
+    /// assert!(matches!(gate.send(&location, 3).await, Err(Error::Declined)));
+    /// # }
+    /// ```
+    fn is_declined(_output: &Self::Output) -> bool {
+        false
+    }
+
+    /// Indicates if handling the message more than once has the same effect as handling it once
+    ///
+    /// By default, no message is idempotent. [send_retry](crate::Gate::send_retry) only sends a message again after an [ambiguous](crate::Error::is_ambiguous) failure, where the demon might have handled it already, if the message is idempotent. Failures that happen before the message reaches the demon are retried either way.
+    ///
+    /// ```rust
+    /// use apocalypse::{Hell, Demon, RetryPolicy};
+    ///
+    /// #[derive(Clone)]
+    /// enum Command {
+    ///     Get,
+    ///     Increment
+    /// }
+    ///
+    /// struct Counter(u64);
+    ///
+    /// impl Demon for Counter {
+    ///     type Input = Command;
+    ///     type Output = u64;
+    ///     async fn handle(&mut self, message: Self::Input) -> Self::Output {
+    ///         if let Command::Increment = message {
+    ///             self.0 += 1;
+    ///         }
+    ///         self.0
+    ///     }
+    ///
+    ///     fn is_idempotent(input: &Self::Input) -> bool {
+    ///         // Reading twice does no harm, incrementing twice does
+    ///         matches!(input, Command::Get)
+    ///     }
+    /// }
+    ///
+    /// # #[tokio::main]
+    /// # async fn main() {
+    /// let (gate, _join_handle) = Hell::new().ignite().await.unwrap();
+    /// let location = gate.spawn(Counter(0)).await.unwrap();
+    /// assert_eq!(1, gate.send_retry(&location, Command::Increment, RetryPolicy::new(3)).await.unwrap());
+    /// assert_eq!(1, gate.send_retry(&location, Command::Get, RetryPolicy::new(3)).await.unwrap());
+    /// # }
+    /// ```
+    fn is_idempotent(_input: &Self::Input) -> bool {
+        false
+    }
+
+    /// Number of replicas that [spawn_multiple_auto](crate::Gate::spawn_multiple_auto) will use for this demon type
+    ///
+    /// By default, a single replica is used. CPU-bound demons might want to return the available parallelism, while IO-bound ones can use a higher fixed number.
+    ///
+    /// ```rust,no_run
+    /// use apocalypse::Demon;
+    ///
+    /// struct Hasher;
+    ///
+    /// impl Demon for Hasher {
+    ///     type Input = Vec<u8>;
+    ///     type Output = u64;
+    ///
+    ///     async fn handle(&mut self, message: Self::Input) -> Self::Output {
+    ///         message.iter().map(|v| *v as u64).sum()
+    ///     }
+    ///
+    ///     // One replica per available core
+    ///     fn default_replicas() -> usize {
+    ///         std::thread::available_parallelism().map(|v| v.get()).unwrap_or(1)
+    ///     }
+    /// }
+    /// ```
+    fn default_replicas() -> usize {
+        1
+    }
+
     /// This id will be printed in the debug logs of the demon's thread.
     ///
     /// It is useful when some lockup is happening and you have trouble to find it.
@@ -101,4 +653,42 @@ pub trait Demon: Sized + std::marker::Send + 'static{
     fn multiple_id() -> &'static str {
         ""
     }
-}
\ No newline at end of file
+}
+
+/// Marker for demons that are safe to be used in request-reply interactions
+///
+/// Request-reply interactions through [send](crate::Gate::send) can lead to lockups if message-cycles exist. [request](crate::Gate::request) works like [send](crate::Gate::send), but only accepts demons that implement this trait, so codebases that forbid the unchecked send functions, for example with the `disallowed_methods` lint of clippy, can only await the replies of demons that were explicitly marked as safe. All other demons are still reachable through [send_and_ignore](crate::Gate::send_and_ignore). The type-erased [send_any](crate::Gate::send_any) cannot check the marker.
+///
+/// ```rust
+/// use apocalypse::{Demon, RequestResponseDemon};
+///
+/// struct EchoBot;
+///
+/// impl Demon for EchoBot {
+///     type Input = String;
+///     type Output = String;
+///     async fn handle(&mut self, message: Self::Input) -> Self::Output {
+///         message
+///     }
+/// }
+///
+/// // The echo bot never sends messages, so it can never be part of a message-cycle
+/// impl RequestResponseDemon for EchoBot {}
+///
+/// # #[tokio::main]
+/// # async fn main() {
+/// let (gate, _join_handle) = apocalypse::Hell::new().ignite().await.unwrap();
+/// let location = gate.spawn(EchoBot).await.unwrap();
+/// assert_eq!("Hallo", gate.request(&location, "Hallo".to_string()).await.unwrap());
+/// # }
+/// ```
+pub trait RequestResponseDemon: Demon {}
+
+/// Type tag of the demon, falling back to its type name
+pub(crate) fn type_tag<D: Demon>() -> &'static str {
+    if D::TYPE_TAG.is_empty() {
+        std::any::type_name::<D>()
+    } else {
+        D::TYPE_TAG
+    }
+}
@@ -0,0 +1,68 @@
+use crate::{Demon, RequestResponseDemon};
+
+/// Blocking demon trait
+///
+/// Blocking demons are demons whose handle function is synchronous and CPU-bound. Each call is executed in tokio's blocking thread pool, so that other demons are not starved. Spawn them with [spawn_blocking_demon](crate::Gate::spawn_blocking_demon).
+pub trait BlockingDemon: Sized + std::marker::Send + 'static {
+    type Input: Send + 'static;
+    type Output: Send + 'static;
+
+    /// Handler function for messages, executed in the blocking thread pool
+    ///
+    /// ```rust,no_run
+    /// use apocalypse::BlockingDemon;
+    ///
+    /// struct Summer;
+    ///
+    /// impl BlockingDemon for Summer {
+    ///     type Input = Vec<u64>;
+    ///     type Output = u64;
+    ///
+    ///     // Heavy synchronous work
+    ///     fn handle(&mut self, message: Self::Input) -> Self::Output {
+    ///         message.iter().sum()
+    ///     }
+    /// }
+    /// ```
+    fn handle(&mut self, message: Self::Input) -> Self::Output;
+}
+
+/// Demon wrapper around a [BlockingDemon](BlockingDemon)
+///
+/// The inner demon is moved into the blocking thread pool for each message, and moved back once the handle function returns.
+pub struct Blocking<B> {
+    /// Inner demon, only missing while a message is being processed
+    demon: Option<B>
+}
+
+impl<B> Blocking<B> {
+    /// Wraps a blocking demon
+    pub fn new(demon: B) -> Blocking<B> {
+        Blocking {
+            demon: Some(demon)
+        }
+    }
+}
+
+impl<B: BlockingDemon> Demon for Blocking<B> {
+    type Input = B::Input;
+    type Output = B::Output;
+
+    async fn handle(&mut self, message: Self::Input) -> Self::Output {
+        let mut demon = self.demon.take().expect("blocking demon is only missing while a message is being processed");
+        match tokio::task::spawn_blocking(move || {
+            let output = demon.handle(message);
+            (demon, output)
+        }).await {
+            Ok((demon, output)) => {
+                self.demon = Some(demon);
+                output
+            },
+            // Same behaviour as a panic inside of a regular handle function
+            Err(e) => std::panic::resume_unwind(e.into_panic())
+        }
+    }
+}
+
+// A synchronous handle function cannot await replies, so blocking demons cannot be part of a message-cycle
+impl<B: BlockingDemon> RequestResponseDemon for Blocking<B> {}
@@ -0,0 +1,152 @@
+use crate::{Error, Demon, DemonContext, Location, RequestResponseDemon, WeakGate, trace, hell::HellInstruction};
+use tokio::sync::oneshot;
+
+/// Message for a [Broadcaster](Broadcaster)
+pub enum BroadcasterInput<M> {
+    /// Adds the demon at the given address to the subscribers, replying with the amount of subscribers
+    Subscribe(usize),
+    /// Removes the demon at the given address from the subscribers, replying with the amount of subscribers
+    Unsubscribe(usize),
+    /// Forwards a copy of the message to all the subscribers, replying with the amount of them that got it
+    Broadcast(M)
+}
+
+impl<M> BroadcasterInput<M> {
+    /// Subscription of a demon whose `Input` is the broadcasted message
+    ///
+    /// Raw addresses can be subscribed as well, but nothing checks that the demon living there takes the message.
+    pub fn subscribe<S: Demon<Input = M>>(location: &Location<S>) -> BroadcasterInput<M> {
+        BroadcasterInput::Subscribe(location.address)
+    }
+
+    /// Removal of a subscription made with [subscribe](BroadcasterInput::subscribe)
+    pub fn unsubscribe<S: Demon<Input = M>>(location: &Location<S>) -> BroadcasterInput<M> {
+        BroadcasterInput::Unsubscribe(location.address)
+    }
+}
+
+/// Demon that forwards each message it gets to all of its subscribers
+///
+/// It packages the fan-out pattern in a single demon, without the topics of [publish](crate::Gate::publish). Each forwarded copy works like [send_and_ignore](crate::Gate::send_and_ignore), so replies of the subscribers are discarded, and a slow subscriber does not hold the broadcaster back. Subscribers that left hell are detected when a copy cannot be delivered to them, and removed right away. The broadcaster reaches hell through its [context](crate::DemonContext::gate), so it does not keep hell alive.
+///
+/// ```rust
+/// use apocalypse::{Hell, Demon, Broadcaster, BroadcasterInput};
+/// use std::sync::{Arc, atomic::{AtomicUsize, Ordering}};
+///
+/// struct Listener(Arc<AtomicUsize>);
+///
+/// impl Demon for Listener {
+///     type Input = usize;
+///     type Output = ();
+///     async fn handle(&mut self, message: Self::Input) -> Self::Output {
+///         self.0.fetch_add(message, Ordering::Relaxed);
+///     }
+/// }
+///
+/// # #[tokio::main]
+/// # async fn main() {
+/// let (gate, join_handle) = Hell::new().ignite().await.unwrap();
+/// let total = Arc::new(AtomicUsize::new(0));
+/// let first = gate.spawn(Listener(total.clone())).await.unwrap();
+/// let second = gate.spawn(Listener(total.clone())).await.unwrap();
+/// let hub = gate.spawn(Broadcaster::new()).await.unwrap();
+/// gate.send(&hub, BroadcasterInput::subscribe(&first)).await.unwrap();
+/// assert_eq!(2, gate.send(&hub, BroadcasterInput::subscribe(&second)).await.unwrap());
+/// assert_eq!(2, gate.send(&hub, BroadcasterInput::Broadcast(5)).await.unwrap());
+/// // Subscribers that leave are pruned on the next broadcast
+/// gate.vanquish(&first).await.unwrap();
+/// assert_eq!(1, gate.send(&hub, BroadcasterInput::Broadcast(1)).await.unwrap());
+/// assert_eq!(0, gate.send(&hub, BroadcasterInput::unsubscribe(&second)).await.unwrap());
+/// # }
+/// ```
+pub struct Broadcaster<M> {
+    /// Connection to hell, available once the broadcaster is spawned
+    gate: Option<WeakGate>,
+    /// Addresses of the subscribers, in subscription order
+    subscribers: Vec<usize>,
+    /// Only messages of this type are broadcasted
+    phantom: std::marker::PhantomData<fn(M)>
+}
+
+impl<M> Broadcaster<M> {
+    /// Creates a broadcaster without subscribers
+    pub fn new() -> Broadcaster<M> {
+        Broadcaster {
+            gate: None,
+            subscribers: Vec::new(),
+            phantom: std::marker::PhantomData
+        }
+    }
+}
+
+impl<M> Default for Broadcaster<M> {
+    fn default() -> Broadcaster<M> {
+        Broadcaster::new()
+    }
+}
+
+impl<M: 'static + Clone + Send> Broadcaster<M> {
+    /// Forwards a copy of the message to each subscriber, removing the ones that are gone
+    async fn broadcast(&mut self, message: M) -> usize {
+        let Some(gate) = self.gate.as_ref().and_then(WeakGate::upgrade) else {
+            return 0;
+        };
+
+        // Every copy is sent before waiting for the first acknowledgement
+        let trace = trace::inherit_or_new();
+        let mut pending = Vec::with_capacity(self.subscribers.len());
+        for &address in &self.subscribers {
+            let (tx, rx) = oneshot::channel();
+            if gate.hell_channel.send(HellInstruction::Message {
+                tx,
+                address,
+                ignore: true,
+                trace,
+                input: Box::new(message.clone()),
+                types: None
+            }).is_err() {
+                // Hell is going away, there is no one left to deliver to
+                return 0;
+            }
+            pending.push((address, rx));
+        }
+
+        let mut delivered = 0;
+        for (address, rx) in pending {
+            match rx.await {
+                Ok(Ok(_)) => delivered += 1,
+                Ok(Err(Error::InvalidLocation | Error::DemonCommunication)) => self.subscribers.retain(|subscriber| *subscriber != address),
+                _ => ()
+            }
+        }
+        delivered
+    }
+}
+
+impl<M: 'static + Clone + Send> Demon for Broadcaster<M> {
+    type Input = BroadcasterInput<M>;
+    type Output = usize;
+
+    async fn spawned_with_context(&mut self, ctx: DemonContext<Self>) {
+        self.gate = Some(ctx.gate().clone());
+    }
+
+    async fn handle(&mut self, message: Self::Input) -> Self::Output {
+        match message {
+            BroadcasterInput::Subscribe(address) => {
+                if !self.subscribers.contains(&address) {
+                    self.subscribers.push(address);
+                }
+                self.subscribers.len()
+            },
+            BroadcasterInput::Unsubscribe(address) => {
+                self.subscribers.retain(|subscriber| *subscriber != address);
+                self.subscribers.len()
+            },
+            BroadcasterInput::Broadcast(message) => self.broadcast(message).await
+        }
+    }
+}
+
+// Copies are only acknowledged once they are in the mailbox of each subscriber, never handled, so the broadcaster cannot take part in a message-cycle
+impl<M: 'static + Clone + Send> RequestResponseDemon for Broadcaster<M> {}
@@ -0,0 +1,367 @@
+use crate::{Error, Clock, Demon, Location, WeakGate, hell::HandleTime};
+use std::future::Future;
+use std::sync::{Arc, Mutex, atomic::{AtomicUsize, Ordering}};
+use tokio::{sync::{broadcast, watch, mpsc::UnboundedSender}, task::AbortHandle, time::Instant};
+
+/// Demon's context
+///
+/// The context is handed to each demon through the [spawned_with_context](crate::Demon::spawned_with_context) function, and gives access to facilities that are tied to the demon's own lifecycle. It can be cloned and stored inside of the demon for later use. The context does not keep hell alive.
+pub struct DemonContext<D> {
+    /// Location of the demon that owns this context
+    pub(crate) location: Location<D>,
+    /// Number of demons that took the address before this one, through handoffs
+    pub(crate) generation: usize,
+    /// Connection to hell that does not prevent its shutdown
+    pub(crate) gate: WeakGate,
+    /// Tasks whose lifecycle is tied to the demon
+    pub(crate) linked: Arc<Mutex<Vec<AbortHandle>>>,
+    /// Messages delivered to the demon that were not handled yet
+    pub(crate) mailbox: Arc<AtomicUsize>,
+    /// Scale requests for the pool of the demon, if it can scale
+    pub(crate) scale: Option<UnboundedSender<i32>>,
+    /// Clock of hell
+    pub(crate) clock: Arc<dyn Clock>,
+    /// Heads-up that hell is being extinguished
+    pub(crate) shutdown: broadcast::Sender<()>,
+    /// Time spent by demons inside their handle functions, across all of hell
+    pub(crate) handle_time: Arc<HandleTime>,
+    /// Readiness of the demon, only for demons spawned with a warm-up
+    pub(crate) ready: Option<Arc<watch::Sender<bool>>>
+}
+
+impl<D> Clone for DemonContext<D> {
+    fn clone(&self) -> DemonContext<D> {
+        DemonContext {
+            location: self.location.clone(),
+            generation: self.generation,
+            gate: self.gate.clone(),
+            linked: self.linked.clone(),
+            mailbox: self.mailbox.clone(),
+            scale: self.scale.clone(),
+            clock: self.clock.clone(),
+            shutdown: self.shutdown.clone(),
+            handle_time: self.handle_time.clone(),
+            ready: self.ready.clone()
+        }
+    }
+}
+
+/// Identifies the demon by its location and generation, as in `d-5.3`
+impl<D> std::fmt::Display for DemonContext<D> {
+    fn fmt(&self, formatter: &mut std::fmt::Formatter) -> Result<(), std::fmt::Error> {
+        write!(formatter, "{}.{}", self.location, self.generation)
+    }
+}
+
+impl<D> DemonContext<D> {
+    /// Marks one more message in the mailbox
+    pub(crate) fn put_in_mailbox(&self) {
+        self.mailbox.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// Marks one message of the mailbox as taken
+    pub(crate) fn take_from_mailbox(&self) {
+        self.mailbox.fetch_sub(1, Ordering::Relaxed);
+    }
+
+    /// Marks several messages of the mailbox as taken
+    pub(crate) fn take_many_from_mailbox(&self, amount: usize) {
+        self.mailbox.fetch_sub(amount, Ordering::Relaxed);
+    }
+
+    /// Same context, for a demon wrapped by the one that owns it
+    ///
+    /// Both demons share the address, so the wrapped demon has to accept the same messages.
+    pub(crate) fn cast<E>(&self) -> DemonContext<E> {
+        DemonContext {
+            location: Location {
+                address: self.location.address,
+                phantom: std::marker::PhantomData
+            },
+            generation: self.generation,
+            gate: self.gate.clone(),
+            linked: self.linked.clone(),
+            mailbox: self.mailbox.clone(),
+            scale: self.scale.clone(),
+            clock: self.clock.clone(),
+            shutdown: self.shutdown.clone(),
+            handle_time: self.handle_time.clone(),
+            ready: self.ready.clone()
+        }
+    }
+
+    /// Aborts all the tasks linked to the demon
+    pub(crate) fn abort_linked(&self) {
+        if let Ok(mut linked) = self.linked.lock() {
+            for handle in linked.drain(..) {
+                handle.abort();
+            }
+        }
+    }
+}
+
+impl<D: Demon> DemonContext<D> {
+    /// Location of the demon that owns this context
+    pub fn location(&self) -> &Location<D> {
+        &self.location
+    }
+
+    /// Generation of the demon that owns this context, see [generation](crate::Gate::generation)
+    pub fn generation(&self) -> usize {
+        self.generation
+    }
+
+    /// Connection to hell, which does not keep it alive
+    ///
+    /// The [WeakGate](crate::WeakGate) has to be upgraded before use, which only works while hell is still alive.
+    pub fn gate(&self) -> &WeakGate {
+        &self.gate
+    }
+
+    /// Current time, according to the [clock](crate::Clock) of hell
+    ///
+    /// Demons that make decisions based on time should read it from here, instead of calling `Instant::now` directly, so that tests can control it with a [MockClock](crate::MockClock).
+    ///
+    /// ```rust
+    /// use apocalypse::{HellBuilder, Demon, DemonContext, MockClock};
+    /// use tokio::time::Instant;
+    /// use std::time::Duration;
+    ///
+    /// // Remembers a value for ten seconds
+    /// struct Cache {
+    ///     ctx: Option<DemonContext<Cache>>,
+    ///     value: Option<(u64, Instant)>
+    /// }
+    ///
+    /// enum Request {
+    ///     Set(u64),
+    ///     Get
+    /// }
+    ///
+    /// impl Demon for Cache {
+    ///     type Input = Request;
+    ///     type Output = Option<u64>;
+    ///
+    ///     async fn spawned_with_context(&mut self, ctx: DemonContext<Self>) {
+    ///         self.ctx = Some(ctx);
+    ///     }
+    ///
+    ///     async fn handle(&mut self, message: Self::Input) -> Self::Output {
+    ///         let now = self.ctx.as_ref()?.now();
+    ///         match message {
+    ///             Request::Set(value) => self.value = Some((value, now)),
+    ///             Request::Get => self.value = self.value.filter(|(_, set)| now - *set < Duration::from_secs(10))
+    ///         }
+    ///         self.value.map(|(value, _)| value)
+    ///     }
+    /// }
+    ///
+    /// # #[tokio::main]
+    /// # async fn main() {
+    /// let clock = MockClock::new();
+    /// let (gate, _join_handle) = HellBuilder::new().clock(clock.clone()).build().ignite().await.unwrap();
+    /// let location = gate.spawn(Cache{ctx: None, value: None}).await.unwrap();
+    /// gate.send(&location, Request::Set(7)).await.unwrap();
+    /// clock.advance(Duration::from_secs(9));
+    /// assert_eq!(Some(7), gate.send(&location, Request::Get).await.unwrap());
+    /// // No need to wait for the value to expire
+    /// clock.advance(Duration::from_secs(1));
+    /// assert_eq!(None, gate.send(&location, Request::Get).await.unwrap());
+    /// # }
+    /// ```
+    pub fn now(&self) -> Instant {
+        self.clock.now()
+    }
+
+    /// Amount of messages waiting to be handled by this demon
+    ///
+    /// The message currently being handled is not counted. For demons spawned with replicas, the count is shared among all of them. This allows handlers to adapt to the load, for example skipping optional work when the backlog is deep.
+    ///
+    /// ```rust,no_run
+    /// use apocalypse::{Demon, DemonContext};
+    ///
+    /// struct Indexer {
+    ///     ctx: Option<DemonContext<Indexer>>
+    /// }
+    ///
+    /// impl Demon for Indexer {
+    ///     type Input = String;
+    ///     type Output = ();
+    ///
+    ///     async fn spawned_with_context(&mut self, ctx: DemonContext<Self>) {
+    ///         self.ctx = Some(ctx);
+    ///     }
+    ///
+    ///     async fn handle(&mut self, message: Self::Input) -> Self::Output {
+    ///         let backlog = self.ctx.as_ref().map(|ctx| ctx.mailbox_len()).unwrap_or(0);
+    ///         if backlog < 100 {
+    ///             // Expensive optional work, only when there is time for it
+    ///         }
+    ///     }
+    /// }
+    /// ```
+    pub fn mailbox_len(&self) -> usize {
+        self.mailbox.load(Ordering::Relaxed)
+    }
+
+    /// Requests a change in the amount of replicas of this demon
+    ///
+    /// Only demons spawned with [spawn_multiple_scalable](crate::Gate::spawn_multiple_scalable) can scale, all others get `Error::DemonCommunication`. A positive `delta` adds replicas created with the stored factory, and a negative one removes replicas, waiting for busy ones to finish their current message. At least one replica always remains. The request is processed asynchronously, after this function returns.
+    ///
+    /// ```rust,no_run
+    /// use apocalypse::{Demon, DemonContext};
+    ///
+    /// struct Worker {
+    ///     ctx: Option<DemonContext<Worker>>
+    /// }
+    ///
+    /// impl Demon for Worker {
+    ///     type Input = String;
+    ///     type Output = ();
+    ///
+    ///     async fn spawned_with_context(&mut self, ctx: DemonContext<Self>) {
+    ///         self.ctx = Some(ctx);
+    ///     }
+    ///
+    ///     async fn handle(&mut self, message: Self::Input) -> Self::Output {
+    ///         if let Some(ctx) = &self.ctx {
+    ///             // The pool grows when the backlog gets deep
+    ///             if ctx.mailbox_len() > 100 {
+    ///                 let _ = ctx.request_scale(1);
+    ///             }
+    ///         }
+    ///     }
+    /// }
+    /// ```
+    pub fn request_scale(&self, delta: i32) -> Result<(), Error> {
+        let scale = self.scale.as_ref().ok_or(Error::DemonCommunication)?;
+        scale.send(delta).map_err(|_| Error::DemonCommunication)
+    }
+
+    /// Spawns a demon as a child of this demon
+    ///
+    /// Children are vanquished (depth-first) before their parent whenever the parent is vanquished. A child that gets vanquished on its own is simply unlinked from its parent. As children always receive a fresh address, cycles in the hierarchy cannot be built.
+    ///
+    /// ```rust,no_run
+    /// use apocalypse::{Demon, DemonContext};
+    ///
+    /// struct Worker;
+    ///
+    /// impl Demon for Worker {
+    ///     type Input = ();
+    ///     type Output = ();
+    ///     async fn handle(&mut self, _message: Self::Input) -> Self::Output {}
+    /// }
+    ///
+    /// struct Supervisor;
+    ///
+    /// impl Demon for Supervisor {
+    ///     type Input = ();
+    ///     type Output = ();
+    ///
+    ///     async fn spawned_with_context(&mut self, ctx: DemonContext<Self>) {
+    ///         // The worker will be vanquished before the supervisor
+    ///         ctx.spawn_child(Worker).await.unwrap();
+    ///     }
+    ///
+    ///     async fn handle(&mut self, _message: Self::Input) -> Self::Output {}
+    /// }
+    /// ```
+    pub async fn spawn_child<C: 'static + Demon<Input = I, Output = O>, I: 'static + Send, O: 'static + Send>(&self, demon: C) -> Result<Location<C>, Error> {
+        let gate = self.gate.upgrade().ok_or(Error::BrokerGone)?;
+        gate.spawn_with_parent(demon, Some(self.location.address), Default::default()).await
+    }
+
+    /// Spawns a task whose lifecycle is tied to the demon
+    ///
+    /// The task is aborted as soon as the demon leaves hell, right before the [vanquished](crate::Demon::vanquished) function is called. This is the right place for background tickers that send messages to the demon itself, as they will not outlive it. The returned handle can be used to abort the task earlier.
+    ///
+    /// ```rust,no_run
+    /// use apocalypse::{Demon, DemonContext};
+    ///
+    /// struct Ticker;
+    ///
+    /// impl Demon for Ticker {
+    ///     type Input = ();
+    ///     type Output = ();
+    ///
+    ///     async fn spawned_with_context(&mut self, ctx: DemonContext<Self>) {
+    ///         let linked_ctx = ctx.clone();
+    ///         ctx.spawn_linked(async move {
+    ///             loop {
+    ///                 tokio::time::sleep(std::time::Duration::from_secs(1)).await;
+    ///                 log::info!("tick from {}", linked_ctx.location());
+    ///             }
+    ///         });
+    ///     }
+    ///
+    ///     async fn handle(&mut self, _message: Self::Input) -> Self::Output {}
+    /// }
+    /// ```
+    pub fn spawn_linked<F: 'static + Future<Output = ()> + Send>(&self, future: F) -> AbortHandle {
+        let handle = tokio::spawn(future).abort_handle();
+        if let Ok(mut linked) = self.linked.lock() {
+            // We get rid of the tasks that already finished
+            linked.retain(|handle| !handle.is_finished());
+            linked.push(handle.clone());
+        }
+        handle
+    }
+
+    /// Subscribes to the signal that hell sends when it starts being extinguished
+    ///
+    /// The signal is a heads-up, sent once, right before hell asks every demon to leave. Demons busy with a long message, or running loops of their own, can use it to wind down on their own terms, instead of being cut short by the timeout of the extinguish call. Nothing else changes, and demons that ignore the signal are vanquished as usual. Only receivers that exist when the signal is sent get it, so demons should subscribe early, for example in their [spawned_with_context](crate::Demon::spawned_with_context) function.
+    ///
+    /// ```rust
+    /// use apocalypse::{Hell, Demon, DemonContext};
+    /// use tokio::sync::broadcast::Receiver;
+    /// use std::time::Duration;
+    ///
+    /// struct Crawler {
+    ///     shutdown: Option<Receiver<()>>
+    /// }
+    ///
+    /// impl Demon for Crawler {
+    ///     type Input = ();
+    ///     type Output = &'static str;
+    ///
+    ///     async fn spawned_with_context(&mut self, ctx: DemonContext<Self>) {
+    ///         self.shutdown = Some(ctx.shutdown_signal());
+    ///     }
+    ///
+    ///     async fn handle(&mut self, _message: Self::Input) -> Self::Output {
+    ///         let shutdown = self.shutdown.as_mut().unwrap();
+    ///         tokio::select! {
+    ///             _ = tokio::time::sleep(Duration::from_secs(60)) => "crawled everything",
+    ///             _ = shutdown.recv() => "stopped early"
+    ///         }
+    ///     }
+    /// }
+    ///
+    /// # #[tokio::main]
+    /// # async fn main() {
+    /// let (gate, _join_handle) = Hell::new().ignite().await.unwrap();
+    /// let location = gate.spawn(Crawler{shutdown: None}).await.unwrap();
+    /// let sender = gate.clone();
+    /// let reply = tokio::spawn(async move {
+    ///     sender.send(&location, ()).await
+    /// });
+    /// // The crawler gets to work, and without the signal, extinguishing hell would take a minute
+    /// tokio::time::sleep(Duration::from_millis(50)).await;
+    /// gate.extinguish().await.unwrap();
+    /// assert_eq!("stopped early", reply.await.unwrap().unwrap());
+    /// # }
+    /// ```
+    pub fn shutdown_signal(&self) -> broadcast::Receiver<()> {
+        self.shutdown.subscribe()
+    }
+
+    /// Marks the demon as ready to handle messages, once its warm-up is over
+    ///
+    /// Only demons spawned with [spawn_with_warm_up](crate::Gate::spawn_with_warm_up) wait for it, for all others it does nothing. See that function for an example.
+    pub fn mark_ready(&self) {
+        if let Some(ready) = &self.ready {
+            ready.send_replace(true);
+        }
+    }
+}
@@ -0,0 +1,100 @@
+use std::collections::HashMap;
+use super::Location;
+
+/// Map from demons to arbitrary data
+///
+/// A `HashMap<Location<D>, T>` requires a different map for each demon type. This map erases the type of the location, so that data about demons of different types can live in the same map. Locations are only unique within the same hell instance, so a map should not mix demons from different hells.
+///
+/// ```rust
+/// use apocalypse::{Hell, Demon, DemonMap};
+///
+/// struct Reader;
+///
+/// impl Demon for Reader {
+///     type Input = ();
+///     type Output = ();
+///     async fn handle(&mut self, _message: Self::Input) -> Self::Output {}
+/// }
+///
+/// struct Writer;
+///
+/// impl Demon for Writer {
+///     type Input = String;
+///     type Output = ();
+///     async fn handle(&mut self, _message: Self::Input) -> Self::Output {}
+/// }
+///
+/// # #[tokio::main]
+/// # async fn main() {
+/// let (gate, _join_handle) = Hell::new().ignite().await.unwrap();
+/// let reader = gate.spawn(Reader).await.unwrap();
+/// let writer = gate.spawn(Writer).await.unwrap();
+///
+/// let mut owners = DemonMap::new();
+/// owners.insert(&reader, "alice");
+/// owners.insert(&writer, "bob");
+/// assert_eq!(Some(&"alice"), owners.get(&reader));
+/// assert_eq!(Some("bob"), owners.remove(&writer));
+/// assert_eq!(1, owners.len());
+/// # }
+/// ```
+#[derive(Clone, Debug)]
+pub struct DemonMap<T> {
+    /// Data, by demon address
+    inner: HashMap<usize, T>
+}
+
+impl<T> Default for DemonMap<T> {
+    fn default() -> DemonMap<T> {
+        DemonMap {
+            inner: HashMap::new()
+        }
+    }
+}
+
+impl<T> DemonMap<T> {
+    /// Creates an empty map
+    pub fn new() -> DemonMap<T> {
+        DemonMap::default()
+    }
+
+    /// Inserts data for a demon, returning the previous data, if any
+    pub fn insert<D>(&mut self, location: &Location<D>, value: T) -> Option<T> {
+        self.inner.insert(location.address, value)
+    }
+
+    /// Data of a demon
+    pub fn get<D>(&self, location: &Location<D>) -> Option<&T> {
+        self.inner.get(&location.address)
+    }
+
+    /// Mutable data of a demon
+    pub fn get_mut<D>(&mut self, location: &Location<D>) -> Option<&mut T> {
+        self.inner.get_mut(&location.address)
+    }
+
+    /// Removes the data of a demon, returning it
+    pub fn remove<D>(&mut self, location: &Location<D>) -> Option<T> {
+        self.inner.remove(&location.address)
+    }
+
+    /// Indicates if there is data for a demon
+    pub fn contains<D>(&self, location: &Location<D>) -> bool {
+        self.inner.contains_key(&location.address)
+    }
+
+    /// Amount of demons in the map
+    pub fn len(&self) -> usize {
+        self.inner.len()
+    }
+
+    /// Indicates if the map is empty
+    pub fn is_empty(&self) -> bool {
+        self.inner.is_empty()
+    }
+
+    /// Iterates over the raw addresses of the demons and their data
+    pub fn iter(&self) -> impl Iterator<Item = (usize, &T)> {
+        self.inner.iter().map(|(address, value)| (*address, value))
+    }
+}
@@ -0,0 +1,45 @@
+/// Outcome of a handle function, for demons that can fail beyond recovery
+///
+/// Demons that use it as their `Output` can terminate themselves by replying with `HandlerOutcome::Fatal`, as long as they forward the [is_fatal](crate::Demon::is_fatal) check to it. The caller still gets the reply, and the demon leaves hell right after. Likewise, they can decline messages with `HandlerOutcome::Decline`, as long as they forward the [is_declined](crate::Demon::is_declined) check to it.
+///
+/// ```rust
+/// use apocalypse::HandlerOutcome;
+///
+/// let outcome: HandlerOutcome<usize, String> = HandlerOutcome::Fatal("disk is gone".to_string());
+/// assert!(outcome.is_fatal());
+/// assert_eq!(Some(Err("disk is gone".to_string())), outcome.into_result());
+/// ```
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum HandlerOutcome<T, E> {
+    /// The message was handled
+    Ok(T),
+    /// The message could not be handled, but the demon keeps serving
+    Err(E),
+    /// The message could not be handled, and the demon leaves hell
+    Fatal(E),
+    /// The message was not handled, and can be retried later
+    Decline
+}
+
+impl<T, E> HandlerOutcome<T, E> {
+    /// Indicates if the demon has to leave hell
+    pub fn is_fatal(&self) -> bool {
+        matches!(self, HandlerOutcome::Fatal(_))
+    }
+
+    /// Indicates if the demon declined the message
+    pub fn is_declined(&self) -> bool {
+        matches!(self, HandlerOutcome::Decline)
+    }
+
+    /// Converts the outcome into a result, where both kinds of errors are the same
+    ///
+    /// Declined messages have neither a value nor an error, so they give `None`.
+    pub fn into_result(self) -> Option<Result<T, E>> {
+        match self {
+            HandlerOutcome::Ok(value) => Some(Ok(value)),
+            HandlerOutcome::Err(e) | HandlerOutcome::Fatal(e) => Some(Err(e)),
+            HandlerOutcome::Decline => None
+        }
+    }
+}
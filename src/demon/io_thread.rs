@@ -0,0 +1,41 @@
+use crate::Demon;
+use std::future::Future;
+
+/// Byte stream demon trait
+///
+/// Protocol-agnostic counterpart of the websockets support, for demons driven by any byte stream, like tcp or unix sockets, or serial ports. Demons implementing this trait are spawned with [spawn_io](crate::Gate::spawn_io), and process both messages from apocalypse and the bytes read from the stream, one at a time. Writing is up to the demon, which usually owns the writing half of the stream.
+pub trait IoThread: Demon {
+    /// Function that is called with the bytes read from the stream
+    ///
+    /// Reads are not aligned with the writes of the other side, so a single call can hold part of a frame of the protocol, or several of them. Buffering incomplete frames is up to the demon.
+    ///
+    /// ```rust,no_run
+    /// use apocalypse::{Demon, IoThread};
+    ///
+    /// struct LineCounter {
+    ///     lines: usize
+    /// }
+    ///
+    /// impl Demon for LineCounter {
+    ///     type Input = ();
+    ///     type Output = usize;
+    ///     async fn handle(&mut self, _message: Self::Input) -> Self::Output {
+    ///         self.lines
+    ///     }
+    /// }
+    ///
+    /// impl IoThread for LineCounter {
+    ///     async fn on_bytes(&mut self, buf: &[u8]) {
+    ///         self.lines += buf.iter().filter(|byte| **byte == b'\n').count();
+    ///     }
+    /// }
+    /// ```
+    fn on_bytes(&mut self, buf: &[u8]) -> impl Future<Output = ()> + Send;
+
+    /// Function that is called once the stream ends, right before the demon leaves hell
+    ///
+    /// `clean` is true when the other side closed the stream, and false after a read error. By default, the function does nothing.
+    fn on_close(&mut self, _clean: bool) -> impl Future<Output = ()> + Send {
+        async {}
+    }
+}
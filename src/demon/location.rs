@@ -11,6 +11,15 @@ pub struct Location<E: ?Sized> {
     pub(crate) phantom: PhantomData<E>
 }
 
+impl<E> Location<E> {
+    /// Returns the raw address of the demon
+    ///
+    /// Raw addresses are only useful for type-erased operations, like [send_any](crate::Gate::send_any).
+    pub fn address(&self) -> usize {
+        self.address
+    }
+}
+
 impl<A> AsRef<Location<A>> for Location<A> {
     fn as_ref(&self) -> &Location<A> {
         &self
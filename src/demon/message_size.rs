@@ -0,0 +1,19 @@
+/// Message size trait
+///
+/// Inputs that can cheaply report their size can implement this trait, which allows to spawn demons with a maximum message size through [spawn_with_max_message_size](crate::Gate::spawn_with_max_message_size). Messages that exceed the limit are rejected by hell before reaching the demon's queue, which protects demons fed by network input from a single pathological message.
+///
+/// ```rust
+/// use apocalypse::MessageSize;
+///
+/// struct Payload(Vec<u8>);
+///
+/// impl MessageSize for Payload {
+///     fn size_hint(&self) -> usize {
+///         self.0.len()
+///     }
+/// }
+/// ```
+pub trait MessageSize {
+    /// Approximate size of the message, in bytes
+    fn size_hint(&self) -> usize;
+}
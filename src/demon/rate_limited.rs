@@ -0,0 +1,248 @@
+use crate::{Error, Clock, Demon, DemonContext, RequestResponseDemon, ShutdownReason};
+use std::future::Future;
+use std::sync::Arc;
+use futures::future::BoxFuture;
+use tokio::time::Instant;
+
+/// Rejection of a message by a [RateLimited](RateLimited) demon
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct RateLimitError;
+
+impl std::fmt::Display for RateLimitError {
+    fn fmt(&self, formatter: &mut std::fmt::Formatter) -> Result<(), std::fmt::Error> {
+        write!(formatter, "rate limit exceeded")
+    }
+}
+
+impl std::error::Error for RateLimitError {}
+
+/// Demon wrapper that limits the rate at which messages reach the inner demon
+///
+/// The limit is a token bucket, that holds up to `burst` tokens and gets `rate` tokens per second. Each message takes one token, and messages that find the bucket empty are answered with a rejection output instead of reaching the inner demon. The bucket starts full. Time is read from the [clock](crate::Clock) of hell once the demon is spawned, so that a [MockClock](crate::MockClock) can drive the refills. All other functions of the [Demon](Demon) trait are forwarded to the inner demon, which shares the address of the wrapper. For demons spawned with replicas, each replica has its own bucket.
+///
+/// ```rust
+/// use apocalypse::{Hell, Demon, RateLimited, RateLimitError};
+///
+/// #[derive(Debug, PartialEq)]
+/// enum Reply {
+///     Echo(String),
+///     SlowDown
+/// }
+///
+/// impl From<RateLimitError> for Reply {
+///     fn from(_: RateLimitError) -> Reply {
+///         Reply::SlowDown
+///     }
+/// }
+///
+/// struct EchoBot;
+///
+/// impl Demon for EchoBot {
+///     type Input = String;
+///     type Output = Reply;
+///     async fn handle(&mut self, message: Self::Input) -> Self::Output {
+///         Reply::Echo(message)
+///     }
+/// }
+///
+/// # #[tokio::main]
+/// # async fn main() {
+/// let (gate, join_handle) = Hell::new().ignite().await.unwrap();
+/// // One message per second, with bursts of up to two messages
+/// let location = gate.spawn(RateLimited::new(EchoBot, 1.0, 2)).await.unwrap();
+/// assert_eq!(Reply::Echo("a".to_string()), gate.send(&location, "a".to_string()).await.unwrap());
+/// assert_eq!(Reply::Echo("b".to_string()), gate.send(&location, "b".to_string()).await.unwrap());
+/// assert_eq!(Reply::SlowDown, gate.send(&location, "c".to_string()).await.unwrap());
+/// # }
+/// ```
+///
+/// Tests can refill the bucket without waiting.
+///
+/// ```rust
+/// use apocalypse::{HellBuilder, Demon, MockClock, RateLimited};
+/// use std::time::Duration;
+///
+/// struct Counter(usize);
+///
+/// impl Demon for Counter {
+///     type Input = ();
+///     type Output = Option<usize>;
+///     async fn handle(&mut self, _message: Self::Input) -> Self::Output {
+///         self.0 += 1;
+///         Some(self.0)
+///     }
+/// }
+///
+/// # #[tokio::main]
+/// # async fn main() {
+/// let clock = MockClock::new();
+/// let (gate, _join_handle) = HellBuilder::new().clock(clock.clone()).build().ignite().await.unwrap();
+/// let location = gate.spawn(RateLimited::with_rejection(Counter(0), 1.0, 1, || None)).await.unwrap();
+/// assert_eq!(Some(1), gate.send(&location, ()).await.unwrap());
+/// assert_eq!(None, gate.send(&location, ()).await.unwrap());
+/// clock.advance(Duration::from_secs(1));
+/// assert_eq!(Some(2), gate.send(&location, ()).await.unwrap());
+/// # }
+/// ```
+pub struct RateLimited<D: Demon> {
+    /// Inner demon
+    demon: D,
+    /// Tokens added per second
+    rate: f64,
+    /// Maximum amount of tokens
+    burst: f64,
+    /// Tokens currently available
+    tokens: f64,
+    /// Last time tokens were added
+    refilled: Instant,
+    /// Clock of hell, known once the demon is spawned
+    clock: Option<Arc<dyn Clock>>,
+    /// Output for rejected messages
+    rejection: fn() -> D::Output
+}
+
+impl<D: Demon> RateLimited<D> where D::Output: From<RateLimitError> {
+    /// Wraps a demon, rejecting messages with the conversion of [RateLimitError](RateLimitError)
+    ///
+    /// The burst is at least one message.
+    pub fn new(demon: D, rate: f64, burst: usize) -> RateLimited<D> {
+        RateLimited::with_rejection(demon, rate, burst, || RateLimitError.into())
+    }
+}
+
+impl<D: Demon> RateLimited<D> {
+    /// Wraps a demon, rejecting messages with the output produced by `rejection`
+    ///
+    /// The burst is at least one message.
+    ///
+    /// ```rust
+    /// use apocalypse::{Demon, RateLimited};
+    ///
+    /// struct Counter(usize);
+    ///
+    /// impl Demon for Counter {
+    ///     type Input = ();
+    ///     type Output = Result<usize, String>;
+    ///     async fn handle(&mut self, _message: Self::Input) -> Self::Output {
+    ///         self.0 += 1;
+    ///         Ok(self.0)
+    ///     }
+    /// }
+    ///
+    /// let _limited = RateLimited::with_rejection(Counter(0), 10.0, 5, || Err("too many requests".to_string()));
+    /// ```
+    pub fn with_rejection(demon: D, rate: f64, burst: usize, rejection: fn() -> D::Output) -> RateLimited<D> {
+        let burst = burst.max(1) as f64;
+        RateLimited {
+            demon,
+            rate,
+            burst,
+            tokens: burst,
+            refilled: Instant::now(),
+            clock: None,
+            rejection
+        }
+    }
+
+    /// Takes a token from the bucket, if there is any
+    fn take_token(&mut self) -> bool {
+        let now = self.clock.as_ref().map(|clock| clock.now()).unwrap_or_else(Instant::now);
+        let elapsed = now.duration_since(self.refilled).as_secs_f64();
+        self.tokens = (self.tokens + elapsed * self.rate).min(self.burst);
+        self.refilled = now;
+        if self.tokens >= 1.0 {
+            self.tokens -= 1.0;
+            true
+        } else {
+            false
+        }
+    }
+}
+
+impl<D: Demon> Demon for RateLimited<D> {
+    type Input = D::Input;
+    type Output = D::Output;
+
+    const TYPE_TAG: &'static str = D::TYPE_TAG;
+
+    async fn spawned_with_context(&mut self, ctx: DemonContext<Self>) {
+        // The bucket is full at spawn time, as seen by the clock of hell
+        self.refilled = ctx.now();
+        self.clock = Some(ctx.clock.clone());
+        self.demon.spawned_with_context(ctx.cast()).await;
+    }
+
+    // Inputs are not required to be `Send`, so the message cannot be held across the await
+    fn handle(&mut self, message: Self::Input) -> impl Future<Output = Self::Output> + Send {
+        let handled = if self.take_token() {
+            Ok(self.demon.handle(message))
+        } else {
+            Err(self.rejection)
+        };
+        async move {
+            match handled {
+                Ok(output) => output.await,
+                Err(rejection) => rejection()
+            }
+        }
+    }
+
+    fn handle_with_followup(&mut self, message: Self::Input) -> impl Future<Output = (Self::Output, Option<BoxFuture<'static, ()>>)> + Send {
+        let handled = if self.take_token() {
+            Ok(self.demon.handle_with_followup(message))
+        } else {
+            Err(self.rejection)
+        };
+        async move {
+            match handled {
+                Ok(handled) => handled.await,
+                Err(rejection) => (rejection(), None)
+            }
+        }
+    }
+
+    fn vanquished_with_context(self, ctx: DemonContext<Self>) -> impl Future<Output = ()> + Send {
+        self.demon.vanquished_with_context(ctx.cast())
+    }
+
+    fn vanquished_with_reason(self, ctx: DemonContext<Self>, reason: ShutdownReason) -> impl Future<Output = ()> + Send {
+        self.demon.vanquished_with_reason(ctx.cast(), reason)
+    }
+
+    fn on_spawn_rejected(self, reason: &Error) -> impl Future<Output = ()> + Send {
+        self.demon.on_spawn_rejected(reason)
+    }
+
+    fn aborted(&mut self) {
+        self.demon.aborted();
+    }
+
+    fn is_fatal(output: &Self::Output) -> bool {
+        D::is_fatal(output)
+    }
+
+    fn is_declined(output: &Self::Output) -> bool {
+        D::is_declined(output)
+    }
+
+    fn is_idempotent(input: &Self::Input) -> bool {
+        D::is_idempotent(input)
+    }
+
+    fn default_replicas() -> usize {
+        D::default_replicas()
+    }
+
+    #[cfg(feature = "full_log")]
+    fn id(&self) -> String {
+        self.demon.id()
+    }
+
+    #[cfg(feature = "full_log")]
+    fn multiple_id() -> &'static str {
+        D::multiple_id()
+    }
+}
+
+// Rejections never send messages, so the wrapper takes part in the same interactions as the inner demon
+impl<D: RequestResponseDemon> RequestResponseDemon for RateLimited<D> {}
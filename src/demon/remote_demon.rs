@@ -0,0 +1,24 @@
+use crate::Demon;
+use serde::{Serialize, de::DeserializeOwned};
+
+/// Marker for demons that can be reached from outside of the process
+///
+/// Both the input and the output of the demon need to be serializable, so that they can travel as bytes. Demons that implement this trait can be exposed through a [RemoteGate](crate::RemoteGate).
+///
+/// ```rust
+/// use apocalypse::{Demon, RemoteDemon};
+///
+/// struct EchoBot;
+///
+/// impl Demon for EchoBot {
+///     type Input = String;
+///     type Output = String;
+///     async fn handle(&mut self, message: Self::Input) -> Self::Output {
+///         message
+///     }
+/// }
+///
+/// // Strings are serializable, so the echo bot can be reached remotely
+/// impl RemoteDemon for EchoBot {}
+/// ```
+pub trait RemoteDemon: Demon where Self::Input: Serialize + DeserializeOwned, Self::Output: Serialize + DeserializeOwned {}
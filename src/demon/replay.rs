@@ -0,0 +1,40 @@
+use crate::Demon;
+
+/// Feeds recorded messages to a demon, in order, and returns its replies
+///
+/// Paired with [spawn_recorded](crate::Gate::spawn_recorded) and [message_history](crate::Gate::message_history), this reproduces the exact sequence of messages that led a stateful demon into a bug. The demon is not spawned, its handle function is called directly, one message after the other, so neither [spawned](crate::Demon::spawned) nor [vanquished](crate::Demon::vanquished) are called, and messages that the demon sends to other demons are not replayed.
+///
+/// ```rust
+/// use apocalypse::{Hell, Demon};
+///
+/// struct Accumulator(u64);
+///
+/// impl Demon for Accumulator {
+///     type Input = u64;
+///     type Output = u64;
+///     async fn handle(&mut self, message: Self::Input) -> Self::Output {
+///         self.0 += message;
+///         self.0
+///     }
+/// }
+///
+/// # #[tokio::main]
+/// # async fn main() {
+/// let (gate, _join_handle) = Hell::new().ignite().await.unwrap();
+/// let location = gate.spawn_recorded(Accumulator(0), 100).await.unwrap();
+/// for message in [3, 1, 4] {
+///     gate.send(&location, message).await.unwrap();
+/// }
+/// let history = gate.message_history(&location).await.unwrap();
+/// // A fresh demon goes through the same states
+/// let mut fresh = Accumulator(0);
+/// assert_eq!(vec![3, 4, 8], apocalypse::replay(&mut fresh, history).await);
+/// # }
+/// ```
+pub async fn replay<D: Demon, H: IntoIterator<Item = D::Input>>(demon: &mut D, history: H) -> Vec<D::Output> {
+    let mut outputs = Vec::new();
+    for input in history {
+        outputs.push(demon.handle(input).await);
+    }
+    outputs
+}
@@ -0,0 +1,20 @@
+/// Reason for a demon to leave hell, given to [vanquished_with_reason](crate::Demon::vanquished_with_reason)
+///
+/// Panics have no reason of their own, as a demon that panicked is gone before `vanquished_with_reason` could run.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ShutdownReason {
+    /// The demon was vanquished through a gate
+    Vanquished,
+    /// The parent of the demon was vanquished, and its children go first
+    ParentVanquished,
+    /// Hell is being extinguished
+    Extinguished,
+    /// The demon replied with a [fatal](crate::Demon::is_fatal) output
+    Fatal,
+    /// Another demon takes the place of this one, through a [handoff](crate::Gate::handoff) or a [replica restart](crate::Gate::restart_replica)
+    Replaced,
+    /// The replica was removed when its pool was scaled down
+    Retired,
+    /// The connection of a demon spawned with a websockets connection or a byte stream was closed, either cleanly or not
+    ConnectionLost
+}
@@ -0,0 +1,39 @@
+use cataclysm::ws::{WebSocketThread, WebSocketWriter};
+use futures::future::BoxFuture;
+use std::future::Future;
+
+/// Reconnection hook for websockets demons
+///
+/// Demons spawned with a [ReconnectPolicy](crate::ReconnectPolicy) need to implement this trait, to get hold of the writing half of each new connection.
+pub trait WebSocketReconnect: WebSocketThread {
+    /// Function that is called once the connection has been reestablished
+    ///
+    /// ```rust,no_run
+    /// use apocalypse::WebSocketReconnect;
+    /// use cataclysm::ws::{WebSocketThread, WebSocketWriter, Message};
+    ///
+    /// struct Client {
+    ///     writer: WebSocketWriter
+    /// }
+    ///
+    /// impl WebSocketThread for Client {
+    ///     type Output = ();
+    ///     async fn on_message(&mut self, _message: Message) {}
+    ///     async fn on_close(&mut self, _clean: bool) -> Self::Output {}
+    /// }
+    ///
+    /// impl WebSocketReconnect for Client {
+    ///     async fn on_reconnect(&mut self, writer: WebSocketWriter) {
+    ///         self.writer = writer;
+    ///     }
+    /// }
+    /// ```
+    fn on_reconnect(&mut self, writer: WebSocketWriter) -> impl Future<Output = ()> + Send;
+}
+
+/// Type-erased call to the reconnection hook, so that mini hells do not require the trait
+pub(crate) type ReconnectHook<D> = for<'a> fn(&'a mut D, WebSocketWriter) -> BoxFuture<'a, ()>;
+
+pub(crate) fn reconnect_hook<D: WebSocketReconnect>(demon: &mut D, writer: WebSocketWriter) -> BoxFuture<'_, ()> {
+    Box::pin(demon.on_reconnect(writer))
+}
@@ -0,0 +1,80 @@
+use crate::Demon;
+use cataclysm::ws::{WebSocketReader, Frame, WSError};
+use futures::stream::{self, Stream, BoxStream};
+use std::{pin::Pin, task::{Context, Poll}};
+
+/// Stream of incoming websockets frames
+///
+/// The stream yields every frame read from the connection. It finishes right after yielding a close frame, or after the first read error.
+pub struct WebSocketFrames {
+    /// Inner stream, driven by the websockets reader
+    inner: BoxStream<'static, Result<Frame, WSError>>
+}
+
+impl WebSocketFrames {
+    /// Wraps a websockets reader, assuming the handshake has already been performed
+    pub fn new(wsr: WebSocketReader) -> WebSocketFrames {
+        let inner = stream::unfold(Some(wsr), |wsr| async move {
+            let wsr = wsr?;
+            match wsr.try_read_frame().await {
+                Ok(frame) => {
+                    let next = if frame.message.is_close() { None } else { Some(wsr) };
+                    Some((Ok(frame), next))
+                },
+                Err(e) => Some((Err(e), None))
+            }
+        });
+
+        WebSocketFrames {
+            inner: Box::pin(inner)
+        }
+    }
+}
+
+impl Stream for WebSocketFrames {
+    type Item = Result<Frame, WSError>;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        self.inner.as_mut().poll_next(cx)
+    }
+}
+
+/// Stream-based websockets demon trait
+///
+/// Alternative to the callback model of `WebSocketThread`, for protocols where stateful framing is easier to express with stream combinators. Demons implementing this trait are spawned with [spawn_ws_stream](crate::Gate::spawn_ws_stream).
+pub trait WebSocketStreamThread: Demon {
+    /// Builds the processing pipeline for the incoming frames
+    ///
+    /// This function is called once, before the demon is spawned. Each item of the returned stream is delivered to the demon as a regular message, waiting for the demon to process it before pulling the next one. Once the stream finishes, the demon is vanquished.
+    ///
+    /// ```rust,no_run
+    /// use apocalypse::{Demon, WebSocketStreamThread, WebSocketFrames};
+    /// use cataclysm::ws::Message;
+    /// use futures::stream::{Stream, StreamExt};
+    ///
+    /// struct LineCounter {
+    ///     lines: usize
+    /// }
+    ///
+    /// impl Demon for LineCounter {
+    ///     type Input = Vec<String>;
+    ///     type Output = ();
+    ///     async fn handle(&mut self, message: Self::Input) -> Self::Output {
+    ///         self.lines += message.len();
+    ///     }
+    /// }
+    ///
+    /// impl WebSocketStreamThread for LineCounter {
+    ///     fn on_stream(&mut self, frames: WebSocketFrames) -> impl Stream<Item = Self::Input> + Send + 'static {
+    ///         // Text frames are processed in batches of 10
+    ///         frames.filter_map(|frame| async move {
+    ///             match frame.ok()?.message {
+    ///                 Message::Text(text) => Some(text),
+    ///                 _ => None
+    ///             }
+    ///         }).chunks(10)
+    ///     }
+    /// }
+    /// ```
+    fn on_stream(&mut self, frames: WebSocketFrames) -> impl Stream<Item = Self::Input> + Send + 'static;
+}
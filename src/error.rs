@@ -3,35 +3,155 @@
 pub enum Error {
     /// Error when using either oneshot or mpsc channels
     TokioSend(String),
+    /// Indicates that the broker of hell is gone, for example because hell was extinguished, so hell takes no more requests
+    BrokerGone,
     /// Error when receiving a confirmation of operation from a channel
     RecvError(std::sync::mpsc::RecvError),
     /// IO errors (for example, tcp errors)
     IO(std::io::Error),
     /// In theory, impossible error for Any to Input and Any to Output conversion
     WrongType,
+    /// The demon received a message that is not of its `Input` type, which is indicated here
+    WrongInputType {
+        expected: &'static str
+    },
     /// The replicas parameter supplied needs to be at least 1
     WrongReplicas,
     /// Indicates that there is no such demon with this location 
     InvalidLocation,
     /// Indicates that the address that is trying to be occupied is already taken
     OccupiedAddress,
+    /// Indicates that the name that is trying to be registered already belongs to another demon
+    NameTaken,
     /// Indicates that communication with the demon could not be stablished (probably a broken channel)
-    DemonCommunication
+    DemonCommunication,
+    /// Indicates that the declared dependencies between demons contain a cycle, formed by these addresses
+    DependencyCycle(Vec<usize>),
+    /// Indicates that the message exceeds the maximum message size of the demon
+    MessageTooLarge,
+    /// Indicates that the websockets handshake could not be performed
+    WebSocketHandshake(String),
+    /// Indicates that the types expected for the demon differ from its actual types
+    TypeMismatch {
+        expected: String,
+        found: String
+    },
+    /// Indicates that all the replicas of the demon are busy, and its internal queue is full, or that a request that cannot wait found its gate at the concurrency limit
+    Overloaded,
+    /// Indicates that the message was cancelled before the demon started handling it
+    Cancelled,
+    /// Indicates that the maximum amount of demons alive at the same time was reached
+    TooManyDemons,
+    /// Indicates that a message or a reply could not be serialized or deserialized
+    Serialization(String),
+    /// Indicates that the reply did not arrive in time
+    Timeout,
+    /// Indicates that there is no tokio runtime to run hell
+    NoRuntime,
+    /// Indicates that the demon declined the message, which can be retried later
+    Declined,
+    /// Indicates that a demon sent a request to itself from its handle function, which would never be answered
+    SelfSend,
+    /// Indicates that the message was denied by the interceptor of the broker
+    Denied,
+    /// Indicates that the demon panicked while handling the message
+    Panicked,
+    /// Indicates that the demon was not spawned with recording, so it has no message history
+    NotRecorded,
+    /// Indicates that the demon cannot be handed off, as only single demons can
+    HandoffUnsupported
+}
+
+impl Error {
+    /// Indicates if the operation that gave this error might succeed if it is attempted again
+    ///
+    /// Retryable errors come from conditions that can go away on their own, while the rest come from the request itself, or from the state of hell, and repeating the request gives the same error. This is the classification used by [send_retry](crate::Gate::send_retry).
+    ///
+    /// ```rust
+    /// use apocalypse::Error;
+    ///
+    /// assert!(Error::Timeout.is_retryable());
+    /// assert!(!Error::WrongType.is_retryable());
+    /// ```
+    pub fn is_retryable(&self) -> bool {
+        match self {
+            // The demon might be about to be spawned, or restarted at the same address
+            Error::InvalidLocation => true,
+            // The demon was leaving, and a replacement might take its address
+            Error::DemonCommunication => true,
+            // The demon was slow this time, which is not necessarily the case next time
+            Error::Timeout => true,
+            // Replicas, and slots of limited gates, become available again as replies are sent
+            Error::Overloaded => true,
+            // The demon itself asked for the message to be sent again later
+            Error::Declined => true,
+            // Demons leaving hell make room for new ones
+            Error::TooManyDemons => true,
+            // Channels to hell only fail once hell is gone
+            Error::TokioSend(_) | Error::RecvError(_) | Error::BrokerGone => false,
+            // Connection errors are left to the reconnection logic of the demons
+            Error::IO(_) | Error::WebSocketHandshake(_) => false,
+            // Types and sizes do not change between attempts
+            Error::WrongType | Error::WrongInputType{..} | Error::TypeMismatch{..} | Error::MessageTooLarge | Error::Serialization(_) => false,
+            // The request itself is wrong
+            Error::WrongReplicas | Error::OccupiedAddress | Error::NameTaken | Error::DependencyCycle(_) | Error::SelfSend | Error::NoRuntime => false,
+            // Someone decided against the message, on purpose
+            Error::Cancelled | Error::Denied => false,
+            // The same message is likely to trigger the same bug
+            Error::Panicked => false,
+            // Recording is chosen when the demon is spawned, and so is its kind
+            Error::NotRecorded | Error::HandoffUnsupported => false
+        }
+    }
+
+    /// Indicates if a message that failed with this error might have been handled by the demon anyway
+    ///
+    /// After an ambiguous error, sending the message again can make the demon handle it twice, so [send_retry](crate::Gate::send_retry) only does so for [idempotent](crate::Demon::is_idempotent) messages. A demon that panicked was handling the message, and might have had some of its effects already. All other errors are definite, the message either never reached the demon, or the demon refused it.
+    ///
+    /// ```rust
+    /// use apocalypse::Error;
+    ///
+    /// assert!(Error::Timeout.is_ambiguous());
+    /// assert!(Error::Panicked.is_ambiguous());
+    /// assert!(!Error::InvalidLocation.is_ambiguous());
+    /// ```
+    pub fn is_ambiguous(&self) -> bool {
+        // The demon went away, or the wait ended, without a reply, or the handling broke halfway
+        matches!(self, Error::DemonCommunication | Error::Timeout | Error::Panicked)
+    }
 }
 
 impl std::fmt::Display for Error {
     fn fmt(&self, formatter: &mut std::fmt::Formatter) -> Result<(), std::fmt::Error> {
-        let content = match self {
-            Error::TokioSend(detail) => format!("{}", detail),
-            Error::RecvError(detail) => format!("{}", detail),
-            Error::IO(e) => format!("{}", e),
-            Error::WrongType => format!("a correct `Any` to `Input` or `Any` to `Output` downcast failed... contact this library's developer"),
-            Error::WrongReplicas => format!("the minimum number of replicas needs to be 1"),
-            Error::InvalidLocation => format!("the location is no longer valid"),
-            Error::OccupiedAddress => format!("the location for this demon is already taken"),
-            Error::DemonCommunication => format!("message to the demon could not be delivered")
-        };
-        write!(formatter, "{}", content)
+        match self {
+            Error::TokioSend(detail) => write!(formatter, "{}", detail),
+            Error::BrokerGone => write!(formatter, "the broker is gone, hell takes no more requests"),
+            Error::RecvError(detail) => write!(formatter, "{}", detail),
+            Error::IO(e) => write!(formatter, "{}", e),
+            Error::WrongType => write!(formatter, "a correct `Any` to `Input` or `Any` to `Output` downcast failed... contact this library's developer"),
+            Error::WrongInputType{expected} => write!(formatter, "the demon received a message of the wrong type, expected {}", expected),
+            Error::WrongReplicas => write!(formatter, "the minimum number of replicas needs to be 1"),
+            Error::InvalidLocation => write!(formatter, "the location is no longer valid"),
+            Error::OccupiedAddress => write!(formatter, "the location for this demon is already taken"),
+            Error::NameTaken => write!(formatter, "the name for this demon is already taken"),
+            Error::DemonCommunication => write!(formatter, "message to the demon could not be delivered"),
+            Error::DependencyCycle(addresses) => write!(formatter, "the declared dependencies contain a cycle, {}", addresses.iter().map(|address| format!("d-{}", address)).collect::<Vec<_>>().join(" -> ")),
+            Error::MessageTooLarge => write!(formatter, "message exceeds the maximum message size of the demon"),
+            Error::WebSocketHandshake(detail) => write!(formatter, "websockets handshake failed, {}", detail),
+            Error::TypeMismatch{expected, found} => write!(formatter, "expected {}, but found {}", expected, found),
+            Error::Overloaded => write!(formatter, "all replicas of the demon are busy and its queue is full, or the gate is at its concurrency limit"),
+            Error::Cancelled => write!(formatter, "the message was cancelled before being handled"),
+            Error::TooManyDemons => write!(formatter, "the maximum amount of demons in hell was reached"),
+            Error::Serialization(detail) => write!(formatter, "serialization failed, {}", detail),
+            Error::Timeout => write!(formatter, "the reply did not arrive in time"),
+            Error::NoRuntime => write!(formatter, "hell must be ignited from within a tokio runtime"),
+            Error::Declined => write!(formatter, "the demon declined the message, try again later"),
+            Error::SelfSend => write!(formatter, "a demon cannot wait for its own reply while handling a message"),
+            Error::Denied => write!(formatter, "the message was denied by the broker"),
+            Error::Panicked => write!(formatter, "the demon panicked while handling the message"),
+            Error::NotRecorded => write!(formatter, "the demon does not record its messages"),
+            Error::HandoffUnsupported => write!(formatter, "only single demons can be handed off")
+        }
     }
 }
 
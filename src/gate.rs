@@ -1,10 +1,56 @@
-use crate::{Error, Demon, Location, hell::{MiniHell, MultipleMiniHell, HellInstruction, HellStats}};
-use tokio::sync::{mpsc::UnboundedSender, oneshot::{self}};
+use crate::{Error, Clock, Demon, DemonContext, Location, BlockingDemon, Blocking, MessageSize, IoThread, RequestResponseDemon, trace, hell::{MiniHell, MiniIoHell, MultipleMiniHell, Keyed, Timed, HellInstruction, Observer, HellStats, RegistrySnapshot, ExtinguishReport, SizeGuard, Intake, DemonTypes, DemonChannels, HandleTime}};
+#[cfg(feature = "record")]
+use crate::hell::History;
+use tokio::sync::{broadcast, watch, mpsc::{UnboundedSender, WeakUnboundedSender}, oneshot::{self}, Semaphore, OwnedSemaphorePermit};
 use std::marker::PhantomData;
+use std::collections::VecDeque;
+use std::sync::Arc;
+use std::any::Any;
+use std::time::Duration;
+use tokio::io::AsyncRead;
 #[cfg(feature = "ws")]
-use cataclysm::ws::{WebSocketThread, WebSocketReader};
+use cataclysm::ws::{WebSocketThread, WebSocketReader, WebSocketWriter};
 #[cfg(feature = "ws")]
-use crate::hell::MiniWSHell;
+use tokio::net::TcpStream;
+#[cfg(feature = "ws")]
+use crate::{WebSocketStreamThread, WebSocketFrames, WebSocketReconnect, demon::{ReconnectHook, reconnect_hook}, hell::MiniWSHell};
+use futures::{Stream, StreamExt};
+
+pub use self::overflow_policy::OverflowPolicy;
+mod overflow_policy;
+pub use self::retry_policy::RetryPolicy;
+mod retry_policy;
+pub use self::multi_spawn_result::MultiSpawnResult;
+mod multi_spawn_result;
+pub use self::send_future::SendFuture;
+mod send_future;
+pub use self::reply_receiver::ReplyReceiver;
+mod reply_receiver;
+pub use self::cancellation_token::CancellationToken;
+mod cancellation_token;
+pub use self::wiring_builder::{WiringBuilder, Wire};
+mod wiring_builder;
+pub use self::spawn_options::{SpawnOptions, MessageOrder};
+mod spawn_options;
+#[cfg(feature = "remote")]
+pub use self::remote_gate::{RemoteGate, Envelope};
+#[cfg(feature = "remote")]
+mod remote_gate;
+#[cfg(feature = "ws")]
+pub use self::ws_options::{WsOptions, WsPriority, ReconnectPolicy};
+#[cfg(feature = "ws")]
+pub(crate) use self::ws_options::Connection;
+#[cfg(feature = "ws")]
+mod ws_options;
+#[cfg(feature = "ws")]
+mod ws_handshake;
+
+/// Error for requests that the broker can no longer take, or answer
+///
+/// The channels to the broker only close once its loop is over, either because hell was extinguished, or because it stopped for any other reason.
+pub(crate) fn broker_gone<E>(_: E) -> Error {
+    Error::BrokerGone
+}
 
 /// ## Gate structure
 ///
@@ -15,30 +61,263 @@ use crate::hell::MiniWSHell;
 /// * There are messages still in the queue to be processed
 /// * There is at least one gate alive
 ///
-/// That is, dropping all gates finalizes hell's execution. Due to the fact that a gate is required to send messages, and some Demons will have a gate among their fields, you have to remove all Demons in posession of a Gate to shutdown Hell gracefully. This structure cannot be created without the help of a [Hell](crate::Hell) instance.
+/// That is, dropping all gates finalizes hell's execution. Due to the fact that a gate is required to send messages, and some Demons will have a gate among their fields, you have to remove all Demons in posession of a Gate to shutdown Hell gracefully. Demons that only send messages occasionally can hold a [WeakGate](crate::WeakGate) instead, which does not keep hell alive. This structure cannot be created without the help of a [Hell](crate::Hell) instance.
+///
+/// Messages sent through one gate reach the broker in the order they were sent. Control instructions do not, as they go through a priority lane that the broker serves first, so that they stay responsive while hell is flooded with messages. They are the vanquish and extinguish functions, [stats](Gate::stats), [dump_registry](Gate::dump_registry), [set_default_timeout](Gate::set_default_timeout) and [health_sweep](Gate::health_sweep). A vanquish can thus overtake messages sent right before it, which then give `Error::InvalidLocation`. This is no different from messages that reached the demon but were still waiting in its mailbox, which are rejected the same way. Only a message whose reply was awaited is certain to have been handled before the demon leaves.
+///
+/// ```rust
+/// use apocalypse::{Hell, Demon, Error};
+///
+/// struct EchoBot;
+///
+/// impl Demon for EchoBot {
+///     type Input = u64;
+///     type Output = u64;
+///     async fn handle(&mut self, message: Self::Input) -> Self::Output {
+///         message
+///     }
+/// }
+///
+/// # #[tokio::main(flavor = "current_thread")]
+/// # async fn main() {
+/// let (gate, _join_handle) = Hell::new().ignite().await.unwrap();
+/// let location = gate.spawn(EchoBot).await.unwrap();
+/// assert_eq!(1, gate.send(&location, 1).await.unwrap());
+/// // The broker has not seen the message yet when the vanquish request arrives
+/// let reply = gate.send_with_responder(&location, 2).unwrap();
+/// gate.vanquish(&location).await.unwrap();
+/// assert!(matches!(reply.await.unwrap(), Err(Error::InvalidLocation)));
+/// # }
+/// ```
 pub struct Gate {
     /// Communication with main hell instance
     pub(crate) hell_channel: UnboundedSender<HellInstruction>,
-    #[cfg(feature = "ws")]
-    /// Endpoint to send locations from extinct demons due to websocket close connection
-    pub(crate) on_close_tx: UnboundedSender<usize>
+    /// Control plane communication with main hell instance, which takes precedence over the main channel
+    pub(crate) priority_channel: UnboundedSender<HellInstruction>,
+    /// Endpoint to send locations from demons that finished on their own
+    pub(crate) on_close_tx: UnboundedSender<(usize, usize)>,
+    /// Clock of hell, handed to the demons through their context
+    pub(crate) clock: Arc<dyn Clock>,
+    /// Permits for outstanding requests, shared by all the clones of a limited gate
+    pub(crate) concurrency: Option<Arc<Semaphore>>,
+    /// Heads-up that hell is being extinguished, handed to the demons through their context
+    pub(crate) shutdown: broadcast::Sender<()>,
+    /// Time spent by demons inside their handle functions, handed to the demons through their context
+    pub(crate) handle_time: Arc<HandleTime>
 }
 
 impl Clone for Gate {
     fn clone(&self) -> Self {
         Gate {
             hell_channel: self.hell_channel.clone(),
-            #[cfg(feature = "ws")]
-            on_close_tx: self.on_close_tx.clone()
+            priority_channel: self.priority_channel.clone(),
+            on_close_tx: self.on_close_tx.clone(),
+            clock: self.clock.clone(),
+            concurrency: self.concurrency.clone(),
+            shutdown: self.shutdown.clone(),
+            handle_time: self.handle_time.clone()
+        }
+    }
+}
+
+/// Gates are equal when they lead to the same hell, see [same_hell](Gate::same_hell)
+impl PartialEq for Gate {
+    fn eq(&self, other: &Gate) -> bool {
+        self.same_hell(other)
+    }
+}
+
+impl Eq for Gate {}
+
+/// ## Weak gate structure
+///
+/// Version of a [Gate](crate::Gate) that does not keep hell alive, obtained through [downgrade](crate::Gate::downgrade). It has to be upgraded to a gate in order to communicate with hell, which only works while hell is still alive. Store weak gates inside of demons, so they do not prevent hell from shutting down once the rest of the gates are dropped.
+pub struct WeakGate {
+    /// Communication with main hell instance
+    hell_channel: WeakUnboundedSender<HellInstruction>,
+    /// Control plane communication with main hell instance
+    priority_channel: WeakUnboundedSender<HellInstruction>,
+    /// Endpoint to send locations from demons that finished on their own
+    on_close_tx: WeakUnboundedSender<(usize, usize)>,
+    /// Clock of hell, which does not keep it alive either
+    clock: Arc<dyn Clock>,
+    /// Permits for outstanding requests of the gate this one comes from
+    concurrency: Option<Arc<Semaphore>>,
+    /// Heads-up that hell is being extinguished, which does not keep it alive either
+    shutdown: broadcast::Sender<()>,
+    /// Time spent by demons inside their handle functions
+    handle_time: Arc<HandleTime>
+}
+
+impl Clone for WeakGate {
+    fn clone(&self) -> Self {
+        WeakGate {
+            hell_channel: self.hell_channel.clone(),
+            priority_channel: self.priority_channel.clone(),
+            on_close_tx: self.on_close_tx.clone(),
+            clock: self.clock.clone(),
+            concurrency: self.concurrency.clone(),
+            shutdown: self.shutdown.clone(),
+            handle_time: self.handle_time.clone()
         }
     }
 }
 
+impl WeakGate {
+    /// Attempts to obtain a gate, which only works while hell is still alive
+    ///
+    /// The obtained gate keeps hell alive as any other gate, so it should be dropped as soon as it is no longer required.
+    pub fn upgrade(&self) -> Option<Gate> {
+        Some(Gate {
+            hell_channel: self.hell_channel.upgrade()?,
+            priority_channel: self.priority_channel.upgrade()?,
+            on_close_tx: self.on_close_tx.upgrade()?,
+            clock: self.clock.clone(),
+            concurrency: self.concurrency.clone(),
+            shutdown: self.shutdown.clone(),
+            handle_time: self.handle_time.clone()
+        })
+    }
+}
+
 impl Gate {
+    /// Indicates if both gates lead to the same hell instance
+    ///
+    /// All the gates that come from the same [ignite](crate::Hell::ignite) call lead to the same hell, no matter how they were obtained, and even if they have a different [concurrency limit](Gate::with_max_concurrency).
+    ///
+    /// ```rust
+    /// use apocalypse::Hell;
+    ///
+    /// # #[tokio::main]
+    /// # async fn main() {
+    /// let (gate, _join_handle) = Hell::new().ignite().await.unwrap();
+    /// let (other_gate, _other_join_handle) = Hell::new().ignite().await.unwrap();
+    /// assert!(gate.same_hell(&gate.clone()));
+    /// assert!(gate.same_hell(&gate.with_max_concurrency(1)));
+    /// assert!(!gate.same_hell(&other_gate));
+    /// // Equality means the same
+    /// assert!(gate == gate.clone());
+    /// assert!(gate != other_gate);
+    /// # }
+    /// ```
+    pub fn same_hell(&self, other: &Gate) -> bool {
+        self.hell_channel.same_channel(&other.hell_channel)
+    }
+
+    /// Creates a weak version of this gate
+    ///
+    /// See [WeakGate](crate::WeakGate) for more details.
+    ///
+    /// ```rust
+    /// use apocalypse::{Hell, Demon, WeakGate, Location};
+    ///
+    /// struct Forwarder {
+    ///     gate: WeakGate,
+    ///     target: Option<Location<Forwarder>>
+    /// }
+    ///
+    /// impl Demon for Forwarder {
+    ///     type Input = ();
+    ///     type Output = ();
+    ///     async fn handle(&mut self, message: Self::Input) -> Self::Output {
+    ///         // The gate only lives during this call
+    ///         if let (Some(gate), Some(target)) = (self.gate.upgrade(), &self.target) {
+    ///             let _ = gate.send_and_ignore(target, message).await;
+    ///         }
+    ///     }
+    /// }
+    ///
+    /// # #[tokio::main]
+    /// # async fn main() {
+    /// let (gate, join_handle) = Hell::new().ignite().await.unwrap();
+    /// let target = gate.spawn(Forwarder{gate: gate.downgrade(), target: None}).await.unwrap();
+    /// let forwarder = gate.spawn(Forwarder{gate: gate.downgrade(), target: Some(target)}).await.unwrap();
+    /// gate.send_and_ignore(&forwarder, ()).await.unwrap();
+    /// // Demons with weak gates do not keep hell alive
+    /// drop(gate);
+    /// join_handle.await.unwrap();
+    /// # }
+    /// ```
+    pub fn downgrade(&self) -> WeakGate {
+        WeakGate {
+            hell_channel: self.hell_channel.downgrade(),
+            priority_channel: self.priority_channel.downgrade(),
+            on_close_tx: self.on_close_tx.downgrade(),
+            clock: self.clock.clone(),
+            concurrency: self.concurrency.clone(),
+            shutdown: self.shutdown.clone(),
+            handle_time: self.handle_time.clone()
+        }
+    }
+
+    /// Creates a clone of this gate that can only have `max_concurrency` requests waiting for a reply at the same time
+    ///
+    /// Requests sent through the limited gate, or any of its clones, wait for one of the outstanding ones to be answered once the limit is reached, which keeps a single caller from flooding hell. The limit only applies to functions that wait for a reply, such as [send](crate::Gate::send), and not to [send_and_ignore](crate::Gate::send_and_ignore). Functions that cannot wait, such as [send_with_responder](crate::Gate::send_with_responder), give `Error::Overloaded` at the limit instead. Unlike mailbox bounds, which protect a demon from all of its callers, this limits a single caller. The gate this one was created from keeps its own limit, if any, and demons spawned through the limited gate do not inherit it. The limit is at least one request.
+    ///
+    /// ```rust
+    /// use apocalypse::{Hell, Demon};
+    /// use std::time::Duration;
+    ///
+    /// struct Sleeper;
+    ///
+    /// impl Demon for Sleeper {
+    ///     type Input = ();
+    ///     type Output = ();
+    ///     async fn handle(&mut self, _message: Self::Input) -> Self::Output {
+    ///         tokio::time::sleep(Duration::from_millis(50)).await;
+    ///     }
+    /// }
+    ///
+    /// # #[tokio::main]
+    /// # async fn main() {
+    /// let (gate, join_handle) = Hell::new().ignite().await.unwrap();
+    /// let location = gate.spawn_multiple(|| Sleeper, 3).await.unwrap();
+    /// // Three replicas could handle all requests at once, but this gate only lets one through
+    /// let limited = gate.with_max_concurrency(1);
+    /// let start = tokio::time::Instant::now();
+    /// let (first, second, third) = tokio::join!(
+    ///     limited.send(&location, ()),
+    ///     limited.send(&location, ()),
+    ///     limited.send(&location, ())
+    /// );
+    /// assert!(first.is_ok() && second.is_ok() && third.is_ok());
+    /// assert!(start.elapsed() >= Duration::from_millis(150));
+    /// # }
+    /// ```
+    pub fn with_max_concurrency(&self, max_concurrency: usize) -> Gate {
+        Gate {
+            concurrency: Some(Arc::new(Semaphore::new(max_concurrency.max(1)))),
+            ..self.clone()
+        }
+    }
+
+    /// Waits until the gate can have one more outstanding request
+    ///
+    /// The permit has to be held until the reply arrives. Gates without a limit never wait.
+    async fn permit(&self) -> Option<OwnedSemaphorePermit> {
+        match &self.concurrency {
+            // The semaphore is never closed
+            Some(concurrency) => concurrency.clone().acquire_owned().await.ok(),
+            None => None
+        }
+    }
+
+    /// Takes a slot for one more outstanding request without waiting, for functions that cannot wait
+    ///
+    /// Gates that already reached their limit give `Error::Overloaded`.
+    fn try_permit(&self) -> Result<Option<OwnedSemaphorePermit>, Error> {
+        match &self.concurrency {
+            Some(concurrency) => concurrency.clone().try_acquire_owned().map(Some).map_err(|_| Error::Overloaded),
+            None => Ok(None)
+        }
+    }
+
     /// Sends a message to a demon
     ///
     /// In this actor implementaton, all messages do have to return some kind of reply. Be aware that this decision can lead to lockups if used carelessly (as the mutable access that the handle function has to the demons blocks the message processing loop until each handle call ends). If you manage to create a message-cycle (that is, a chain of requests that has as element the same actor twice), then you will end up in a lockup situation. Try to use this function **only** when necessary, keep [send_and_ignore](crate::Gate::send_and_ignore) as your first option, unless you carefully thought about the message-chains in your software.
     ///
+    /// The most common lockup, a demon sending a request to itself from its own handle function, is detected, and gives `Error::SelfSend` right away. The same check applies to every function that returns a reply, such as [send_with_responder](crate::Gate::send_with_responder) or [send_poll](crate::Gate::send_poll). Demons spawned with replicas are not checked, as another replica may answer.
+    ///
     /// ```rust
     /// use apocalypse::{Hell, Demon};
     ///
@@ -60,23 +339,55 @@ impl Gate {
     /// let message = gate.send(&location, "Hallo, welt!").await.unwrap();
     /// # }
     /// ```
+    ///
+    /// A demon that waits for itself gets an error instead of hanging forever.
+    ///
+    /// ```rust
+    /// use apocalypse::{Hell, Demon, Gate, Location, Error};
+    ///
+    /// struct Narcissus {
+    ///     gate: Gate
+    /// }
+    ///
+    /// impl Demon for Narcissus {
+    ///     type Input = Location<Narcissus>;
+    ///     type Output = bool;
+    ///     async fn handle(&mut self, myself: Self::Input) -> Self::Output {
+    ///         matches!(self.gate.send(&myself, myself.clone()).await, Err(Error::SelfSend))
+    ///             && matches!(self.gate.send_with_responder(&myself, myself.clone()), Err(Error::SelfSend))
+    ///             && matches!(self.gate.send_poll(&myself, myself.clone()), Err(Error::SelfSend))
+    ///             && matches!(self.gate.send_keyed_multiple(&myself, myself.clone(), 0).await, Err(Error::SelfSend))
+    ///     }
+    /// }
+    ///
+    /// # #[tokio::main]
+    /// # async fn main() {
+    /// let (gate, _join_handle) = Hell::new().ignite().await.unwrap();
+    /// let location = gate.spawn(Narcissus{gate: gate.clone()}).await.unwrap();
+    /// assert!(gate.send(&location, location.clone()).await.unwrap());
+    /// # }
+    /// ```
     pub async fn send<A: AsRef<Location<D>>, D, I, O>(&self, location: A, message: I) -> Result<O, Error> 
         where 
             D: Demon<Input = I, Output = O>,
             I: 'static + Send,
             O: 'static + Send {
+        let address = location.as_ref().address;
+        trace::check_self_send(address)?;
+        let _permit = self.permit().await;
         // async channel to get the response
         let (tx, rx) = oneshot::channel();
-        let address = location.as_ref().address;
         
         self.hell_channel.send(HellInstruction::Message {
             tx,
             address,
             ignore: false,
-            input: Box::new(message)
-        }).map_err(|e| Error::TokioSend(format!("hell channel error, {}", e)))?;
+            trace: trace::inherit_or_new(),
+            input: Box::new(message),
+            types: None
+        }).map_err(broker_gone)?;
 
-        let any_output = rx.await.map_err(|s| Error::TokioSend(format!("{}", s)))??;
+        let any_output = rx.await.map_err(broker_gone)??;
 
         if let Ok(output) = any_output.downcast::<O>() {
             Ok(*output)
@@ -85,240 +396,2620 @@ impl Gate {
         }
     }
 
-    /// Sends a message to a demon, and ignore the result.
+    /// Sends a message to a demon marked as [RequestResponseDemon](crate::RequestResponseDemon)
     ///
-    /// This is your go-to function when you don't have to wait for the actor to give you a response back. This function fails if the request could not be delivered to the demon. If you absolutely require to call this function without awaiting, use `tokio::spawn`.
+    /// Works exactly as [send](Gate::send), but only compiles for demons that are explicitly marked as safe to await, see [RequestResponseDemon](crate::RequestResponseDemon).
+    pub async fn request<A: AsRef<Location<D>>, D, I, O>(&self, location: A, message: I) -> Result<O, Error>
+        where
+            D: RequestResponseDemon<Input = I, Output = O>,
+            I: 'static + Send,
+            O: 'static + Send {
+        self.send(location, message).await
+    }
+
+    /// Sends a message to a demon, retrying when the demon cannot be reached
+    ///
+    /// Works like [send](crate::Gate::send), but attempts that fail with a [retryable](crate::Error::is_retryable) error, like `Error::InvalidLocation` or `Error::DemonCommunication`, are repeated after a wait given by the [RetryPolicy](crate::RetryPolicy), which is useful for demons that are about to be spawned or restarted at the same address. Any other error, like `Error::WrongType`, is returned right away, as are successful replies. When the policy runs out of attempts, the error of the last one is returned. The message is cloned for every attempt.
+    ///
+    /// Errors after which the demon might have handled the message already, like `Error::DemonCommunication`, are [ambiguous](crate::Error::is_ambiguous), and are only retried if the demon declares the message [idempotent](crate::Demon::is_idempotent). Otherwise, they are returned right away, so that messages are never handled twice by accident.
     ///
     /// ```rust
-    /// use apocalypse::{Hell, Demon};
+    /// use apocalypse::{Hell, Demon, RetryPolicy, WiringBuilder};
+    /// use std::time::Duration;
     ///
-    /// struct PrintBot;
+    /// struct EchoBot;
     ///
-    /// impl Demon for PrintBot {
-    ///     type Input = &'static str;
-    ///     type Output = ();
-    ///     async fn handle(&mut self, message: Self::Input) {
-    ///         println!("{}", message);
+    /// impl Demon for EchoBot {
+    ///     type Input = String;
+    ///     type Output = String;
+    ///     async fn handle(&mut self, message: Self::Input) -> Self::Output {
+    ///         message
     ///     }
     /// }
     ///
     /// # #[tokio::main]
     /// # async fn main() {
-    /// let (gate, jh) = Hell::new().ignite().await.unwrap();
-    /// let location = gate.spawn(PrintBot).await.unwrap();
-    /// // Use the send and ignore function to send a message without waiting for it
-    /// gate.send_and_ignore(&location, "Hallo, welt!").await.unwrap();
+    /// let (gate, _join_handle) = Hell::new().ignite().await.unwrap();
+    /// // The location exists, but the demon is only spawned a bit later
+    /// let mut wiring = WiringBuilder::new(&gate);
+    /// let location = wiring.add(EchoBot).await.unwrap();
+    /// tokio::spawn(async move {
+    ///     tokio::time::sleep(Duration::from_millis(30)).await;
+    ///     wiring.build().await.unwrap();
+    /// });
+    /// let policy = RetryPolicy::new(10).base_delay(Duration::from_millis(10)).max_delay(Duration::from_millis(20));
+    /// let reply = gate.send_retry(&location, "hello".to_string(), policy).await.unwrap();
+    /// assert_eq!("hello", reply);
     /// # }
     /// ```
-    pub async fn send_and_ignore<D, I, O>(&self, location: &Location<D>, message: I) -> Result<(), Error> 
-        where 
+    pub async fn send_retry<A: AsRef<Location<D>>, D, I, O>(&self, location: A, message: I, policy: RetryPolicy) -> Result<O, Error>
+        where
             D: Demon<Input = I, Output = O>,
-            I: 'static + Send,
+            I: 'static + Send + Clone,
             O: 'static + Send {
-        // async channel to get the response
-        let (tx, rx) = oneshot::channel();
-        let address = location.address;
-        
-        self.hell_channel.send(HellInstruction::Message {
-            tx,
-            address,
-            ignore: true,
-            input: Box::new(message)
-        }).map_err(|e| Error::TokioSend(format!("hell channel error, {}", e)))?;
-        rx.await.map_err(|s| Error::TokioSend(format!("{}", s)))??;
-        Ok(())
+        let location = location.as_ref();
+        let idempotent = D::is_idempotent(&message);
+        let mut attempt = 0;
+        loop {
+            match self.send(location, message.clone()).await {
+                Err(e) if e.is_retryable() && (idempotent || !e.is_ambiguous()) => {
+                    attempt += 1;
+                    if attempt >= policy.max_attempts() {
+                        return Err(e);
+                    }
+                    tokio::time::sleep(policy.delay(attempt - 1)).await;
+                },
+                result => return result
+            }
+        }
     }
 
-    /// Spawns a demon in hell
+    /// Sends a message to a demon, and also returns how long the demon took to handle it
+    ///
+    /// Works like [send](crate::Gate::send), but the reply comes together with the time spent in the [handle](crate::Demon::handle) function of the demon, measured by the demon itself. The time that the message waited in the mailbox of the demon, or for a free replica, is not included, so callers can tell a slow demon apart from a busy one.
     ///
     /// ```rust
     /// use apocalypse::{Hell, Demon};
+    /// use std::time::Duration;
     ///
-    /// struct Basic;
+    /// struct Sleeper;
     ///
-    /// impl Demon for Basic {
-    ///     type Input = String;
-    ///     type Output = ();
-    ///     async fn handle(&mut self, message: Self::Input) -> Self::Output {
-    ///         println!("Hello, world!");
+    /// impl Demon for Sleeper {
+    ///     type Input = u64;
+    ///     type Output = u64;
+    ///     async fn handle(&mut self, millis: Self::Input) -> Self::Output {
+    ///         tokio::time::sleep(Duration::from_millis(millis)).await;
+    ///         millis
     ///     }
     /// }
     ///
     /// # #[tokio::main]
     /// # async fn main() {
-    /// let (gate, join_handle) = Hell::new().ignite().await.unwrap();
-    /// // we spawn the demon
-    /// let _location = gate.spawn(Basic).await.unwrap();
-    /// // Do something
+    /// let (gate, _join_handle) = Hell::new().ignite().await.unwrap();
+    /// let location = gate.spawn(Sleeper).await.unwrap();
+    /// let (reply, handled) = gate.send_timed(&location, 50).await.unwrap();
+    /// assert_eq!(50, reply);
+    /// assert!(handled >= Duration::from_millis(50));
     /// # }
     /// ```
-    pub async fn spawn<D: 'static + Demon<Input = I, Output = O>, I: 'static + Send, O: 'static + Send>(&self, demon: D) -> Result<Location<D>, Error> {
-        // First return channel, to get a valid address
+    pub async fn send_timed<A: AsRef<Location<D>>, D, I, O>(&self, location: A, message: I) -> Result<(O, Duration), Error>
+        where
+            D: Demon<Input = I, Output = O>,
+            I: 'static + Send,
+            O: 'static + Send {
+        let address = location.as_ref().address;
+        trace::check_self_send(address)?;
+        let _permit = self.permit().await;
         let (tx, rx) = oneshot::channel();
 
-        // We request an address
-        self.hell_channel.send(HellInstruction::CreateAddress {
-            tx
-        }).map_err(|e| Error::TokioSend(format!("{}", e)))?;
-        let address = rx.await.map_err(|s| Error::TokioSend(format!("{}", s)))?;
-
-        let location = Location {
+        self.hell_channel.send(HellInstruction::Message {
+            tx,
             address,
-            phantom: PhantomData
-        };
-
-        // We spawn the demon in a mini hell instance
-        let demon_channels = MiniHell::spawn(demon, location.clone());
-
-        // Second return channel, for knowing if the registration was successful
-        let (tx, rx) = oneshot::channel();
+            ignore: false,
+            trace: trace::inherit_or_new(),
+            input: Box::new(Timed(Box::new(message))),
+            types: None
+        }).map_err(broker_gone)?;
 
-        // We attempt the registration process
-        self.hell_channel.send(HellInstruction::RegisterDemon {
-            address,
-            demon_channels,
-            tx
-        }).map_err(|e| Error::TokioSend(format!("{}", e)))?;
+        let any_output = rx.await.map_err(broker_gone)??;
 
-        // If it returned true, then everything is ok
-        rx.await.map_err(|s| Error::TokioSend(format!("{}", s)))?.map(move |_| location)
+        match any_output.downcast::<(Box<dyn Any + Send>, Duration)>().map(|timed| *timed) {
+            Ok((output, handled)) => match output.downcast::<O>() {
+                Ok(output) => Ok((*output, handled)),
+                Err(_) => Err(Error::WrongType)
+            },
+            Err(_) => Err(Error::WrongType)
+        }
     }
 
-    /// Spawns multiple demons in Hell, that reply to the same [Location](Location)
+    /// Sends a message to a demon, verifying at runtime that the demon has the expected types
     ///
-    /// This might be useful if you have one task that consumes some time to be processed, and you can also parallelize. The load balancing method is just using whichever Demon is free at the moment, in a sequential order (that is, sequential but skipping if one is busy).
+    /// Behaves like [send](crate::Gate::send), but the `Input` and `Output` types of the location are checked against the ones of the demon before the message is delivered. This catches locations that were built with the wrong type, for example from an address that got reused, and reports it as `Error::TypeMismatch` with a description of both demon types, instead of a generic downcast failure.
     ///
     /// ```rust
     /// use apocalypse::{Hell, Demon};
     ///
-    /// struct Basic;
+    /// struct EchoBot;
     ///
-    /// impl Demon for Basic {
-    ///     type Input = String;
-    ///     type Output = ();
+    /// impl Demon for EchoBot {
+    ///     type Input = &'static str;
+    ///     type Output = String;
     ///     async fn handle(&mut self, message: Self::Input) -> Self::Output {
-    ///         println!("Hello, world!");
+    ///         message.to_string()
     ///     }
     /// }
     ///
     /// # #[tokio::main]
     /// # async fn main() {
-    /// let (gate, join_handle) = Hell::new().ignite().await.unwrap();
-    /// // Demon factory, needs to implement FnMut
-    /// let basic_factory = || {
-    ///     Basic
-    /// };
-    /// // We spawn three instances of the demon
-    /// let _location = gate.spawn_multiple(basic_factory, 3).await.unwrap();
-    /// // Do something
+    /// let (gate, jh) = Hell::new().ignite().await.unwrap();
+    /// let location = gate.spawn(EchoBot).await.unwrap();
+    /// let message = gate.request_reply_typed(&location, "Hallo, welt!").await.unwrap();
+    /// assert_eq!("Hallo, welt!", message);
     /// # }
     /// ```
-    pub async fn spawn_multiple<D: 'static + Demon<Input = I, Output = O>, I: 'static + Send, O: 'static + Send, F: FnMut() -> D>(&self, demon_factory: F, replicas: usize) -> Result<Location<D>, Error> {
-        // First return channel, to get a valid address
+    pub async fn request_reply_typed<A: AsRef<Location<D>>, D, I, O>(&self, location: A, message: I) -> Result<O, Error> 
+        where 
+            D: Demon<Input = I, Output = O>,
+            I: 'static + Send,
+            O: 'static + Send {
+        let address = location.as_ref().address;
+        trace::check_self_send(address)?;
+        let _permit = self.permit().await;
+        // async channel to get the response
         let (tx, rx) = oneshot::channel();
-
-        // We request an address
-        self.hell_channel.send(HellInstruction::CreateAddress {
-            tx
-        }).map_err(|e| Error::TokioSend(format!("{}", e)))?;
-        let address = rx.await.map_err(|s| Error::TokioSend(format!("{}", s)))?;
-
-        let location = Location {
+        
+        self.hell_channel.send(HellInstruction::Message {
+            tx,
             address,
-            phantom: PhantomData
-        };
-
-        // We spawn the demon in a mini hell instance
-        let demon_channels = MultipleMiniHell::spawn(demon_factory, replicas, location.clone())?;
-
-        // Second return channel, for knowing if the registration was successful
-        let (tx, rx) = oneshot::channel();
+            ignore: false,
+            trace: trace::inherit_or_new(),
+            input: Box::new(message),
+            types: Some(DemonTypes::of::<D, I, O>())
+        }).map_err(broker_gone)?;
 
-        // We attempt the registration process
-        self.hell_channel.send(HellInstruction::RegisterDemon {
-            address,
-            demon_channels,
-            tx
-        }).map_err(|e| Error::TokioSend(format!("{}", e)))?;
+        let any_output = rx.await.map_err(broker_gone)??;
 
-        // If it returned true, then everything is ok
-        rx.await.map_err(|s| Error::TokioSend(format!("{}", s)))?.map(move |_| location)
+        if let Ok(output) = any_output.downcast::<O>() {
+            Ok(*output)
+        } else {
+            Err(Error::WrongType)
+        }
     }
 
-    /// Spawns a demon with websockets processing in hell
+    /// Sends a type-erased message to a demon, and returns the type-erased reply
     ///
-    /// Demons spawned with this method need to implement the WebSocketThread trait. Demons will process both messages incoming from apocalypse, as well as from the websockets connection. It is important to note that the websockets handshake is not at all performed by this library.
+    /// This is an escape hatch for generic proxies, routers or serialization bridges that do not know the concrete `Input` and `Output` types at compile time. No type checking is performed by the compiler, so the boxed input has to contain exactly the `Input` type of the demon living at `address`, otherwise `Error::WrongInputType` is returned. The reply is returned as-is, and downcasting it to the demon's `Output` is the caller's responsibility. Prefer [send](crate::Gate::send) whenever a typed [Location](crate::Location) is available. As the type of the demon is unknown, the [RequestResponseDemon](crate::RequestResponseDemon) marker cannot be checked either.
     ///
-    /// ```rust,no_run
-    /// use apocalypse::{Hell, Demon};
-    /// use cataclysm::ws::{WebSocketThread, Message};
+    /// ```rust
+    /// use apocalypse::{Hell, Demon, Error};
     ///
-    /// struct PrintBot;
+    /// struct EchoBot;
     ///
-    /// impl Demon for PrintBot {
-    ///     type Input = ();
-    ///     type Output = ();
+    /// impl Demon for EchoBot {
+    ///     type Input = &'static str;
+    ///     type Output = String;
     ///     async fn handle(&mut self, message: Self::Input) -> Self::Output {
-    ///         println!("Hello, world!");
-    ///     }
-    /// }
-    /// 
-    /// impl WebSocketThread for PrintBot {
-    ///     type Output = ();
-    ///     async fn on_message(&mut self, message: Message) {
-    ///         // ... do something with the message
+    ///         message.to_string()
     ///     }
-    ///
-    ///     async fn on_close(&mut self, _clean: bool) -> Self::Output {}
     /// }
     ///
-    /// #[tokio::main]
-    /// async fn main() {
+    /// # #[tokio::main]
+    /// # async fn main() {
+    /// let (gate, jh) = Hell::new().ignite().await.unwrap();
+    /// let location = gate.spawn(EchoBot).await.unwrap();
+    /// // The reply is a boxed Any, which has to be downcasted manually
+    /// let reply = gate.send_any(location.address(), Box::new("Hallo, welt!")).await.unwrap();
+    /// assert_eq!("Hallo, welt!", *reply.downcast::<String>().unwrap());
+    /// // A message of the wrong type is reported with the expected type
+    /// assert!(matches!(gate.send_any(location.address(), Box::new(5u8)).await, Err(Error::WrongInputType{expected: "&str"})));
+    /// # }
+    /// ```
+    pub async fn send_any(&self, address: usize, input: Box<dyn Any + Send>) -> Result<Box<dyn Any + Send>, Error> {
+        trace::check_self_send(address)?;
+        let _permit = self.permit().await;
+        // async channel to get the response
+        let (tx, rx) = oneshot::channel();
+
+        self.hell_channel.send(HellInstruction::Message {
+            tx,
+            address,
+            ignore: false,
+            trace: trace::inherit_or_new(),
+            input,
+            types: None
+        }).map_err(broker_gone)?;
+
+        rx.await.map_err(broker_gone)?
+    }
+
+    /// Sends a message to a demon, and waits for the reply for a limited time, or until the wait gets cancelled
+    ///
+    /// The message is delivered regardless of what happens with the wait. If the timeout elapses first, `Error::Timeout` is returned, and if the token gets cancelled first, `Error::Cancelled` is returned. In both cases, the demon still handles the message, and its reply is discarded. With neither a timeout nor a token, this function behaves as [send](crate::Gate::send).
+    ///
+    /// ```rust
+    /// use apocalypse::{Hell, Demon, CancellationToken, Error};
+    /// use std::time::Duration;
+    ///
+    /// struct Slow;
+    ///
+    /// impl Demon for Slow {
+    ///     type Input = u64;
+    ///     type Output = ();
+    ///     async fn handle(&mut self, millis: Self::Input) -> Self::Output {
+    ///         tokio::time::sleep(Duration::from_millis(millis)).await;
+    ///     }
+    /// }
+    ///
+    /// # #[tokio::main]
+    /// # async fn main() {
+    /// let (gate, jh) = Hell::new().ignite().await.unwrap();
+    /// let location = gate.spawn(Slow).await.unwrap();
+    /// // Bounded request
+    /// assert!(matches!(gate.send_select(&location, 500, Some(Duration::from_millis(10)), None).await, Err(Error::Timeout)));
+    /// // Cancellable request
+    /// let token = CancellationToken::new();
+    /// let canceller = token.clone();
+    /// tokio::spawn(async move {
+    ///     tokio::time::sleep(Duration::from_millis(10)).await;
+    ///     canceller.cancel();
+    /// });
+    /// assert!(matches!(gate.send_select(&location, 500, None, Some(token)).await, Err(Error::Cancelled)));
+    /// # }
+    /// ```
+    pub async fn send_select<A: AsRef<Location<D>>, D, I, O>(&self, location: A, message: I, timeout: Option<std::time::Duration>, cancel: Option<CancellationToken>) -> Result<O, Error>
+        where
+            D: Demon<Input = I, Output = O>,
+            I: 'static + Send,
+            O: 'static + Send {
+        trace::check_self_send(location.as_ref().address)?;
+        let timeout = async {
+            match timeout {
+                Some(timeout) => tokio::time::sleep(timeout).await,
+                None => std::future::pending().await
+            }
+        };
+        let cancelled = async {
+            match &cancel {
+                Some(cancel) => cancel.cancelled().await,
+                None => std::future::pending().await
+            }
+        };
+        tokio::pin!(timeout, cancelled);
+
+        // Waiting for a permit of a limited gate counts towards the timeout as well
+        let permit = tokio::select! {
+            permit = self.permit() => permit,
+            _ = &mut timeout => return Err(Error::Timeout),
+            _ = &mut cancelled => return Err(Error::Cancelled)
+        };
+        let rx = self.dispatch(location, message, permit)?;
+
+        let any_output = tokio::select! {
+            reply = rx => reply.map_err(broker_gone)??,
+            _ = timeout => return Err(Error::Timeout),
+            _ = cancelled => return Err(Error::Cancelled)
+        };
+
+        if let Ok(output) = any_output.downcast::<O>() {
+            Ok(*output)
+        } else {
+            Err(Error::WrongType)
+        }
+    }
+
+    /// Sends a message to a demon, and returns the raw reply channel instead of awaiting it
+    ///
+    /// The message is dispatched right away, and the reply can be awaited later, stored, or selected together with other replies, which is useful for schedulers or request multiplexers built on top of this library. The reply arrives type-erased, and downcasting it to exactly the demon's `Output` type is the caller's responsibility. Dropping the receiver does not cancel the message. Prefer [send](crate::Gate::send) for the common case.
+    ///
+    /// On a gate with a [concurrency limit](Gate::with_max_concurrency), the receiver holds one of the slots of the gate until the reply arrives, or the receiver is dropped. As this function cannot wait for a free slot, it gives `Error::Overloaded` once the limit is reached.
+    ///
+    /// ```rust
+    /// use apocalypse::{Hell, Demon};
+    ///
+    /// struct Doubler;
+    ///
+    /// impl Demon for Doubler {
+    ///     type Input = u32;
+    ///     type Output = u32;
+    ///     async fn handle(&mut self, message: Self::Input) -> Self::Output {
+    ///         message * 2
+    ///     }
+    /// }
+    ///
+    /// # #[tokio::main]
+    /// # async fn main() {
+    /// let (gate, jh) = Hell::new().ignite().await.unwrap();
+    /// let location = gate.spawn(Doubler).await.unwrap();
+    /// // Both messages are in flight at the same time
+    /// let first = gate.send_with_responder(&location, 1).unwrap();
+    /// let second = gate.send_with_responder(&location, 2).unwrap();
+    /// let reply = second.await.unwrap().unwrap();
+    /// assert_eq!(4, *reply.downcast::<u32>().unwrap());
+    /// let reply = first.await.unwrap().unwrap();
+    /// assert_eq!(2, *reply.downcast::<u32>().unwrap());
+    /// # }
+    /// ```
+    ///
+    /// A limited gate does not take more receivers than its limit.
+    ///
+    /// ```rust
+    /// use apocalypse::{Hell, Demon, Error};
+    ///
+    /// struct Doubler;
+    ///
+    /// impl Demon for Doubler {
+    ///     type Input = u32;
+    ///     type Output = u32;
+    ///     async fn handle(&mut self, message: Self::Input) -> Self::Output {
+    ///         message * 2
+    ///     }
+    /// }
+    ///
+    /// # #[tokio::main]
+    /// # async fn main() {
+    /// let (gate, jh) = Hell::new().ignite().await.unwrap();
+    /// let location = gate.spawn(Doubler).await.unwrap();
+    /// let limited = gate.with_max_concurrency(1);
+    /// let first = limited.send_with_responder(&location, 1).unwrap();
+    /// assert!(matches!(limited.send_with_responder(&location, 2), Err(Error::Overloaded)));
+    /// assert!(matches!(limited.send_poll(&location, 2), Err(Error::Overloaded)));
+    /// // The slot is free again once the reply arrives
+    /// first.await.unwrap().unwrap();
+    /// assert_eq!(4, limited.send_poll(&location, 2).unwrap().await.unwrap());
+    /// # }
+    /// ```
+    pub fn send_with_responder<A: AsRef<Location<D>>, D, I, O>(&self, location: A, message: I) -> Result<ReplyReceiver, Error>
+        where
+            D: Demon<Input = I, Output = O>,
+            I: 'static + Send,
+            O: 'static + Send {
+        trace::check_self_send(location.as_ref().address)?;
+        let permit = self.try_permit()?;
+        self.dispatch(location, message, permit)
+    }
+
+    /// Sends a message to a demon, and returns the reply channel, which holds the permit of a limited gate
+    fn dispatch<A: AsRef<Location<D>>, D, I, O>(&self, location: A, message: I, permit: Option<OwnedSemaphorePermit>) -> Result<ReplyReceiver, Error>
+        where
+            D: Demon<Input = I, Output = O>,
+            I: 'static + Send,
+            O: 'static + Send {
+        let (tx, rx) = oneshot::channel();
+
+        self.hell_channel.send(HellInstruction::Message {
+            tx,
+            address: location.as_ref().address,
+            ignore: false,
+            trace: trace::inherit_or_new(),
+            input: Box::new(message),
+            types: None
+        }).map_err(broker_gone)?;
+
+        Ok(ReplyReceiver::new(rx, permit))
+    }
+
+    /// Sends a message to a demon, and returns its reply as a future that can also be polled by hand
+    ///
+    /// Works like [send_with_responder](crate::Gate::send_with_responder), but the reply is typed. The [SendFuture](crate::SendFuture) can be awaited, or checked with [try_poll_now](crate::SendFuture::try_poll_now), which needs no waker, so that sends can be embedded in custom event loops or behind FFI boundaries that cannot drive a future. Limited gates are handled as in [send_with_responder](crate::Gate::send_with_responder).
+    ///
+    /// ```rust
+    /// use apocalypse::{Hell, Demon};
+    ///
+    /// struct Doubler;
+    ///
+    /// impl Demon for Doubler {
+    ///     type Input = u32;
+    ///     type Output = u32;
+    ///     async fn handle(&mut self, message: Self::Input) -> Self::Output {
+    ///         message * 2
+    ///     }
+    /// }
+    ///
+    /// # #[tokio::main]
+    /// # async fn main() {
+    /// let (gate, jh) = Hell::new().ignite().await.unwrap();
+    /// let location = gate.spawn(Doubler).await.unwrap();
+    /// let mut reply = gate.send_poll(&location, 21).unwrap();
+    /// // An event loop of our own checks the reply between its other chores
+    /// let reply = loop {
+    ///     if let Some(reply) = reply.try_poll_now() {
+    ///         break reply;
+    ///     }
+    ///     tokio::task::yield_now().await;
+    /// };
+    /// assert_eq!(42, reply.unwrap());
+    /// // Or the reply can simply be awaited
+    /// assert_eq!(4, gate.send_poll(&location, 2).unwrap().await.unwrap());
+    /// # }
+    /// ```
+    pub fn send_poll<A: AsRef<Location<D>>, D, I, O>(&self, location: A, message: I) -> Result<SendFuture<O>, Error>
+        where
+            D: Demon<Input = I, Output = O>,
+            I: 'static + Send,
+            O: 'static + Send {
+        self.send_with_responder(location, message).map(SendFuture::new)
+    }
+
+    /// Sends a message to a demon, and ignore the result.
+    ///
+    /// This is your go-to function when you don't have to wait for the actor to give you a response back. This function fails if the request could not be delivered to the demon. If you absolutely require to call this function without awaiting, use `tokio::spawn`.
+    ///
+    /// Only the routing of the message is awaited, never its handling. Once this function returns `Ok(())`, the message is in the queue of the demon. A demon that is no longer in hell gives `Error::InvalidLocation`, which allows to detect stale locations without waiting for the demon.
+    ///
+    /// ```rust
+    /// use apocalypse::{Hell, Demon, Error};
+    ///
+    /// struct PrintBot;
+    ///
+    /// impl Demon for PrintBot {
+    ///     type Input = &'static str;
+    ///     type Output = ();
+    ///     async fn handle(&mut self, message: Self::Input) {
+    ///         println!("{}", message);
+    ///     }
+    /// }
+    ///
+    /// # #[tokio::main]
+    /// # async fn main() {
+    /// let (gate, jh) = Hell::new().ignite().await.unwrap();
+    /// let location = gate.spawn(PrintBot).await.unwrap();
+    /// // Use the send and ignore function to send a message without waiting for it
+    /// gate.send_and_ignore(&location, "Hallo, welt!").await.unwrap();
+    /// // Messages to demons that already left are not routed
+    /// gate.vanquish(&location).await.unwrap();
+    /// assert!(matches!(gate.send_and_ignore(&location, "Hallo?").await, Err(Error::InvalidLocation)));
+    /// # }
+    /// ```
+    pub async fn send_and_ignore<D, I, O>(&self, location: &Location<D>, message: I) -> Result<(), Error> 
+        where 
+            D: Demon<Input = I, Output = O>,
+            I: 'static + Send,
+            O: 'static + Send {
+        // async channel to get the response
+        let (tx, rx) = oneshot::channel();
+        let address = location.address;
+        
+        self.hell_channel.send(HellInstruction::Message {
+            tx,
+            address,
+            ignore: true,
+            trace: trace::inherit_or_new(),
+            input: Box::new(message),
+            types: None
+        }).map_err(broker_gone)?;
+        rx.await.map_err(broker_gone)??;
+        Ok(())
+    }
+
+    /// Sends a message to a demon spawned with replicas, always to the same replica for the same key
+    ///
+    /// Behaves like [send](crate::Gate::send), but instead of going to any free replica, the message goes to the replica at position `key % replicas` among the alive replicas, sorted by index, and waits for it if it is busy. Messages with the same key are thus handled one after the other, in the order they were sent, while messages with different keys are still handled in parallel. Changing the amount of replicas, either by scaling the pool or because of fatal replies, moves some keys to another replica. Demons spawned without replicas reply with `Error::WrongInputType`.
+    ///
+    /// ```rust
+    /// use apocalypse::{Hell, Demon};
+    ///
+    /// struct Replica {
+    ///     id: usize
+    /// }
+    ///
+    /// impl Demon for Replica {
+    ///     type Input = ();
+    ///     type Output = usize;
+    ///     async fn handle(&mut self, _message: Self::Input) -> Self::Output {
+    ///         self.id
+    ///     }
+    /// }
+    ///
+    /// # #[tokio::main]
+    /// # async fn main() {
+    /// let (gate, join_handle) = Hell::new().ignite().await.unwrap();
+    /// let location = gate.spawn_multiple_indexed(|id| Replica{id}, 3).await.unwrap();
+    /// // Keys 1 and 4 belong to the same replica, even when sent at the same time
+    /// let (first, second, third) = tokio::join!(
+    ///     gate.send_keyed_multiple(&location, (), 1),
+    ///     gate.send_keyed_multiple(&location, (), 4),
+    ///     gate.send_keyed_multiple(&location, (), 5)
+    /// );
+    /// assert_eq!(1, first.unwrap());
+    /// assert_eq!(1, second.unwrap());
+    /// assert_eq!(2, third.unwrap());
+    /// # }
+    /// ```
+    pub async fn send_keyed_multiple<A: AsRef<Location<D>>, D, I, O>(&self, location: A, message: I, key: u64) -> Result<O, Error>
+        where
+            D: Demon<Input = I, Output = O>,
+            I: 'static + Send,
+            O: 'static + Send {
+        let address = location.as_ref().address;
+        trace::check_self_send(address)?;
+        let _permit = self.permit().await;
+        // async channel to get the response
+        let (tx, rx) = oneshot::channel();
+
+        self.hell_channel.send(HellInstruction::Message {
+            tx,
+            address,
+            ignore: false,
+            trace: trace::inherit_or_new(),
+            input: Box::new(Keyed{key, input: Box::new(message)}),
+            types: None
+        }).map_err(broker_gone)?;
+
+        let any_output = rx.await.map_err(broker_gone)??;
+
+        if let Ok(output) = any_output.downcast::<O>() {
+            Ok(*output)
+        } else {
+            Err(Error::WrongType)
+        }
+    }
+
+    /// Spawns a demon in hell
+    ///
+    /// ```rust
+    /// use apocalypse::{Hell, Demon};
+    ///
+    /// struct Basic;
+    ///
+    /// impl Demon for Basic {
+    ///     type Input = String;
+    ///     type Output = ();
+    ///     async fn handle(&mut self, message: Self::Input) -> Self::Output {
+    ///         println!("Hello, world!");
+    ///     }
+    /// }
+    ///
+    /// # #[tokio::main]
+    /// # async fn main() {
+    /// let (gate, join_handle) = Hell::new().ignite().await.unwrap();
+    /// // we spawn the demon
+    /// let _location = gate.spawn(Basic).await.unwrap();
+    /// // Do something
+    /// # }
+    /// ```
+    ///
+    /// Dropping the future before it completes never leaves a demon behind. If the demon was already registered by then, it is vanquished, and its [vanquished](crate::Demon::vanquished) function is called.
+    ///
+    /// ```rust
+    /// use apocalypse::{Hell, Demon};
+    /// use std::sync::{Arc, atomic::{AtomicBool, Ordering}};
+    ///
+    /// struct Tracked {
+    ///     vanquished: Arc<AtomicBool>
+    /// }
+    ///
+    /// impl Demon for Tracked {
+    ///     type Input = ();
+    ///     type Output = ();
+    ///     async fn handle(&mut self, _message: Self::Input) -> Self::Output {}
+    ///
+    ///     async fn vanquished(self) {
+    ///         self.vanquished.store(true, Ordering::SeqCst);
+    ///     }
+    /// }
+    ///
+    /// # #[tokio::main(flavor = "current_thread")]
+    /// # async fn main() {
+    /// let (gate, join_handle) = Hell::new().ignite().await.unwrap();
+    /// let vanquished = Arc::new(AtomicBool::new(false));
+    /// let mut spawn = Box::pin(gate.spawn(Tracked{vanquished: vanquished.clone()}));
+    /// // The spawn is requested, and gets abandoned before learning its outcome
+    /// assert!(futures::poll!(&mut spawn).is_pending());
+    /// drop(spawn);
+    /// tokio::time::sleep(std::time::Duration::from_millis(50)).await;
+    /// assert!(vanquished.load(Ordering::SeqCst));
+    /// assert_eq!(0, gate.stats().await.unwrap().active_demons);
+    /// # }
+    /// ```
+    pub async fn spawn<D: 'static + Demon<Input = I, Output = O>, I: 'static + Send, O: 'static + Send>(&self, demon: D) -> Result<Location<D>, Error> {
+        self.spawn_with_parent(demon, None, Intake::default()).await
+    }
+
+    /// Spawns a demon in hell, with the given options
+    ///
+    /// With [MessageOrder::Lifo](crate::MessageOrder::Lifo), the demon takes the most recent of its waiting messages first. Under a sustained load, the oldest messages might wait for as long as the load lasts, see [MessageOrder](crate::MessageOrder).
+    ///
+    /// ```rust
+    /// use apocalypse::{Hell, Demon, SpawnOptions, MessageOrder};
+    /// use std::sync::{Arc, Mutex};
+    /// use std::time::Duration;
+    ///
+    /// struct Undo(Arc<Mutex<Vec<usize>>>);
+    ///
+    /// impl Demon for Undo {
+    ///     type Input = usize;
+    ///     type Output = ();
+    ///     async fn handle(&mut self, message: Self::Input) -> Self::Output {
+    ///         if message == 0 {
+    ///             // Other messages arrive while the first one is handled
+    ///             tokio::time::sleep(Duration::from_millis(100)).await;
+    ///         }
+    ///         self.0.lock().unwrap().push(message);
+    ///     }
+    /// }
+    ///
+    /// # #[tokio::main]
+    /// # async fn main() {
+    /// let (gate, join_handle) = Hell::new().ignite().await.unwrap();
+    /// let handled = Arc::new(Mutex::new(Vec::new()));
+    /// let location = gate.spawn_with_options(Undo(handled.clone()), SpawnOptions{ordering: MessageOrder::Lifo}).await.unwrap();
+    /// gate.send_and_ignore(&location, 0).await.unwrap();
+    /// tokio::time::sleep(Duration::from_millis(20)).await;
+    /// for message in 1..4 {
+    ///     gate.send_and_ignore(&location, message).await.unwrap();
+    /// }
+    /// tokio::time::sleep(Duration::from_millis(200)).await;
+    /// assert_eq!(vec![0, 3, 2, 1], *handled.lock().unwrap());
+    /// # }
+    /// ```
+    pub async fn spawn_with_options<D: 'static + Demon<Input = I, Output = O>, I: 'static + Send, O: 'static + Send>(&self, demon: D, options: SpawnOptions) -> Result<Location<D>, Error> {
+        self.spawn_with_parent(demon, None, Intake {
+            order: options.ordering,
+            ..Default::default()
+        }).await
+    }
+
+    /// Spawns a demon in hell, with a maximum size for its messages
+    ///
+    /// Messages whose [size_hint](crate::MessageSize::size_hint) exceeds `max_message_size` are rejected before being enqueued, and the sender receives `Error::MessageTooLarge`. Rejections are counted in the `rejected_messages` field of the [stats](crate::Gate::stats).
+    ///
+    /// ```rust
+    /// use apocalypse::{Hell, Demon, MessageSize, Error};
+    ///
+    /// struct Payload(Vec<u8>);
+    ///
+    /// impl MessageSize for Payload {
+    ///     fn size_hint(&self) -> usize {
+    ///         self.0.len()
+    ///     }
+    /// }
+    ///
+    /// struct Parser;
+    ///
+    /// impl Demon for Parser {
+    ///     type Input = Payload;
+    ///     type Output = usize;
+    ///     async fn handle(&mut self, message: Self::Input) -> Self::Output {
+    ///         message.0.len()
+    ///     }
+    /// }
+    ///
+    /// # #[tokio::main]
+    /// # async fn main() {
+    /// let (gate, join_handle) = Hell::new().ignite().await.unwrap();
+    /// let location = gate.spawn_with_max_message_size(Parser, 4).await.unwrap();
+    /// assert_eq!(3, gate.send(&location, Payload(vec![0; 3])).await.unwrap());
+    /// assert!(matches!(gate.send(&location, Payload(vec![0; 5])).await, Err(Error::MessageTooLarge)));
+    /// assert_eq!(1, gate.stats().await.unwrap().rejected_messages);
+    /// # }
+    /// ```
+    pub async fn spawn_with_max_message_size<D: 'static + Demon<Input = I, Output = O>, I: 'static + Send + MessageSize, O: 'static + Send>(&self, demon: D, max_message_size: usize) -> Result<Location<D>, Error> {
+        self.spawn_with_parent(demon, None, Intake {
+            size_guard: Some(SizeGuard::new::<I>(max_message_size)),
+            ..Default::default()
+        }).await
+    }
+
+    /// Spawns a demon in hell, that only handles messages once it marks itself as ready
+    ///
+    /// Demons that need an asynchronous warm-up, like loading a cache in a [linked task](crate::DemonContext::spawn_linked), call [mark_ready](crate::DemonContext::mark_ready) once it is over. Until then, messages wait in the mailbox of the demon, while instructions like vanquish are served as usual. This function only returns once the demon is ready. If it is not ready within `timeout`, the demon is vanquished, and `Error::Timeout` is returned.
+    ///
+    /// ```rust
+    /// use apocalypse::{Hell, Demon, DemonContext, Error};
+    /// use std::sync::{Arc, Mutex};
+    /// use std::time::Duration;
+    ///
+    /// #[derive(Default)]
+    /// struct Cache {
+    ///     data: Arc<Mutex<Option<u64>>>,
+    ///     lazy: bool
+    /// }
+    ///
+    /// impl Demon for Cache {
+    ///     type Input = ();
+    ///     type Output = Option<u64>;
+    ///
+    ///     async fn spawned_with_context(&mut self, ctx: DemonContext<Self>) {
+    ///         if self.lazy {
+    ///             return;
+    ///         }
+    ///         let data = self.data.clone();
+    ///         let linked_ctx = ctx.clone();
+    ///         ctx.spawn_linked(async move {
+    ///             // Loading takes a while
+    ///             tokio::time::sleep(Duration::from_millis(50)).await;
+    ///             *data.lock().unwrap() = Some(42);
+    ///             linked_ctx.mark_ready();
+    ///         });
+    ///     }
+    ///
+    ///     async fn handle(&mut self, _message: Self::Input) -> Self::Output {
+    ///         *self.data.lock().unwrap()
+    ///     }
+    /// }
+    ///
+    /// # #[tokio::main]
+    /// # async fn main() {
+    /// let (gate, join_handle) = Hell::new().ignite().await.unwrap();
+    /// let location = gate.spawn_with_warm_up(Cache::default(), Duration::from_secs(1)).await.unwrap();
+    /// assert_eq!(Some(42), gate.send(&location, ()).await.unwrap());
+    /// // A demon that never gets ready is not kept around
+    /// let lazy = Cache{lazy: true, ..Default::default()};
+    /// assert!(matches!(gate.spawn_with_warm_up(lazy, Duration::from_millis(50)).await, Err(Error::Timeout)));
+    /// assert_eq!(1, gate.stats().await.unwrap().active_demons);
+    /// # }
+    /// ```
+    pub async fn spawn_with_warm_up<D: 'static + Demon<Input = I, Output = O>, I: 'static + Send, O: 'static + Send>(&self, demon: D, timeout: Duration) -> Result<Location<D>, Error> {
+        let (ready, mut warm) = watch::channel(false);
+        let location = self.spawn_with_parent(demon, None, Intake {
+            warm_up: Some(Arc::new(ready)),
+            ..Default::default()
+        }).await?;
+
+        // The borrow of the value is released right away, as it locks the channel
+        let warmed = tokio::time::timeout(timeout, warm.wait_for(|ready| *ready)).await.map(|ready| ready.is_ok());
+        match warmed {
+            Ok(true) => Ok(location),
+            // The demon left during its warm-up
+            Ok(false) => Err(Error::InvalidLocation),
+            Err(_) => {
+                let _ = self.vanquish(&location).await;
+                Err(Error::Timeout)
+            }
+        }
+    }
+
+    /// Spawns a demon in hell, recording the last `capacity` messages delivered to it
+    ///
+    /// The recorded messages can be retrieved with [message_history](crate::Gate::message_history), and fed to a fresh instance of the demon with [replay](crate::replay), to reproduce bugs that depend on the exact sequence of messages. Messages are recorded by hell as they get delivered, so each one is cloned once, and only the newest `capacity` messages are kept, with a minimum of one. Messages rejected before being delivered, for example with `Error::TypeMismatch`, are not recorded.
+    ///
+    /// Only available with the `record` feature. See [replay](crate::replay) for an example.
+    #[cfg(feature = "record")]
+    pub async fn spawn_recorded<D: 'static + Demon<Input = I, Output = O>, I: 'static + Send + Clone, O: 'static + Send>(&self, demon: D, capacity: usize) -> Result<Location<D>, Error> {
+        self.spawn_with_parent(demon, None, Intake {
+            history: Some(Arc::new(History::<I>::new(capacity))),
+            ..Default::default()
+        }).await
+    }
+
+    /// Retrieves the messages recorded for a demon spawned with [spawn_recorded](crate::Gate::spawn_recorded), oldest first
+    ///
+    /// The request goes after the messages already sent through this gate, so they are always part of the history, even if the demon did not handle them yet. Demons spawned without recording give `Error::NotRecorded`.
+    ///
+    /// Only available with the `record` feature. See [replay](crate::replay) for an example.
+    #[cfg(feature = "record")]
+    pub async fn message_history<D: Demon<Input = I>, I: 'static + Clone>(&self, location: &Location<D>) -> Result<Vec<I>, Error> {
+        let (tx, rx) = oneshot::channel();
+        self.hell_channel.send(HellInstruction::History {
+            address: location.address,
+            tx
+        }).map_err(broker_gone)?;
+        let history = rx.await.map_err(broker_gone)??;
+        history.as_any().downcast_ref::<History<I>>().map(History::inputs).ok_or(Error::WrongType)
+    }
+
+    /// Spawns a demon in hell, optionally linked to a parent demon, and with the given treatment for its messages
+    pub(crate) async fn spawn_with_parent<D: 'static + Demon<Input = I, Output = O>, I: 'static + Send, O: 'static + Send>(&self, demon: D, parent: Option<usize>, intake: Intake) -> Result<Location<D>, Error> {
+        match self.try_spawn_with_parent(demon, parent, intake).await {
+            Ok(location) => Ok(location),
+            // The demon gets to release its resources before being dropped
+            Err((e, demon)) => {
+                if let Some(demon) = demon {
+                    demon.on_spawn_rejected(&e).await;
+                }
+                Err(e)
+            }
+        }
+    }
+
+    /// Spawns a demon in hell, giving the demon back if the spawn fails
+    ///
+    /// Works exactly as [spawn](Gate::spawn), but if the demon could not be spawned, it is returned together with the error, so that it can be spawned again without building it from scratch. The demon is only missing if its task was dropped before giving it back, as when the runtime shuts down, and the error is then `Error::BrokerGone`.
+    ///
+    /// ```rust
+    /// use apocalypse::{Hell, Demon};
+    ///
+    /// struct Expensive {
+    ///     model: Vec<u8>
+    /// }
+    ///
+    /// impl Demon for Expensive {
+    ///     type Input = ();
+    ///     type Output = ();
+    ///     async fn handle(&mut self, _message: Self::Input) -> Self::Output {}
+    /// }
+    ///
+    /// # #[tokio::main]
+    /// # async fn main() {
+    /// let (gate, join_handle) = Hell::new().ignite().await.unwrap();
+    /// let demon = Expensive{model: vec![0; 1024]};
+    /// let location = match gate.try_spawn(demon).await {
+    ///     Ok(location) => location,
+    ///     // We get to keep the demon, and can try again
+    ///     Err((_error, Some(demon))) => gate.spawn(demon).await.unwrap(),
+    ///     // The demon is only lost if the runtime shuts down during the spawn
+    ///     Err((error, None)) => panic!("{}", error)
+    /// };
+    /// # }
+    /// ```
+    pub async fn try_spawn<D: 'static + Demon<Input = I, Output = O>, I: 'static + Send, O: 'static + Send>(&self, demon: D) -> Result<Location<D>, (Error, Option<D>)> {
+        self.try_spawn_with_parent(demon, None, Intake::default()).await
+    }
+
+    /// Spawns a demon in hell under a unique name
+    ///
+    /// The name is checked and taken in the same step as the registration of the demon, so out of several concurrent spawns with the same name, exactly one succeeds, and the rest fail with `Error::NameTaken` after calling the [on_spawn_rejected](crate::Demon::on_spawn_rejected) function of their demons. The name belongs to the demon until it leaves hell, and passes on to its replacement through a [handoff](Gate::handoff). Named demons are found with [named](Gate::named).
+    ///
+    /// ```rust
+    /// use apocalypse::{Hell, Demon, Error};
+    ///
+    /// struct ConfigManager;
+    ///
+    /// impl Demon for ConfigManager {
+    ///     type Input = ();
+    ///     type Output = ();
+    ///     async fn handle(&mut self, _message: Self::Input) -> Self::Output {}
+    /// }
+    ///
+    /// # #[tokio::main]
+    /// # async fn main() {
+    /// let (gate, join_handle) = Hell::new().ignite().await.unwrap();
+    /// let (first, second) = tokio::join!(
+    ///     gate.try_spawn_named("config-manager", ConfigManager),
+    ///     gate.try_spawn_named("config-manager", ConfigManager)
+    /// );
+    /// // Only one of them made it
+    /// let location = match (first, second) {
+    ///     (Ok(location), Err(Error::NameTaken)) | (Err(Error::NameTaken), Ok(location)) => location,
+    ///     _ => unreachable!()
+    /// };
+    /// assert!(location == gate.named::<ConfigManager>("config-manager").await.unwrap());
+    /// // Once the demon leaves, the name is free again
+    /// gate.vanquish(&location).await.unwrap();
+    /// assert!(gate.try_spawn_named("config-manager", ConfigManager).await.is_ok());
+    /// # }
+    /// ```
+    pub async fn try_spawn_named<D: 'static + Demon<Input = I, Output = O>, I: 'static + Send, O: 'static + Send>(&self, name: &str, demon: D) -> Result<Location<D>, Error> {
+        self.spawn_with_parent(demon, None, Intake {
+            name: Some(name.to_string()),
+            ..Default::default()
+        }).await
+    }
+
+    /// Returns the location of the demon spawned under a name, see [try_spawn_named](Gate::try_spawn_named)
+    ///
+    /// If no demon has the name, `Error::InvalidLocation` is returned, and if the demon is not of type `D`, `Error::TypeMismatch`.
+    pub async fn named<D: 'static + Demon>(&self, name: &str) -> Result<Location<D>, Error> {
+        let (tx, rx) = oneshot::channel();
+        self.hell_channel.send(HellInstruction::Named {
+            name: name.to_string(),
+            demon: std::any::TypeId::of::<D>(),
+            expected: std::any::type_name::<D>(),
+            tx
+        }).map_err(broker_gone)?;
+        let address = rx.await.map_err(broker_gone)??;
+        Ok(Location {
+            address,
+            phantom: PhantomData
+        })
+    }
+
+    /// Spawns `count` zero-sized demons in hell, built with their `Default` implementation
+    ///
+    /// Demons live inline in the task of their mini hell and are never boxed, so a zero-sized demon already takes no storage of its own, and what each demon costs is its channels and its task. What this function saves are the round trips to hell: all the spawns are requested before waiting for any answer, instead of one demon after the other as a loop over [spawn](Gate::spawn) does. See the `spawn_many` bench for a comparison.
+    ///
+    /// The demons are spawned all or none. If any registration fails, for example because of the [maximum amount of demons](crate::HellBuilder::max_total_demons), the rejected demons get their [on_spawn_rejected](crate::Demon::on_spawn_rejected) function called, the ones already registered are vanquished, and the first error is returned. Using it with a demon type that is not zero-sized does not compile.
+    ///
+    /// ```rust
+    /// use apocalypse::{Hell, Demon};
+    ///
+    /// #[derive(Default)]
+    /// struct Worker;
+    ///
+    /// impl Demon for Worker {
+    ///     type Input = u64;
+    ///     type Output = u64;
+    ///     async fn handle(&mut self, message: Self::Input) -> Self::Output {
+    ///         message + 1
+    ///     }
+    /// }
+    ///
+    /// # #[tokio::main]
+    /// # async fn main() {
+    /// let (gate, join_handle) = Hell::new().ignite().await.unwrap();
+    /// let workers = gate.spawn_many_zst::<Worker, _, _>(100).await.unwrap();
+    /// assert_eq!(100, workers.len());
+    /// assert_eq!(100, gate.stats().await.unwrap().active_demons);
+    /// assert_eq!(42, gate.send(&workers[99], 41).await.unwrap());
+    /// # }
+    /// ```
+    pub async fn spawn_many_zst<D: 'static + Demon<Input = I, Output = O> + Default, I: 'static + Send, O: 'static + Send>(&self, count: usize) -> Result<Vec<Location<D>>, Error> {
+        const { assert!(std::mem::size_of::<D>() == 0, "spawn_many_zst only takes zero-sized demons") };
+
+        // Every spawn is sent before waiting for the first answer
+        let mut pending = Vec::with_capacity(count);
+        for _ in 0..count {
+            let (ready, registered) = oneshot::channel();
+            let (give_back, given_back) = oneshot::channel();
+            let demon_channels = MiniHell::spawn(D::default(), self.unregistered_context(), registered, Some(give_back), None, MessageOrder::Fifo, self.on_close_tx.clone());
+            let (tx, rx) = oneshot::channel();
+            self.hell_channel.send(HellInstruction::SpawnDemon {
+                demon_channels,
+                ready,
+                parent: None,
+                tx
+            }).map_err(broker_gone)?;
+            pending.push((rx, given_back));
+        }
+
+        let mut spawned = Vec::with_capacity(count);
+        let mut failure = None;
+        for (rx, given_back) in pending {
+            match rx.await.map_err(broker_gone).and_then(|added| added) {
+                Ok(address) => spawned.push(Location {
+                    address,
+                    phantom: PhantomData
+                }),
+                Err(e) => {
+                    // The failed registration drops the readiness channel, so the mini hell returns the demon
+                    if let Ok(demon) = given_back.await {
+                        demon.on_spawn_rejected(&e).await;
+                    }
+                    failure.get_or_insert(e);
+                }
+            }
+        }
+
+        match failure {
+            Some(e) => {
+                for location in spawned {
+                    let (tx, rx) = oneshot::channel();
+                    if self.priority_channel.send(HellInstruction::RemoveDemon {
+                        address: location.address,
+                        tx,
+                        ignore: true,
+                        force: None
+                    }).is_ok() {
+                        let _ = rx.await;
+                    }
+                }
+                Err(e)
+            },
+            None => Ok(spawned)
+        }
+    }
+
+    /// Spawns a demon in hell, optionally linked to a parent demon and with a size guard for its messages, giving the demon back on failure
+    async fn try_spawn_with_parent<D: 'static + Demon<Input = I, Output = O>, I: 'static + Send, O: 'static + Send>(&self, demon: D, parent: Option<usize>, intake: Intake) -> Result<Location<D>, (Error, Option<D>)> {
+        self.try_spawn_at(None, demon, parent, intake).await
+    }
+
+    /// Reserves an address, for a demon to be spawned later at it
+    pub(crate) async fn reserve_address(&self) -> Result<usize, Error> {
+        let (tx, rx) = oneshot::channel();
+        self.hell_channel.send(HellInstruction::CreateAddress {
+            tx
+        }).map_err(broker_gone)?;
+        rx.await.map_err(broker_gone)
+    }
+
+    /// Registers a demon whose mini hell waits to start, either at a reserved address, or at the one that hell picks
+    ///
+    /// Without a reserved address, the address is picked and the demon registered with a single instruction.
+    async fn register_demon<D>(&self, address: Option<usize>, demon_channels: DemonChannels, ready: oneshot::Sender<(usize, usize)>, parent: Option<usize>) -> Result<Location<D>, Error> {
+        let address = match address {
+            Some(address) => {
+                let (tx, rx) = oneshot::channel();
+                self.hell_channel.send(HellInstruction::RegisterDemon {
+                    address,
+                    demon_channels,
+                    ready,
+                    parent,
+                    tx
+                }).map_err(broker_gone)?;
+                rx.await.map_err(broker_gone)??;
+                address
+            },
+            None => {
+                let (tx, rx) = oneshot::channel();
+                self.hell_channel.send(HellInstruction::SpawnDemon {
+                    demon_channels,
+                    ready,
+                    parent,
+                    tx
+                }).map_err(broker_gone)?;
+                rx.await.map_err(broker_gone)??
+            }
+        };
+        Ok(Location {
+            address,
+            phantom: PhantomData
+        })
+    }
+
+    /// Spawns a demon at an address that was already reserved, or at the one that hell picks if there is none
+    pub(crate) async fn try_spawn_at<D: 'static + Demon<Input = I, Output = O>, I: 'static + Send, O: 'static + Send>(&self, address: Option<usize>, demon: D, parent: Option<usize>, intake: Intake) -> Result<Location<D>, (Error, Option<D>)> {
+        // We spawn the demon in a mini hell instance, which starts once it gets registered, or gives the demon back otherwise
+        let (ready, registered) = oneshot::channel();
+        let (give_back, given_back) = oneshot::channel();
+        let mut context = self.unregistered_context();
+        context.ready = intake.warm_up;
+        let mut demon_channels = MiniHell::spawn(demon, context, registered, Some(give_back), None, intake.order, self.on_close_tx.clone());
+        demon_channels.size_guard = intake.size_guard;
+        demon_channels.name = intake.name;
+        #[cfg(feature = "record")]
+        {
+            demon_channels.history = intake.history;
+        }
+
+        match self.register_demon(address, demon_channels, ready, parent).await {
+            Ok(location) => Ok(location),
+            // The failed registration drops the readiness channel, so the mini hell returns the demon, unless its task was dropped with the runtime
+            Err(e) => match given_back.await {
+                Ok(demon) => Err((e, Some(demon))),
+                Err(_) => Err((Error::BrokerGone, None))
+            }
+        }
+    }
+
+    /// Spawns a blocking demon in hell
+    ///
+    /// Each call to the handle function of a [BlockingDemon](crate::BlockingDemon) is executed in tokio's blocking thread pool, so CPU-bound demons do not starve the rest of the demons.
+    ///
+    /// ```rust
+    /// use apocalypse::{Hell, BlockingDemon};
+    ///
+    /// struct Summer;
+    ///
+    /// impl BlockingDemon for Summer {
+    ///     type Input = Vec<u64>;
+    ///     type Output = u64;
+    ///     fn handle(&mut self, message: Self::Input) -> Self::Output {
+    ///         message.iter().sum()
+    ///     }
+    /// }
+    ///
+    /// # #[tokio::main]
+    /// # async fn main() {
+    /// let (gate, join_handle) = Hell::new().ignite().await.unwrap();
+    /// // we spawn the demon
+    /// let location = gate.spawn_blocking_demon(Summer).await.unwrap();
+    /// assert_eq!(6, gate.send(&location, vec![1, 2, 3]).await.unwrap());
+    /// # }
+    /// ```
+    pub async fn spawn_blocking_demon<B: BlockingDemon>(&self, demon: B) -> Result<Location<Blocking<B>>, Error> {
+        self.spawn(Blocking::new(demon)).await
+    }
+
+    /// Spawns multiple demons in Hell, that reply to the same [Location](Location)
+    ///
+    /// This might be useful if you have one task that consumes some time to be processed, and you can also parallelize. The load balancing method is just using whichever Demon is free at the moment, in a sequential order (that is, sequential but skipping if one is busy).
+    ///
+    /// If the handle function of a replica panics, the sender gets `Error::Panicked`, and the replica leaves the pool without being vanquished, as its state cannot be trusted anymore. Once all replicas are gone, the demon leaves hell. Pools spawned with [spawn_multiple_scalable](Gate::spawn_multiple_scalable) replace the replica instead.
+    ///
+    /// ```rust
+    /// use apocalypse::{Hell, Demon};
+    ///
+    /// struct Basic;
+    ///
+    /// impl Demon for Basic {
+    ///     type Input = String;
+    ///     type Output = ();
+    ///     async fn handle(&mut self, message: Self::Input) -> Self::Output {
+    ///         println!("Hello, world!");
+    ///     }
+    /// }
+    ///
+    /// # #[tokio::main]
+    /// # async fn main() {
+    /// let (gate, join_handle) = Hell::new().ignite().await.unwrap();
+    /// // Demon factory, needs to implement FnMut
+    /// let basic_factory = || {
+    ///     Basic
+    /// };
+    /// // We spawn three instances of the demon
+    /// let _location = gate.spawn_multiple(basic_factory, 3).await.unwrap();
+    /// // Do something
+    /// # }
+    /// ```
+    ///
+    /// A pool that loses its last replica to a panic is gone.
+    ///
+    /// ```rust
+    /// use apocalypse::{Hell, Demon, Error};
+    ///
+    /// struct Fragile;
+    ///
+    /// impl Demon for Fragile {
+    ///     type Input = bool;
+    ///     type Output = ();
+    ///     async fn handle(&mut self, explode: Self::Input) -> Self::Output {
+    ///         if explode {
+    ///             panic!("boom");
+    ///         }
+    ///     }
+    /// }
+    ///
+    /// # #[tokio::main]
+    /// # async fn main() {
+    /// let (gate, _join_handle) = Hell::new().ignite().await.unwrap();
+    /// let location = gate.spawn_multiple(|| Fragile, 2).await.unwrap();
+    /// assert!(matches!(gate.send(&location, true).await, Err(Error::Panicked)));
+    /// // The other replica is still there
+    /// assert!(gate.send(&location, false).await.is_ok());
+    /// assert!(matches!(gate.send(&location, true).await, Err(Error::Panicked)));
+    /// assert!(matches!(gate.send(&location, false).await, Err(Error::InvalidLocation)));
+    /// # }
+    /// ```
+    pub async fn spawn_multiple<D: 'static + Demon<Input = I, Output = O>, I: 'static + Send, O: 'static + Send, F: FnMut() -> D>(&self, mut demon_factory: F, replicas: usize) -> Result<Location<D>, Error> {
+        self.spawn_multiple_indexed(move |_| demon_factory(), replicas).await
+    }
+
+    /// Spawns multiple demons in Hell, passing the index of each replica to the factory
+    ///
+    /// Works exactly as [spawn_multiple](Gate::spawn_multiple), but the factory receives the index of the replica being created, from `0` to `replicas - 1`, so that demons can identify themselves.
+    ///
+    /// ```rust
+    /// use apocalypse::{Hell, Demon};
+    ///
+    /// struct Basic {
+    ///     id: usize
+    /// }
+    ///
+    /// impl Demon for Basic {
+    ///     type Input = String;
+    ///     type Output = ();
+    ///     async fn handle(&mut self, message: Self::Input) -> Self::Output {
+    ///         println!("Hello from replica {}!", self.id);
+    ///     }
+    /// }
+    ///
+    /// # #[tokio::main]
+    /// # async fn main() {
+    /// let (gate, join_handle) = Hell::new().ignite().await.unwrap();
+    /// // We spawn three instances of the demon, with ids 0, 1 and 2
+    /// let _location = gate.spawn_multiple_indexed(|id| Basic{id}, 3).await.unwrap();
+    /// // Do something
+    /// # }
+    /// ```
+    pub async fn spawn_multiple_indexed<D: 'static + Demon<Input = I, Output = O>, I: 'static + Send, O: 'static + Send, F: FnMut(usize) -> D>(&self, demon_factory: F, replicas: usize) -> Result<Location<D>, Error> {
+        self.spawn_pool(move |context, registered, on_close_tx| MultipleMiniHell::spawn(demon_factory, replicas, None, context, registered, on_close_tx)).await
+    }
+
+    /// Spawns multiple demons in Hell, with a factory that can fail to build some of the replicas
+    ///
+    /// Works exactly as [spawn_multiple_indexed](Gate::spawn_multiple_indexed), but replicas whose construction fails are left out of the pool, and the [MultiSpawnResult](crate::MultiSpawnResult) tells how many of them started, so that the caller can decide whether a partial pool is good enough. The remaining replicas keep the index they got from the factory, and the errors of the factory are dropped. If no replica could be built, `Error::WrongReplicas` is returned.
+    ///
+    /// ```rust
+    /// use apocalypse::{Hell, Demon};
+    ///
+    /// struct Connection {
+    ///     id: usize
+    /// }
+    ///
+    /// impl Demon for Connection {
+    ///     type Input = ();
+    ///     type Output = usize;
+    ///     async fn handle(&mut self, _message: Self::Input) -> Self::Output {
+    ///         self.id
+    ///     }
+    /// }
+    ///
+    /// # #[tokio::main]
+    /// # async fn main() {
+    /// let (gate, join_handle) = Hell::new().ignite().await.unwrap();
+    /// // The database refuses the second connection
+    /// let pool = gate.spawn_multiple_fallible(|id| if id == 1 {
+    ///     Err("connection refused")
+    /// } else {
+    ///     Ok(Connection{id})
+    /// }, 3).await.unwrap();
+    /// assert_eq!(2, pool.started);
+    /// assert_eq!(3, pool.requested);
+    /// assert!(!pool.is_complete());
+    /// // The pool works with the replicas that started
+    /// assert_ne!(1, gate.send(&pool, ()).await.unwrap());
+    /// # }
+    /// ```
+    pub async fn spawn_multiple_fallible<D: 'static + Demon<Input = I, Output = O>, I: 'static + Send, O: 'static + Send, E, F: FnMut(usize) -> Result<D, E>>(&self, mut demon_factory: F, replicas: usize) -> Result<MultiSpawnResult<D>, Error> {
+        let demons: VecDeque<(usize, D)> = (0..replicas).filter_map(|idx| demon_factory(idx).ok().map(|demon| (idx, demon))).collect();
+        let started = demons.len();
+        let location = self.spawn_pool(move |context, registered, on_close_tx| MultipleMiniHell::spawn_replicas(demons, None, context, registered, on_close_tx)).await?;
+        Ok(MultiSpawnResult {
+            location,
+            started,
+            requested: replicas
+        })
+    }
+
+    /// Spawns multiple demons in Hell, with a limit for the messages waiting on busy replicas
+    ///
+    /// Works exactly as [spawn_multiple](Gate::spawn_multiple), but at most `max_queued` messages wait in the internal queue of the demon while all replicas are busy. Once the limit is reached, the [OverflowPolicy](crate::OverflowPolicy) decides what happens with new messages. With `Reject`, the sender receives `Error::Overloaded`. With `Wait`, the message stays in the mailbox of the demon until a replica becomes available. The length of the internal queues can be observed in the [stats](crate::Gate::stats).
+    ///
+    /// ```rust
+    /// use apocalypse::{Hell, Demon, Error, OverflowPolicy};
+    ///
+    /// struct Slow;
+    ///
+    /// impl Demon for Slow {
+    ///     type Input = ();
+    ///     type Output = ();
+    ///     async fn handle(&mut self, _message: Self::Input) -> Self::Output {
+    ///         tokio::time::sleep(std::time::Duration::from_millis(100)).await;
+    ///     }
+    /// }
+    ///
+    /// # #[tokio::main]
+    /// # async fn main() {
+    /// let (gate, join_handle) = Hell::new().ignite().await.unwrap();
+    /// // A single replica, and room for one more message
+    /// let location = gate.spawn_multiple_bounded(|| Slow, 1, 1, OverflowPolicy::Reject).await.unwrap();
+    /// let (first, second, third) = tokio::join!(
+    ///     gate.send(&location, ()),
+    ///     gate.send(&location, ()),
+    ///     gate.send(&location, ())
+    /// );
+    /// assert!(first.is_ok());
+    /// assert!(second.is_ok());
+    /// assert!(matches!(third, Err(Error::Overloaded)));
+    /// assert_eq!(1, gate.stats().await.unwrap().queued_messages_high_water);
+    /// # }
+    /// ```
+    pub async fn spawn_multiple_bounded<D: 'static + Demon<Input = I, Output = O>, I: 'static + Send, O: 'static + Send, F: FnMut() -> D>(&self, mut demon_factory: F, replicas: usize, max_queued: usize, overflow: OverflowPolicy) -> Result<Location<D>, Error> {
+        self.spawn_pool(move |context, registered, on_close_tx| MultipleMiniHell::spawn(move |_| demon_factory(), replicas, Some((max_queued, overflow)), context, registered, on_close_tx)).await
+    }
+
+    /// Spawns multiple demons in Hell, whose amount of replicas can be changed by the demons themselves
+    ///
+    /// Works exactly as [spawn_multiple_indexed](Gate::spawn_multiple_indexed), but the factory is kept alive, so that the demons can add or remove replicas of themselves through [request_scale](crate::DemonContext::request_scale). New replicas receive increasing indexes, which are never reused. The factory also replaces replicas whose handle function panics, with a new replica that takes the same index, so the pool keeps its size.
+    ///
+    /// ```rust
+    /// use apocalypse::{Hell, Demon, DemonContext};
+    ///
+    /// struct Worker {
+    ///     ctx: Option<DemonContext<Worker>>
+    /// }
+    ///
+    /// impl Demon for Worker {
+    ///     type Input = i32;
+    ///     type Output = ();
+    ///     async fn spawned_with_context(&mut self, ctx: DemonContext<Self>) {
+    ///         self.ctx = Some(ctx);
+    ///     }
+    ///     async fn handle(&mut self, delta: Self::Input) -> Self::Output {
+    ///         if let Some(ctx) = &self.ctx {
+    ///             ctx.request_scale(delta).unwrap();
+    ///         }
+    ///     }
+    /// }
+    ///
+    /// # #[tokio::main]
+    /// # async fn main() {
+    /// let (gate, join_handle) = Hell::new().ignite().await.unwrap();
+    /// let location = gate.spawn_multiple_scalable(|_| Worker{ctx: None}, 1).await.unwrap();
+    /// // The pool grows to four replicas
+    /// gate.send_and_ignore(&location, 3).await.unwrap();
+    /// # }
+    /// ```
+    ///
+    /// A replica that panics is replaced.
+    ///
+    /// ```rust
+    /// use apocalypse::{Hell, Demon, Error};
+    ///
+    /// struct Fragile;
+    ///
+    /// impl Demon for Fragile {
+    ///     type Input = bool;
+    ///     type Output = ();
+    ///     async fn handle(&mut self, explode: Self::Input) -> Self::Output {
+    ///         if explode {
+    ///             panic!("boom");
+    ///         }
+    ///     }
+    /// }
+    ///
+    /// # #[tokio::main]
+    /// # async fn main() {
+    /// let (gate, _join_handle) = Hell::new().ignite().await.unwrap();
+    /// let location = gate.spawn_multiple_scalable(|_| Fragile, 1).await.unwrap();
+    /// assert!(matches!(gate.send(&location, true).await, Err(Error::Panicked)));
+    /// // The only replica was rebuilt
+    /// assert!(gate.send(&location, false).await.is_ok());
+    /// # }
+    /// ```
+    pub async fn spawn_multiple_scalable<D: 'static + Demon<Input = I, Output = O>, I: 'static + Send, O: 'static + Send, F: 'static + FnMut(usize) -> D + Send>(&self, demon_factory: F, replicas: usize) -> Result<Location<D>, Error> {
+        self.spawn_pool(move |context, registered, on_close_tx| MultipleMiniHell::spawn_scalable(demon_factory, replicas, context, registered, on_close_tx)).await
+    }
+
+    /// Spawns a pool of demons in Hell, started by the given function and registered at an address picked by hell
+    async fn spawn_pool<D: 'static + Demon, S: FnOnce(DemonContext<D>, oneshot::Receiver<(usize, usize)>, UnboundedSender<(usize, usize)>) -> Result<DemonChannels, Error>>(&self, start: S) -> Result<Location<D>, Error> {
+        // We spawn the demon in a mini hell instance, which starts once it gets registered
+        let (ready, registered) = oneshot::channel();
+        let demon_channels = start(self.unregistered_context(), registered, self.on_close_tx.clone())?;
+        self.register_demon(None, demon_channels, ready, None).await
+    }
+
+    /// Spawns multiple demons in Hell, using the amount of replicas suggested by the demon type
+    ///
+    /// Works exactly as [spawn_multiple](Gate::spawn_multiple), but the number of replicas is taken from [Demon::default_replicas](crate::Demon::default_replicas), so that each demon type encodes its own parallelism policy.
+    ///
+    /// ```rust
+    /// use apocalypse::{Hell, Demon};
+    ///
+    /// struct Basic;
+    ///
+    /// impl Demon for Basic {
+    ///     type Input = String;
+    ///     type Output = ();
+    ///     async fn handle(&mut self, message: Self::Input) -> Self::Output {
+    ///         println!("Hello, world!");
+    ///     }
+    ///
+    ///     fn default_replicas() -> usize {
+    ///         4
+    ///     }
+    /// }
+    ///
+    /// # #[tokio::main]
+    /// # async fn main() {
+    /// let (gate, join_handle) = Hell::new().ignite().await.unwrap();
+    /// // We spawn four instances of the demon
+    /// let _location = gate.spawn_multiple_auto(|| Basic).await.unwrap();
+    /// // Do something
+    /// # }
+    /// ```
+    pub async fn spawn_multiple_auto<D: 'static + Demon<Input = I, Output = O>, I: 'static + Send, O: 'static + Send, F: FnMut() -> D>(&self, demon_factory: F) -> Result<Location<D>, Error> {
+        self.spawn_multiple(demon_factory, D::default_replicas()).await
+    }
+
+    /// Replaces a single replica of a demon spawned with replicas, keeping the address of the pool
+    ///
+    /// The replica is identified by the index that the factory received when creating it, see [spawn_multiple_indexed](Gate::spawn_multiple_indexed). A busy replica first finishes the message it is handling. Then it is vanquished, and the new demon takes its place, with the same index, after its [spawned](crate::Demon::spawned) function is called. The other replicas keep handling messages all along, so restarting the replicas one at a time upgrades the whole pool without downtime. The function returns once the new demon is in place. Replicas that do not exist, including the ones of demons spawned without replicas, give `Error::InvalidLocation`. If another restart of the same replica arrives while it is busy, only the latest one is kept, and the earlier one gives `Error::Cancelled`.
+    ///
+    /// ```rust
+    /// use apocalypse::{Hell, Demon, Error};
+    /// use std::time::Duration;
+    ///
+    /// struct Worker {
+    ///     version: u32
+    /// }
+    ///
+    /// impl Demon for Worker {
+    ///     type Input = u64;
+    ///     type Output = u32;
+    ///     async fn handle(&mut self, millis: Self::Input) -> Self::Output {
+    ///         tokio::time::sleep(Duration::from_millis(millis)).await;
+    ///         self.version
+    ///     }
+    /// }
+    ///
+    /// # #[tokio::main]
+    /// # async fn main() {
+    /// let (gate, _join_handle) = Hell::new().ignite().await.unwrap();
+    /// let location = gate.spawn_multiple(|| Worker{version: 1}, 2).await.unwrap();
+    /// let slow = {
+    ///     let (gate, location) = (gate.clone(), location.clone());
+    ///     tokio::spawn(async move { gate.send(&location, 50).await.unwrap() })
+    /// };
+    /// tokio::time::sleep(Duration::from_millis(10)).await;
+    /// // Replicas are upgraded one at a time, and the busy one finishes its message first
+    /// for replica in 0..2 {
+    ///     gate.restart_replica(&location, replica, Worker{version: 2}).await.unwrap();
+    /// }
+    /// assert_eq!(1, slow.await.unwrap());
+    /// assert_eq!(2, gate.send(&location, 0).await.unwrap());
+    /// assert!(matches!(gate.restart_replica(&location, 5, Worker{version: 3}).await, Err(Error::InvalidLocation)));
+    /// # }
+    /// ```
+    pub async fn restart_replica<D: 'static + Demon<Input = I, Output = O>, I: 'static + Send, O: 'static + Send>(&self, location: &Location<D>, replica: usize, demon: D) -> Result<(), Error> {
+        let (tx, rx) = oneshot::channel();
+        self.hell_channel.send(HellInstruction::RestartReplica{
+            address: location.address,
+            replica,
+            demon: Box::new(demon),
+            tx
+        }).map_err(broker_gone)?;
+        rx.await.map_err(broker_gone)?
+    }
+
+    /// Spawns a demon that reads from a byte stream in hell
+    ///
+    /// Demons spawned with this method need to implement the [IoThread](crate::IoThread) trait. They process both messages incoming from apocalypse, and the bytes read from `reader`, which can be any stream, like the reading half of a tcp or unix socket. Once the stream ends, the [on_close](crate::IoThread::on_close) function of the demon is called, and the demon leaves hell. Vanquishing the demon drops the reader. See [spawn_ws](Gate::spawn_ws) for websockets.
+    ///
+    /// ```rust
+    /// use apocalypse::{Hell, Demon, IoThread};
+    /// use tokio::io::{AsyncReadExt, AsyncWriteExt, DuplexStream, WriteHalf};
+    ///
+    /// struct Echo {
+    ///     writer: WriteHalf<DuplexStream>,
+    ///     echoed: usize
+    /// }
+    ///
+    /// impl Demon for Echo {
+    ///     type Input = ();
+    ///     type Output = usize;
+    ///     async fn handle(&mut self, _message: Self::Input) -> Self::Output {
+    ///         self.echoed
+    ///     }
+    /// }
+    ///
+    /// impl IoThread for Echo {
+    ///     async fn on_bytes(&mut self, buf: &[u8]) {
+    ///         self.echoed += buf.len();
+    ///         let _ = self.writer.write_all(buf).await;
+    ///     }
+    /// }
+    ///
+    /// # #[tokio::main]
+    /// # async fn main() {
+    /// let (gate, _join_handle) = Hell::new().ignite().await.unwrap();
+    /// // Any byte stream works, an in-memory one in this case
+    /// let (mut client, server) = tokio::io::duplex(64);
+    /// let (reader, writer) = tokio::io::split(server);
+    /// let location = gate.spawn_io(Echo{writer, echoed: 0}, reader).await.unwrap();
+    /// client.write_all(b"hello").await.unwrap();
+    /// let mut echo = [0; 5];
+    /// client.read_exact(&mut echo).await.unwrap();
+    /// assert_eq!(b"hello", &echo);
+    /// assert_eq!(5, gate.send(&location, ()).await.unwrap());
+    /// // Closing the stream makes the demon leave
+    /// drop(client);
+    /// while gate.stats().await.unwrap().active_demons > 0 {
+    ///     tokio::task::yield_now().await;
+    /// }
+    /// # }
+    /// ```
+    pub async fn spawn_io<D: 'static + Demon<Input = I, Output = O> + IoThread, I: 'static + Send, O: 'static + Send, R: 'static + AsyncRead + Unpin + Send>(&self, demon: D, reader: R) -> Result<Location<D>, Error> {
+        // We spawn the demon in a mini hell instance, which starts once it gets registered
+        let (ready, registered) = oneshot::channel();
+        let demon_channels = MiniIoHell::spawn(demon, self.unregistered_context(), registered, self.on_close_tx.clone(), reader);
+        self.register_demon(None, demon_channels, ready, None).await
+    }
+
+    /// Spawns a demon in hell, running on its own thread
+    ///
+    /// Regular demons are tasks of the runtime where hell was ignited, and they can move from one worker thread to another between messages. A pinned demon runs instead on a dedicated single threaded runtime, in its own OS thread, so it always runs on the same thread and keeps its CPU caches warm, and it never waits for a worker that is busy with other tasks. Messages reach it through its channels as usual. This is meant for a few demons where tail latency matters, as each one costs a thread. Tasks spawned by the demon, like [linked](crate::DemonContext::spawn_linked) ones, run on its runtime, and they are dropped together with the thread once the demon leaves hell.
+    ///
+    /// ```rust
+    /// use apocalypse::{Hell, Demon};
+    ///
+    /// struct ThreadName;
+    ///
+    /// impl Demon for ThreadName {
+    ///     type Input = ();
+    ///     type Output = Option<String>;
+    ///     async fn handle(&mut self, _message: Self::Input) -> Self::Output {
+    ///         std::thread::current().name().map(|name| name.to_string())
+    ///     }
+    /// }
+    ///
+    /// # #[tokio::main]
+    /// # async fn main() {
+    /// let (gate, _join_handle) = Hell::new().ignite().await.unwrap();
+    /// let location = gate.spawn_pinned(ThreadName).await.unwrap();
+    /// let first = gate.send(&location, ()).await.unwrap();
+    /// // Always the same thread, named after the address of the demon
+    /// assert_eq!(first, gate.send(&location, ()).await.unwrap());
+    /// assert!(first.unwrap().starts_with("apocalypse-d-"));
+    /// gate.vanquish(&location).await.unwrap();
+    /// # }
+    /// ```
+    pub async fn spawn_pinned<D: 'static + Demon<Input = I, Output = O>, I: 'static + Send, O: 'static + Send>(&self, demon: D) -> Result<Location<D>, Error> {
+        // The thread is named after the address, so it has to be known before the demon is registered
+        let address = self.reserve_address().await?;
+        let location = Location {
+            address,
+            phantom: PhantomData
+        };
+
+        // We spawn the demon in a mini hell instance on its own thread, which starts once it gets registered
+        let (ready, registered) = oneshot::channel();
+        let demon_channels = MiniHell::spawn_pinned(demon, self.context(&location), registered, self.on_close_tx.clone())?;
+        self.register_demon(Some(address), demon_channels, ready, None).await
+    }
+
+    /// Spawns a demon in hell that also receives the items of a stream as messages
+    ///
+    /// Each item of the stream reaches the [handle](crate::Demon::handle) function of the demon as a regular message, sent as with [send_and_ignore](Gate::send_and_ignore), so its output is dropped. Items are interleaved with the messages sent through gates, in arrival order. When the stream ends, the demon keeps serving regular messages. The stream is read by a task [linked](crate::DemonContext::spawn_linked) to the demon, so it is dropped when the demon leaves hell. Items that cannot be delivered, for example because the [interceptor](crate::HellBuilder::intercept) denies them, or because they are too large, are dropped, and the next items are still delivered. The stream is only dropped early once the demon, or hell, is gone.
+    ///
+    /// ```rust
+    /// use apocalypse::{Hell, Demon};
+    ///
+    /// struct Summer {
+    ///     total: u64
+    /// }
+    ///
+    /// impl Demon for Summer {
+    ///     type Input = u64;
+    ///     type Output = u64;
+    ///     async fn handle(&mut self, message: Self::Input) -> Self::Output {
+    ///         self.total += message;
+    ///         self.total
+    ///     }
+    /// }
+    ///
+    /// # #[tokio::main]
+    /// # async fn main() {
+    /// let (gate, _join_handle) = Hell::new().ignite().await.unwrap();
+    /// let location = gate.spawn_from_stream(Summer{total: 0}, futures::stream::iter(vec![1, 2, 3])).await.unwrap();
+    /// // Once the stream is over, the demon still answers
+    /// while gate.send(&location, 0).await.unwrap() < 6 {
+    ///     tokio::task::yield_now().await;
+    /// }
+    /// assert_eq!(10, gate.send(&location, 4).await.unwrap());
+    /// # }
+    /// ```
+    ///
+    /// A denied item does not end the stream.
+    ///
+    /// ```rust
+    /// use apocalypse::{HellBuilder, Demon, InterceptDecision};
+    /// use std::sync::atomic::{AtomicBool, Ordering};
+    /// use std::time::Duration;
+    ///
+    /// struct Summer {
+    ///     total: u64
+    /// }
+    ///
+    /// impl Demon for Summer {
+    ///     type Input = u64;
+    ///     type Output = u64;
+    ///     async fn handle(&mut self, message: Self::Input) -> Self::Output {
+    ///         self.total += message;
+    ///         self.total
+    ///     }
+    /// }
+    ///
+    /// # #[tokio::main]
+    /// # async fn main() {
+    /// // Only the first item of the stream is denied, as items are sent without waiting for a reply
+    /// let denied = AtomicBool::new(false);
+    /// let hell = HellBuilder::new().intercept(move |context| if context.ignore && !denied.swap(true, Ordering::Relaxed) {
+    ///     InterceptDecision::Deny
+    /// } else {
+    ///     InterceptDecision::Allow
+    /// }).build();
+    /// let (gate, _join_handle) = hell.ignite().await.unwrap();
+    /// let location = gate.spawn_from_stream(Summer{total: 0}, futures::stream::iter(vec![1, 2, 3])).await.unwrap();
+    /// tokio::time::timeout(Duration::from_secs(1), async {
+    ///     while gate.send(&location, 0).await.unwrap() < 5 {
+    ///         tokio::task::yield_now().await;
+    ///     }
+    /// }).await.unwrap();
+    /// # }
+    /// ```
+    pub async fn spawn_from_stream<D: 'static + Demon<Input = I, Output = O>, I: 'static + Send, O: 'static + Send, S: 'static + Stream<Item = I> + Send>(&self, demon: D, stream: S) -> Result<Location<D>, Error> {
+        // The context is kept, to link the task that reads the stream once the demon is registered
+        let context = self.unregistered_context();
+        let (ready, registered) = oneshot::channel();
+        let demon_channels = MiniHell::spawn(demon, context.clone(), registered, None, None, MessageOrder::Fifo, self.on_close_tx.clone());
+        let location = self.register_demon(None, demon_channels, ready, None).await?;
+        let address = location.address;
+
+        // The task only holds a weak gate, so that it does not keep hell alive
+        let feeder = context.gate().clone();
+        context.spawn_linked(async move {
+            let mut stream = std::pin::pin!(stream);
+            while let Some(item) = stream.next().await {
+                let gate = match feeder.upgrade() {
+                    Some(gate) => gate,
+                    None => break
+                };
+                // Same as send_and_ignore, which cannot be used as locations are not `Sync`
+                let (tx, rx) = oneshot::channel();
+                if gate.hell_channel.send(HellInstruction::Message {
+                    tx,
+                    address,
+                    ignore: true,
+                    trace: trace::inherit_or_new(),
+                    input: Box::new(item),
+                    types: None
+                }).is_err() {
+                    break;
+                }
+                match rx.await {
+                    Ok(Ok(_)) => (),
+                    // Only the item is lost, the next ones might still get through
+                    Ok(Err(e)) if !matches!(e, Error::InvalidLocation | Error::DemonCommunication | Error::BrokerGone) => {
+                        #[cfg(feature = "full_log")]
+                        log::debug!("[Gate] item of the stream of demon {} dropped, {}", address, e);
+                    },
+                    // The demon or hell is gone
+                    _result => {
+                        #[cfg(feature = "full_log")]
+                        log::debug!("[Gate] stream of demon {} dropped, {:?}", address, _result.map(|reply| reply.map(|_| ())));
+                        break;
+                    }
+                }
+            }
+        });
+
+        Ok(location)
+    }
+
+    /// Spawns a demon with websockets processing in hell
+    ///
+    /// Demons spawned with this method need to implement the WebSocketThread trait. Demons will process both messages incoming from apocalypse, as well as from the websockets connection. It is important to note that this method does not perform the websockets handshake, see [spawn_ws_upgrade](Gate::spawn_ws_upgrade) for that.
+    ///
+    /// When the demon leaves hell while the connection is still open, a close frame is sent and the close frame of the other side is awaited for up to one second before dropping the connection. The wait can be changed through [WsOptions](crate::WsOptions).
+    ///
+    /// ```rust,no_run
+    /// use apocalypse::{Hell, Demon};
+    /// use cataclysm::ws::{WebSocketThread, Message};
+    ///
+    /// struct PrintBot;
+    ///
+    /// impl Demon for PrintBot {
+    ///     type Input = ();
+    ///     type Output = ();
+    ///     async fn handle(&mut self, message: Self::Input) -> Self::Output {
+    ///         println!("Hello, world!");
+    ///     }
+    /// }
+    /// 
+    /// impl WebSocketThread for PrintBot {
+    ///     type Output = ();
+    ///     async fn on_message(&mut self, message: Message) {
+    ///         // ... do something with the message
+    ///     }
+    ///
+    ///     async fn on_close(&mut self, _clean: bool) -> Self::Output {}
+    /// }
+    ///
+    /// #[tokio::main]
+    /// async fn main() {
+    ///     let hell = Hell::new();
+    ///     let (gate, join_handle) = hell.ignite().await.unwrap();
+    ///     // In order to spawn, you should be able to obtain a
+    ///     // OwnedHalfRead tcp stream from tokio or similar
+    ///     // -> let _location = gate.spawn_ws(Basic{}, read_stream).await;
+    /// }
+    /// ```
+    #[cfg(feature = "ws")]
+    pub async fn spawn_ws<D: 'static + Demon<Input = I, Output = O> + WebSocketThread, I: 'static + Send, O: 'static + Send>(&self, demon: D, wsr: WebSocketReader) -> Result<Location<D>, Error> {
+        self.spawn_ws_with_reconnect(demon, wsr, WsOptions::default(), None).await
+    }
+
+    /// Spawns a demon with websockets processing in hell, with additional options
+    ///
+    /// Works like [spawn_ws](Gate::spawn_ws). When a [ReconnectPolicy](crate::ReconnectPolicy) is given, a connection that drops with an error is reestablished instead of vanquishing the demon. Messages from apocalypse keep being processed while reconnecting, and the demon keeps its address. Once a new connection is available, the [on_reconnect](crate::WebSocketReconnect::on_reconnect) function of the demon is called with its writing half. If the policy runs out of attempts, the demon closes as it would without reconnection.
+    ///
+    /// The [priority](crate::WsPriority) decides whether incoming frames or messages from apocalypse go first when both are waiting. A plain [spawn_ws](Gate::spawn_ws) picks between them at random.
+    ///
+    /// ```rust,no_run
+    /// use apocalypse::{Hell, Demon, WebSocketReconnect, WsOptions, WsPriority, ReconnectPolicy};
+    /// use cataclysm::ws::{WebSocketThread, WebSocketWriter, WebSocketStream, Message};
+    /// use tokio::net::TcpStream;
+    ///
+    /// struct Client {
+    ///     writer: WebSocketWriter
+    /// }
+    ///
+    /// impl Demon for Client {
+    ///     type Input = String;
+    ///     type Output = ();
+    ///     async fn handle(&mut self, message: Self::Input) -> Self::Output {
+    ///         let _ = self.writer.text(message).await;
+    ///     }
+    /// }
+    ///
+    /// impl WebSocketThread for Client {
+    ///     type Output = ();
+    ///     async fn on_message(&mut self, _message: Message) {}
+    ///     async fn on_close(&mut self, _clean: bool) -> Self::Output {}
+    /// }
+    ///
+    /// impl WebSocketReconnect for Client {
+    ///     async fn on_reconnect(&mut self, writer: WebSocketWriter) {
+    ///         self.writer = writer;
+    ///     }
+    /// }
+    ///
+    /// async fn connect() -> Option<(WebSocketWriter, cataclysm::ws::WebSocketReader)> {
+    ///     let stream = TcpStream::connect("127.0.0.1:8000").await.ok()?;
+    ///     // ... perform the client side of the handshake here
+    ///     Some(WebSocketStream::from_tcp_stream_unchecked(stream).split())
+    /// }
+    ///
+    /// #[tokio::main]
+    /// async fn main() {
+    ///     let (gate, join_handle) = Hell::new().ignite().await.unwrap();
+    ///     let (writer, wsr) = connect().await.unwrap();
+    ///     let options = WsOptions {
+    ///         reconnect: Some(ReconnectPolicy::new(connect)),
+    ///         // Frames are latency critical for this client
+    ///         priority: WsPriority::Frames,
+    ///         ..WsOptions::default()
+    ///     };
+    ///     let _location = gate.spawn_ws_with_options(Client{writer}, wsr, options).await.unwrap();
+    /// }
+    /// ```
+    #[cfg(feature = "ws")]
+    pub async fn spawn_ws_with_options<D: 'static + Demon<Input = I, Output = O> + WebSocketReconnect, I: 'static + Send, O: 'static + Send>(&self, demon: D, wsr: WebSocketReader, options: WsOptions) -> Result<Location<D>, Error> {
+        self.spawn_ws_with_reconnect(demon, wsr, options, Some(reconnect_hook::<D> as ReconnectHook<D>)).await
+    }
+
+    /// Spawns a demon with websockets processing in hell, with its options and the reconnection hook required by a reconnection policy
+    #[cfg(feature = "ws")]
+    async fn spawn_ws_with_reconnect<D: 'static + Demon<Input = I, Output = O> + WebSocketThread, I: 'static + Send, O: 'static + Send>(&self, demon: D, wsr: WebSocketReader, options: WsOptions, on_reconnect: Option<ReconnectHook<D>>) -> Result<Location<D>, Error> {
+        // We spawn the demon in a mini hell instance, which starts once it gets registered
+        let (ready, registered) = oneshot::channel();
+        let demon_channels = MiniWSHell::spawn(demon, self.unregistered_context(), registered, self.on_close_tx.clone(), wsr, options, on_reconnect);
+        self.register_demon(None, demon_channels, ready, None).await
+    }
+
+    /// Performs the websockets handshake over a tcp stream, and spawns a websockets demon in hell
+    ///
+    /// The http upgrade request is read from the stream and answered according to RFC 6455. Once the handshake succeeds, the stream is split, the writing half is handed to the demon factory, and the resulting demon is spawned just like with [spawn_ws](Gate::spawn_ws). If the request is not a valid upgrade request, a `400 Bad Request` response is sent and `Error::WebSocketHandshake` is returned.
+    ///
+    /// ```rust,no_run
+    /// use apocalypse::{Hell, Demon};
+    /// use cataclysm::ws::{WebSocketThread, WebSocketWriter, Message};
+    /// use tokio::net::TcpListener;
+    ///
+    /// struct EchoBot {
+    ///     writer: WebSocketWriter
+    /// }
+    ///
+    /// impl Demon for EchoBot {
+    ///     type Input = ();
+    ///     type Output = ();
+    ///     async fn handle(&mut self, _message: Self::Input) -> Self::Output {}
+    /// }
+    ///
+    /// impl WebSocketThread for EchoBot {
+    ///     type Output = ();
+    ///     async fn on_message(&mut self, message: Message) {
+    ///         if let Message::Text(text) = message {
+    ///             self.writer.text(text).await.unwrap();
+    ///         }
+    ///     }
+    ///
+    ///     async fn on_close(&mut self, _clean: bool) -> Self::Output {}
+    /// }
+    ///
+    /// #[tokio::main]
+    /// async fn main() {
+    ///     let (gate, _join_handle) = Hell::new().ignite().await.unwrap();
+    ///     let listener = TcpListener::bind("127.0.0.1:8000").await.unwrap();
+    ///     loop {
+    ///         let (tcp_stream, _) = listener.accept().await.unwrap();
+    ///         if let Err(e) = gate.spawn_ws_upgrade(|writer| EchoBot{writer}, tcp_stream).await {
+    ///             println!("could not spawn demon, {}", e);
+    ///         }
+    ///     }
+    /// }
+    /// ```
+    #[cfg(feature = "ws")]
+    pub async fn spawn_ws_upgrade<D: 'static + Demon<Input = I, Output = O> + WebSocketThread, I: 'static + Send, O: 'static + Send, F: FnOnce(WebSocketWriter) -> D>(&self, demon_factory: F, mut tcp_stream: TcpStream) -> Result<Location<D>, Error> {
+        ws_handshake::perform(&mut tcp_stream).await?;
+
+        let (read_stream, write_stream) = tcp_stream.into_split();
+        let demon = demon_factory(WebSocketWriter::new_unchecked(write_stream));
+        self.spawn_ws(demon, WebSocketReader::new_unchecked(read_stream)).await
+    }
+
+    /// Spawns a demon that consumes the websockets frames as a stream
+    ///
+    /// The frames read from `wsr` are handed to the [on_stream](crate::WebSocketStreamThread::on_stream) function of the demon, and the items of the resulting stream are delivered to the demon as regular messages. The stream is driven by a task linked to the demon, and the demon is vanquished once the stream finishes. See [spawn_ws](Gate::spawn_ws) for the callback based model.
+    ///
+    /// ```rust,no_run
+    /// use apocalypse::{Hell, Demon, WebSocketStreamThread, WebSocketFrames};
+    /// use futures::stream::{Stream, StreamExt};
+    ///
+    /// struct FrameCounter {
+    ///     frames: usize
+    /// }
+    ///
+    /// impl Demon for FrameCounter {
+    ///     type Input = ();
+    ///     type Output = ();
+    ///     async fn handle(&mut self, _message: Self::Input) -> Self::Output {
+    ///         self.frames += 1;
+    ///     }
+    /// }
+    ///
+    /// impl WebSocketStreamThread for FrameCounter {
+    ///     fn on_stream(&mut self, frames: WebSocketFrames) -> impl Stream<Item = Self::Input> + Send + 'static {
+    ///         frames.map(|_frame| ())
+    ///     }
+    /// }
+    ///
+    /// #[tokio::main]
+    /// async fn main() {
+    ///     let (gate, join_handle) = Hell::new().ignite().await.unwrap();
+    ///     // In order to spawn, you should be able to obtain a
+    ///     // WebSocketReader after the handshake
+    ///     // -> let _location = gate.spawn_ws_stream(FrameCounter{frames: 0}, wsr).await;
+    /// }
+    /// ```
+    #[cfg(feature = "ws")]
+    pub async fn spawn_ws_stream<D: 'static + Demon<Input = I, Output = O> + WebSocketStreamThread, I: 'static + Send, O: 'static + Send>(&self, mut demon: D, wsr: WebSocketReader) -> Result<Location<D>, Error> {
+        let mut inputs = Box::pin(demon.on_stream(WebSocketFrames::new(wsr)));
+
+        // We spawn the demon in a mini hell instance, which starts once it gets registered
+        let (ready, registered) = oneshot::channel();
+        let context = self.unregistered_context();
+        let demon_channels = MiniHell::spawn(demon, context.clone(), registered, None, None, MessageOrder::Fifo, self.on_close_tx.clone());
+        let location = self.register_demon(None, demon_channels, ready, None).await?;
+        let address = location.address;
+
+        // The stream gets driven by a task that dies with the demon
+        let gate = self.downgrade();
+        context.spawn_linked(async move {
+            while let Some(input) = inputs.next().await {
+                let Some(gate) = gate.upgrade() else {
+                    return;
+                };
+                let (tx, rx) = oneshot::channel();
+                if gate.hell_channel.send(HellInstruction::Message {
+                    tx,
+                    address,
+                    ignore: false,
+                    trace: trace::inherit_or_new(),
+                    input: Box::new(input),
+                    types: None
+                }).is_err() {
+                    return;
+                }
+                // Waiting for the reply keeps the stream from outrunning the demon
+                if !matches!(rx.await, Ok(Ok(_))) {
+                    return;
+                }
+            }
+
+            #[cfg(feature = "full_log")]
+            log::debug!("[Gate] frame stream of demon {} finished", address);
+            if let Some(gate) = gate.upgrade() {
+                // The reply is not awaited, as this very task gets aborted during the removal
+                let (tx, _rx) = oneshot::channel();
+                let _ = gate.priority_channel.send(HellInstruction::RemoveDemon {
+                    address,
+                    tx,
+                    ignore: true,
+                    force: None
+                });
+            }
+        });
+
+        Ok(location)
+    }
+
+    /// Get rid of one demon gracefully
+    ///
+    /// With this method, you request one demon to be dropped. Notice that locations will not automatically reflect this change, and further messages sent to the dropped demon will return `Error::InvalidLocation`. This method with block until the demon confirms is no longer executing anything. There is no guarantee that all pending messages will be processed before termination.
+    /// 
+    /// If the hell instance has a default `timeout` for vanquishing demons, this function will return the latest at `timeout`. If you want to override this behaviour for a single call, see [vanquish_with_timeout](Gate::vanquish_with_timeout).
+    ///
+    /// ```rust
+    /// use apocalypse::{Hell, Demon};
+    ///
+    /// struct EchoDemon{}
+    ///
+    /// impl Demon for EchoDemon {
+    ///     type Input = &'static str;
+    ///     type Output = ();
+    ///     async fn handle(&mut self, message: Self::Input) -> Self::Output {
+    ///         println!("{}", message);
+    ///     }
+    /// }
+    ///
+    /// #[tokio::main]
+    /// async fn main() {
+    ///     let hell = Hell::new();
+    ///     let join_handle = {
+    ///         // We ignite our hell instance in another span to guarantee our gate is dropped after use
+    ///         let (gate, join_handle) = hell.ignite().await.unwrap();
+    ///         // We spawn the echo demon
+    ///         let location = gate.spawn(EchoDemon{}).await.unwrap();
+    ///         // We wait until the demon fades away
+    ///         gate.vanquish(&location).await.unwrap();
+    ///         join_handle
+    ///     };
+    ///     // We await the system
+    ///     join_handle.await.unwrap();
+    /// }
+    /// ```
+    pub async fn vanquish<D: 'static + Demon<Input = I, Output = O>, I: 'static + Send, O: 'static + Send>(&self, location: &Location<D>) -> Result<(), Error> {
+        let (tx, rx) = oneshot::channel();
+        self.priority_channel.send(HellInstruction::RemoveDemon{
+            address: location.address,
+            tx,
+            ignore: false,
+            force: None
+        }).map_err(broker_gone)?;
+        rx.await.map_err(broker_gone)?
+    }
+
+    /// Get rid of one demon gracefully
+    ///
+    /// With this method, you request one demon to be dropped. Notice that locations will not automatically reflect this change, and further messages sent to the dropped demon will return `Error::InvalidLocation`. This method with block until the demon confirms is no longer executing anything. There is no guarantee that all pending messages will be processed before termination.
+    /// 
+    /// If the hell instance has a default `timeout` for vanquishing demons, this function will return the latest at `timeout`. If you want to override this behaviour for a single call, see [vanquish_with_timeout](Gate::vanquish_with_timeout).
+    ///
+    /// ```rust
+    /// use apocalypse::{Hell, Demon};
+    /// use std::time::Duration;
+    ///
+    /// struct EchoDemon{}
+    ///
+    /// impl Demon for EchoDemon {
+    ///     type Input = &'static str;
+    ///     type Output = ();
+    ///     async fn handle(&mut self, message: Self::Input) -> Self::Output {
+    ///         // The demon is slow, takes 2 seconds to reply
+    ///         tokio::time::sleep(Duration::from_secs(2)).await;
+    ///         println!("{}", message);
+    ///     }
+    /// }
+    ///
+    /// #[tokio::main]
+    /// async fn main() {
+    ///     let hell = Hell::new();
+    ///     let join_handle = {
+    ///         // We ignite our hell instance in another span to guarantee our gate is dropped after use
+    ///         let (gate, join_handle) = hell.ignite().await.unwrap();
+    ///         // We spawn the echo demon
+    ///         let location = gate.spawn(EchoDemon{}).await.unwrap();
+    ///         // We wait maximum 1 second for the demon to finish whatever it is doing
+    ///         gate.vanquish_with_timeout(&location, Some(Duration::from_secs(1))).await.unwrap();
+    ///         join_handle
+    ///     };
+    ///     // We await the system
+    ///     join_handle.await.unwrap();
+    /// }
+    /// ```
+    pub async fn vanquish_with_timeout<D: 'static + Demon<Input = I, Output = O>, I: 'static + Send, O: 'static + Send>(&self, location: &Location<D>, timeout: Option<std::time::Duration>) -> Result<(), Error> {
+        let (tx, rx) = oneshot::channel();
+        self.priority_channel.send(HellInstruction::RemoveDemon{
+            address: location.address,
+            tx,
+            ignore: false,
+            force: Some(timeout)
+        }).map_err(broker_gone)?;
+        rx.await.map_err(broker_gone)?
+    }
+
+    /// Get rid of one demon gracefully, and ignore the result
+    ///
+    /// As with [send](crate::Gate::send) and [send_and_ignore](crate::Gate::send_and_ignore), this method is prefered because there is a lower chance of a lockup happening. For example, if you were to allow your own demon to vanquish itself, you should use this method. The method fails if the vanquish request does not reach the demon. If you absolutely require to call this function without awaiting, use `tokio::spawn`.
+    ///
+    /// If the hell instance has a default `timeout` for vanquishing demons, this function will return the latest at `timeout`. If you want to override this behaviour for a single call, see [vanquish_and_ignore_with_timeout](Gate::vanquish_and_ignore_with_timeout).
+    ///
+    /// ```rust
+    /// use apocalypse::{Hell, Demon};
+    ///
+    /// struct EchoDemon{}
+    ///
+    /// impl Demon for EchoDemon {
+    ///     type Input = &'static str;
+    ///     type Output = ();
+    ///     async fn handle(&mut self, message: Self::Input) -> Self::Output {
+    ///         println!("{}", message);
+    ///     }
+    /// }
+    ///
+    /// #[tokio::main]
+    /// async fn main() {
+    ///     let hell = Hell::new();
+    ///     let join_handle = {
+    ///         // We ignite our hell instance in another span to guarantee our gate is dropped after use
+    ///         let (gate, join_handle) = hell.ignite().await.unwrap();
+    ///         // We spawn the echo demon
+    ///         let location = gate.spawn(EchoDemon{}).await.unwrap();
+    ///         // This function fails if the vanquish request does not reach the demon, but if
+    ///         // if it does, it does not block.
+    ///         gate.vanquish_and_ignore(&location).await.unwrap();
+    ///         join_handle
+    ///     };
+    ///     // We await the system
+    ///     join_handle.await.unwrap();
+    /// }
+    /// ```
+    pub async fn vanquish_and_ignore<D: 'static + Demon<Input = I, Output = O>, I: 'static + Send, O: 'static + Send>(&self, location: &Location<D>) -> Result<(), Error> {
+        let (tx, rx) = oneshot::channel();
+        self.priority_channel.send(HellInstruction::RemoveDemon {
+            address: location.address,
+            tx,
+            ignore: true,
+            force: None
+        }).map_err(broker_gone)?;
+        rx.await.map_err(broker_gone)?
+    }
+
+    /// Get rid of one demon, and ignore the result.
+    ///
+    /// As with [send](crate::Gate::send) and [send_and_ignore](crate::Gate::send_and_ignore), this method is prefered because there is a lower chance of a lockup happening. For example, if you were to allow your own demon to vanquish itself, you should use this method. If you absolutely require to call this function without awaiting, use `tokio::spawn`.
+    ///
+    /// ```rust
+    /// use apocalypse::{Hell, Demon};
+    /// use std::time::Duration;
+    ///
+    /// struct EchoDemon{}
+    ///
+    /// impl Demon for EchoDemon {
+    ///     type Input = &'static str;
+    ///     type Output = ();
+    ///     async fn handle(&mut self, message: Self::Input) -> Self::Output {
+    ///         // The demon is slow, takes 2 seconds to reply
+    ///         tokio::time::sleep(Duration::from_secs(2)).await;
+    ///         println!("{}", message);
+    ///     }
+    /// }
+    ///
+    /// #[tokio::main]
+    /// async fn main() {
+    ///     let hell = Hell::new();
+    ///     let join_handle = {
+    ///         // We ignite our hell instance in another span to guarantee our gate is dropped after use
+    ///         let (gate, join_handle) = hell.ignite().await.unwrap();
+    ///         // We spawn the echo demon
+    ///         let location = gate.spawn(EchoDemon{}).await.unwrap();
+    ///         // We wait maximum 1 second for the demon to finish whatever it is doing
+    ///         gate.vanquish_and_ignore_with_timeout(&location, Some(Duration::from_secs(1))).await.unwrap();
+    ///         join_handle
+    ///     };
+    ///     // We await the system
+    ///     join_handle.await.unwrap();
+    /// }
+    /// ```
+    pub async fn vanquish_and_ignore_with_timeout<D: 'static + Demon<Input = I, Output = O>, I: 'static + Send, O: 'static + Send>(&self, location: &Location<D>, timeout: Option<std::time::Duration>) -> Result<(), Error> {
+        let (tx, rx) = oneshot::channel();
+        self.priority_channel.send(HellInstruction::RemoveDemon{
+            address: location.address,
+            tx,
+            ignore: true,
+            force: Some(timeout)
+        }).map_err(broker_gone)?;
+        rx.await.map_err(broker_gone)?
+    }
+
+    /// Replaces a demon by a new one, without losing any message
+    ///
+    /// The old demon finishes the message it is handling, and hands all the messages that it did not handle yet over to the new demon, which takes its address. The new demon handles the inherited messages before any other, and the old one leaves through its [vanquished](crate::Demon::vanquished) function. Replies to the inherited messages come from the new demon, which is the next [generation](Gate::generation) at the address. If the old demon is gone, `Error::InvalidLocation` is returned and the new demon is dropped. Only single demons can be handed off, pools and demons spawned with a reader or a websocket connection give `Error::HandoffUnsupported`, and keep their address.
+    ///
+    /// ```rust
+    /// use apocalypse::{Hell, Demon};
+    /// use std::time::Duration;
+    ///
+    /// struct Parser {
+    ///     version: usize
+    /// }
+    ///
+    /// impl Demon for Parser {
+    ///     type Input = String;
+    ///     type Output = (usize, usize);
+    ///     async fn handle(&mut self, message: Self::Input) -> Self::Output {
+    ///         tokio::time::sleep(Duration::from_millis(50)).await;
+    ///         (self.version, message.len())
+    ///     }
+    /// }
+    ///
+    /// # #[tokio::main]
+    /// # async fn main() {
+    /// let (gate, join_handle) = Hell::new().ignite().await.unwrap();
+    /// let location = gate.spawn(Parser{version: 1}).await.unwrap();
+    /// // Some messages are still waiting in the mailbox during the upgrade
+    /// let replies: Vec<_> = (0..3).map(|_| gate.send_with_responder(&location, "Hallo".to_string()).unwrap()).collect();
+    /// gate.handoff(&location, Parser{version: 2}).await.unwrap();
+    /// for reply in replies {
+    ///     let reply = reply.await.unwrap().unwrap().downcast::<(usize, usize)>().unwrap();
+    ///     assert_eq!(5, reply.1);
+    /// }
+    /// // Same address, new demon
+    /// assert_eq!((2, 5), gate.send(&location, "Welt!".to_string()).await.unwrap());
+    /// # }
+    /// ```
+    ///
+    /// The new demon keeps the address even if the old one leaves before handing its messages over, for example because it panics.
+    ///
+    /// ```rust
+    /// use apocalypse::{Hell, Demon};
+    /// use std::time::Duration;
+    ///
+    /// struct Fragile {
+    ///     broken: bool
+    /// }
+    ///
+    /// impl Demon for Fragile {
+    ///     type Input = ();
+    ///     type Output = bool;
+    ///     async fn handle(&mut self, _message: Self::Input) -> Self::Output {
+    ///         if self.broken {
+    ///             tokio::time::sleep(Duration::from_millis(50)).await;
+    ///             panic!("the old demon breaks during the handoff");
+    ///         }
+    ///         true
+    ///     }
+    /// }
+    ///
+    /// # #[tokio::main]
+    /// # async fn main() {
+    /// let (gate, join_handle) = Hell::new().ignite().await.unwrap();
+    /// let location = gate.spawn(Fragile{broken: true}).await.unwrap();
+    /// gate.send_and_ignore(&location, ()).await.unwrap();
+    /// tokio::time::sleep(Duration::from_millis(10)).await;
+    /// gate.handoff(&location, Fragile{broken: false}).await.unwrap();
+    /// tokio::time::sleep(Duration::from_millis(100)).await;
+    /// assert!(gate.send(&location, ()).await.unwrap());
+    /// # }
+    /// ```
+    ///
+    /// A pool is not replaced, and keeps serving.
+    ///
+    /// ```rust
+    /// use apocalypse::{Hell, Demon, Error};
+    ///
+    /// struct Replica {
+    ///     id: usize
+    /// }
+    ///
+    /// impl Demon for Replica {
+    ///     type Input = ();
+    ///     type Output = usize;
+    ///     async fn handle(&mut self, _message: Self::Input) -> Self::Output {
+    ///         self.id
+    ///     }
+    /// }
+    ///
+    /// # #[tokio::main]
+    /// # async fn main() {
+    /// let (gate, join_handle) = Hell::new().ignite().await.unwrap();
+    /// let location = gate.spawn_multiple_indexed(|id| Replica{id}, 1).await.unwrap();
+    /// assert!(matches!(gate.handoff(&location, Replica{id: 7}).await, Err(Error::HandoffUnsupported)));
+    /// assert_eq!(0, gate.send(&location, ()).await.unwrap());
+    /// assert_eq!(0, gate.generation(&location).await.unwrap());
+    /// # }
+    /// ```
+    pub async fn handoff<D: 'static + Demon<Input = I, Output = O>, I: 'static + Send, O: 'static + Send>(&self, old: &Location<D>, new_demon: D) -> Result<(), Error> {
+        // The new demon waits for the messages of the old one before starting
+        let (ready, registered) = oneshot::channel();
+        let (inherited_tx, inherited) = oneshot::channel();
+        let demon_channels = MiniHell::spawn(new_demon, self.context(old), registered, None, Some(inherited), MessageOrder::Fifo, self.on_close_tx.clone());
+
+        let (tx, rx) = oneshot::channel();
+        self.hell_channel.send(HellInstruction::Handoff {
+            address: old.address,
+            demon_channels,
+            ready,
+            inherited: inherited_tx,
+            tx
+        }).map_err(broker_gone)?;
+        rx.await.map_err(broker_gone)?
+    }
+
+    /// Returns the generation of the demon at a location
+    ///
+    /// The first demon spawned at an address is generation zero, and each [handoff](Gate::handoff) at that address increments it, so restarts of a demon that keeps its address can be told apart. Replica restarts replace a single replica, and do not change the generation. Demons can read their own generation from their [context](crate::DemonContext::generation), whose `Display` shows it next to the address. If the demon is gone, `Error::InvalidLocation` is returned.
+    ///
+    /// ```rust
+    /// use apocalypse::{Hell, Demon, DemonContext};
+    ///
+    /// struct Worker {
+    ///     ctx: Option<DemonContext<Worker>>
+    /// }
+    ///
+    /// impl Demon for Worker {
+    ///     type Input = ();
+    ///     type Output = String;
+    ///
+    ///     async fn spawned_with_context(&mut self, ctx: DemonContext<Self>) {
+    ///         self.ctx = Some(ctx);
+    ///     }
+    ///
+    ///     async fn handle(&mut self, _message: Self::Input) -> Self::Output {
+    ///         self.ctx.as_ref().map(|ctx| ctx.to_string()).unwrap_or_default()
+    ///     }
+    /// }
+    ///
+    /// # #[tokio::main]
+    /// # async fn main() {
+    /// let (gate, join_handle) = Hell::new().ignite().await.unwrap();
+    /// let location = gate.spawn(Worker{ctx: None}).await.unwrap();
+    /// assert_eq!(0, gate.generation(&location).await.unwrap());
+    /// gate.handoff(&location, Worker{ctx: None}).await.unwrap();
+    /// gate.handoff(&location, Worker{ctx: None}).await.unwrap();
+    /// assert_eq!(2, gate.generation(&location).await.unwrap());
+    /// assert_eq!(format!("{}.2", location), gate.send(&location, ()).await.unwrap());
+    /// # }
+    /// ```
+    pub async fn generation<D>(&self, location: &Location<D>) -> Result<usize, Error> {
+        let (tx, rx) = oneshot::channel();
+        self.hell_channel.send(HellInstruction::Generation {
+            address: location.address,
+            tx
+        }).map_err(broker_gone)?;
+        rx.await.map_err(broker_gone)?
+    }
+
+    /// Stops the broker
+    ///
+    /// By default, the timeout will be used (if set) to put a maximum wait time for all remaining demons to finalize. You can override the behaviour for this function by using the [extinguish_with_timeout](Gate::extinguish_with_timeout). The returned [ExtinguishReport](crate::ExtinguishReport) tells which demons left gracefully, and which ones were killswitched.
+    ///
+    /// Once the broker stops, requests through any other gate of the same hell fail with `Error::BrokerGone`.
+    ///
+    /// ```rust
+    /// use apocalypse::{Hell, Demon, Error};
+    ///
+    /// struct EchoDemon{}
+    ///
+    /// impl Demon for EchoDemon {
+    ///     type Input = &'static str;
+    ///     type Output = ();
+    ///     async fn handle(&mut self, message: Self::Input) -> Self::Output {
+    ///         println!("{}", message);
+    ///     }
+    /// }
+    ///
+    /// #[tokio::main]
+    /// async fn main() {
     ///     let hell = Hell::new();
-    ///     let (gate, join_handle) = hell.ignite().await.unwrap();
-    ///     // In order to spawn, you should be able to obtain a
-    ///     // OwnedHalfRead tcp stream from tokio or similar
-    ///     // -> let _location = gate.spawn_ws(Basic{}, read_stream).await;
+    ///     let (join_handle, kept) = {
+    ///         // We ignite our hell instance in another span to guarantee our gate is dropped after use
+    ///         let (gate, join_handle) = hell.ignite().await.unwrap();
+    ///         let kept = gate.clone();
+    ///         gate.extinguish().await.unwrap();
+    ///         (join_handle, kept)
+    ///     };
+    ///     // We await the system
+    ///     join_handle.await.unwrap();
+    ///     // Gates that outlive hell are told so
+    ///     assert!(matches!(kept.spawn(EchoDemon{}).await, Err(Error::BrokerGone)));
+    /// }
+    /// ```
+    pub async fn extinguish(self) -> Result<ExtinguishReport, Error>{
+        let (tx, rx) = oneshot::channel();
+        self.priority_channel.send(HellInstruction::Extinguish{tx, timeout: None, deadline: None}).map_err(broker_gone)?;
+        rx.await.map_err(broker_gone)?
+    }
+
+    /// Stops the broker
+    ///
+    /// Same as [extinguish](Gate::extinguish), but with an override to the timeout parameter
+    ///
+    /// ```rust
+    /// use apocalypse::{Hell, Demon};
+    ///
+    /// struct EchoDemon{}
+    ///
+    /// impl Demon for EchoDemon {
+    ///     type Input = &'static str;
+    ///     type Output = ();
+    ///     async fn handle(&mut self, message: Self::Input) -> Self::Output {
+    ///         println!("{}", message);
+    ///     }
     /// }
+    ///
+    /// #[tokio::main]
+    /// async fn main() {
+    ///     let hell = Hell::new();
+    ///     let join_handle = {
+    ///         // We ignite our hell instance in another span to guarantee our gate is dropped after use
+    ///         let (gate, join_handle) = hell.ignite().await.unwrap();
+    ///         gate.extinguish_with_timeout(Some(std::time::Duration::from_secs(2))).await.unwrap();
+    ///         join_handle
+    ///     };
+    ///     // We await the system
+    ///     join_handle.await.unwrap();
+    /// }
+    /// ```
+    pub async fn extinguish_with_timeout(self, timeout: Option<std::time::Duration>) -> Result<ExtinguishReport, Error>{
+        let (tx, rx) = oneshot::channel();
+        self.priority_channel.send(HellInstruction::Extinguish{tx, timeout: Some(timeout), deadline: None}).map_err(broker_gone)?;
+        rx.await.map_err(broker_gone)?
+    }
+
+    /// Stops the broker, within a hard deadline
+    ///
+    /// Same as [extinguish](Gate::extinguish), with the default timeout of hell for each demon, but the shutdown as a whole never lasts past the deadline. Demons that did not leave by then are killswitched, and the report lists them as abandoned without waiting for them, so their [vanquished](crate::Demon::vanquished) functions might still be running, or be cancelled, after this function returns. A `std::time::Instant` can be converted with `tokio::time::Instant::from_std`.
+    ///
+    /// ```rust
+    /// use apocalypse::{Hell, Demon};
+    /// use std::time::Duration;
+    /// use tokio::time::Instant;
+    ///
+    /// struct Stubborn;
+    ///
+    /// impl Demon for Stubborn {
+    ///     type Input = ();
+    ///     type Output = ();
+    ///     async fn handle(&mut self, _message: Self::Input) -> Self::Output {}
+    ///     async fn vanquished(self) {
+    ///         tokio::time::sleep(Duration::from_secs(60)).await;
+    ///     }
+    /// }
+    ///
+    /// # #[tokio::main]
+    /// # async fn main() {
+    /// let (gate, join_handle) = Hell::new().ignite().await.unwrap();
+    /// gate.spawn(Stubborn).await.unwrap();
+    /// let start = Instant::now();
+    /// let report = gate.extinguish_by(start + Duration::from_millis(50)).await.unwrap();
+    /// assert!(start.elapsed() < Duration::from_secs(60));
+    /// assert_eq!(vec![0], report.abandoned);
+    /// # }
+    /// ```
+    pub async fn extinguish_by(self, deadline: tokio::time::Instant) -> Result<ExtinguishReport, Error>{
+        let (tx, rx) = oneshot::channel();
+        self.priority_channel.send(HellInstruction::Extinguish{tx, timeout: None, deadline: Some(deadline)}).map_err(broker_gone)?;
+        rx.await.map_err(broker_gone)?
+    }
+
+    /// Stops the broker, without consuming the gate
+    ///
+    /// Same as [extinguish_with_timeout](Gate::extinguish_with_timeout), but any holder of a gate reference can call it, for example through an `Arc<Gate>`. Calling it once hell already stopped, or while it is shutting down, is not an error. In that case, the call returns an empty report once hell is gone.
+    ///
+    /// ```rust
+    /// use apocalypse::{Hell};
+    /// use std::sync::Arc;
+    ///
+    /// #[tokio::main]
+    /// async fn main() {
+    ///     let (gate, join_handle) = Hell::new().ignite().await.unwrap();
+    ///     let gate = Arc::new(gate);
+    ///     let coordinator = gate.clone();
+    ///     coordinator.extinguish_ref(None).await.unwrap();
+    ///     join_handle.await.unwrap();
+    ///     // Further calls are harmless
+    ///     gate.extinguish_ref(None).await.unwrap();
+    /// }
+    /// ```
+    pub async fn extinguish_ref(&self, timeout: Option<std::time::Duration>) -> Result<ExtinguishReport, Error>{
+        let (tx, rx) = oneshot::channel();
+        if self.priority_channel.send(HellInstruction::Extinguish{tx, timeout: Some(timeout), deadline: None}).is_err() {
+            // Hell is already gone
+            return Ok(ExtinguishReport::default());
+        }
+        // A dropped reply means that another extinguish request was serviced first
+        rx.await.unwrap_or(Ok(ExtinguishReport::default()))
+    }
+
+    /// Cancels all the messages that a demon did not start to handle yet
+    ///
+    /// Each cancelled message is answered with `Error::Cancelled`, without calling the [handle](crate::Demon::handle) function, and the amount of cancelled messages is returned. Only messages sent before this call, and not started by the time the cancellation reaches the demon, are affected. Messages already being handled always run to completion, and for demons spawned with a single instance, the cancellation takes place once the current message finishes. The demon stays alive and keeps processing new messages.
+    ///
+    /// ```rust
+    /// use apocalypse::{Hell, Demon, Error};
+    ///
+    /// struct Slow;
+    ///
+    /// impl Demon for Slow {
+    ///     type Input = ();
+    ///     type Output = ();
+    ///     async fn handle(&mut self, _message: Self::Input) -> Self::Output {
+    ///         tokio::time::sleep(std::time::Duration::from_millis(100)).await;
+    ///     }
+    /// }
+    ///
+    /// # #[tokio::main]
+    /// # async fn main() {
+    /// let (gate, join_handle) = Hell::new().ignite().await.unwrap();
+    /// let location = gate.spawn(Slow).await.unwrap();
+    /// let (first, second, third, cancelled) = tokio::join!(
+    ///     gate.send(&location, ()),
+    ///     gate.send(&location, ()),
+    ///     gate.send(&location, ()),
+    ///     gate.cancel_pending(&location)
+    /// );
+    /// // Messages that did not start are answered with an error
+    /// let errors = [first, second, third].into_iter().filter(|reply| matches!(reply, Err(Error::Cancelled))).count();
+    /// assert_eq!(errors, cancelled.unwrap());
+    /// // The demon is still alive
+    /// assert!(gate.send(&location, ()).await.is_ok());
+    /// # }
+    /// ```
+    pub async fn cancel_pending<D>(&self, location: &Location<D>) -> Result<usize, Error> {
+        let (tx, rx) = oneshot::channel();
+        self.hell_channel.send(HellInstruction::CancelPending{
+            address: location.address,
+            tx
+        }).map_err(broker_gone)?;
+        rx.await.map_err(broker_gone)?
+    }
+
+    /// Installs an observer that is called with every output of a demon, right before it is sent back
+    ///
+    /// Observers see the outputs of all the messages handled after the installation, including the ones sent with [send_and_ignore](Gate::send_and_ignore), and they cannot modify them. They are called from the task of the demon, so they should be quick. For demons spawned with replicas, a single observer is shared by all of them. Observers stay until the demon leaves hell, and a demon replaced through [handoff](Gate::handoff) does not pass them on.
+    ///
+    /// ```rust
+    /// use apocalypse::{Hell, Demon};
+    /// use std::sync::{Arc, atomic::{AtomicUsize, Ordering}};
+    ///
+    /// struct Doubler;
+    ///
+    /// impl Demon for Doubler {
+    ///     type Input = usize;
+    ///     type Output = usize;
+    ///     async fn handle(&mut self, message: Self::Input) -> Self::Output {
+    ///         message * 2
+    ///     }
+    /// }
+    ///
+    /// # #[tokio::main]
+    /// # async fn main() {
+    /// let (gate, join_handle) = Hell::new().ignite().await.unwrap();
+    /// let location = gate.spawn(Doubler).await.unwrap();
+    /// let total = Arc::new(AtomicUsize::new(0));
+    /// let observed = total.clone();
+    /// gate.tap(&location, move |output: &usize| {
+    ///     observed.fetch_add(*output, Ordering::Relaxed);
+    /// }).await.unwrap();
+    /// gate.send(&location, 1).await.unwrap();
+    /// gate.send(&location, 2).await.unwrap();
+    /// assert_eq!(total.load(Ordering::Relaxed), 6);
+    /// # }
+    /// ```
+    pub async fn tap<D: 'static + Demon<Input = I, Output = O>, I: 'static + Send, O: 'static + Send, F: 'static + Fn(&O) + Send + Sync>(&self, location: &Location<D>, observer: F) -> Result<(), Error> {
+        // The observer travels type erased, and the mini hell recovers it with its output type
+        let observer: Observer<O> = Arc::new(observer);
+        let (tx, rx) = oneshot::channel();
+        self.hell_channel.send(HellInstruction::Tap{
+            address: location.address,
+            observer: Box::new(observer),
+            tx
+        }).map_err(broker_gone)?;
+        rx.await.map_err(broker_gone)?
+    }
+
+    /// Declares that a demon sends requests to (and awaits replies from) another demon
+    ///
+    /// Declarations are just bookkeeping, they do not change how messages are delivered. Together with [check_cycles](Gate::check_cycles), they allow to verify that the request-reply topology of the system is free of message-cycles, and therefore free of lockups. Declarations are removed when any of the two demons leaves hell.
+    ///
+    /// ```rust
+    /// use apocalypse::{Hell, Demon, Error};
+    ///
+    /// struct Ping;
+    ///
+    /// impl Demon for Ping {
+    ///     type Input = ();
+    ///     type Output = ();
+    ///     async fn handle(&mut self, _message: Self::Input) -> Self::Output {}
+    /// }
+    ///
+    /// # #[tokio::main]
+    /// # async fn main() {
+    /// let (gate, join_handle) = Hell::new().ignite().await.unwrap();
+    /// let a = gate.spawn(Ping).await.unwrap();
+    /// let b = gate.spawn(Ping).await.unwrap();
+    /// gate.declare_dependency(&a, &b).await.unwrap();
+    /// assert!(gate.check_cycles().await.is_ok());
+    /// // b requesting from a would close a cycle
+    /// gate.declare_dependency(&b, &a).await.unwrap();
+    /// assert!(matches!(gate.check_cycles().await, Err(Error::DependencyCycle(_))));
+    /// # }
+    /// ```
+    pub async fn declare_dependency<A, B>(&self, from: &Location<A>, to: &Location<B>) -> Result<(), Error> {
+        let (tx, rx) = oneshot::channel();
+        self.hell_channel.send(HellInstruction::DeclareDependency{
+            from: from.address,
+            to: to.address,
+            tx
+        }).map_err(broker_gone)?;
+        rx.await.map_err(broker_gone)?
+    }
+
+    /// Checks the declared dependencies for message-cycles
+    ///
+    /// Returns `Error::DependencyCycle` with the addresses that form a cycle, if any. See [declare_dependency](Gate::declare_dependency).
+    pub async fn check_cycles(&self) -> Result<(), Error> {
+        let (tx, rx) = oneshot::channel();
+        self.hell_channel.send(HellInstruction::CheckCycles{tx}).map_err(broker_gone)?;
+        rx.await.map_err(broker_gone)?
+    }
+
+    /// Subscribes a demon to a topic
+    ///
+    /// Messages published to the topic with [publish](Gate::publish) get delivered to the demon, as long as they are of its `Input` type. Subscribing twice to the same topic has no effect. Subscriptions are removed when the demon leaves hell.
+    ///
+    /// ```rust
+    /// use apocalypse::{Hell, Demon};
+    ///
+    /// struct Reader;
+    ///
+    /// impl Demon for Reader {
+    ///     type Input = String;
+    ///     type Output = ();
+    ///     async fn handle(&mut self, headline: Self::Input) -> Self::Output {
+    ///         println!("read: {}", headline);
+    ///     }
+    /// }
+    ///
+    /// struct Counter;
+    ///
+    /// impl Demon for Counter {
+    ///     type Input = usize;
+    ///     type Output = ();
+    ///     async fn handle(&mut self, _message: Self::Input) -> Self::Output {}
+    /// }
+    ///
+    /// # #[tokio::main]
+    /// # async fn main() {
+    /// let (gate, join_handle) = Hell::new().ignite().await.unwrap();
+    /// let first = gate.spawn(Reader).await.unwrap();
+    /// let second = gate.spawn(Reader).await.unwrap();
+    /// let counter = gate.spawn(Counter).await.unwrap();
+    /// gate.subscribe(&first, "news").await.unwrap();
+    /// gate.subscribe(&second, "news").await.unwrap();
+    /// gate.subscribe(&counter, "news").await.unwrap();
+    /// // The counter does not take strings
+    /// assert_eq!(2, gate.publish("news", "Hell freezes over".to_string()).await.unwrap());
+    /// gate.unsubscribe(&first, "news").await.unwrap();
+    /// assert_eq!(1, gate.publish("news", "Still frozen".to_string()).await.unwrap());
+    /// # }
     /// ```
-    #[cfg(feature = "ws")]
-    pub async fn spawn_ws<D: 'static + Demon<Input = I, Output = O> + WebSocketThread, I: 'static + Send, O: 'static + Send>(&self, demon: D, wsr: WebSocketReader) -> Result<Location<D>, Error> {
-        // First return channel, to get a valid address
+    pub async fn subscribe<D: Demon>(&self, location: &Location<D>, topic: &str) -> Result<(), Error> {
         let (tx, rx) = oneshot::channel();
-
-        // We request an address
-        self.hell_channel.send(HellInstruction::CreateAddress {
+        self.hell_channel.send(HellInstruction::Subscribe{
+            address: location.address,
+            topic: topic.to_string(),
             tx
-        }).map_err(|e| Error::TokioSend(format!("{}", e)))?;
-        let address = rx.await.map_err(|s| Error::TokioSend(format!("{}", s)))?;
-
-        let location = Location {
-            address,
-            phantom: PhantomData
-        };
-
-        // We spawn the demon in a mini hell instance
-        let demon_channels = MiniWSHell::spawn(demon, location.clone(), self.on_close_tx.clone(), wsr);
+        }).map_err(broker_gone)?;
+        rx.await.map_err(broker_gone)?
+    }
 
-        // Second return channel, for knowing if the registration was successful
+    /// Removes the subscription of a demon to a topic
+    ///
+    /// Removing a subscription that does not exist has no effect. See [subscribe](Gate::subscribe).
+    pub async fn unsubscribe<D: Demon>(&self, location: &Location<D>, topic: &str) -> Result<(), Error> {
         let (tx, rx) = oneshot::channel();
+        self.hell_channel.send(HellInstruction::Unsubscribe{
+            address: location.address,
+            topic: topic.to_string(),
+            tx
+        }).map_err(broker_gone)?;
+        rx.await.map_err(broker_gone)?
+    }
 
-        // We attempt the registration process
-        self.hell_channel.send(HellInstruction::RegisterDemon {
-            address,
-            demon_channels,
+    /// Delivers a copy of a message to all the demons subscribed to a topic whose `Input` is of the message's type
+    ///
+    /// Each delivery works like [send_and_ignore](Gate::send_and_ignore), so replies are discarded, and the amount of demons that got the message is returned. Subscribers of other types are skipped, as well as the ones whose maximum message size is exceeded. A topic without subscribers is not an error. See [subscribe](Gate::subscribe).
+    pub async fn publish<M: 'static + Clone + Send>(&self, topic: &str, message: M) -> Result<usize, Error> {
+        let (tx, rx) = oneshot::channel();
+        self.hell_channel.send(HellInstruction::Publish{
+            topic: topic.to_string(),
+            input: Box::new(message),
+            // The broker only ever hands the published message to this function
+            clone: |input| Box::new(input.downcast_ref::<M>().cloned().expect("published message keeps its type")),
+            trace: trace::inherit_or_new(),
             tx
-        }).map_err(|e| Error::TokioSend(format!("{}", e)))?;
+        }).map_err(broker_gone)?;
+        rx.await.map_err(broker_gone)
+    }
 
-        // If it returned true, then everything is ok
-        rx.await.map_err(|s| Error::TokioSend(format!("{}", s)))?.map(move |_| location)
+    /// Builds the context for a demon about to be spawned
+    fn context<D>(&self, location: &Location<D>) -> DemonContext<D> {
+        DemonContext {
+            location: location.clone(),
+            generation: 0,
+            // Demons do not share the limit of the gate that spawned them
+            gate: WeakGate {
+                concurrency: None,
+                ..self.downgrade()
+            },
+            linked: Default::default(),
+            mailbox: Default::default(),
+            scale: None,
+            clock: self.clock.clone(),
+            shutdown: self.shutdown.clone(),
+            handle_time: self.handle_time.clone(),
+            ready: None
+        }
     }
 
-    /// Get rid of one demon gracefully
+    /// Builds the context for a demon about to be spawned at an address picked by hell
     ///
-    /// With this method, you request one demon to be dropped. Notice that locations will not automatically reflect this change, and further messages sent to the dropped demon will return `Error::InvalidLocation`. This method with block until the demon confirms is no longer executing anything. There is no guarantee that all pending messages will be processed before termination.
-    /// 
-    /// If the hell instance has a default `timeout` for vanquishing demons, this function will return the latest at `timeout`. If you want to override this behaviour for a single call, see [vanquish_with_timeout](Gate::vanquish_with_timeout).
+    /// The mini hell fills the address in once the demon is registered, before anything else gets to see the context.
+    fn unregistered_context<D>(&self) -> DemonContext<D> {
+        self.context(&Location {
+            address: usize::MAX,
+            phantom: PhantomData
+        })
+    }
+
+    /// Requests hell statistics
+    ///
+    /// This method returns a structure containing operation stats. Stats requests, as well as vanquish and extinguish requests, travel through a control channel that hell services before regular messages, so they stay responsive under heavy load.
     ///
     /// ```rust
     /// use apocalypse::{Hell, Demon};
@@ -339,36 +3030,18 @@ impl Gate {
     ///     let join_handle = {
     ///         // We ignite our hell instance in another span to guarantee our gate is dropped after use
     ///         let (gate, join_handle) = hell.ignite().await.unwrap();
-    ///         // We spawn the echo demon
-    ///         let location = gate.spawn(EchoDemon{}).await.unwrap();
-    ///         // We wait until the demon fades away
-    ///         gate.vanquish(&location).await.unwrap();
+    ///         println!("{:?}", gate.stats().await.unwrap());
     ///         join_handle
     ///     };
     ///     // We await the system
     ///     join_handle.await.unwrap();
     /// }
     /// ```
-    pub async fn vanquish<D: 'static + Demon<Input = I, Output = O>, I: 'static + Send, O: 'static + Send>(&self, location: &Location<D>) -> Result<(), Error> {
-        let (tx, rx) = oneshot::channel();
-        self.hell_channel.send(HellInstruction::RemoveDemon{
-            address: location.address,
-            tx,
-            ignore: false,
-            force: None
-        }).map_err(|e| Error::TokioSend(format!("{}", e)))?;
-        rx.await.map_err(|e| Error::TokioSend(format!("{}", e)))?
-    }
-
-    /// Get rid of one demon gracefully
     ///
-    /// With this method, you request one demon to be dropped. Notice that locations will not automatically reflect this change, and further messages sent to the dropped demon will return `Error::InvalidLocation`. This method with block until the demon confirms is no longer executing anything. There is no guarantee that all pending messages will be processed before termination.
-    /// 
-    /// If the hell instance has a default `timeout` for vanquishing demons, this function will return the latest at `timeout`. If you want to override this behaviour for a single call, see [vanquish_with_timeout](Gate::vanquish_with_timeout).
+    /// The stats break down the work of the broker by type of instruction, telling apart the lifecycle of demons from the delivery of messages.
     ///
     /// ```rust
     /// use apocalypse::{Hell, Demon};
-    /// use std::time::Duration;
     ///
     /// struct EchoDemon{}
     ///
@@ -376,238 +3049,280 @@ impl Gate {
     ///     type Input = &'static str;
     ///     type Output = ();
     ///     async fn handle(&mut self, message: Self::Input) -> Self::Output {
-    ///         // The demon is slow, takes 2 seconds to reply
-    ///         tokio::time::sleep(Duration::from_secs(2)).await;
     ///         println!("{}", message);
     ///     }
     /// }
     ///
-    /// #[tokio::main]
-    /// async fn main() {
-    ///     let hell = Hell::new();
-    ///     let join_handle = {
-    ///         // We ignite our hell instance in another span to guarantee our gate is dropped after use
-    ///         let (gate, join_handle) = hell.ignite().await.unwrap();
-    ///         // We spawn the echo demon
-    ///         let location = gate.spawn(EchoDemon{}).await.unwrap();
-    ///         // We wait maximum 1 second for the demon to finish whatever it is doing
-    ///         gate.vanquish_with_timeout(&location, Some(Duration::from_secs(1))).await.unwrap();
-    ///         join_handle
-    ///     };
-    ///     // We await the system
-    ///     join_handle.await.unwrap();
+    /// # #[tokio::main]
+    /// # async fn main() {
+    /// let (gate, _join_handle) = Hell::new().ignite().await.unwrap();
+    /// let location = gate.spawn(EchoDemon{}).await.unwrap();
+    /// gate.send_and_ignore(&location, "Hallo, welt!").await.unwrap();
+    /// gate.vanquish(&location).await.unwrap();
+    /// let stats = gate.stats().await.unwrap();
+    /// assert_eq!(1, stats.message_instructions);
+    /// // Spawning takes a single instruction
+    /// assert_eq!(1, stats.spawn_demon_instructions);
+    /// assert_eq!(0, stats.create_address_instructions);
+    /// assert_eq!(0, stats.register_demon_instructions);
+    /// assert_eq!(1, stats.remove_demon_instructions);
+    /// assert_eq!(1, stats.stats_instructions);
+    /// # }
+    /// ```
+    ///
+    /// They also tell how long demons spent inside their handle functions, which together with the uptime shows how busy they are.
+    ///
+    /// ```rust
+    /// use apocalypse::{Hell, Demon};
+    /// use std::time::Duration;
+    ///
+    /// struct Sleeper;
+    ///
+    /// impl Demon for Sleeper {
+    ///     type Input = u64;
+    ///     type Output = ();
+    ///     async fn handle(&mut self, millis: Self::Input) -> Self::Output {
+    ///         tokio::time::sleep(Duration::from_millis(millis)).await;
+    ///     }
     /// }
+    ///
+    /// # #[tokio::main]
+    /// # async fn main() {
+    /// let (gate, _join_handle) = Hell::new().ignite().await.unwrap();
+    /// let location = gate.spawn(Sleeper).await.unwrap();
+    /// gate.send(&location, 20).await.unwrap();
+    /// gate.send(&location, 30).await.unwrap();
+    /// // Time spent handling counts even after the demon is gone
+    /// gate.vanquish(&location).await.unwrap();
+    /// assert!(gate.stats().await.unwrap().total_handle_time >= Duration::from_millis(50));
+    /// # }
     /// ```
-    pub async fn vanquish_with_timeout<D: 'static + Demon<Input = I, Output = O>, I: 'static + Send, O: 'static + Send>(&self, location: &Location<D>, timeout: Option<std::time::Duration>) -> Result<(), Error> {
+    pub async fn stats(&self) -> Result<HellStats, Error> {
         let (tx, rx) = oneshot::channel();
-        self.hell_channel.send(HellInstruction::RemoveDemon{
-            address: location.address,
-            tx,
-            ignore: false,
-            force: Some(timeout)
-        }).map_err(|e| Error::TokioSend(format!("{}", e)))?;
-        rx.await.map_err(|e| Error::TokioSend(format!("{}", e)))?
+        self.priority_channel.send(HellInstruction::Stats{tx}).map_err(broker_gone)?;
+        rx.await.map_err(broker_gone)
     }
 
-    /// Get rid of one demon gracefully, and ignore the result
-    ///
-    /// As with [send](crate::Gate::send) and [send_and_ignore](crate::Gate::send_and_ignore), this method is prefered because there is a lower chance of a lockup happening. For example, if you were to allow your own demon to vanquish itself, you should use this method. The method fails if the vanquish request does not reach the demon. If you absolutely require to call this function without awaiting, use `tokio::spawn`.
+    /// Takes a snapshot of all the demons in hell, for diagnostics
     ///
-    /// If the hell instance has a default `timeout` for vanquishing demons, this function will return the latest at `timeout`. If you want to override this behaviour for a single call, see [vanquish_and_ignore_with_timeout](Gate::vanquish_and_ignore_with_timeout).
+    /// Unlike [stats](Gate::stats), the snapshot details every demon, with its types, message counts, hierarchy and subscriptions. Like stats requests, it travels through the control channel. With the `serde` feature, the snapshot can be serialized to attach it to a bug report.
     ///
     /// ```rust
     /// use apocalypse::{Hell, Demon};
     ///
-    /// struct EchoDemon{}
+    /// struct Logger;
     ///
-    /// impl Demon for EchoDemon {
-    ///     type Input = &'static str;
+    /// impl Demon for Logger {
+    ///     type Input = String;
     ///     type Output = ();
+    ///     const TYPE_TAG: &'static str = "logger";
     ///     async fn handle(&mut self, message: Self::Input) -> Self::Output {
     ///         println!("{}", message);
     ///     }
     /// }
     ///
-    /// #[tokio::main]
-    /// async fn main() {
-    ///     let hell = Hell::new();
-    ///     let join_handle = {
-    ///         // We ignite our hell instance in another span to guarantee our gate is dropped after use
-    ///         let (gate, join_handle) = hell.ignite().await.unwrap();
-    ///         // We spawn the echo demon
-    ///         let location = gate.spawn(EchoDemon{}).await.unwrap();
-    ///         // This function fails if the vanquish request does not reach the demon, but if
-    ///         // if it does, it does not block.
-    ///         gate.vanquish_and_ignore(&location).await.unwrap();
-    ///         join_handle
-    ///     };
-    ///     // We await the system
-    ///     join_handle.await.unwrap();
-    /// }
+    /// # #[tokio::main]
+    /// # async fn main() {
+    /// let (gate, join_handle) = Hell::new().ignite().await.unwrap();
+    /// let location = gate.spawn(Logger).await.unwrap();
+    /// gate.subscribe(&location, "logs").await.unwrap();
+    /// let snapshot = gate.dump_registry().await.unwrap();
+    /// assert_eq!(1, snapshot.demons.len());
+    /// assert_eq!("logger", snapshot.demons[0].tag);
+    /// assert_eq!(vec!["logs".to_string()], snapshot.demons[0].topics);
+    /// # }
     /// ```
-    pub async fn vanquish_and_ignore<D: 'static + Demon<Input = I, Output = O>, I: 'static + Send, O: 'static + Send>(&self, location: &Location<D>) -> Result<(), Error> {
+    pub async fn dump_registry(&self) -> Result<RegistrySnapshot, Error> {
         let (tx, rx) = oneshot::channel();
-        self.hell_channel.send(HellInstruction::RemoveDemon {
-            address: location.address,
-            tx,
-            ignore: true,
-            force: None
-        }).map_err(|e| Error::TokioSend(format!("{}", e)))?;
-        rx.await.map_err(|e| Error::TokioSend(format!("{}", e)))?
+        self.priority_channel.send(HellInstruction::DumpRegistry{tx}).map_err(broker_gone)?;
+        rx.await.map_err(broker_gone)
     }
 
-    /// Get rid of one demon, and ignore the result.
+    /// Waits until no demon in hell has work left to do
     ///
-    /// As with [send](crate::Gate::send) and [send_and_ignore](crate::Gate::send_and_ignore), this method is prefered because there is a lower chance of a lockup happening. For example, if you were to allow your own demon to vanquish itself, you should use this method. If you absolutely require to call this function without awaiting, use `tokio::spawn`.
+    /// Resolves once every message delivered to a demon was handled, cancelled, or rejected, including the messages that demons send to each other while handling. The request goes through the same queue as messages, so all the messages sent through this gate before calling this function are waited for. Messages sent from tasks that are detached from any handle call, and still on their way to the broker, cannot be seen, and neither can the messages that websocket demons receive from their socket before delivery.
     ///
     /// ```rust
     /// use apocalypse::{Hell, Demon};
-    /// use std::time::Duration;
+    /// use std::sync::{Arc, atomic::{AtomicUsize, Ordering}};
     ///
-    /// struct EchoDemon{}
+    /// struct Worker {
+    ///     done: Arc<AtomicUsize>
+    /// }
     ///
-    /// impl Demon for EchoDemon {
-    ///     type Input = &'static str;
+    /// impl Demon for Worker {
+    ///     type Input = u64;
     ///     type Output = ();
-    ///     async fn handle(&mut self, message: Self::Input) -> Self::Output {
-    ///         // The demon is slow, takes 2 seconds to reply
-    ///         tokio::time::sleep(Duration::from_secs(2)).await;
-    ///         println!("{}", message);
+    ///     async fn handle(&mut self, millis: Self::Input) -> Self::Output {
+    ///         tokio::time::sleep(std::time::Duration::from_millis(millis)).await;
+    ///         self.done.fetch_add(1, Ordering::SeqCst);
     ///     }
     /// }
     ///
-    /// #[tokio::main]
-    /// async fn main() {
-    ///     let hell = Hell::new();
-    ///     let join_handle = {
-    ///         // We ignite our hell instance in another span to guarantee our gate is dropped after use
-    ///         let (gate, join_handle) = hell.ignite().await.unwrap();
-    ///         // We spawn the echo demon
-    ///         let location = gate.spawn(EchoDemon{}).await.unwrap();
-    ///         // We wait maximum 1 second for the demon to finish whatever it is doing
-    ///         gate.vanquish_and_ignore_with_timeout(&location, Some(Duration::from_secs(1))).await.unwrap();
-    ///         join_handle
-    ///     };
-    ///     // We await the system
-    ///     join_handle.await.unwrap();
+    /// # #[tokio::main]
+    /// # async fn main() {
+    /// let (gate, join_handle) = Hell::new().ignite().await.unwrap();
+    /// let done = Arc::new(AtomicUsize::new(0));
+    /// let location = gate.spawn(Worker{done: done.clone()}).await.unwrap();
+    /// for millis in [30, 10, 20] {
+    ///     gate.send_and_ignore(&location, millis).await.unwrap();
     /// }
+    /// // The whole batch is processed by now
+    /// gate.await_idle().await.unwrap();
+    /// assert_eq!(3, done.load(Ordering::SeqCst));
+    /// # }
     /// ```
-    pub async fn vanquish_and_ignore_with_timeout<D: 'static + Demon<Input = I, Output = O>, I: 'static + Send, O: 'static + Send>(&self, location: &Location<D>, timeout: Option<std::time::Duration>) -> Result<(), Error> {
+    pub async fn await_idle(&self) -> Result<(), Error> {
         let (tx, rx) = oneshot::channel();
-        self.hell_channel.send(HellInstruction::RemoveDemon{
-            address: location.address,
-            tx,
-            ignore: true,
-            force: Some(timeout)
-        }).map_err(|e| Error::TokioSend(format!("{}", e)))?;
-        rx.await.map_err(|e| Error::TokioSend(format!("{}", e)))?
+        self.hell_channel.send(HellInstruction::InFlight{tx}).map_err(broker_gone)?;
+        let in_flight = rx.await.map_err(broker_gone)?;
+        in_flight.idle().await;
+        Ok(())
     }
 
-    /// Stops the broker
+    /// Replaces the default timeout of hell
+    ///
+    /// The new timeout is used by all the following [vanquish](Gate::vanquish) and [extinguish](Gate::extinguish) calls that do not override it, while `None` removes it. Vanquish calls that are already in progress keep the timeout they started with.
+    ///
+    /// ```rust
+    /// use apocalypse::{Hell};
+    /// use std::time::Duration;
+    ///
+    /// # #[tokio::main]
+    /// # async fn main() {
+    /// let (gate, join_handle) = Hell::builder().timeout(Duration::from_secs(30)).build().ignite().await.unwrap();
+    /// // The deploy is taking too long, demons get less time to finish
+    /// gate.set_default_timeout(Some(Duration::from_secs(1))).await.unwrap();
+    /// gate.extinguish().await.unwrap();
+    /// join_handle.await.unwrap();
+    /// # }
+    /// ```
+    pub async fn set_default_timeout(&self, timeout: Option<std::time::Duration>) -> Result<(), Error> {
+        let (tx, rx) = oneshot::channel();
+        self.priority_channel.send(HellInstruction::SetTimeout{timeout, tx}).map_err(broker_gone)?;
+        rx.await.map_err(broker_gone)
+    }
+
+    /// Pings all the demons in hell, and reports which ones answered within the timeout
     ///
-    /// By default, the timeout will be used (if set) to put a maximum wait time for all remaining demons to finalize. You can override the behaviour for this function by using the [extinguish_with_timeout](Gate::extinguish_with_timeout)
+    /// The report contains one entry per address, sorted, with `false` for the demons that did not answer in time. Pings are answered by demons in between messages, so a demon that is stuck handling a message, or has a long queue of them, will not answer. Demons spawned with replicas answer as long as their internal loop is running, even while all the replicas are busy.
     ///
     /// ```rust
     /// use apocalypse::{Hell, Demon};
+    /// use std::time::Duration;
     ///
-    /// struct EchoDemon{}
+    /// struct Stuck;
     ///
-    /// impl Demon for EchoDemon {
-    ///     type Input = &'static str;
+    /// impl Demon for Stuck {
+    ///     type Input = ();
     ///     type Output = ();
-    ///     async fn handle(&mut self, message: Self::Input) -> Self::Output {
-    ///         println!("{}", message);
+    ///     async fn handle(&mut self, _message: Self::Input) -> Self::Output {
+    ///         tokio::time::sleep(Duration::from_secs(60)).await;
     ///     }
     /// }
     ///
-    /// #[tokio::main]
-    /// async fn main() {
-    ///     let hell = Hell::new();
-    ///     let join_handle = {
-    ///         // We ignite our hell instance in another span to guarantee our gate is dropped after use
-    ///         let (gate, join_handle) = hell.ignite().await.unwrap();
-    ///         gate.extinguish().await.unwrap();
-    ///         join_handle
-    ///     };
-    ///     // We await the system
-    ///     join_handle.await.unwrap();
-    /// }
+    /// # #[tokio::main]
+    /// # async fn main() {
+    /// let (gate, join_handle) = Hell::new().ignite().await.unwrap();
+    /// let healthy = gate.spawn(Stuck).await.unwrap();
+    /// let stuck = gate.spawn(Stuck).await.unwrap();
+    /// gate.send_and_ignore(&stuck, ()).await.unwrap();
+    /// tokio::time::sleep(Duration::from_millis(10)).await;
+    /// let report = gate.health_sweep(Duration::from_millis(50)).await.unwrap();
+    /// assert_eq!(vec![(healthy.address(), true), (stuck.address(), false)], report);
+    /// # }
     /// ```
-    pub async fn extinguish(self) -> Result<(), Error>{
+    pub async fn health_sweep(&self, per_demon_timeout: std::time::Duration) -> Result<Vec<(usize, bool)>, Error> {
         let (tx, rx) = oneshot::channel();
-        self.hell_channel.send(HellInstruction::Extinguish{tx, timeout: None}).map_err(|e| Error::TokioSend(format!("{}", e)))?;
-        rx.await.map_err(|e| Error::TokioSend(format!("{}", e)))?
+        self.priority_channel.send(HellInstruction::HealthSweep{
+            timeout: per_demon_timeout,
+            tx
+        }).map_err(broker_gone)?;
+        rx.await.map_err(broker_gone)
     }
 
-    /// Stops the broker
+    /// Returns the locations of all the demons of a given type, sorted by address
     ///
-    /// Same as [extinguish](Gate::extinguish), but with an override to the timeout parameter
+    /// Only demons whose type is exactly `D` are returned. Demons spawned with [spawn_blocking_demon](Gate::spawn_blocking_demon) are of type [Blocking](crate::Blocking).
     ///
     /// ```rust
     /// use apocalypse::{Hell, Demon};
     ///
-    /// struct EchoDemon{}
+    /// struct Cache;
     ///
-    /// impl Demon for EchoDemon {
-    ///     type Input = &'static str;
+    /// impl Demon for Cache {
+    ///     type Input = ();
     ///     type Output = ();
-    ///     async fn handle(&mut self, message: Self::Input) -> Self::Output {
-    ///         println!("{}", message);
-    ///     }
+    ///     async fn handle(&mut self, _message: Self::Input) -> Self::Output {}
     /// }
     ///
-    /// #[tokio::main]
-    /// async fn main() {
-    ///     let hell = Hell::new();
-    ///     let join_handle = {
-    ///         // We ignite our hell instance in another span to guarantee our gate is dropped after use
-    ///         let (gate, join_handle) = hell.ignite().await.unwrap();
-    ///         gate.extinguish_with_timeout(Some(std::time::Duration::from_secs(2))).await.unwrap();
-    ///         join_handle
-    ///     };
-    ///     // We await the system
-    ///     join_handle.await.unwrap();
+    /// struct Other;
+    ///
+    /// impl Demon for Other {
+    ///     type Input = ();
+    ///     type Output = ();
+    ///     async fn handle(&mut self, _message: Self::Input) -> Self::Output {}
+    /// }
+    ///
+    /// # #[tokio::main]
+    /// # async fn main() {
+    /// let (gate, join_handle) = Hell::new().ignite().await.unwrap();
+    /// gate.spawn(Cache).await.unwrap();
+    /// gate.spawn(Other).await.unwrap();
+    /// gate.spawn(Cache).await.unwrap();
+    /// // We flush all caches
+    /// let caches = gate.locations_of_type::<Cache>().await.unwrap();
+    /// assert_eq!(2, caches.len());
+    /// for cache in &caches {
+    ///     gate.send_and_ignore(cache, ()).await.unwrap();
     /// }
+    /// # }
     /// ```
-    pub async fn extinguish_with_timeout(self, timeout: Option<std::time::Duration>) -> Result<(), Error>{
+    pub async fn locations_of_type<D: 'static + Demon>(&self) -> Result<Vec<Location<D>>, Error> {
         let (tx, rx) = oneshot::channel();
-        self.hell_channel.send(HellInstruction::Extinguish{tx, timeout: Some(timeout)}).map_err(|e| Error::TokioSend(format!("{}", e)))?;
-        rx.await.map_err(|e| Error::TokioSend(format!("{}", e)))?
+        self.hell_channel.send(HellInstruction::LocationsOfType{
+            demon: std::any::TypeId::of::<D>(),
+            tx
+        }).map_err(broker_gone)?;
+        let addresses = rx.await.map_err(broker_gone)?;
+        Ok(addresses.into_iter().map(|address| Location {
+            address,
+            phantom: PhantomData
+        }).collect())
     }
 
-    /// Requests hell statistics
+    /// Returns the [type tag](crate::Demon::TYPE_TAG) of the demon at a raw address
     ///
-    /// This method returns a structure containing operation stats.
+    /// Demons without a type tag give their type name instead. It lets tools that only hold raw addresses, see [address](crate::Location::address), tell what kind of demon lives at each of them. Addresses without a demon give `Error::InvalidLocation`.
     ///
     /// ```rust
-    /// use apocalypse::{Hell, Demon};
+    /// use apocalypse::{Hell, Demon, Error};
     ///
-    /// struct EchoDemon{}
+    /// struct PaymentWorker;
     ///
-    /// impl Demon for EchoDemon {
-    ///     type Input = &'static str;
-    ///     type Output = ();
-    ///     async fn handle(&mut self, message: Self::Input) -> Self::Output {
-    ///         println!("{}", message);
+    /// impl Demon for PaymentWorker {
+    ///     type Input = u64;
+    ///     type Output = bool;
+    ///     const TYPE_TAG: &'static str = "PaymentWorker";
+    ///     async fn handle(&mut self, amount: Self::Input) -> Self::Output {
+    ///         amount < 1000
     ///     }
     /// }
     ///
-    /// #[tokio::main]
-    /// async fn main() {
-    ///     let hell = Hell::new();
-    ///     let join_handle = {
-    ///         // We ignite our hell instance in another span to guarantee our gate is dropped after use
-    ///         let (gate, join_handle) = hell.ignite().await.unwrap();
-    ///         println!("{:?}", gate.stats().await.unwrap());
-    ///         join_handle
-    ///     };
-    ///     // We await the system
-    ///     join_handle.await.unwrap();
-    /// }
+    /// # #[tokio::main]
+    /// # async fn main() {
+    /// let (gate, join_handle) = Hell::new().ignite().await.unwrap();
+    /// let address = gate.spawn(PaymentWorker).await.unwrap().address();
+    /// assert_eq!("PaymentWorker", gate.demon_type(address).await.unwrap());
+    /// assert!(matches!(gate.demon_type(address + 1).await, Err(Error::InvalidLocation)));
+    /// # }
     /// ```
-    pub async fn stats(&self) -> Result<HellStats, Error> {
+    pub async fn demon_type(&self, address: usize) -> Result<&'static str, Error> {
         let (tx, rx) = oneshot::channel();
-        self.hell_channel.send(HellInstruction::Stats{tx}).map_err(|e| Error::TokioSend(format!("{}", e)))?;
-        rx.await.map_err(|e| Error::TokioSend(format!("{}", e)))
+        self.hell_channel.send(HellInstruction::DemonType {
+            address,
+            tx
+        }).map_err(broker_gone)?;
+        rx.await.map_err(broker_gone)?
     }
 }
\ No newline at end of file
@@ -0,0 +1,49 @@
+use std::sync::{Arc, atomic::{AtomicBool, Ordering}};
+use tokio::sync::Notify;
+
+/// Token that allows to cancel the wait for a reply, see [send_select](crate::Gate::send_select)
+///
+/// Clones of a token share their state, so cancelling any of them cancels all of them. Once cancelled, a token stays cancelled.
+///
+/// ```rust
+/// use apocalypse::CancellationToken;
+///
+/// let token = CancellationToken::new();
+/// let clone = token.clone();
+/// clone.cancel();
+/// assert!(token.is_cancelled());
+/// ```
+#[derive(Clone, Default)]
+pub struct CancellationToken {
+    inner: Arc<(AtomicBool, Notify)>
+}
+
+impl CancellationToken {
+    /// Creates a new token, not cancelled
+    pub fn new() -> CancellationToken {
+        CancellationToken::default()
+    }
+
+    /// Cancels the token, and all of its clones
+    pub fn cancel(&self) {
+        self.inner.0.store(true, Ordering::SeqCst);
+        self.inner.1.notify_waiters();
+    }
+
+    /// Indicates if the token was already cancelled
+    pub fn is_cancelled(&self) -> bool {
+        self.inner.0.load(Ordering::SeqCst)
+    }
+
+    /// Waits until the token gets cancelled
+    pub async fn cancelled(&self) {
+        let notified = self.inner.1.notified();
+        tokio::pin!(notified);
+        // The waiter is registered before checking, so a cancellation in between is not missed
+        notified.as_mut().enable();
+        if self.is_cancelled() {
+            return;
+        }
+        notified.await;
+    }
+}
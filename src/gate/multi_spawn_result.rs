@@ -0,0 +1,26 @@
+use crate::Location;
+
+/// Outcome of [spawn_multiple_fallible](crate::Gate::spawn_multiple_fallible), with the amount of replicas that actually started
+///
+/// It can be used wherever a location is expected, so a caller that does not care about partial pools can send messages through it directly.
+pub struct MultiSpawnResult<D> {
+    /// Location of the pool
+    pub location: Location<D>,
+    /// Replicas that were built and are part of the pool
+    pub started: usize,
+    /// Replicas that were requested
+    pub requested: usize
+}
+
+impl<D> MultiSpawnResult<D> {
+    /// Indicates if all the requested replicas started
+    pub fn is_complete(&self) -> bool {
+        self.started == self.requested
+    }
+}
+
+impl<D> AsRef<Location<D>> for MultiSpawnResult<D> {
+    fn as_ref(&self) -> &Location<D> {
+        &self.location
+    }
+}
@@ -0,0 +1,8 @@
+/// Behaviour of a demon pool once its internal queue is full, see [spawn_multiple_bounded](crate::Gate::spawn_multiple_bounded)
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum OverflowPolicy {
+    /// New messages are rejected, and the sender receives `Error::Overloaded`
+    Reject,
+    /// New messages stay in the mailbox of the demon until a replica becomes available
+    Wait
+}
@@ -0,0 +1,176 @@
+use crate::{Error, Gate, Location, RemoteDemon, demon::type_tag};
+use serde::{Serialize, Deserialize, de::DeserializeOwned};
+use std::any::Any;
+use std::collections::HashMap;
+
+/// Bytes to boxed input
+type Decoder = fn(&[u8]) -> Result<Box<dyn Any + Send>, Error>;
+/// Boxed output to bytes
+type Encoder = fn(Box<dyn Any + Send>) -> Result<Vec<u8>, Error>;
+
+/// Type-erased conversion between bytes and the input and output of a demon
+struct Codec {
+    /// Type tag of the demon
+    tag: &'static str,
+    decode: Decoder,
+    encode: Encoder
+}
+
+impl Codec {
+    fn of<D: RemoteDemon<Input = I, Output = O>, I: 'static + Send + Serialize + DeserializeOwned, O: 'static + Serialize + DeserializeOwned>() -> Codec {
+        Codec {
+            tag: type_tag::<D>(),
+            decode: |bytes| {
+                let input: I = bincode::deserialize(bytes).map_err(|e| Error::Serialization(format!("{}", e)))?;
+                Ok(Box::new(input))
+            },
+            encode: |output| {
+                let output = output.downcast::<O>().map_err(|_| Error::WrongType)?;
+                bincode::serialize(&*output).map_err(|e| Error::Serialization(format!("{}", e)))
+            }
+        }
+    }
+}
+
+/// Serialized message, together with the information required to deliver it
+///
+/// The [type tag](crate::Demon::TYPE_TAG) is verified against the demon at the address, so that a message is never decoded as the input of a different demon type.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Envelope {
+    /// Type tag of the target demon
+    pub tag: String,
+    /// Address of the target demon
+    pub address: usize,
+    /// Serialized input of the demon
+    pub payload: Vec<u8>
+}
+
+/// ## Remote gate structure
+///
+/// Serialization bridge around a [Gate](crate::Gate), for messages that come from outside of the process. Demons have to be exposed first, and then they can be reached with serialized messages through their address. Messages and replies are encoded with `bincode`. The transport of the bytes is up to the user.
+pub struct RemoteGate {
+    /// Gate used to deliver the messages
+    gate: Gate,
+    /// Codecs of the exposed demons, by address
+    codecs: HashMap<usize, Codec>
+}
+
+impl RemoteGate {
+    /// Creates a new remote gate, without any exposed demon
+    pub fn new(gate: Gate) -> RemoteGate {
+        RemoteGate {
+            gate,
+            codecs: HashMap::new()
+        }
+    }
+
+    /// Allows a demon to be reached through this remote gate
+    pub fn expose<D>(&mut self, location: &Location<D>) where D: RemoteDemon, D::Input: 'static + Send + Serialize + DeserializeOwned, D::Output: 'static + Serialize + DeserializeOwned {
+        self.codecs.insert(location.address, Codec::of::<D, D::Input, D::Output>());
+    }
+
+    /// Stops a demon from being reached through this remote gate
+    pub fn conceal(&mut self, address: usize) {
+        self.codecs.remove(&address);
+    }
+
+    /// Sends a serialized message to an exposed demon, and returns the serialized reply
+    ///
+    /// Addresses that were not exposed get `Error::InvalidLocation`, and bytes that cannot be decoded as the input of the demon get `Error::Serialization`.
+    ///
+    /// ```rust
+    /// use apocalypse::{Hell, Demon, RemoteDemon, RemoteGate};
+    ///
+    /// struct EchoBot;
+    ///
+    /// impl Demon for EchoBot {
+    ///     type Input = String;
+    ///     type Output = String;
+    ///     async fn handle(&mut self, message: Self::Input) -> Self::Output {
+    ///         message
+    ///     }
+    /// }
+    ///
+    /// impl RemoteDemon for EchoBot {}
+    ///
+    /// # #[tokio::main]
+    /// # async fn main() {
+    /// let (gate, join_handle) = Hell::new().ignite().await.unwrap();
+    /// let location = gate.spawn(EchoBot).await.unwrap();
+    /// let mut remote_gate = RemoteGate::new(gate);
+    /// remote_gate.expose(&location);
+    /// // These bytes would come from the network
+    /// let request = bincode::serialize("Hallo, welt!").unwrap();
+    /// let reply = remote_gate.send_bytes(location.address(), &request).await.unwrap();
+    /// assert_eq!("Hallo, welt!", bincode::deserialize::<String>(&reply).unwrap());
+    /// # }
+    /// ```
+    pub async fn send_bytes(&self, address: usize, bytes: &[u8]) -> Result<Vec<u8>, Error> {
+        let codec = self.codecs.get(&address).ok_or(Error::InvalidLocation)?;
+        let input = (codec.decode)(bytes)?;
+        let output = self.gate.send_any(address, input).await?;
+        (codec.encode)(output)
+    }
+
+    /// Sends a serialized [Envelope](Envelope) to an exposed demon, and returns the serialized reply
+    ///
+    /// Envelopes whose tag differs from the one of the demon get `Error::TypeMismatch`.
+    ///
+    /// ```rust
+    /// use apocalypse::{Hell, Demon, Error, RemoteDemon, RemoteGate, Envelope};
+    ///
+    /// struct EchoBot;
+    ///
+    /// impl Demon for EchoBot {
+    ///     type Input = String;
+    ///     type Output = String;
+    ///     const TYPE_TAG: &'static str = "echo-bot-v1";
+    ///     async fn handle(&mut self, message: Self::Input) -> Self::Output {
+    ///         message
+    ///     }
+    /// }
+    ///
+    /// impl RemoteDemon for EchoBot {}
+    ///
+    /// # #[tokio::main]
+    /// # async fn main() {
+    /// let (gate, join_handle) = Hell::new().ignite().await.unwrap();
+    /// let location = gate.spawn(EchoBot).await.unwrap();
+    /// let mut remote_gate = RemoteGate::new(gate);
+    /// remote_gate.expose(&location);
+    /// // This envelope would come from the network
+    /// let mut envelope = Envelope {
+    ///     tag: "echo-bot-v1".to_string(),
+    ///     address: location.address(),
+    ///     payload: bincode::serialize("Hallo, welt!").unwrap()
+    /// };
+    /// let reply = remote_gate.send_envelope(&bincode::serialize(&envelope).unwrap()).await.unwrap();
+    /// assert_eq!("Hallo, welt!", bincode::deserialize::<String>(&reply).unwrap());
+    /// // A different demon type was expected at this address
+    /// envelope.tag = "echo-bot-v2".to_string();
+    /// let reply = remote_gate.send_envelope(&bincode::serialize(&envelope).unwrap()).await;
+    /// assert!(matches!(reply, Err(Error::TypeMismatch{..})));
+    /// # }
+    /// ```
+    pub async fn send_envelope(&self, bytes: &[u8]) -> Result<Vec<u8>, Error> {
+        let envelope: Envelope = bincode::deserialize(bytes).map_err(|e| Error::Serialization(format!("{}", e)))?;
+        let codec = self.codecs.get(&envelope.address).ok_or(Error::InvalidLocation)?;
+        if envelope.tag != codec.tag {
+            return Err(Error::TypeMismatch {
+                expected: envelope.tag,
+                found: codec.tag.to_string()
+            });
+        }
+        self.send_bytes(envelope.address, &envelope.payload).await
+    }
+
+    /// Type tag of an exposed demon
+    pub fn tag(&self, address: usize) -> Option<&'static str> {
+        self.codecs.get(&address).map(|codec| codec.tag)
+    }
+
+    /// Gate used to deliver the messages
+    pub fn gate(&self) -> &Gate {
+        &self.gate
+    }
+}
@@ -0,0 +1,48 @@
+use crate::Error;
+use std::future::Future;
+use std::pin::Pin;
+use std::task::{Context, Poll};
+use std::any::Any;
+use tokio::sync::{OwnedSemaphorePermit, oneshot::{self, error::{RecvError, TryRecvError}}};
+
+/// Type-erased reply channel, returned by [send_with_responder](crate::Gate::send_with_responder)
+///
+/// It resolves like a oneshot receiver. On a gate with a [concurrency limit](crate::Gate::with_max_concurrency), it holds one of the slots of the gate until the reply arrives, or the receiver is dropped.
+pub struct ReplyReceiver {
+    /// Reply channel
+    rx: oneshot::Receiver<Result<Box<dyn Any + Send>, Error>>,
+    /// Slot of a limited gate, if any
+    permit: Option<OwnedSemaphorePermit>
+}
+
+impl ReplyReceiver {
+    pub(crate) fn new(rx: oneshot::Receiver<Result<Box<dyn Any + Send>, Error>>, permit: Option<OwnedSemaphorePermit>) -> ReplyReceiver {
+        ReplyReceiver {
+            rx,
+            permit
+        }
+    }
+
+    /// Checks if the reply arrived, without waiting for it
+    ///
+    /// Works as the `try_recv` function of a oneshot receiver.
+    pub fn try_recv(&mut self) -> Result<Result<Box<dyn Any + Send>, Error>, TryRecvError> {
+        let reply = self.rx.try_recv();
+        if !matches!(reply, Err(TryRecvError::Empty)) {
+            self.permit = None;
+        }
+        reply
+    }
+}
+
+impl Future for ReplyReceiver {
+    type Output = Result<Result<Box<dyn Any + Send>, Error>, RecvError>;
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let reply = Pin::new(&mut self.rx).poll(cx);
+        if reply.is_ready() {
+            self.permit = None;
+        }
+        reply
+    }
+}
@@ -0,0 +1,70 @@
+use std::collections::hash_map::RandomState;
+use std::hash::{BuildHasher, Hasher};
+use std::time::Duration;
+
+/// Retry policy for [send_retry](crate::Gate::send_retry)
+///
+/// Between attempts, the sender waits with an exponential backoff, starting at the base delay and doubling up to the maximum delay. Each wait is shortened by a random amount, up to the jitter fraction of it, so that many senders retrying at once do not stay in sync.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct RetryPolicy {
+    /// Maximum number of attempts, the first one included
+    max_attempts: usize,
+    /// Wait after the first failed attempt
+    base_delay: Duration,
+    /// Maximum wait between attempts
+    max_delay: Duration,
+    /// Fraction of each wait that can be randomly removed
+    jitter: f64
+}
+
+impl RetryPolicy {
+    /// Creates a new retry policy, with at least one attempt
+    ///
+    /// By default, the first wait is 50 milliseconds, waits double up to 5 seconds, and up to half of each wait is randomly removed.
+    ///
+    /// ```rust
+    /// use apocalypse::RetryPolicy;
+    /// use std::time::Duration;
+    ///
+    /// let policy = RetryPolicy::new(5).base_delay(Duration::from_millis(10)).jitter(0.2);
+    /// ```
+    pub fn new(max_attempts: usize) -> RetryPolicy {
+        RetryPolicy {
+            max_attempts: max_attempts.max(1),
+            base_delay: Duration::from_millis(50),
+            max_delay: Duration::from_secs(5),
+            jitter: 0.5
+        }
+    }
+
+    /// Sets the wait after the first failed attempt
+    pub fn base_delay(mut self, base_delay: Duration) -> Self {
+        self.base_delay = base_delay;
+        self
+    }
+
+    /// Sets the maximum wait between attempts
+    pub fn max_delay(mut self, max_delay: Duration) -> Self {
+        self.max_delay = max_delay;
+        self
+    }
+
+    /// Sets the fraction of each wait that can be randomly removed, between 0 and 1
+    pub fn jitter(mut self, jitter: f64) -> Self {
+        self.jitter = jitter.clamp(0.0, 1.0);
+        self
+    }
+
+    /// Maximum number of attempts, the first one included
+    pub(crate) fn max_attempts(&self) -> usize {
+        self.max_attempts
+    }
+
+    /// Wait after the given failed attempt, starting from zero
+    pub(crate) fn delay(&self, attempt: usize) -> Duration {
+        let backoff = self.base_delay.saturating_mul(2u32.saturating_pow(attempt.min(u32::MAX as usize) as u32)).min(self.max_delay);
+        // Each random state gets new keys, which is random enough to spread the retries
+        let random = RandomState::new().build_hasher().finish() as f64 / u64::MAX as f64;
+        backoff.mul_f64(1.0 - self.jitter * random)
+    }
+}
@@ -0,0 +1,53 @@
+use crate::{Error, ReplyReceiver, gate::broker_gone};
+use std::future::Future;
+use std::marker::PhantomData;
+use std::pin::Pin;
+use std::task::{Context, Poll};
+use std::any::Any;
+use tokio::sync::oneshot::error::TryRecvError;
+
+/// Reply of a message sent with [send_poll](crate::Gate::send_poll)
+///
+/// It can be awaited as any other future, or checked without a waker through [try_poll_now](SendFuture::try_poll_now), for event loops that are not driven by an async executor.
+pub struct SendFuture<O> {
+    /// Type-erased reply channel
+    rx: ReplyReceiver,
+    /// Type of the reply
+    phantom: PhantomData<fn() -> O>
+}
+
+impl<O: 'static> SendFuture<O> {
+    pub(crate) fn new(rx: ReplyReceiver) -> SendFuture<O> {
+        SendFuture {
+            rx,
+            phantom: PhantomData
+        }
+    }
+
+    /// Checks if the reply arrived, without waiting for it
+    ///
+    /// Returns `None` while the demon did not reply yet. The reply can only be taken once, later calls give an error.
+    pub fn try_poll_now(&mut self) -> Option<Result<O, Error>> {
+        match self.rx.try_recv() {
+            Ok(reply) => Some(SendFuture::downcast(reply)),
+            Err(TryRecvError::Empty) => None,
+            Err(e) => Some(Err(broker_gone(e)))
+        }
+    }
+
+    /// Recovers the output of the demon from the type-erased reply
+    fn downcast(reply: Result<Box<dyn Any + Send>, Error>) -> Result<O, Error> {
+        reply?.downcast::<O>().map(|output| *output).map_err(|_| Error::WrongType)
+    }
+}
+
+impl<O: 'static> Future for SendFuture<O> {
+    type Output = Result<O, Error>;
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        Pin::new(&mut self.rx).poll(cx).map(|reply| match reply {
+            Ok(reply) => SendFuture::downcast(reply),
+            Err(e) => Err(broker_gone(e))
+        })
+    }
+}
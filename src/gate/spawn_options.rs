@@ -0,0 +1,18 @@
+/// Options for single demons, see [spawn_with_options](crate::Gate::spawn_with_options)
+#[derive(Clone, Copy, Debug, Default)]
+pub struct SpawnOptions {
+    /// Order in which waiting messages reach the demon
+    pub ordering: MessageOrder
+}
+
+/// Order in which a demon takes the messages waiting in its mailbox
+///
+/// With the default FIFO order, messages are handled as they arrive. With a LIFO order, the most recent message is handled first, which suits task stacks, undo queues, or demons that only care about the freshest data. Under a sustained load, a LIFO demon might never get to its oldest messages, which keep waiting until the load goes down, or the demon leaves hell.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum MessageOrder {
+    /// First in, first out, the default
+    #[default]
+    Fifo,
+    /// Last in, first out
+    Lifo
+}
@@ -0,0 +1,170 @@
+use crate::{Error, Demon, Gate, Location, hell::HellInstruction};
+use futures::future::BoxFuture;
+use std::any::Any;
+use std::marker::PhantomData;
+use tokio::sync::oneshot;
+
+/// Demon that sends messages to demons of type `P`, and gets their location through a [WiringBuilder](WiringBuilder)
+///
+/// A demon can implement it once per type of demon it talks to.
+pub trait Wire<P>: Demon {
+    /// Stores the location of the demon that this one talks to
+    fn wire(&mut self, producer: Location<P>);
+}
+
+/// Demon waiting in a [WiringBuilder](WiringBuilder) to be spawned at its reserved address
+trait Pending: Send {
+    fn address(&self) -> usize;
+
+    fn as_any_mut(&mut self) -> &mut dyn Any;
+
+    fn spawn(self: Box<Self>, gate: Gate) -> BoxFuture<'static, Result<(), Error>>;
+}
+
+struct PendingDemon<D> {
+    address: usize,
+    demon: D
+}
+
+impl<D: 'static + Demon<Input = I, Output = O>, I: 'static + Send, O: 'static + Send> Pending for PendingDemon<D> {
+    fn address(&self) -> usize {
+        self.address
+    }
+
+    fn as_any_mut(&mut self) -> &mut dyn Any {
+        self
+    }
+
+    fn spawn(self: Box<Self>, gate: Gate) -> BoxFuture<'static, Result<(), Error>> {
+        Box::pin(async move {
+            match gate.try_spawn_at(Some(self.address), self.demon, None, Default::default()).await {
+                Ok(_) => Ok(()),
+                Err((e, demon)) => {
+                    if let Some(demon) = demon {
+                        demon.on_spawn_rejected(&e).await;
+                    }
+                    Err(e)
+                }
+            }
+        })
+    }
+}
+
+/// Builder that wires demons together before spawning them, all at once
+///
+/// Demons added to the builder get their [Location](crate::Location) right away, even though they are not spawned yet. Demons that send messages to others receive their locations with [connect](WiringBuilder::connect), which only compiles when the consumer implements [Wire](Wire) for the type of the producer, so a location can never end up in a demon that expects another type. Cycles are fine, as all locations exist before any demon is spawned.
+///
+/// Nothing is spawned until [build](WiringBuilder::build) is called. Demons are spawned in the order they were added, so demons that send messages from their [spawned](crate::Demon::spawned) function should be added after the demons they talk to. If any demon fails to spawn, it gets its [on_spawn_rejected](crate::Demon::on_spawn_rejected) function called, the ones already spawned are vanquished, and the rest are dropped, so that either the whole system is in hell, or none of it. Dropping the builder drops all the demons in it.
+///
+/// ```rust
+/// use apocalypse::{Hell, Demon, Gate, Location, WiringBuilder, Wire};
+///
+/// struct Doubler;
+///
+/// impl Demon for Doubler {
+///     type Input = u64;
+///     type Output = u64;
+///     async fn handle(&mut self, message: Self::Input) -> Self::Output {
+///         message * 2
+///     }
+/// }
+///
+/// struct Client {
+///     gate: Gate,
+///     doubler: Option<Location<Doubler>>
+/// }
+///
+/// impl Demon for Client {
+///     type Input = u64;
+///     type Output = Option<u64>;
+///     async fn handle(&mut self, message: Self::Input) -> Self::Output {
+///         self.gate.send(self.doubler.as_ref()?, message).await.ok()
+///     }
+/// }
+///
+/// impl Wire<Doubler> for Client {
+///     fn wire(&mut self, doubler: Location<Doubler>) {
+///         self.doubler = Some(doubler);
+///     }
+/// }
+///
+/// # #[tokio::main]
+/// # async fn main() {
+/// let (gate, _join_handle) = Hell::new().ignite().await.unwrap();
+/// let mut wiring = WiringBuilder::new(&gate);
+/// let client = wiring.add(Client{gate: gate.clone(), doubler: None}).await.unwrap();
+/// let doubler = wiring.add(Doubler).await.unwrap();
+/// wiring.connect(&client, &doubler).unwrap();
+/// // Connecting them the other way around does not compile, as the doubler does not talk to clients
+/// // wiring.connect(&doubler, &client).unwrap();
+/// wiring.build().await.unwrap();
+/// assert_eq!(Some(42), gate.send(&client, 21).await.unwrap());
+/// # }
+/// ```
+pub struct WiringBuilder {
+    /// Gate to hell, where the demons get spawned
+    gate: Gate,
+    /// Demons to be spawned, in the order they were added
+    pending: Vec<Box<dyn Pending>>
+}
+
+impl WiringBuilder {
+    /// Creates a new, empty, builder
+    pub fn new(gate: &Gate) -> WiringBuilder {
+        WiringBuilder {
+            gate: gate.clone(),
+            pending: Vec::new()
+        }
+    }
+
+    /// Adds a demon to the builder, reserving its location
+    ///
+    /// The location can be connected to other demons right away, but messages sent to it fail with `Error::InvalidLocation` until the builder is built.
+    pub async fn add<D: 'static + Demon<Input = I, Output = O>, I: 'static + Send, O: 'static + Send>(&mut self, demon: D) -> Result<Location<D>, Error> {
+        let address = self.gate.reserve_address().await?;
+        self.pending.push(Box::new(PendingDemon{address, demon}));
+        Ok(Location {
+            address,
+            phantom: PhantomData
+        })
+    }
+
+    /// Gives the location of the producer to the consumer
+    ///
+    /// The producer can be any demon, either added to this builder or already in hell, while the consumer has to be a demon added to this builder, otherwise `Error::InvalidLocation` is returned.
+    pub fn connect<C: 'static + Wire<P>, P>(&mut self, consumer: &Location<C>, producer: &Location<P>) -> Result<(), Error> {
+        let pending = self.pending.iter_mut()
+            .filter_map(|pending| pending.as_any_mut().downcast_mut::<PendingDemon<C>>())
+            .find(|pending| pending.address == consumer.address)
+            .ok_or(Error::InvalidLocation)?;
+        pending.demon.wire(producer.clone());
+        Ok(())
+    }
+
+    /// Spawns all the demons, or none of them
+    ///
+    /// The error of the first demon that failed to spawn is returned.
+    pub async fn build(self) -> Result<(), Error> {
+        let mut spawned = Vec::new();
+        for demon in self.pending {
+            let address = demon.address();
+            if let Err(e) = demon.spawn(self.gate.clone()).await {
+                // The demons that were spawned already leave in the background
+                for address in spawned {
+                    let (tx, rx) = oneshot::channel();
+                    if self.gate.priority_channel.send(HellInstruction::RemoveDemon {
+                        address,
+                        tx,
+                        ignore: true,
+                        force: None
+                    }).is_ok() {
+                        let _ = rx.await;
+                    }
+                }
+                return Err(e);
+            }
+            spawned.push(address);
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,79 @@
+use crate::Error;
+use base64::{Engine, engine::general_purpose};
+use tokio::{io::{AsyncReadExt, AsyncWriteExt}, net::TcpStream};
+
+/// Maximum size of the http request head of the handshake
+const MAX_HEAD_SIZE: usize = 8_192;
+/// Magic string used to compute the accept header, see RFC 6455
+const WEBSOCKET_GUID: &str = "258EAFA5-E914-47DA-95CA-C5AB0DC85B11";
+
+/// Performs the server side of the websockets handshake
+///
+/// The http upgrade request is read from the stream, validated, and answered with a `101 Switching Protocols` response. Invalid requests are answered with a `400 Bad Request` response.
+pub(crate) async fn perform(stream: &mut TcpStream) -> Result<(), Error> {
+    let head = read_head(stream).await?;
+    match accept_key(&head) {
+        Ok(accept) => {
+            let response = format!("HTTP/1.1 101 Switching Protocols\r\nUpgrade: websocket\r\nConnection: Upgrade\r\nSec-WebSocket-Accept: {}\r\n\r\n", accept);
+            stream.write_all(response.as_bytes()).await.map_err(Error::IO)
+        },
+        Err(detail) => {
+            stream.write_all(b"HTTP/1.1 400 Bad Request\r\nContent-Length: 0\r\n\r\n").await.map_err(Error::IO)?;
+            Err(Error::WebSocketHandshake(detail))
+        }
+    }
+}
+
+/// Reads the request head, up to the empty line
+///
+/// Clients are not allowed to send frames before the handshake response, so nothing beyond the head is consumed.
+async fn read_head(stream: &mut TcpStream) -> Result<String, Error> {
+    let mut head = Vec::new();
+    let mut buf = [0; 1_024];
+    while !head.ends_with(b"\r\n\r\n") {
+        let n = stream.read(&mut buf).await.map_err(Error::IO)?;
+        if n == 0 {
+            return Err(Error::WebSocketHandshake("connection closed during the handshake".to_string()));
+        }
+        head.extend_from_slice(&buf[..n]);
+        if head.len() > MAX_HEAD_SIZE {
+            return Err(Error::WebSocketHandshake("handshake request is too large".to_string()));
+        }
+    }
+    String::from_utf8(head).map_err(|_| Error::WebSocketHandshake("handshake request is not valid utf-8".to_string()))
+}
+
+/// Validates the upgrade request, and computes the `Sec-WebSocket-Accept` value
+fn accept_key(head: &str) -> Result<String, String> {
+    let mut lines = head.split("\r\n");
+    let request_line = lines.next().unwrap_or_default();
+    if !request_line.starts_with("GET ") || !request_line.ends_with(" HTTP/1.1") {
+        return Err(format!("unexpected request line, {}", request_line));
+    }
+
+    let mut upgrade = false;
+    let mut connection = false;
+    let mut version = false;
+    let mut key = None;
+    for (name, value) in lines.filter_map(|line| line.split_once(':')) {
+        let value = value.trim();
+        match name.trim().to_ascii_lowercase().as_str() {
+            "upgrade" => upgrade = value.eq_ignore_ascii_case("websocket"),
+            "connection" => connection = value.split(',').any(|token| token.trim().eq_ignore_ascii_case("upgrade")),
+            "sec-websocket-version" => version = value == "13",
+            "sec-websocket-key" => key = Some(value.to_string()),
+            _ => ()
+        }
+    }
+
+    if !upgrade || !connection {
+        return Err("missing upgrade headers".to_string());
+    }
+    if !version {
+        return Err("unsupported websockets version".to_string());
+    }
+    let key = key.ok_or_else(|| "missing Sec-WebSocket-Key header".to_string())?;
+
+    let nonce = format!("{}{}", key, WEBSOCKET_GUID);
+    Ok(general_purpose::STANDARD.encode(ring::digest::digest(&ring::digest::SHA1_FOR_LEGACY_USE_ONLY, nonce.as_bytes())))
+}
@@ -0,0 +1,119 @@
+use cataclysm::ws::{WebSocketReader, WebSocketWriter};
+use futures::future::BoxFuture;
+use std::{future::Future, time::Duration};
+
+/// New websockets connection, split in its writing and reading halves
+pub(crate) type Connection = (WebSocketWriter, WebSocketReader);
+
+/// Options for websockets demons, see [spawn_ws_with_options](crate::Gate::spawn_ws_with_options)
+pub struct WsOptions {
+    /// Reconnection policy, used when the connection drops with an error
+    pub reconnect: Option<ReconnectPolicy>,
+    /// Maximum wait for the close frame of the other side, once the demon sends its own while leaving hell
+    ///
+    /// Defaults to one second. A zero duration drops the connection without any close handshake.
+    pub close_timeout: Duration,
+    /// Priority between incoming frames and messages from hell, when both are waiting
+    pub priority: WsPriority
+}
+
+impl Default for WsOptions {
+    fn default() -> WsOptions {
+        WsOptions {
+            reconnect: None,
+            close_timeout: Duration::from_secs(1),
+            priority: WsPriority::Fair
+        }
+    }
+}
+
+/// Priority between incoming frames and messages from hell, for websockets demons
+///
+/// When both a frame and a message are waiting, the demon can only take one of them at a time. With a fair priority, the choice is random, so that a flood of one kind cannot starve the other for long. The other two priorities always serve the given kind first, which keeps its latency low at the cost of possibly starving the other kind under a sustained flood.
+///
+/// Both [on_message](cataclysm::ws::WebSocketThread::on_message) and [handle](crate::Demon::handle) take the demon mutably, so they never overlap. A slow `on_message` delays the next frame and the next message alike, regardless of the priority. Work that does not need the demon can be moved out of it, for example with [spawn_linked](crate::DemonContext::spawn_linked), so that the reader goes back to the socket right away.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum WsPriority {
+    /// Frames and messages are picked at random, the default
+    Fair,
+    /// Frames are always served before messages
+    Frames,
+    /// Messages are always served before frames
+    Messages
+}
+
+/// Reconnection policy for websockets demons
+///
+/// When reading from the connection fails, the `connect` closure is called until it produces a new connection, waiting between attempts with an exponential backoff. A connection closed gracefully by the other side is never reestablished.
+pub struct ReconnectPolicy {
+    /// Function that creates a new connection, handshake included
+    connect: Box<dyn FnMut() -> BoxFuture<'static, Option<Connection>> + Send>,
+    /// Wait before the first attempt
+    initial_backoff: Duration,
+    /// Maximum wait between attempts
+    max_backoff: Duration,
+    /// Maximum number of attempts per disconnection
+    max_attempts: Option<usize>
+}
+
+impl ReconnectPolicy {
+    /// Creates a new reconnection policy
+    ///
+    /// By default, the first attempt happens after 100 milliseconds, the wait doubles up to 30 seconds, and attempts never stop.
+    ///
+    /// ```rust,no_run
+    /// use apocalypse::ReconnectPolicy;
+    /// use cataclysm::ws::{WebSocketStream};
+    /// use tokio::net::TcpStream;
+    /// use std::time::Duration;
+    ///
+    /// let policy = ReconnectPolicy::new(|| async {
+    ///     let stream = TcpStream::connect("127.0.0.1:8000").await.ok()?;
+    ///     // ... perform the client side of the handshake here
+    ///     Some(WebSocketStream::from_tcp_stream_unchecked(stream).split())
+    /// }).max_backoff(Duration::from_secs(5));
+    /// ```
+    pub fn new<F: 'static + FnMut() -> R + Send, R: 'static + Future<Output = Option<(WebSocketWriter, WebSocketReader)>> + Send>(mut connect: F) -> ReconnectPolicy {
+        ReconnectPolicy {
+            connect: Box::new(move || Box::pin(connect())),
+            initial_backoff: Duration::from_millis(100),
+            max_backoff: Duration::from_secs(30),
+            max_attempts: None
+        }
+    }
+
+    /// Sets the wait before the first attempt
+    pub fn initial_backoff(mut self, initial_backoff: Duration) -> Self {
+        self.initial_backoff = initial_backoff;
+        self
+    }
+
+    /// Sets the maximum wait between attempts
+    pub fn max_backoff(mut self, max_backoff: Duration) -> Self {
+        self.max_backoff = max_backoff;
+        self
+    }
+
+    /// Sets the maximum number of attempts per disconnection, after which the demon leaves hell
+    pub fn max_attempts(mut self, max_attempts: usize) -> Self {
+        self.max_attempts = Some(max_attempts);
+        self
+    }
+
+    /// Attempts to reconnect, giving back the policy together with the new connection, if any
+    pub(crate) fn reconnection(mut self) -> BoxFuture<'static, (ReconnectPolicy, Option<Connection>)> {
+        Box::pin(async move {
+            let mut backoff = self.initial_backoff;
+            let mut attempts = 0;
+            while self.max_attempts.map(|max_attempts| attempts < max_attempts).unwrap_or(true) {
+                attempts += 1;
+                tokio::time::sleep(backoff).await;
+                if let Some(connection) = (self.connect)().await {
+                    return (self, Some(connection));
+                }
+                backoff = (backoff * 2).min(self.max_backoff);
+            }
+            (self, None)
+        })
+    }
+}
@@ -1,43 +1,109 @@
 use std::{
-    collections::{HashMap},
+    collections::{HashMap, HashSet},
+    sync::{Arc, atomic::Ordering},
     time::Duration
 };
-use futures::future::join_all;
-use crate::{Gate, Error};
+use futures::{future::{join_all, BoxFuture}, stream::{FuturesUnordered, StreamExt}};
+use crate::{Gate, Error, Clock, SystemClock, ShutdownReason};
 use tokio::{
     sync::{
+        broadcast,
         oneshot::{self},
-        mpsc::{self}
+        mpsc::{self, UnboundedSender}
     },
-    task::JoinHandle
+    task::JoinHandle,
+    time::Instant
 };
 use chrono::{DateTime, Utc};
 
 pub(crate) use self::mini_hell::MiniHell;
 mod mini_hell;
-pub(crate) use self::multiple_mini_hell::MultipleMiniHell;
+pub(crate) use self::multiple_mini_hell::{MultipleMiniHell, Keyed};
 mod multiple_mini_hell;
+pub(crate) use self::mini_io_hell::MiniIoHell;
+mod mini_io_hell;
 #[cfg(feature = "ws")]
 pub(crate) use self::mini_ws_hell::MiniWSHell;
 #[cfg(feature = "ws")]
 mod mini_ws_hell;
 
-pub(crate) use self::demon_channels::{DemonChannels};
+pub(crate) use self::demon_channels::{DemonChannels, DemonKind, SizeGuard, Intake, DemonTypes, QueueGauge};
 mod demon_channels;
 
+pub(crate) use self::exit_notice::{ExitNotice};
+mod exit_notice;
+
+pub(crate) use self::abort_guard::AbortGuard;
+mod abort_guard;
+
 pub use self::hell_stats::{HellStats};
 mod hell_stats;
+pub use self::extinguish_report::ExtinguishReport;
+mod extinguish_report;
+pub use self::registry_snapshot::{RegistrySnapshot, DemonSnapshot};
+mod registry_snapshot;
+pub use self::interceptor::{BrokerContext, InterceptDecision};
+pub(crate) use self::interceptor::Interceptor;
+mod interceptor;
 
-pub(crate) use self::hell_instruction::{HellInstruction};
+pub(crate) use self::hell_instruction::{HellInstruction, InstructionCounts};
 mod hell_instruction;
 
-pub(crate) use self::mini_hell_instruction::{MiniHellInstruction};
+pub(crate) use self::mini_hell_instruction::{MiniHellInstruction, PendingMessage, ReplyTo, Timed, cancel_pending, drain_pending, reject_pending};
 mod mini_hell_instruction;
 
+pub(crate) use self::taps::{Taps, Observer};
+mod taps;
+
+pub(crate) use self::in_flight::{InFlight, InFlightGuard};
+mod in_flight;
+
+pub(crate) use self::handle_time::HandleTime;
+mod handle_time;
+
+pub(crate) use self::throttle::Throttle;
+mod throttle;
+
+#[cfg(feature = "record")]
+pub(crate) use self::history::{History, Recorder};
+#[cfg(feature = "record")]
+mod history;
+
+/// Extinguish request, with the notification channel, the timeout override and the overall deadline
+type ExtinguishRequest = (oneshot::Sender<Result<ExtinguishReport, Error>>, Option<Option<Duration>>, Option<Instant>);
+
+/// Reason for the broker to wake up
+enum Wakeup {
+    /// Instruction from a gate, either regular or from the control plane
+    Instruction(Option<HellInstruction>),
+    /// Zombie counter decrease
+    Zombie(Option<()>),
+    /// Demon that finished on its own, with its address and generation
+    Exit(Option<(usize, usize)>),
+    /// Parked message that can be delivered now
+    Release,
+    /// Message delayed by the interceptor, that can be routed now
+    Delayed(HellInstruction)
+}
+
 /// Builder helper for a Hell instance
 pub struct HellBuilder {
     /// Timeout before shutdown of a demon
-    timeout: Option<Duration>
+    timeout: Option<Duration>,
+    /// Fixed priority order in the broker loop
+    biased: bool,
+    /// Maximum amount of demons alive at the same time
+    max_total_demons: Option<usize>,
+    /// Name of the hell instance
+    name: String,
+    /// Instructions processed by the broker before yielding
+    yield_budget: Option<usize>,
+    /// Maximum amount of messages delivered per second
+    global_rate_limit: Option<u32>,
+    /// Source of the current time for demons
+    clock: Arc<dyn Clock>,
+    /// Function that sees every message before it is routed
+    interceptor: Option<Interceptor>
 }
 
 impl HellBuilder {
@@ -52,7 +118,14 @@ impl HellBuilder {
     /// ```
     pub fn new() -> HellBuilder {
         HellBuilder {
-            timeout: None
+            timeout: None,
+            biased: false,
+            max_total_demons: None,
+            name: "Hell".to_string(),
+            yield_budget: None,
+            global_rate_limit: None,
+            clock: Arc::new(SystemClock),
+            interceptor: None
         }
     }
 
@@ -72,6 +145,224 @@ impl HellBuilder {
         self
     }
 
+    /// Sets a fixed priority order for the broker loop
+    ///
+    /// By default, the broker picks randomly among the channels that are ready. With a biased broker, control instructions, zombie counter updates and exit notices from demons are always serviced before regular instructions, so they cannot be starved by a flood of messages.
+    ///
+    /// ```rust
+    /// use apocalypse::{HellBuilder, Demon};
+    /// use std::time::Duration;
+    ///
+    /// struct Slow;
+    ///
+    /// impl Demon for Slow {
+    ///     type Input = ();
+    ///     type Output = ();
+    ///     async fn handle(&mut self, _message: Self::Input) -> Self::Output {}
+    ///     async fn vanquished(self) {
+    ///         tokio::time::sleep(Duration::from_millis(10)).await;
+    ///     }
+    /// }
+    ///
+    /// # #[tokio::main]
+    /// # async fn main() {
+    /// let (gate, _join_handle) = HellBuilder::new().biased_broker(true).build().ignite().await.unwrap();
+    /// // We flood the broker with messages
+    /// let target = gate.spawn(Slow).await.unwrap();
+    /// let flood_gate = gate.clone();
+    /// let flood = tokio::spawn(async move {
+    ///     while flood_gate.send_and_ignore(&target, ()).await.is_ok() {}
+    /// });
+    /// // And we create some zombies in the meantime
+    /// for _ in 0..10 {
+    ///     let location = gate.spawn(Slow).await.unwrap();
+    ///     gate.vanquish_and_ignore(&location).await.unwrap();
+    /// }
+    /// tokio::time::sleep(Duration::from_millis(200)).await;
+    /// // All zombies are gone, and the counter did not lag behind
+    /// assert_eq!(0, gate.stats().await.unwrap().zombie_demons);
+    /// flood.abort();
+    /// # }
+    /// ```
+    pub fn biased_broker(mut self, biased: bool) -> Self {
+        self.biased = biased;
+        self
+    }
+
+    /// Sets a maximum amount of demons alive at the same time
+    ///
+    /// Once the limit is reached, spawning a new demon fails with `Error::TooManyDemons`, until some demon leaves hell. This protects the system from a runaway loop that spawns demons without bounds. Demons spawned with replicas count as a single demon.
+    ///
+    /// ```rust
+    /// use apocalypse::{HellBuilder, Demon, Error};
+    ///
+    /// struct Basic;
+    ///
+    /// impl Demon for Basic {
+    ///     type Input = ();
+    ///     type Output = ();
+    ///     async fn handle(&mut self, _message: Self::Input) -> Self::Output {}
+    /// }
+    ///
+    /// # #[tokio::main]
+    /// # async fn main() {
+    /// let (gate, _join_handle) = HellBuilder::new().max_total_demons(2).build().ignite().await.unwrap();
+    /// let first = gate.spawn(Basic).await.unwrap();
+    /// let _second = gate.spawn(Basic).await.unwrap();
+    /// assert!(matches!(gate.spawn(Basic).await, Err(Error::TooManyDemons)));
+    /// // Once a demon leaves, there is room again
+    /// gate.vanquish(&first).await.unwrap();
+    /// assert!(gate.spawn(Basic).await.is_ok());
+    /// # }
+    /// ```
+    pub fn max_total_demons(mut self, max_total_demons: usize) -> Self {
+        self.max_total_demons = Some(max_total_demons);
+        self
+    }
+
+    /// Sets a name for the hell instance
+    ///
+    /// The name tells hell instances apart when several of them run in the same process. It prefixes the logs of the broker with the `full_log` feature, it is part of the [stats](crate::Gate::stats), and it names the broker task when tokio's unstable task names are enabled. By default, the name is `Hell`.
+    ///
+    /// ```rust
+    /// use apocalypse::{HellBuilder};
+    ///
+    /// # #[tokio::main]
+    /// # async fn main() {
+    /// let (gate, _join_handle) = HellBuilder::new().name("payments").build().ignite().await.unwrap();
+    /// assert_eq!("payments", gate.stats().await.unwrap().name);
+    /// # }
+    /// ```
+    pub fn name(mut self, name: &str) -> Self {
+        self.name = name.to_string();
+        self
+    }
+
+    /// Makes the broker yield to other tasks every `yield_budget` processed instructions
+    ///
+    /// Under heavy load, the broker might always have an instruction ready, and keep its worker thread busy for long stretches. Other tasks that share the runtime, like an http server, then wait longer to be scheduled. With a yield budget, the broker voluntarily gives way after processing that many instructions. Low budgets trade throughput for fairness. As a reference, sending 200k messages to a single demon in a release build, a budget of 1 cut the throughput by around 60%, a budget of 8 by around 10%, and a budget of 64 had no measurable impact. By default, the broker does not yield on its own, and relies on tokio's cooperative scheduling.
+    ///
+    /// ```rust
+    /// use apocalypse::{HellBuilder, Demon};
+    ///
+    /// struct EchoBot;
+    ///
+    /// impl Demon for EchoBot {
+    ///     type Input = usize;
+    ///     type Output = usize;
+    ///     async fn handle(&mut self, message: Self::Input) -> Self::Output {
+    ///         message
+    ///     }
+    /// }
+    ///
+    /// # #[tokio::main]
+    /// # async fn main() {
+    /// let (gate, _join_handle) = HellBuilder::new().yield_budget(64).build().ignite().await.unwrap();
+    /// let location = gate.spawn(EchoBot).await.unwrap();
+    /// for i in 0..100 {
+    ///     assert_eq!(i, gate.send(&location, i).await.unwrap());
+    /// }
+    /// # }
+    /// ```
+    pub fn yield_budget(mut self, yield_budget: usize) -> Self {
+        self.yield_budget = Some(yield_budget.max(1));
+        self
+    }
+
+    /// Limits the amount of messages that hell delivers to demons per second, all demons together
+    ///
+    /// This protects a downstream that is shared by all demons, no matter how many gates push messages. Up to one second worth of messages can be delivered in a burst, and the rest wait in the broker, in arrival order, until they can be delivered. Waiting messages do not hold back any other instruction, so spawns, stats or shutdowns are serviced as usual. The rate is at least one message per second. Messages published to topics are not limited.
+    ///
+    /// ```rust
+    /// use apocalypse::{HellBuilder, Demon};
+    /// use std::time::{Duration, Instant};
+    ///
+    /// struct Downstream;
+    ///
+    /// impl Demon for Downstream {
+    ///     type Input = ();
+    ///     type Output = ();
+    ///     async fn handle(&mut self, _message: Self::Input) -> Self::Output {}
+    /// }
+    ///
+    /// # #[tokio::main]
+    /// # async fn main() {
+    /// let (gate, _join_handle) = HellBuilder::new().global_rate_limit(20).build().ignite().await.unwrap();
+    /// let location = gate.spawn(Downstream).await.unwrap();
+    /// let start = Instant::now();
+    /// let sender = gate.clone();
+    /// let messages = tokio::spawn(async move {
+    ///     for _ in 0..30 {
+    ///         sender.send_and_ignore(&location, ()).await.unwrap();
+    ///     }
+    /// });
+    /// // The broker is not stalled by the waiting messages
+    /// assert_eq!(1, gate.stats().await.unwrap().active_demons);
+    /// messages.await.unwrap();
+    /// // The first 20 messages go right away, the other 10 take half a second
+    /// assert!(start.elapsed() >= Duration::from_millis(400));
+    /// # }
+    /// ```
+    pub fn global_rate_limit(mut self, per_second: u32) -> Self {
+        self.global_rate_limit = Some(per_second);
+        self
+    }
+
+    /// Sets the clock that demons read the time from, through their [context](crate::DemonContext::now)
+    ///
+    /// By default, the [SystemClock](crate::SystemClock) is used. A [MockClock](crate::MockClock) allows tests to control the time that demons see, see [now](crate::DemonContext::now) for an example.
+    pub fn clock<C: 'static + Clock>(mut self, clock: C) -> Self {
+        self.clock = Arc::new(clock);
+        self
+    }
+
+    /// Sets a function that sees every message before the broker routes it
+    ///
+    /// The interceptor gets a [BrokerContext](crate::BrokerContext) with the target of the message, and decides whether the message is allowed, denied, or delayed. Denied messages never reach the demon, and the sender gets `Error::Denied`. Delayed messages are routed once the delay elapses, so they can be overtaken by later messages, and they are dropped if hell is extinguished in the meantime. This is the place for policies that apply to all demons alike, like auditing or access control. Messages published to topics are not intercepted. The interceptor runs on the broker, so it should return quickly. Without an interceptor, messages are routed with no extra cost.
+    ///
+    /// ```rust
+    /// use apocalypse::{HellBuilder, Demon, InterceptDecision, Error};
+    ///
+    /// struct Vault;
+    ///
+    /// impl Demon for Vault {
+    ///     type Input = ();
+    ///     type Output = &'static str;
+    ///     const TYPE_TAG: &'static str = "vault";
+    ///     async fn handle(&mut self, _message: Self::Input) -> Self::Output {
+    ///         "secret"
+    ///     }
+    /// }
+    ///
+    /// struct Lobby;
+    ///
+    /// impl Demon for Lobby {
+    ///     type Input = ();
+    ///     type Output = &'static str;
+    ///     async fn handle(&mut self, _message: Self::Input) -> Self::Output {
+    ///         "welcome"
+    ///     }
+    /// }
+    ///
+    /// # #[tokio::main]
+    /// # async fn main() {
+    /// let hell = HellBuilder::new().intercept(|context| if context.tag == Some("vault") {
+    ///     InterceptDecision::Deny
+    /// } else {
+    ///     InterceptDecision::Allow
+    /// }).build();
+    /// let (gate, _join_handle) = hell.ignite().await.unwrap();
+    /// let vault = gate.spawn(Vault).await.unwrap();
+    /// let lobby = gate.spawn(Lobby).await.unwrap();
+    /// assert!(matches!(gate.send(&vault, ()).await, Err(Error::Denied)));
+    /// assert_eq!("welcome", gate.send(&lobby, ()).await.unwrap());
+    /// # }
+    /// ```
+    pub fn intercept<F: 'static + Fn(&BrokerContext) -> InterceptDecision + Send + Sync>(mut self, interceptor: F) -> Self {
+        self.interceptor = Some(Box::new(interceptor));
+        self
+    }
+
     /// Generates the hell instance from the builder params
     ///
     /// ```rust
@@ -86,8 +377,27 @@ impl HellBuilder {
             zombie_counter: 0,
             successful_messages: 0,
             failed_messages: 0,
+            rejected_messages: 0,
+            queue_high_water: 0,
+            broker_backlog: 0,
+            instruction_counts: InstructionCounts::default(),
             demons: HashMap::new(),
+            children: HashMap::new(),
+            parents: HashMap::new(),
+            dependencies: HashMap::new(),
+            subscriptions: HashMap::new(),
+            names: HashMap::new(),
             timeout: self.timeout,
+            biased: self.biased,
+            max_total_demons: self.max_total_demons,
+            name: self.name.into(),
+            yield_budget: self.yield_budget,
+            throttle: self.global_rate_limit.map(Throttle::new),
+            clock: self.clock,
+            interceptor: self.interceptor,
+            in_flight: Arc::default(),
+            shutdown: broadcast::channel(1).0,
+            handle_time: Arc::default(),
             ignition_time: Utc::now()
         }
     }
@@ -103,12 +413,50 @@ pub struct Hell {
     successful_messages: usize,
     /// Amount of messages delivered to demons
     failed_messages: usize,
+    /// Amount of messages rejected due to their size
+    rejected_messages: usize,
+    /// Longest internal queue reached by demons that already left
+    queue_high_water: usize,
+    /// Instructions waiting for the broker, at the time the current one was taken
+    broker_backlog: usize,
     /// Zombie counter
     zombie_counter: usize,
+    /// Instructions processed by the broker, by type
+    instruction_counts: InstructionCounts,
     /// Communication channels with demons.
     demons: HashMap<usize, DemonChannels>,
+    /// Children of each demon, in spawn order
+    children: HashMap<usize, Vec<usize>>,
+    /// Parent of each demon, if any
+    parents: HashMap<usize, usize>,
+    /// Declared request dependencies between demons
+    dependencies: HashMap<usize, Vec<usize>>,
+    /// Subscribers of each topic, in subscription order
+    subscriptions: HashMap<String, Vec<usize>>,
+    /// Addresses of the demons spawned with a name
+    names: HashMap<String, usize>,
     /// Maximum wait time for killswitch calls
     timeout: Option<Duration>,
+    /// Fixed priority order in the broker loop
+    biased: bool,
+    /// Maximum amount of demons alive at the same time
+    max_total_demons: Option<usize>,
+    /// Name of the hell instance, used in logs and stats
+    name: Arc<str>,
+    /// Instructions processed by the broker before yielding
+    yield_budget: Option<usize>,
+    /// Global limit for the delivery of messages, if any
+    throttle: Option<Throttle>,
+    /// Source of the current time for demons
+    clock: Arc<dyn Clock>,
+    /// Function that sees every message before it is routed, if any
+    interceptor: Option<Interceptor>,
+    /// Messages delivered to demons that were not handled yet
+    in_flight: Arc<InFlight>,
+    /// Heads-up for the demons once extinguish begins
+    shutdown: broadcast::Sender<()>,
+    /// Time spent by demons inside their handle functions
+    handle_time: Arc<HandleTime>,
     /// Time that hell has been active
     ignition_time: DateTime<Utc>
 }
@@ -129,8 +477,27 @@ impl Hell {
             zombie_counter: 0,
             successful_messages: 0,
             failed_messages: 0,
+            rejected_messages: 0,
+            queue_high_water: 0,
+            broker_backlog: 0,
+            instruction_counts: InstructionCounts::default(),
             demons: HashMap::new(),
+            children: HashMap::new(),
+            parents: HashMap::new(),
+            dependencies: HashMap::new(),
+            subscriptions: HashMap::new(),
+            names: HashMap::new(),
             timeout: None,
+            biased: false,
+            max_total_demons: None,
+            name: "Hell".into(),
+            yield_budget: None,
+            throttle: None,
+            clock: Arc::new(SystemClock),
+            interceptor: None,
+            in_flight: Arc::default(),
+            shutdown: broadcast::channel(1).0,
+            handle_time: Arc::default(),
             ignition_time: Utc::now()
         }
     }
@@ -146,6 +513,840 @@ impl Hell {
         HellBuilder::new()
     }
 
+    /// Removes a demon and all of its descendants from the registry
+    ///
+    /// The returned list is ordered depth-first, with children before their parents, so the requested demon is always the last element.
+    fn unregister(&mut self, address: usize) -> Option<Vec<(usize, DemonChannels)>> {
+        let demon_channels = self.demons.remove(&address)?;
+        if let Some(queue) = &demon_channels.queue {
+            self.queue_high_water = self.queue_high_water.max(queue.high_water());
+        }
+
+        // The demon is no longer a child of its parent
+        if let Some(parent) = self.parents.remove(&address) {
+            if let Some(siblings) = self.children.get_mut(&parent) {
+                siblings.retain(|sibling| *sibling != address);
+            }
+        }
+
+        // Declared dependencies die with the demon
+        self.dependencies.remove(&address);
+        for dependencies in self.dependencies.values_mut() {
+            dependencies.retain(|to| *to != address);
+        }
+
+        // And so do subscriptions
+        self.subscriptions.retain(|_, subscribers| {
+            subscribers.retain(|subscriber| *subscriber != address);
+            !subscribers.is_empty()
+        });
+
+        // The name becomes free again
+        if let Some(name) = &demon_channels.name {
+            self.names.remove(name);
+        }
+
+        let mut removed = Vec::new();
+        for child in self.children.remove(&address).unwrap_or_default() {
+            if let Some(descendants) = self.unregister(child) {
+                removed.extend(descendants);
+            }
+        }
+        removed.push((address, demon_channels));
+        Some(removed)
+    }
+
+    /// Looks for a cycle in the declared dependencies, returning the addresses that form it
+    fn find_cycle(&self) -> Option<Vec<usize>> {
+        let mut addresses: Vec<usize> = self.dependencies.keys().cloned().collect();
+        addresses.sort();
+
+        let mut done = HashSet::new();
+        let mut path = Vec::new();
+        addresses.into_iter().find_map(|address| self.visit(address, &mut path, &mut done))
+    }
+
+    /// Depth-first search step of the cycle detection
+    fn visit(&self, address: usize, path: &mut Vec<usize>, done: &mut HashSet<usize>) -> Option<Vec<usize>> {
+        if let Some(idx) = path.iter().position(|visited| *visited == address) {
+            return Some(path[idx..].to_vec());
+        }
+        if done.contains(&address) {
+            return None;
+        }
+
+        path.push(address);
+        for to in self.dependencies.get(&address).into_iter().flatten() {
+            if let Some(cycle) = self.visit(*to, path, done) {
+                return Some(cycle);
+            }
+        }
+        path.pop();
+        done.insert(address);
+        None
+    }
+
+    /// Requests a demon shutdown, and waits until the demon is gone (either gracefully or through the killswitch)
+    async fn shutdown_demon(_name: Arc<str>, _address: usize, demon_channels: DemonChannels, timeout: Option<Duration>, reason: ShutdownReason) {
+        // This channel will tell us when the demon is gone
+        let (demon_tx, mut demon_rx) = oneshot::channel();
+
+        if demon_channels.instructions.send(MiniHellInstruction::Shutdown(demon_tx, reason)).is_err() {
+            #[cfg(feature = "full_log")]
+            log::debug!("[{}] could not notify demon thread the requested demon at address {} removal", _name, _address);
+            return;
+        }
+
+        // The killswitch timer runs right here, instead of in a task of its own that would outlive demons that leave in time
+        if let Some(timeout) = timeout {
+            #[cfg(feature = "full_log")]
+            log::trace!("[{}] killswitch trigger requested in {}ms", _name, timeout.as_millis());
+            tokio::select! {
+                res = &mut demon_rx => {
+                    if res.is_ok() {
+                        #[cfg(feature = "full_log")]
+                        log::trace!("[{}] gracefull vanquish executed properly at address {}", _name, _address);
+                    }
+                    return;
+                },
+                _ = tokio::time::sleep(timeout) => ()
+            }
+
+            #[cfg(feature = "full_log")]
+            log::trace!("[{}] sending killswitch trigger now", _name);
+            let (killswitch_tx, killswitch) = oneshot::channel();
+            // We ignore the killswitch send, because maybe the demon_channel is already obsolete
+            if demon_channels.killswitch.send(killswitch_tx).is_err() {
+                #[cfg(feature = "full_log")]
+                log::error!("[{}] killswitch not successfully sent", _name);
+            }
+            tokio::select! {
+                res = demon_rx => {
+                    if res.is_ok() {
+                        #[cfg(feature = "full_log")]
+                        log::trace!("[{}] gracefull vanquish executed properly at address {}", _name, _address);
+                    }
+                },
+                res = killswitch => {
+                    if res.is_ok() {
+                        #[cfg(feature = "full_log")]
+                        log::trace!("[{}] killswitch vanquish executed properly at address {}", _name, _address);
+                    }
+                }
+            };
+        } else {
+            #[cfg(feature = "full_log")]
+            log::trace!("[{}] no timeout was set for this vanquish call", _name);
+            if demon_rx.await.is_ok() {
+                #[cfg(feature = "full_log")]
+                log::trace!("[{}] gracefull vanquish executed properly at address {}", _name, _address);
+            }
+        }
+    }
+
+    /// Registers a demon at the given address, letting it start
+    ///
+    /// On failure, the readiness channel is dropped, so the mini hell leaves on its own, giving the demon back if it can.
+    fn register(&mut self, address: usize, demon_channels: DemonChannels, ready: oneshot::Sender<(usize, usize)>, parent: Option<usize>) -> Result<(), Error> {
+        let orphan = parent.map(|parent| !self.demons.contains_key(&parent)).unwrap_or(false);
+        let full = self.max_total_demons.map(|max_total_demons| self.demons.len() >= max_total_demons).unwrap_or(false);
+        let named = demon_channels.name.as_ref().map(|name| self.names.contains_key(name)).unwrap_or(false);
+        match self.demons.entry(address) {
+            std::collections::hash_map::Entry::Occupied(_) => {
+                #[cfg(feature = "full_log")]
+                log::debug!("[{}] demon address {} is already taken", self.name, address);
+                Err(Error::OccupiedAddress)
+            },
+            std::collections::hash_map::Entry::Vacant(_) if orphan => {
+                #[cfg(feature = "full_log")]
+                log::debug!("[{}] parent demon of address {} does not exist", self.name, address);
+                Err(Error::InvalidLocation)
+            },
+            std::collections::hash_map::Entry::Vacant(_) if full => {
+                #[cfg(feature = "full_log")]
+                log::warn!("[{}] maximum amount of demons reached, rejecting demon with address {}", self.name, address);
+                Err(Error::TooManyDemons)
+            },
+            std::collections::hash_map::Entry::Vacant(_) if named => {
+                #[cfg(feature = "full_log")]
+                log::debug!("[{}] demon name for address {} is already taken", self.name, address);
+                Err(Error::NameTaken)
+            },
+            std::collections::hash_map::Entry::Vacant(v) => {
+                #[cfg(feature = "full_log")]
+                log::debug!("[{}] registering new demon with address {} and tag {}", self.name, address, demon_channels.types.tag());
+                let generation = demon_channels.generation;
+                if let Some(name) = &demon_channels.name {
+                    self.names.insert(name.clone(), address);
+                }
+                v.insert(demon_channels);
+                if let Some(parent) = parent {
+                    self.children.entry(parent).or_default().push(address);
+                    self.parents.insert(address, parent);
+                }
+                // The demon can start now
+                let _ = ready.send((address, generation));
+                Ok(())
+            }
+        }
+    }
+
+    /// Removes a demon whose spawner left before learning that it was registered
+    ///
+    /// The demon already started, so it is shut down like any other, and gets to call its vanquished function.
+    fn drop_dangling(&mut self, address: usize) {
+        #[cfg(feature = "full_log")]
+        log::debug!("[{}] dangling demon with address {}, as it could not be notified that it was registered. removing.", self.name, address);
+        if let Some(removed) = self.unregister(address) {
+            let name = self.name.clone();
+            let timeout = self.timeout;
+            tokio::spawn(async move {
+                for (removed_address, demon_channels) in removed {
+                    let reason = if removed_address == address { ShutdownReason::Vanquished } else { ShutdownReason::ParentVanquished };
+                    Hell::shutdown_demon(name.clone(), removed_address, demon_channels, timeout, reason).await;
+                }
+            });
+        }
+    }
+
+    /// Runs the interceptor on messages, if there is any
+    ///
+    /// Instructions are given back when they can be processed right away. Denied messages are answered here, and delayed ones are kept aside until their time comes.
+    fn intercept(&self, instruction: HellInstruction, delayed: &mut FuturesUnordered<BoxFuture<'static, HellInstruction>>) -> Option<HellInstruction> {
+        let interceptor = match &self.interceptor {
+            Some(interceptor) => interceptor,
+            None => return Some(instruction)
+        };
+        let context = match &instruction {
+            HellInstruction::Message{address, ignore, trace, ..} => BrokerContext {
+                address: *address,
+                tag: self.demons.get(address).map(|demon_channels| demon_channels.types.tag()),
+                trace: *trace,
+                ignore: *ignore
+            },
+            _ => return Some(instruction)
+        };
+        match interceptor(&context) {
+            InterceptDecision::Allow => Some(instruction),
+            InterceptDecision::Deny => {
+                #[cfg(feature = "full_log")]
+                log::debug!("[{}] message to demon {} was denied by the interceptor", self.name, context.address);
+                if let HellInstruction::Message{tx, ..} = instruction {
+                    let _ = tx.send(Err(Error::Denied));
+                }
+                None
+            },
+            InterceptDecision::Delay(delay) => {
+                delayed.push(Box::pin(async move {
+                    tokio::time::sleep(delay).await;
+                    instruction
+                }));
+                None
+            }
+        }
+    }
+
+    /// Processes a single instruction
+    ///
+    /// If the instruction was an extinguish request, it gets returned so that the broker loop can stop.
+    async fn process(&mut self, instruction: HellInstruction, zombie_tx: &UnboundedSender<()>) -> Option<ExtinguishRequest> {
+        self.instruction_counts.count(&instruction);
+        match instruction {
+            HellInstruction::CreateAddress{tx} => {
+                #[cfg(feature = "full_log")]
+                log::trace!("[{}] received address creation request", self.name);
+                let current_counter = self.counter;
+                if tx.send(current_counter).is_ok() {
+                    #[cfg(feature = "full_log")]
+                    log::debug!("[{}] reserved address {}", self.name, current_counter);
+                    self.counter += 1;
+                } else {
+                    #[cfg(feature = "full_log")]
+                    log::debug!("[{}] failed to notify address {} reservation", self.name, current_counter);
+                }
+                #[cfg(feature = "full_log")]
+                log::trace!("[{}] leaving address creation request", self.name);
+            },
+            HellInstruction::SpawnDemon{demon_channels, ready, parent, tx} => {
+                #[cfg(feature = "full_log")]
+                log::trace!("[{}] received demon spawn request", self.name);
+                // The address is only taken if the registration succeeds
+                let address = self.counter;
+                let added = self.register(address, demon_channels, ready, parent);
+                if added.is_ok() {
+                    self.counter += 1;
+                }
+                if let Err(Ok(_)) = tx.send(added.map(|_| address)) {
+                    self.drop_dangling(address);
+                }
+
+                #[cfg(feature = "full_log")]
+                log::trace!("[{}] leaving demon spawn request", self.name);
+            },
+            HellInstruction::RegisterDemon{address, demon_channels, ready, parent, tx} => {
+                #[cfg(feature = "full_log")]
+                log::trace!("[{}] received demon registration request", self.name);
+                let added = self.register(address, demon_channels, ready, parent);
+                // Failed registrations left whatever was at the address untouched, so there is nothing to remove
+                if let Err(Ok(())) = tx.send(added) {
+                    self.drop_dangling(address);
+                }
+
+                #[cfg(feature = "full_log")]
+                log::trace!("[{}] leaving demon registration request", self.name);
+            },
+            HellInstruction::Handoff{address, mut demon_channels, ready, inherited, tx} => {
+                #[cfg(feature = "full_log")]
+                log::trace!("[{}] received handoff request for demon at address {}", self.name, address);
+                let handed_off = match self.demons.remove(&address) {
+                    // Pools and demons fed by a reader or a websocket have more than a mailbox to hand over
+                    Some(old_demon_channels) if old_demon_channels.kind != DemonKind::Single => {
+                        #[cfg(feature = "full_log")]
+                        log::debug!("[{}] demon at address {} is not a single demon, and cannot be handed off", self.name, address);
+                        self.demons.insert(address, old_demon_channels);
+                        Err(Error::HandoffUnsupported)
+                    },
+                    Some(mut old_demon_channels) => if let Err(e) = old_demon_channels.types.check(&demon_channels.types) {
+                        self.demons.insert(address, old_demon_channels);
+                        Err(e)
+                    } else if old_demon_channels.instructions.send(MiniHellInstruction::Handoff(inherited)).is_err() {
+                        #[cfg(feature = "full_log")]
+                        log::debug!("[{}] demon at address {} could not be asked to hand its messages over", self.name, address);
+                        self.demons.insert(address, old_demon_channels);
+                        Err(Error::DemonCommunication)
+                    } else {
+                        // Each demon that takes the address is a new generation of it
+                        demon_channels.generation = old_demon_channels.generation + 1;
+                        #[cfg(feature = "full_log")]
+                        log::debug!("[{}] demon d-{}.{} is being replaced by generation {}", self.name, address, old_demon_channels.generation, demon_channels.generation);
+                        // From now on, messages to this address go to the new demon, which handles the inherited ones first
+                        demon_channels.size_guard = old_demon_channels.size_guard.take();
+                        #[cfg(feature = "record")]
+                        {
+                            demon_channels.history = old_demon_channels.history.take();
+                        }
+                        demon_channels.delivered = old_demon_channels.delivered;
+                        // The name stays with the address
+                        demon_channels.name = old_demon_channels.name.take();
+                        let generation = demon_channels.generation;
+                        self.demons.insert(address, demon_channels);
+                        let _ = ready.send((address, generation));
+                        // The old demon is still running its vanquished function, and must not see its channels closed
+                        tokio::spawn(async move {
+                            // Moved as a whole, the killswitch has to stay open as well
+                            let old_demon_channels = old_demon_channels;
+                            old_demon_channels.instructions.closed().await;
+                        });
+                        Ok(())
+                    },
+                    None => Err(Error::InvalidLocation)
+                };
+
+                if tx.send(handed_off).is_err() {
+                    #[cfg(feature = "full_log")]
+                    log::debug!("[{}] could not notify handoff result for demon at address {}", self.name, address);
+                }
+            },
+            HellInstruction::Message{tx, address, ignore, trace, input, types} => {
+                #[cfg(feature = "full_log")]
+                log::trace!("[{}] received message delivery request to demon at location {}, trace {}", self.name, address, trace);
+                if let Some(demon_channels) = self.demons.get_mut(&address).filter(|demon_channels| !demon_channels.is_closed()) {
+                    // Only the reply remembers that the message was timed
+                    let (input, timed) = Timed::open(input);
+                    // Typed requests are verified against the demon's own types
+                    if let Some(Err(e)) = types.map(|types| demon_channels.types.check(&types)) {
+                        #[cfg(feature = "full_log")]
+                        log::debug!("[{}] message to demon {} has the wrong type, {}", self.name, address, e);
+                        if tx.send(Err(e)).is_err() {
+                            #[cfg(feature = "full_log")]
+                            log::debug!("[{}] type mismatch error for address {} could not be delivered back", self.name, address);
+                        }
+                        return None;
+                    }
+                    // Oversized messages never reach the demon's queue
+                    if demon_channels.size_guard.as_ref().map(|size_guard| size_guard.rejects(input.as_ref())).unwrap_or(false) {
+                        self.rejected_messages += 1;
+                        #[cfg(feature = "full_log")]
+                        log::debug!("[{}] message to demon {} exceeds the maximum message size", self.name, address);
+                        if tx.send(Err(Error::MessageTooLarge)).is_err() {
+                            #[cfg(feature = "full_log")]
+                            log::debug!("[{}] message too large error for address {} could not be delivered back", self.name, address);
+                        }
+                        return None;
+                    }
+                    // Ignored messages are acknowledged once they are in the demon's queue
+                    let (tx, routed) = if ignore {
+                        (ReplyTo::ignored(self.in_flight.track()), Some(tx))
+                    } else {
+                        (ReplyTo::new(tx, self.in_flight.track()).timed(timed), None)
+                    };
+                    #[cfg(feature = "record")]
+                    if let Some(history) = &demon_channels.history {
+                        history.record(input.as_ref());
+                    }
+                    // The backlog grows before the delivery, so the demon never observes it going below zero
+                    demon_channels.mailbox.fetch_add(1, Ordering::Relaxed);
+                    if let Err(e) = demon_channels.instructions.send(MiniHellInstruction::Message(tx, trace, input)) {
+                        demon_channels.mailbox.fetch_sub(1, Ordering::Relaxed);
+                        self.failed_messages += 1;
+                        #[cfg(feature = "full_log")]
+                        log::debug!("[{}] message could not be delivered to demon {}", self.name, address);
+                        // The demon closed its channel while leaving, the reply travels back with the message
+                        if let MiniHellInstruction::Message(tx, _, _) = e.0 {
+                            let _ = tx.send(Err(Error::DemonCommunication));
+                        }
+                        if let Some(routed) = routed {
+                            let _ = routed.send(Err(Error::DemonCommunication));
+                        }
+                    } else {
+                        self.successful_messages += 1;
+                        demon_channels.delivered += 1;
+                        if let Some(routed) = routed {
+                            let _ = routed.send(Ok(Box::new(())));
+                        }
+                    };
+                } else {
+                    if tx.send(Err(Error::InvalidLocation)).is_err() {
+                        #[cfg(feature = "full_log")]
+                        log::debug!("[{}] invalid location message for address {} could not be delivered back", self.name, address);
+                    };
+                }
+                #[cfg(feature = "full_log")]
+                log::trace!("[{}] leaving message delivery request", self.name);
+            },
+            HellInstruction::RemoveDemon{address, tx, ignore, force} => {
+                #[cfg(feature = "full_log")]
+                log::trace!("[{}] received demon removal request for demon at location {}", self.name, address);
+                // We remove the demon, and all of its descendants, children first
+                if let Some(removed) = self.unregister(address) {
+                    // force timeout has the prefference
+                    let timeout = match force {
+                        Some(v) => {
+                            #[cfg(feature = "full_log")]
+                            log::trace!("[{}] custom timeout is being used", self.name);
+                            v
+                        },
+                        None => {
+                            #[cfg(feature = "full_log")]
+                            log::trace!("[{}] default timeout in use", self.name);
+                            self.timeout
+                        }
+                    };
+
+                    // The requested demon is always the last one to be shut down
+                    if removed.last().map(|(_, demon_channels)| demon_channels.instructions.is_closed()).unwrap_or(true) {
+                        #[cfg(feature = "full_log")]
+                        log::debug!("[{}] could not notify demon thread the requested demon at address {} removal", self.name, address);
+                        if tx.send(Err(Error::DemonCommunication)).is_err() {
+                            #[cfg(feature = "full_log")]
+                            log::debug!("[{}] could not notify demon at address {} removal failure", self.name, address);
+                        }
+                    } else {
+                        let zombie_tx_clone = zombie_tx.clone();
+                        let name = self.name.clone();
+                        let waiter = async move {
+                            for (removed_address, demon_channels) in removed {
+                                // Only the requested demon is vanquished on its own
+                                let reason = if removed_address == address { ShutdownReason::Vanquished } else { ShutdownReason::ParentVanquished };
+                                Hell::shutdown_demon(name.clone(), removed_address, demon_channels, timeout, reason).await;
+                            }
+
+                            if ignore && zombie_tx_clone.send(()).is_err() {
+                                #[cfg(feature = "full_log")]
+                                log::trace!("[{}] demon zombie counter message decrease could not be sent", name);
+                            }
+                        };
+                        // if the message should be ignored, we need to move it to a different thread
+                        if ignore {
+                            #[cfg(feature = "full_log")]
+                            log::trace!("[{}] ignore requested, zombie demon count increased by one", self.name);
+                            self.zombie_counter += 1;
+                            tokio::spawn(waiter);
+
+                            if tx.send(Ok(())).is_err() {
+                                #[cfg(feature = "full_log")]
+                                log::trace!("[{}] could not notify back demon at address {} removal", self.name, address);
+                            }
+                        } else {
+                            // The broker keeps running in the meantime, so the leaving demons can still send messages
+                            let _name = self.name.clone();
+                            tokio::spawn(async move {
+                                waiter.await;
+                                if tx.send(Ok(())).is_err() {
+                                    #[cfg(feature = "full_log")]
+                                    log::trace!("[{}] could not notify back demon at address {} removal", _name, address);
+                                }
+                            });
+                        }
+                    }
+                } else {
+                    #[cfg(feature = "full_log")]
+                    log::debug!("[{}] demon with address {} was not found", self.name, address);
+                    if tx.send(Err(Error::InvalidLocation)).is_err() {
+                        #[cfg(feature = "full_log")]
+                        log::trace!("[{}] could not notify that demon with address {} was not found", self.name, address);
+                    }
+                }
+
+                #[cfg(feature = "full_log")]
+                log::trace!("[{}] leaving demon removal request", self.name);
+            },
+            HellInstruction::CancelPending{address, tx} => {
+                #[cfg(feature = "full_log")]
+                log::trace!("[{}] received cancellation request for demon at location {}", self.name, address);
+                match self.demons.get(&address) {
+                    Some(demon_channels) => {
+                        // The mini hell replies directly, once it goes through its queue
+                        let (cancel_tx, cancel_rx) = oneshot::channel();
+                        if demon_channels.instructions.send(MiniHellInstruction::CancelPending(cancel_tx)).is_err() {
+                            if tx.send(Err(Error::DemonCommunication)).is_err() {
+                                #[cfg(feature = "full_log")]
+                                log::debug!("[{}] cancellation failure for address {} could not be delivered back", self.name, address);
+                            }
+                        } else {
+                            tokio::spawn(async move {
+                                let _ = tx.send(cancel_rx.await.map_err(|_| Error::DemonCommunication));
+                            });
+                        }
+                    },
+                    None => if tx.send(Err(Error::InvalidLocation)).is_err() {
+                        #[cfg(feature = "full_log")]
+                        log::debug!("[{}] invalid location for address {} could not be delivered back", self.name, address);
+                    }
+                }
+            },
+            HellInstruction::Tap{address, observer, tx} => {
+                #[cfg(feature = "full_log")]
+                log::trace!("[{}] received tap request for demon at location {}", self.name, address);
+                match self.demons.get(&address) {
+                    Some(demon_channels) => {
+                        // The mini hell replies directly, once it goes through its queue
+                        let (tap_tx, tap_rx) = oneshot::channel();
+                        if demon_channels.instructions.send(MiniHellInstruction::Tap(observer, tap_tx)).is_err() {
+                            if tx.send(Err(Error::DemonCommunication)).is_err() {
+                                #[cfg(feature = "full_log")]
+                                log::debug!("[{}] tap failure for address {} could not be delivered back", self.name, address);
+                            }
+                        } else {
+                            tokio::spawn(async move {
+                                let _ = tx.send(tap_rx.await.unwrap_or(Err(Error::DemonCommunication)));
+                            });
+                        }
+                    },
+                    None => if tx.send(Err(Error::InvalidLocation)).is_err() {
+                        #[cfg(feature = "full_log")]
+                        log::debug!("[{}] invalid location for address {} could not be delivered back", self.name, address);
+                    }
+                }
+            },
+            HellInstruction::RestartReplica{address, replica, demon, tx} => {
+                #[cfg(feature = "full_log")]
+                log::trace!("[{}] received restart request for replica {} of demon at location {}", self.name, replica, address);
+                match self.demons.get(&address) {
+                    Some(demon_channels) => {
+                        // The mini hell replies directly, once the replacement is in place
+                        let (restart_tx, restart_rx) = oneshot::channel();
+                        if demon_channels.instructions.send(MiniHellInstruction::RestartReplica(replica, demon, restart_tx)).is_err() {
+                            if tx.send(Err(Error::DemonCommunication)).is_err() {
+                                #[cfg(feature = "full_log")]
+                                log::debug!("[{}] restart failure for address {} could not be delivered back", self.name, address);
+                            }
+                        } else {
+                            tokio::spawn(async move {
+                                let _ = tx.send(restart_rx.await.unwrap_or(Err(Error::DemonCommunication)));
+                            });
+                        }
+                    },
+                    None => if tx.send(Err(Error::InvalidLocation)).is_err() {
+                        #[cfg(feature = "full_log")]
+                        log::debug!("[{}] invalid location for address {} could not be delivered back", self.name, address);
+                    }
+                }
+            },
+            HellInstruction::Subscribe{address, topic, tx} => {
+                #[cfg(feature = "full_log")]
+                log::trace!("[{}] received subscription of demon {} to topic {}", self.name, address, topic);
+                let subscribed = if self.demons.contains_key(&address) {
+                    let subscribers = self.subscriptions.entry(topic).or_default();
+                    if !subscribers.contains(&address) {
+                        subscribers.push(address);
+                    }
+                    Ok(())
+                } else {
+                    Err(Error::InvalidLocation)
+                };
+
+                if tx.send(subscribed).is_err() {
+                    #[cfg(feature = "full_log")]
+                    log::debug!("[{}] could not notify subscription result", self.name);
+                }
+            },
+            HellInstruction::Unsubscribe{address, topic, tx} => {
+                #[cfg(feature = "full_log")]
+                log::trace!("[{}] received unsubscription of demon {} from topic {}", self.name, address, topic);
+                let unsubscribed = if self.demons.contains_key(&address) {
+                    if let Some(subscribers) = self.subscriptions.get_mut(&topic) {
+                        subscribers.retain(|subscriber| *subscriber != address);
+                        if subscribers.is_empty() {
+                            self.subscriptions.remove(&topic);
+                        }
+                    }
+                    Ok(())
+                } else {
+                    Err(Error::InvalidLocation)
+                };
+
+                if tx.send(unsubscribed).is_err() {
+                    #[cfg(feature = "full_log")]
+                    log::debug!("[{}] could not notify unsubscription result", self.name);
+                }
+            },
+            HellInstruction::Publish{topic, input, clone, trace, tx} => {
+                #[cfg(feature = "full_log")]
+                log::trace!("[{}] received publication to topic {}, trace {}", self.name, topic, trace);
+                let input_type = input.as_ref().type_id();
+                let mut delivered = 0;
+                for address in self.subscriptions.get(&topic).into_iter().flatten() {
+                    // Subscribers that do not accept the message are skipped
+                    let Some(demon_channels) = self.demons.get_mut(address).filter(|demon_channels| !demon_channels.is_closed() && demon_channels.types.accepts(input_type)) else {
+                        continue;
+                    };
+                    if demon_channels.size_guard.as_ref().map(|size_guard| size_guard.rejects(input.as_ref())).unwrap_or(false) {
+                        self.rejected_messages += 1;
+                        continue;
+                    }
+                    #[cfg(feature = "record")]
+                    if let Some(history) = &demon_channels.history {
+                        history.record(input.as_ref());
+                    }
+                    demon_channels.mailbox.fetch_add(1, Ordering::Relaxed);
+                    if demon_channels.instructions.send(MiniHellInstruction::Message(ReplyTo::ignored(self.in_flight.track()), trace, clone(input.as_ref()))).is_err() {
+                        demon_channels.mailbox.fetch_sub(1, Ordering::Relaxed);
+                        self.failed_messages += 1;
+                    } else {
+                        self.successful_messages += 1;
+                        demon_channels.delivered += 1;
+                        delivered += 1;
+                    }
+                }
+
+                if tx.send(delivered).is_err() {
+                    #[cfg(feature = "full_log")]
+                    log::debug!("[{}] could not notify publication result", self.name);
+                }
+            },
+            HellInstruction::DeclareDependency{from, to, tx} => {
+                #[cfg(feature = "full_log")]
+                log::trace!("[{}] received dependency declaration from {} to {}", self.name, from, to);
+                let declared = if self.demons.contains_key(&from) && self.demons.contains_key(&to) {
+                    let dependencies = self.dependencies.entry(from).or_default();
+                    if !dependencies.contains(&to) {
+                        dependencies.push(to);
+                    }
+                    Ok(())
+                } else {
+                    #[cfg(feature = "full_log")]
+                    log::debug!("[{}] dependency between {} and {} involves an unknown demon", self.name, from, to);
+                    Err(Error::InvalidLocation)
+                };
+
+                if tx.send(declared).is_err() {
+                    #[cfg(feature = "full_log")]
+                    log::debug!("[{}] could not notify dependency declaration result", self.name);
+                }
+            },
+            HellInstruction::CheckCycles{tx} => {
+                #[cfg(feature = "full_log")]
+                log::trace!("[{}] received cycle check request", self.name);
+                let checked = match self.find_cycle() {
+                    Some(cycle) => Err(Error::DependencyCycle(cycle)),
+                    None => Ok(())
+                };
+
+                if tx.send(checked).is_err() {
+                    #[cfg(feature = "full_log")]
+                    log::debug!("[{}] could not notify cycle check result", self.name);
+                }
+            },
+            HellInstruction::HealthSweep{timeout, tx} => {
+                #[cfg(feature = "full_log")]
+                log::trace!("[{}] received health sweep request", self.name);
+                let pings: Vec<_> = self.demons.iter().map(|(address, demon_channels)| {
+                    let (ping_tx, ping_rx) = oneshot::channel();
+                    let delivered = demon_channels.instructions.send(MiniHellInstruction::Ping(ping_tx)).is_ok();
+                    let address = *address;
+                    async move {
+                        // A failed delivery means that the demon already finished
+                        let answered = delivered && matches!(tokio::time::timeout(timeout, ping_rx).await, Ok(Ok(())));
+                        (address, answered)
+                    }
+                }).collect();
+
+                // All demons are waited for at the same time, without blocking the broker
+                let _name = self.name.clone();
+                tokio::spawn(async move {
+                    let mut report = join_all(pings).await;
+                    report.sort();
+                    if tx.send(report).is_err() {
+                        #[cfg(feature = "full_log")]
+                        log::debug!("[{}] could not return health sweep report, channel closed", _name);
+                    }
+                });
+            },
+            HellInstruction::LocationsOfType{demon, tx} => {
+                #[cfg(feature = "full_log")]
+                log::trace!("[{}] received locations request", self.name);
+                // Demons that already finished are no longer reachable, even if hell was not notified yet
+                let mut addresses: Vec<usize> = self.demons.iter()
+                    .filter(|(_, demon_channels)| demon_channels.demon == demon && !demon_channels.is_closed())
+                    .map(|(address, _)| *address)
+                    .collect();
+                addresses.sort();
+
+                if tx.send(addresses).is_err() {
+                    #[cfg(feature = "full_log")]
+                    log::debug!("[{}] could not return demon locations, channel closed", self.name);
+                }
+            },
+            HellInstruction::DemonType{address, tx} => {
+                #[cfg(feature = "full_log")]
+                log::trace!("[{}] received type request for demon at address {}", self.name, address);
+                let tag = self.demons.get(&address).map(|demon_channels| demon_channels.types.tag()).ok_or(Error::InvalidLocation);
+                if tx.send(tag).is_err() {
+                    #[cfg(feature = "full_log")]
+                    log::debug!("[{}] could not return demon type, channel closed", self.name);
+                }
+            },
+            HellInstruction::Named{name, demon, expected, tx} => {
+                #[cfg(feature = "full_log")]
+                log::trace!("[{}] received request for demon named {}", self.name, name);
+                let address = match self.names.get(&name).and_then(|address| Some((*address, self.demons.get(address)?))) {
+                    Some((address, demon_channels)) if demon_channels.demon == demon => Ok(address),
+                    Some((_, demon_channels)) => Err(Error::TypeMismatch {
+                        expected: expected.to_string(),
+                        found: demon_channels.types.tag().to_string()
+                    }),
+                    None => Err(Error::InvalidLocation)
+                };
+                if tx.send(address).is_err() {
+                    #[cfg(feature = "full_log")]
+                    log::debug!("[{}] could not return named demon, channel closed", self.name);
+                }
+            },
+            HellInstruction::Generation{address, tx} => {
+                #[cfg(feature = "full_log")]
+                log::trace!("[{}] received generation request for demon at address {}", self.name, address);
+                let generation = self.demons.get(&address).map(|demon_channels| demon_channels.generation).ok_or(Error::InvalidLocation);
+                if tx.send(generation).is_err() {
+                    #[cfg(feature = "full_log")]
+                    log::debug!("[{}] could not return demon generation, channel closed", self.name);
+                }
+            },
+            #[cfg(feature = "record")]
+            HellInstruction::History{address, tx} => {
+                #[cfg(feature = "full_log")]
+                log::trace!("[{}] received message history request for demon at address {}", self.name, address);
+                let history = match self.demons.get(&address) {
+                    Some(demon_channels) => demon_channels.history.clone().ok_or(Error::NotRecorded),
+                    None => Err(Error::InvalidLocation)
+                };
+                if tx.send(history).is_err() {
+                    #[cfg(feature = "full_log")]
+                    log::debug!("[{}] could not return message history, channel closed", self.name);
+                }
+            },
+            HellInstruction::SetTimeout{timeout, tx} => {
+                #[cfg(feature = "full_log")]
+                log::debug!("[{}] default timeout changed to {:?}", self.name, timeout);
+                self.timeout = timeout;
+                if tx.send(()).is_err() {
+                    #[cfg(feature = "full_log")]
+                    log::debug!("[{}] could not confirm timeout change, channel closed", self.name);
+                }
+            },
+            HellInstruction::InFlight{tx} => {
+                #[cfg(feature = "full_log")]
+                log::trace!("[{}] received in flight counter request", self.name);
+                if tx.send(self.in_flight.clone()).is_err() {
+                    #[cfg(feature = "full_log")]
+                    log::debug!("[{}] could not return in flight counter, channel closed", self.name);
+                }
+            },
+            HellInstruction::Stats{tx} => {
+                #[cfg(feature = "full_log")]
+                log::trace!("[{}] received stats request", self.name);
+                if tx.send(HellStats {
+                    name: self.name.to_string(),
+                    spawned_demons: self.counter,
+                    active_demons: self.demons.len(),
+                    zombie_demons: self.zombie_counter,
+                    successful_messages: self.successful_messages,
+                    failed_messages: self.failed_messages,
+                    rejected_messages: self.rejected_messages,
+                    demons_by_tag: self.demons.values().fold(HashMap::new(), |mut demons_by_tag, demon_channels| {
+                        *demons_by_tag.entry(demon_channels.types.tag().to_string()).or_insert(0) += 1;
+                        demons_by_tag
+                    }),
+                    broker_backlog: self.broker_backlog,
+                    queued_messages: self.demons.values().filter_map(|demon_channels| demon_channels.queue.as_ref()).map(|queue| queue.len()).sum(),
+                    queued_messages_high_water: self.demons.values().filter_map(|demon_channels| demon_channels.queue.as_ref()).map(|queue| queue.high_water()).fold(self.queue_high_water, usize::max),
+                    message_instructions: self.instruction_counts.message,
+                    create_address_instructions: self.instruction_counts.create_address,
+                    spawn_demon_instructions: self.instruction_counts.spawn_demon,
+                    register_demon_instructions: self.instruction_counts.register_demon,
+                    remove_demon_instructions: self.instruction_counts.remove_demon,
+                    stats_instructions: self.instruction_counts.stats,
+                    other_instructions: self.instruction_counts.other,
+                    total_handle_time: self.handle_time.total(),
+                    ignition_time: self.ignition_time.clone()
+                }).is_err() {
+                    #[cfg(feature = "full_log")]
+                    log::debug!("[{}] could not return hell stats, channel closed", self.name);
+                }
+                #[cfg(feature = "full_log")]
+                log::trace!("[{}] leaving stats request", self.name);
+            },
+            HellInstruction::DumpRegistry{tx} => {
+                #[cfg(feature = "full_log")]
+                log::trace!("[{}] received registry dump request", self.name);
+                let mut demons: Vec<DemonSnapshot> = self.demons.iter().map(|(address, demon_channels)| {
+                    let (input, output) = demon_channels.types.names();
+                    let mut topics: Vec<String> = self.subscriptions.iter().filter(|(_, subscribers)| subscribers.contains(address)).map(|(topic, _)| topic.clone()).collect();
+                    topics.sort();
+                    DemonSnapshot {
+                        address: *address,
+                        generation: demon_channels.generation,
+                        name: demon_channels.name.clone(),
+                        tag: demon_channels.types.tag().to_string(),
+                        input: input.to_string(),
+                        output: output.to_string(),
+                        delivered_messages: demon_channels.delivered,
+                        mailbox: demon_channels.mailbox.load(Ordering::Relaxed),
+                        queued_messages: demon_channels.queue.as_ref().map(|queue| queue.len()),
+                        parent: self.parents.get(address).cloned(),
+                        children: self.children.get(address).cloned().unwrap_or_default(),
+                        topics
+                    }
+                }).collect();
+                demons.sort_by_key(|demon| demon.address);
+                if tx.send(RegistrySnapshot {
+                    name: self.name.to_string(),
+                    taken_at: Utc::now(),
+                    demons
+                }).is_err() {
+                    #[cfg(feature = "full_log")]
+                    log::debug!("[{}] could not return registry snapshot, channel closed", self.name);
+                }
+            },
+            HellInstruction::Extinguish{tx, timeout, deadline} => {
+                #[cfg(feature = "full_log")]
+                log::trace!("[{}] extinguish message received", self.name);
+                return Some((tx, timeout, deadline));
+            }
+        }
+        None
+    }
+
     /// Starts the actor system
     ///
     /// This method returns both a Gate, and a JoinHandle.
@@ -163,291 +1364,169 @@ impl Hell {
     ///     join_handle.await.unwrap();
     /// }
     /// ```
+    ///
+    /// The broker runs as a tokio task, so a tokio runtime is required. When ignited outside of one, `Error::NoRuntime` is returned.
+    ///
+    /// ```rust
+    /// use apocalypse::{Hell, Error};
+    ///
+    /// // Another executor, without tokio's runtime
+    /// let ignition = futures::executor::block_on(Hell::new().ignite());
+    /// assert!(matches!(ignition, Err(Error::NoRuntime)));
+    /// ```
     pub async fn ignite(mut self) -> Result<(Gate, JoinHandle<()>), Error>{
+        // Spawning the broker would panic otherwise
+        let runtime = tokio::runtime::Handle::try_current().map_err(|_| Error::NoRuntime)?;
+
         // ignition time update
         self.ignition_time = Utc::now();
 
         // Message communication for the gate
         let (hell_channel, outer_instructions) = mpsc::unbounded_channel();
-        // Incoming close messages from websockets demons
+        // Control plane communication for the gate, serviced before regular instructions
+        let (priority_channel, mut priority_instructions) = mpsc::unbounded_channel();
+        // Incoming exit notices from demons that finished on their own
         let (on_close_tx, mut on_close_rx) = mpsc::unbounded_channel();
         
         let gate = Gate {
             hell_channel,
-            #[cfg(feature = "ws")]
-            on_close_tx
+            priority_channel,
+            on_close_tx,
+            clock: self.clock.clone(),
+            concurrency: None,
+            shutdown: self.shutdown.clone(),
+            handle_time: self.handle_time.clone()
         };
 
         let gate_clone = gate.clone();
 
-        let jh = tokio::spawn(async move {
+        #[cfg(tokio_unstable)]
+        let name = self.name.clone();
+        let broker = async move {
             #[cfg(feature = "full_log")]
-            log::info!("Broker starts \u{1f525}");
+            log::info!("[{}] Broker starts \u{1f525}", self.name);
 
             // We need another channel, for zombie count removal
             let (zombie_tx, mut zombie_rx) = mpsc::unbounded_channel();
 
             let clean = {
                 let mut instructions = outer_instructions;
+                // Instructions processed since the broker last yielded
+                let mut processed = 0;
+                // Messages delayed by the interceptor
+                let mut delayed = FuturesUnordered::new();
                 loop {
                     #[cfg(feature = "full_log")]
-                    log::trace!("[Hell] entering message process loop iteration, waiting for incoming message...");
-                    tokio::select! {
-                        value = instructions.recv() => if let Some(instruction) = value {
-                            #[cfg(feature = "full_log")]
-                            log::debug!("[Hell] entering instruction handler");
-                            match instruction {
-                                HellInstruction::CreateAddress{tx} => {
-                                    #[cfg(feature = "full_log")]
-                                    log::trace!("[Hell] received address creation request");
-                                    let current_counter = self.counter;
-                                    if tx.send(current_counter).is_ok() {
-                                        #[cfg(feature = "full_log")]
-                                        log::debug!("[Hell] reserved address {}", current_counter);
-                                        self.counter += 1;
-                                    } else {
-                                        #[cfg(feature = "full_log")]
-                                        log::debug!("[Hell] failed to notify address {} reservation", current_counter);
-                                    }
-                                    #[cfg(feature = "full_log")]
-                                    log::trace!("[Hell] leaving address creation request");
-                                },
-                                HellInstruction::RegisterDemon{address, demon_channels, tx} => {
-                                    #[cfg(feature = "full_log")]
-                                    log::trace!("[Hell] received demon registration request");
-                                    let added = match self.demons.entry(address) {
-                                        std::collections::hash_map::Entry::Occupied(_) => {
-                                            #[cfg(feature = "full_log")]
-                                            log::debug!("[Hell] demon address {} is already taken", address);
-                                            Err(Error::OccupiedAddress)
-                                        },
-                                        std::collections::hash_map::Entry::Vacant(v) => {
-                                            #[cfg(feature = "full_log")]
-                                            log::debug!("[Hell] registering new demon with address {}", address);
-                                            v.insert(demon_channels);
-                                            Ok(())
-                                        }
-                                    };
-
-                                    if tx.send(added).is_err() {
-                                        #[cfg(feature = "full_log")]
-                                        log::debug!("[Hell] dangling demon with address {}, as it could not be notified that it was registered. removing.", address);
-                                        self.demons.remove(&address);
-                                    }
-
-                                    #[cfg(feature = "full_log")]
-                                    log::trace!("[Hell] leaving demon registration request");
-                                },
-                                HellInstruction::Message{tx, address, ignore, input} => {
-                                    #[cfg(feature = "full_log")]
-                                    log::trace!("[Hell] received message delivery request to demon at location {}", address);
-                                    if let Some(demon_channels) = self.demons.get_mut(&address) {
-                                        let tx = if ignore {
-                                            let (ignore_tx, ignore_rx) = oneshot::channel();
-                                            tokio::spawn(async move {
-                                                let _ = ignore_rx.await;
-                                                #[cfg(feature = "full_log")]
-                                                log::trace!("[Hell] ignored reply received");
-                                            });
-                                            let _ = tx.send(Ok(Box::new(())));
-                                            ignore_tx
-                                        } else {
-                                            tx
-                                        };
-                                        if demon_channels.instructions.send(MiniHellInstruction::Message(tx, input)).is_err() {
-                                            self.failed_messages += 1;
-                                            #[cfg(feature = "full_log")]
-                                            log::debug!("[Hell] message could not be delivered to demon {}", address);
-                                        } else {
-                                            self.successful_messages += 1;
-                                        };
-                                    } else {
-                                        if tx.send(Err(Error::InvalidLocation)).is_err() {
-                                            #[cfg(feature = "full_log")]
-                                            log::debug!("[Hell] invalid location message for address {} could not be delivered back", address);
-                                        };
-                                    }
-                                    #[cfg(feature = "full_log")]
-                                    log::trace!("[Hell] leaving message delivery request");
-                                },
-                                HellInstruction::RemoveDemon{address, tx, ignore, force} => {
-                                    #[cfg(feature = "full_log")]
-                                    log::trace!("[Hell] received demon removal request for demon at location {}", address);
-                                    // We simply drop the channel, so the mini hell will close automatically
-                                    let removed = self.demons.remove(&address);
-                                    if let Some(demon_channels) = removed {
-                                        // This channel will allow the zombie counter to be decreased, when necessary
-                                        let (demon_tx, demon_rx) = oneshot::channel();
-
-                                        // force timeout has the prefference
-                                        let timeout = match force {
-                                            Some(v) => {
-                                                log::trace!("[Hell] custom timeout is being used");
-                                                v
-                                            },
-                                            None => {
-                                                log::trace!("[Hell] default timeout in use");
-                                                self.timeout
-                                            }
-                                        };
-
-                                        let killswitch = if let Some(timeout) = timeout {
-                                            #[cfg(feature = "full_log")]
-                                            log::trace!("[Hell] killswitch trigger requested in {}ms", timeout.as_millis());
-                                            // We send the killswitch with a timeout
-                                            let demon_channel_killswitch = demon_channels.killswitch;
-                                            let (killswitch_tx, killswitch) = oneshot::channel();
-                                            tokio::spawn(async move {
-                                                tokio::time::sleep(timeout).await;
-                                                #[cfg(feature = "full_log")]
-                                                log::trace!("[Hell] sending killswitch trigger now");
-                                                // We ignore the killswitch send, because maybe the demon_channel is already obsolete
-                                                match demon_channel_killswitch.send(killswitch_tx) {
-                                                    Ok(_) => {
-                                                        #[cfg(feature = "full_log")]
-                                                        log::trace!("[Hell] killswitch sent");
-                                                    },
-                                                    Err(_) => {
-                                                        #[cfg(feature = "full_log")]
-                                                        log::error!("[Hell] killswitch not successfully sent");
-                                                    }
-                                                }
-                                            });
-                                            Some(killswitch)
-                                        } else {
-                                            #[cfg(feature = "full_log")]
-                                            log::trace!("[Hell] no timeout was set for this vanquish call");
-                                            None
-                                        };
-
-                                        if demon_channels.instructions.send(MiniHellInstruction::Shutdown(demon_tx)).is_err() {
-                                            #[cfg(feature = "full_log")]
-                                            log::debug!("[Hell] could not notify demon thread the requested demon at address {} removal", address);
-                                            if tx.send(Err(Error::DemonCommunication)).is_err() {
-                                                #[cfg(feature = "full_log")]
-                                                log::debug!("[Hell] could not notify demon at address {} removal failure", address);
-                                            }
-                                        } else {
-                                            let _address_copy = address.clone();
-                                            let zombie_tx_clone = zombie_tx.clone();
-                                            let waiter = async move {
-                                                if let Some(killswitch) = killswitch {
-                                                    tokio::select! {
-                                                        res = demon_rx => {
-                                                            if res.is_ok() {
-                                                                #[cfg(feature = "full_log")]
-                                                                log::trace!("[Hell] gracefull vanquish executed properly at address {}", _address_copy);
-                                                            }
-                                                        },
-                                                        res = killswitch => {
-                                                            if res.is_ok() {
-                                                                #[cfg(feature = "full_log")]
-                                                                log::trace!("[Hell] killswitch vanquish executed properly at address {}", _address_copy);
-                                                            }
-                                                        }
-                                                    };
-                                                } else {
-                                                    if demon_rx.await.is_ok() {
-                                                        #[cfg(feature = "full_log")]
-                                                        log::trace!("[Hell] gracefull vanquish executed properly at address {}", _address_copy);
-                                                    }
-                                                }
-
-                                                if ignore {
-                                                    if zombie_tx_clone.send(()).is_err() {
-                                                        #[cfg(feature = "full_log")]
-                                                        log::trace!("[Hell] demon zombie counter message decrease could not be sent");
-                                                    }
-                                                }
-                                            };
-                                            // if the message should be ignored, we need to move it to a different thread
-                                            if ignore {
-                                                #[cfg(feature = "full_log")]
-                                                log::trace!("[Hell] ignore requested, zombie demon count increased by one");
-                                                self.zombie_counter += 1;
-                                                tokio::spawn(waiter);
-                                            } else {
-                                                waiter.await;
-                                            }
-
-                                            if tx.send(Ok(())).is_err() {
-                                                #[cfg(feature = "full_log")]
-                                                log::trace!("[Hell] could not notify back demon at address {} removal", address);
-                                            }
-                                        }
-                                    } else {
-                                        #[cfg(feature = "full_log")]
-                                        log::debug!("[Hell] demon with address {} was not found", address);
-                                        if tx.send(Err(Error::InvalidLocation)).is_err() {
-                                            #[cfg(feature = "full_log")]
-                                            log::trace!("[Hell] could not notify that demon with address {} was not found", address);
-                                        }
-                                    }
+                    log::trace!("[{}] entering message process loop iteration, waiting for incoming message...", self.name);
+                    // Parked messages are released once there are tokens for them
+                    let release_at = self.throttle.as_ref().and_then(Throttle::release_at);
+                    // Control plane instructions are always serviced first
+                    let wakeup = if let Ok(instruction) = priority_instructions.try_recv() {
+                        Wakeup::Instruction(Some(instruction))
+                    } else if self.biased {
+                        tokio::select! {
+                            biased;
+                            value = priority_instructions.recv() => Wakeup::Instruction(value),
+                            value = zombie_rx.recv() => Wakeup::Zombie(value),
+                            value = on_close_rx.recv() => Wakeup::Exit(value),
+                            _ = tokio::time::sleep_until(release_at.unwrap_or_else(Instant::now)), if release_at.is_some() => Wakeup::Release,
+                            Some(instruction) = delayed.next(), if !delayed.is_empty() => Wakeup::Delayed(instruction),
+                            value = instructions.recv() => Wakeup::Instruction(value)
+                        }
+                    } else {
+                        tokio::select! {
+                            value = priority_instructions.recv() => Wakeup::Instruction(value),
+                            value = instructions.recv() => Wakeup::Instruction(value),
+                            value = zombie_rx.recv() => Wakeup::Zombie(value),
+                            value = on_close_rx.recv() => Wakeup::Exit(value),
+                            _ = tokio::time::sleep_until(release_at.unwrap_or_else(Instant::now)), if release_at.is_some() => Wakeup::Release,
+                            Some(instruction) = delayed.next(), if !delayed.is_empty() => Wakeup::Delayed(instruction)
+                        }
+                    };
 
-                                    #[cfg(feature = "full_log")]
-                                    log::trace!("[Hell] leaving demon removal request");
-                                },
-                                HellInstruction::Stats{tx} => {
-                                    #[cfg(feature = "full_log")]
-                                    log::trace!("[Hell] received stats request");
-                                    if tx.send(HellStats {
-                                        spawned_demons: self.counter,
-                                        active_demons: self.demons.len(),
-                                        zombie_demons: self.zombie_counter,
-                                        successful_messages: self.successful_messages,
-                                        failed_messages: self.failed_messages,
-                                        ignition_time: self.ignition_time.clone()
-                                    }).is_err() {
-                                        #[cfg(feature = "full_log")]
-                                        log::debug!("[Hell] could not return hell stats, channel closed");
-                                    }
-                                    #[cfg(feature = "full_log")]
-                                    log::trace!("[Hell] leaving stats request");
-                                },
-                                HellInstruction::Extinguish{tx, timeout} => {
-                                    #[cfg(feature = "full_log")]
-                                    log::trace!("[Hell] extinguish message received");
-                                    break Some((tx, timeout));
-                                }
+                    let instruction = match wakeup {
+                        Wakeup::Instruction(value) => if let Some(instruction) = value {
+                            match (self.intercept(instruction, &mut delayed), self.throttle.as_mut()) {
+                                (Some(instruction), Some(throttle)) => throttle.admit(instruction),
+                                (instruction, _) => instruction
                             }
-                            #[cfg(feature = "full_log")]
-                            log::trace!("[Hell] leaving instruction handler");
                         } else {
                             #[cfg(feature = "full_log")]
-                            log::debug!("[Hell] all gates to hell have been dropped");
+                            log::debug!("[{}] all gates to hell have been dropped", self.name);
                             break None;
                         },
-                        value = zombie_rx.recv() => if value.is_some() {
-                            self.zombie_counter -= 1;
-                            #[cfg(feature = "full_log")]
-                            log::debug!("[Hell] zombie counter decrease requested, new zombie count: {}", self.zombie_counter);
+                        Wakeup::Zombie(value) => if value.is_some() {
+                            // Increments and decrements should always be balanced, but a broken counter must never take the broker down
+                            if let Some(zombie_counter) = self.zombie_counter.checked_sub(1) {
+                                self.zombie_counter = zombie_counter;
+                                #[cfg(feature = "full_log")]
+                                log::debug!("[{}] zombie counter decrease requested, new zombie count: {}", self.name, self.zombie_counter);
+                            } else {
+                                #[cfg(feature = "full_log")]
+                                log::error!("[{}] zombie counter decrease requested, but the zombie count is already zero", self.name);
+                            }
+                            None
                         } else {
                             #[cfg(feature = "full_log")]
-                            log::error!("[Hell] impossible failure, channel was closed unexpectedly");
+                            log::error!("[{}] impossible failure, channel was closed unexpectedly", self.name);
                             break None;
                         },
-                        value = on_close_rx.recv() => if let Some(location) = value {
-                            #[cfg(feature = "full_log")]
-                            log::debug!("[Hell] demon closed due to websockets lost connection");
-                            let _ = self.demons.remove(&location);
+                        Wakeup::Exit(value) => if let Some((location, generation)) = value {
+                            // A demon that was replaced before it left is no longer the one at its address
+                            if self.demons.get(&location).map(|demon_channels| demon_channels.generation) == Some(generation) {
+                                // Children of this demon are kept alive, as this is not a vanquish request
+                                for child in self.children.remove(&location).unwrap_or_default() {
+                                    self.parents.remove(&child);
+                                }
+                                if self.unregister(location).is_some() {
+                                    #[cfg(feature = "full_log")]
+                                    log::debug!("[{}] demon {} left on its own (closed connection or panic)", self.name, location);
+                                }
+                            }
+                            None
                         } else {
                             #[cfg(feature = "full_log")]
-                            log::error!("[Hell] impossible failure, on_close channel was closed unexpectedly");
+                            log::error!("[{}] impossible failure, on_close channel was closed unexpectedly", self.name);
                             break None;
+                        },
+                        Wakeup::Release => self.throttle.as_mut().and_then(Throttle::release),
+                        Wakeup::Delayed(instruction) => match self.throttle.as_mut() {
+                            Some(throttle) => throttle.admit(instruction),
+                            None => Some(instruction)
+                        }
+                    };
+
+                    if let Some(instruction) = instruction {
+                        self.broker_backlog = instructions.len() + priority_instructions.len();
+                        #[cfg(feature = "full_log")]
+                        log::debug!("[{}] entering instruction handler", self.name);
+                        if let Some(extinguish) = self.process(instruction, &zombie_tx).await {
+                            break Some(extinguish);
+                        }
+                        #[cfg(feature = "full_log")]
+                        log::trace!("[{}] leaving instruction handler", self.name);
+
+                        processed += 1;
+                        if self.yield_budget.map(|yield_budget| processed >= yield_budget).unwrap_or(false) {
+                            processed = 0;
+                            tokio::task::yield_now().await;
                         }
                     }
 
                     #[cfg(feature = "full_log")]
-                    log::trace!("[Hell] message loop iteration ended");
+                    log::trace!("[{}] message loop iteration ended", self.name);
                 }
             };
 
-            if let Some((tx, timeout)) = clean {
-                // extinguish was requested
-                let mut handles = Vec::new();
+            if let Some((tx, timeout, deadline)) = clean {
+                // extinguish was requested, demons listening get a heads-up before being asked to leave
+                let _ = self.shutdown.send(());
+                let mut handles = FuturesUnordered::new();
+                // Killswitches of the demons still leaving, in case the deadline passes
+                let mut leaving = HashMap::new();
                 for (id, demon_channels) in self.demons {
                     #[cfg(feature = "full_log")]
-                    log::trace!("[Hell] sending demon with id {} shutdown request", id);
+                    log::trace!("[{}] sending demon with id {} shutdown request", self.name, id);
 
                     // This channel will allow the zombie counter to be decreased, when necessary
                     let (demon_tx, demon_rx) = oneshot::channel();
@@ -459,59 +1538,68 @@ impl Hell {
                         None => self.timeout
                     };
 
+                    // The demon reads a closed killswitch as a forced shutdown, so it is kept open until the demon is gone
+                    let demon_killswitch = demon_channels.killswitch;
+
                     if let Some(timeout) = timeout {
                         #[cfg(feature = "full_log")]
-                        log::trace!("[Hell] killswitch trigger requested in {}ms", timeout.as_millis());
+                        log::trace!("[{}] killswitch trigger requested in {}ms", self.name, timeout.as_millis());
                         // We send the killswitch with a timeout
-                        let demon_channel_killswitch = demon_channels.killswitch;
+                        let demon_channel_killswitch = demon_killswitch.clone();
                         let _address_copy = id.clone();
+                        let _name = self.name.clone();
                         tokio::spawn(async move {
                             tokio::time::sleep(timeout).await;
                             #[cfg(feature = "full_log")]
-                            log::trace!("[Hell] sending killswitch trigger now");
+                            log::trace!("[{}] sending killswitch trigger now", _name);
                             // We ignore the killswitch send, because maybe the demon_channel is already obsolete
                             match demon_channel_killswitch.send(killswitch_tx) {
                                 Ok(_) => {
                                     #[cfg(feature = "full_log")]
-                                    log::trace!("[Hell] killswitch sent to address {}", _address_copy);
+                                    log::trace!("[{}] killswitch sent to address {}", _name, _address_copy);
                                 },
                                 Err(_) => {
                                     #[cfg(feature = "full_log")]
-                                    log::error!("[Hell] killswitch not successfully sent to address {}", _address_copy);
+                                    log::error!("[{}] killswitch not successfully sent to address {}", _name, _address_copy);
                                 }
                             }
                         });
                     } else {
                         #[cfg(feature = "full_log")]
-                        log::trace!("[Hell] no timeout was set for this vanquish call");
+                        log::trace!("[{}] no timeout was set for this vanquish call", self.name);
                     }
 
-                    if demon_channels.instructions.send(MiniHellInstruction::Shutdown(demon_tx)).is_err() {
+                    if demon_channels.instructions.send(MiniHellInstruction::Shutdown(demon_tx, ShutdownReason::Extinguished)).is_err() {
                         #[cfg(feature = "full_log")]
-                        log::trace!("[Hell] could not notify demon thread the requested demon at address {} removal", id);
+                        log::trace!("[{}] could not notify demon thread the requested demon at address {} removal", self.name, id);
                     } else {
                         #[cfg(feature = "full_log")]
-                        log::trace!("[Hell] shutdown message sent to address {}", id);
+                        log::trace!("[{}] shutdown message sent to address {}", self.name, id);
+                        leaving.insert(id, demon_killswitch.clone());
                         let _address_copy = id.clone();
+                        let _name = self.name.clone();
                         let waiter = async move {
+                            let _demon_killswitch = demon_killswitch;
                             #[cfg(feature = "full_log")]
-                            log::trace!("[Hell] entering wait selection for address {}", _address_copy);
-                            tokio::select! {
+                            log::trace!("[{}] entering wait selection for address {}", _name, _address_copy);
+                            // Without a timeout the killswitch sender is dropped right away, which must not end the wait
+                            let graceful = tokio::select! {
                                 res = demon_rx => {
                                     if res.is_ok() {
                                         #[cfg(feature = "full_log")]
-                                        log::trace!("[Hell] gracefull vanquish for address {}", _address_copy);
+                                        log::trace!("[{}] gracefull vanquish for address {}", _name, _address_copy);
                                     }
+                                    res.ok().map(|_| true)
                                 },
-                                res = killswitch => {
-                                    if res.is_ok() {
-                                        #[cfg(feature = "full_log")]
-                                        log::trace!("[Hell] killswitch vanquish requested, sending to address {}", _address_copy);
-                                    }
+                                Ok(_) = killswitch => {
+                                    #[cfg(feature = "full_log")]
+                                    log::trace!("[{}] killswitch vanquish requested, sending to address {}", _name, _address_copy);
+                                    Some(false)
                                 }
                             };
                             #[cfg(feature = "full_log")]
-                            log::trace!("[Hell] exiting wait selection for address {}", _address_copy);
+                            log::trace!("[{}] exiting wait selection for address {}", _name, _address_copy);
+                            (_address_copy, graceful)
                         };
                         
                         handles.push(tokio::spawn(waiter));
@@ -519,24 +1607,70 @@ impl Hell {
                 }
 
                 #[cfg(feature = "full_log")]
-                log::trace!("[Hell] waiting for all {} handles to complete...", handles.len());
-                join_all(handles).await;
+                log::trace!("[{}] waiting for all {} handles to complete...", self.name, handles.len());
+                let mut report = ExtinguishReport::default();
+                let expired = async {
+                    match deadline {
+                        Some(deadline) => tokio::time::sleep_until(deadline).await,
+                        None => std::future::pending().await
+                    }
+                };
+                tokio::pin!(expired);
+                loop {
+                    tokio::select! {
+                        res = handles.next() => match res {
+                            Some(Ok((address, graceful))) => {
+                                leaving.remove(&address);
+                                match graceful {
+                                    Some(true) => report.vanquished.push(address),
+                                    Some(false) => report.killswitched.push(address),
+                                    None => {
+                                        #[cfg(feature = "full_log")]
+                                        log::debug!("[{}] demon at address {} finished abnormally during shutdown", self.name, address);
+                                    }
+                                }
+                            },
+                            Some(Err(_)) => (),
+                            None => break
+                        },
+                        _ = &mut expired => {
+                            #[cfg(feature = "full_log")]
+                            log::debug!("[{}] shutdown deadline passed, abandoning {} demons", self.name, leaving.len());
+                            // Demons are forced to leave, but nobody waits for them anymore
+                            for (address, demon_killswitch) in leaving.drain() {
+                                let (killswitch_tx, _killswitch) = oneshot::channel();
+                                let _ = demon_killswitch.send(killswitch_tx);
+                                report.abandoned.push(address);
+                            }
+                            for handle in handles.iter() {
+                                handle.abort();
+                            }
+                            break;
+                        }
+                    }
+                }
+                report.vanquished.sort();
+                report.killswitched.sort();
+                report.abandoned.sort();
                 #[cfg(feature = "full_log")]
-                log::trace!("[Hell] all handles completed");
+                log::trace!("[{}] all handles completed", self.name);
 
-                if tx.send(Ok(())).is_err() {
+                if tx.send(Ok(report)).is_err() {
                     #[cfg(feature = "full_log")]
-                    log::debug!("[Hell] could not notify gate about extintion");
+                    log::debug!("[{}] could not notify gate about extintion", self.name);
                 }
             }
 
-            // We force this thing to move to this thread
-            #[cfg(not(feature = "ws"))]
-            let _ = on_close_tx.closed();
-
             #[cfg(feature = "full_log")]
-            log::info!("Broker stops \u{1f9ca}");
-        });
+            log::info!("[{}] Broker stops \u{1f9ca}", self.name);
+        };
+
+        // Task names are only available with tokio's unstable features
+        #[cfg(tokio_unstable)]
+        let jh = tokio::task::Builder::new().name(&name).spawn_on(broker, &runtime).map_err(Error::IO)?;
+        #[cfg(not(tokio_unstable))]
+        let jh = runtime.spawn(broker);
+
         Ok((gate_clone, jh))
     }
 }
\ No newline at end of file
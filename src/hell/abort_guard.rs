@@ -0,0 +1,49 @@
+use crate::Demon;
+use std::ops::{Deref, DerefMut};
+
+/// Holds a demon inside of a mini hell, calling its [aborted](crate::Demon::aborted) function if it gets dropped
+///
+/// Taking the demon out, to give it back or to vanquish it, disarms the guard. Anything else that drops the demon, like a killswitch, the loss of hell, or the runtime shutting down while the mini hell is still running, goes through the guard, except for a panic of the demon itself, after which its state cannot be trusted.
+pub(crate) struct AbortGuard<D: Demon> {
+    /// Demon, only missing once it was taken out
+    demon: Option<D>
+}
+
+impl<D: Demon> AbortGuard<D> {
+    pub(crate) fn new(demon: D) -> AbortGuard<D> {
+        AbortGuard {
+            demon: Some(demon)
+        }
+    }
+
+    /// Takes the demon out, so that it is not aborted
+    pub(crate) fn into_inner(mut self) -> D {
+        self.demon.take().expect("demon is only taken out once")
+    }
+}
+
+impl<D: Demon> Deref for AbortGuard<D> {
+    type Target = D;
+
+    fn deref(&self) -> &D {
+        self.demon.as_ref().expect("demon is only taken out once")
+    }
+}
+
+impl<D: Demon> DerefMut for AbortGuard<D> {
+    fn deref_mut(&mut self) -> &mut D {
+        self.demon.as_mut().expect("demon is only taken out once")
+    }
+}
+
+impl<D: Demon> Drop for AbortGuard<D> {
+    fn drop(&mut self) {
+        // Dropped while the handle function of the demon unwinds
+        if std::thread::panicking() {
+            return;
+        }
+        if let Some(demon) = self.demon.as_mut() {
+            demon.aborted();
+        }
+    }
+}
@@ -1,14 +1,180 @@
+use std::any::{Any, TypeId};
+use std::sync::{Arc, atomic::{AtomicUsize, Ordering}};
 use tokio::{
     sync::{
         oneshot::{Sender},
-        mpsc::{UnboundedSender}
+        mpsc::{UnboundedSender},
+        watch
     }
 };
+use crate::{Error, Demon, MessageSize, MessageOrder, demon::type_tag};
 use super::{MiniHellInstruction};
+#[cfg(feature = "record")]
+use super::Recorder;
 
 pub(crate) struct DemonChannels {
     /// Channel that receives instructions that execute one after the other
     pub(crate) instructions: UnboundedSender<MiniHellInstruction>,
     /// Killswitch, for demon forced removal
-    pub(crate) killswitch: UnboundedSender<Sender<()>>
+    pub(crate) killswitch: UnboundedSender<Sender<()>>,
+    /// Optional size limit for incoming messages
+    pub(crate) size_guard: Option<SizeGuard>,
+    /// Type of the demon itself
+    pub(crate) demon: TypeId,
+    /// Kind of mini hell that runs the demon
+    pub(crate) kind: DemonKind,
+    /// Input and output types of the demon
+    pub(crate) types: DemonTypes,
+    /// Messages delivered to the demon that were not handled yet, shared with its context
+    pub(crate) mailbox: Arc<AtomicUsize>,
+    /// Total number of messages delivered to the demon
+    pub(crate) delivered: usize,
+    /// Number of handoffs that happened at the address of the demon
+    pub(crate) generation: usize,
+    /// Unique name of the demon, if it was spawned with one
+    pub(crate) name: Option<String>,
+    /// Internal queue of demons spawned with replicas
+    pub(crate) queue: Option<Arc<QueueGauge>>,
+    /// Last messages delivered to the demon, if it was spawned with recording
+    #[cfg(feature = "record")]
+    pub(crate) history: Option<Arc<dyn Recorder>>
+}
+
+/// Kind of mini hell that runs a demon
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) enum DemonKind {
+    /// A single demon, fed only through its mailbox
+    Single,
+    /// A pool of replicas
+    Pool,
+    /// A demon fed by a reader
+    Io,
+    /// A demon fed by a websocket
+    #[cfg(feature = "ws")]
+    WebSocket
+}
+
+/// Length of the internal queue of a demon pool, and the maximum length it ever reached
+#[derive(Default)]
+pub(crate) struct QueueGauge {
+    len: AtomicUsize,
+    high_water: AtomicUsize
+}
+
+impl QueueGauge {
+    /// Updates the current length of the queue
+    pub(crate) fn set(&self, len: usize) {
+        self.len.store(len, Ordering::Relaxed);
+        self.high_water.fetch_max(len, Ordering::Relaxed);
+    }
+
+    pub(crate) fn len(&self) -> usize {
+        self.len.load(Ordering::Relaxed)
+    }
+
+    pub(crate) fn high_water(&self) -> usize {
+        self.high_water.load(Ordering::Relaxed)
+    }
+}
+
+/// Input and output types of a demon, for runtime verification
+#[derive(Clone, Copy)]
+pub(crate) struct DemonTypes {
+    input: TypeId,
+    output: TypeId,
+    /// Type tag of the demon
+    tag: &'static str,
+    /// Readable description of both types
+    name: (&'static str, &'static str)
+}
+
+impl DemonTypes {
+    pub(crate) fn of<D: Demon<Input = I, Output = O>, I: 'static, O: 'static>() -> DemonTypes {
+        DemonTypes {
+            input: TypeId::of::<I>(),
+            output: TypeId::of::<O>(),
+            tag: type_tag::<D>(),
+            name: (std::any::type_name::<I>(), std::any::type_name::<O>())
+        }
+    }
+
+    /// Type tag of the demon
+    pub(crate) fn tag(&self) -> &'static str {
+        self.tag
+    }
+
+    /// Readable names of the input and output types
+    pub(crate) fn names(&self) -> (&'static str, &'static str) {
+        self.name
+    }
+
+    /// Indicates if the demon accepts messages of the given type
+    pub(crate) fn accepts(&self, input: TypeId) -> bool {
+        self.input == input
+    }
+
+    /// Verifies that the demon types match the expected ones
+    pub(crate) fn check(&self, expected: &DemonTypes) -> Result<(), Error> {
+        if self.input == expected.input && self.output == expected.output {
+            Ok(())
+        } else {
+            Err(Error::TypeMismatch {
+                expected: expected.to_string(),
+                found: self.to_string()
+            })
+        }
+    }
+}
+
+impl std::fmt::Display for DemonTypes {
+    fn fmt(&self, formatter: &mut std::fmt::Formatter) -> Result<(), std::fmt::Error> {
+        write!(formatter, "{}<Input = {}, Output = {}>", self.tag, self.name.0, self.name.1)
+    }
+}
+
+impl DemonChannels {
+    /// Indicates if the mini hell of the demon already finished, even if hell was not notified yet
+    pub(crate) fn is_closed(&self) -> bool {
+        self.instructions.is_closed()
+    }
+}
+
+/// Treatment of a demon and of the messages that reach it, chosen when it gets spawned
+#[derive(Default)]
+pub(crate) struct Intake {
+    /// Optional unique name
+    pub(crate) name: Option<String>,
+    /// Optional size limit for incoming messages
+    pub(crate) size_guard: Option<SizeGuard>,
+    /// Optional readiness signal, messages wait until the demon marks itself as ready
+    pub(crate) warm_up: Option<Arc<watch::Sender<bool>>>,
+    /// Order in which waiting messages reach the demon
+    pub(crate) order: MessageOrder,
+    /// Optional record of the delivered messages
+    #[cfg(feature = "record")]
+    pub(crate) history: Option<Arc<dyn Recorder>>
+}
+
+/// Size limit for the messages that a demon accepts
+pub(crate) struct SizeGuard {
+    /// Maximum accepted size
+    max_message_size: usize,
+    /// Type-erased access to the size of the input
+    size_hint: fn(&(dyn Any + Send)) -> Option<usize>
+}
+
+impl SizeGuard {
+    pub(crate) fn new<I: 'static + MessageSize>(max_message_size: usize) -> SizeGuard {
+        SizeGuard {
+            max_message_size,
+            size_hint: |input| input.downcast_ref::<I>().map(MessageSize::size_hint)
+        }
+    }
+
+    /// Indicates if the message exceeds the size limit
+    ///
+    /// Messages of the wrong type are let through, as the demon will reply with the proper error.
+    pub(crate) fn rejects(&self, input: &(dyn Any + Send)) -> bool {
+        (self.size_hint)(input).map(|size| size > self.max_message_size).unwrap_or(false)
+    }
 }
\ No newline at end of file
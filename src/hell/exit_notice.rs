@@ -0,0 +1,45 @@
+use tokio::sync::mpsc::UnboundedSender;
+
+/// Notifies hell that a mini hell finished
+///
+/// The notice is sent at most once, either explicitly or when the mini hell gets dropped. As the mini hell is dropped even when its task panics, hell always learns about demons that are gone. Notices carry the generation of the demon, so that hell ignores the ones of demons that it already removed or replaced, and mini hells that were never registered send none, as they have no address.
+pub(crate) struct ExitNotice {
+    /// Address and generation of the demon, known once hell registers it
+    registration: Option<(usize, usize)>,
+    /// Channel to hell, consumed by the notification
+    on_close_tx: Option<UnboundedSender<(usize, usize)>>
+}
+
+impl ExitNotice {
+    pub(crate) fn new(on_close_tx: UnboundedSender<(usize, usize)>) -> ExitNotice {
+        ExitNotice {
+            registration: None,
+            on_close_tx: Some(on_close_tx)
+        }
+    }
+
+    /// Arms the notice, with the address that hell registered the demon at, and its generation there
+    pub(crate) fn registered(&mut self, address: usize, generation: usize) {
+        self.registration = Some((address, generation));
+    }
+
+    /// Sends the notice, if it was not sent already
+    pub(crate) fn notify(&mut self) {
+        if let (Some(registration), Some(on_close_tx)) = (self.registration, self.on_close_tx.take()) {
+            let _ = on_close_tx.send(registration);
+        }
+    }
+}
+
+impl ExitNotice {
+    /// Discards the notice, for mini hells whose address is taken over by another one
+    pub(crate) fn dismiss(&mut self) {
+        self.on_close_tx = None;
+    }
+}
+
+impl Drop for ExitNotice {
+    fn drop(&mut self) {
+        self.notify();
+    }
+}
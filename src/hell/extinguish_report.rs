@@ -0,0 +1,50 @@
+#[cfg(feature = "serde")]
+use serde::{Serialize, Deserialize};
+
+/// Outcome of the shutdown of hell
+///
+/// Every demon alive at the time of the shutdown ends up in one of the lists, sorted by address, unless its thread finished abnormally (for example, due to a panic in the [vanquished](crate::Demon::vanquished) function). Without a timeout, demons are never killswitched, so the join handle of hell only resolves once all the vanquished functions completed.
+///
+/// ```rust
+/// use apocalypse::{Hell, Demon};
+/// use std::sync::{Arc, atomic::{AtomicUsize, Ordering}};
+///
+/// struct Counted {
+///     farewells: Arc<AtomicUsize>
+/// }
+///
+/// impl Demon for Counted {
+///     type Input = ();
+///     type Output = ();
+///     async fn handle(&mut self, _message: Self::Input) -> Self::Output {}
+///     async fn vanquished(self) {
+///         tokio::time::sleep(std::time::Duration::from_millis(50)).await;
+///         self.farewells.fetch_add(1, Ordering::SeqCst);
+///     }
+/// }
+///
+/// # #[tokio::main]
+/// # async fn main() {
+/// let farewells = Arc::new(AtomicUsize::new(0));
+/// let (gate, join_handle) = Hell::new().ignite().await.unwrap();
+/// for _ in 0..5 {
+///     gate.spawn(Counted{farewells: farewells.clone()}).await.unwrap();
+/// }
+/// let report = gate.extinguish_with_timeout(None).await.unwrap();
+/// join_handle.await.unwrap();
+/// // All vanquished functions ran to completion
+/// assert_eq!(5, farewells.load(Ordering::SeqCst));
+/// assert_eq!(vec![0, 1, 2, 3, 4], report.vanquished);
+/// assert!(report.killswitched.is_empty());
+/// # }
+/// ```
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct ExtinguishReport {
+    /// Demons that left gracefully, with their vanquished function completed
+    pub vanquished: Vec<usize>,
+    /// Demons that were forced to leave by the timeout
+    pub killswitched: Vec<usize>,
+    /// Demons that did not leave before the deadline of [extinguish_by](crate::Gate::extinguish_by), which were killswitched without waiting for them
+    pub abandoned: Vec<usize>
+}
@@ -0,0 +1,20 @@
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::Duration;
+
+/// Time spent by demons inside their handle functions, across all of hell
+///
+/// Kept in nanoseconds, which takes centuries of handling to overflow.
+#[derive(Default)]
+pub(crate) struct HandleTime(AtomicU64);
+
+impl HandleTime {
+    /// Adds the duration of a single handle call
+    pub(crate) fn add(&self, elapsed: Duration) {
+        self.0.fetch_add(elapsed.as_nanos().min(u64::MAX as u128) as u64, Ordering::Relaxed);
+    }
+
+    /// Total time, summed over all the handle calls so far
+    pub(crate) fn total(&self) -> Duration {
+        Duration::from_nanos(self.0.load(Ordering::Relaxed))
+    }
+}
@@ -1,18 +1,45 @@
-use crate::{Error, hell::{DemonChannels, HellStats}};
+use crate::{Error, hell::{DemonChannels, DemonTypes, HellStats, RegistrySnapshot, ExtinguishReport, PendingMessage, InFlight}};
 use tokio::sync::{oneshot::Sender};
-use std::any::Any;
+use std::any::{Any, TypeId};
+use std::sync::Arc;
 use std::time::Duration;
+use tokio::time::Instant;
+#[cfg(feature = "record")]
+use crate::hell::Recorder;
 
 /// Actions that can be performed with the hell instance
 pub(crate) enum HellInstruction {
-    /// Requests address reservation
+    /// Requests address reservation, for a demon to be registered later
     CreateAddress {
         tx: Sender<usize>
     },
-    /// Requests demon registration
+    /// Requests demon registration at the next free address, replying with it
+    SpawnDemon {
+        demon_channels: DemonChannels,
+        /// Signal that allows the demon to start processing, carrying its address and generation
+        ready: Sender<(usize, usize)>,
+        /// Address of the parent demon, if any
+        parent: Option<usize>,
+        tx: Sender<Result<usize, Error>>
+    },
+    /// Requests demon registration at a reserved address
     RegisterDemon {
         address: usize,
         demon_channels: DemonChannels,
+        /// Signal that allows the demon to start processing, carrying its address and generation
+        ready: Sender<(usize, usize)>,
+        /// Address of the parent demon, if any
+        parent: Option<usize>,
+        tx: Sender<Result<(), Error>>
+    },
+    /// Replaces a demon by a new one, which inherits its address and its pending messages
+    Handoff {
+        address: usize,
+        demon_channels: DemonChannels,
+        /// Signal that allows the new demon to start processing, carrying its address and generation
+        ready: Sender<(usize, usize)>,
+        /// Channel where the old demon hands its pending messages over to the new one
+        inherited: Sender<Vec<PendingMessage>>,
         tx: Sender<Result<(), Error>>
     },
     /// Requests demon removal
@@ -32,15 +59,145 @@ pub(crate) enum HellInstruction {
         address: usize,
         /// Ignore flag, indicates if we should wait for the demon to reply or not
         ignore: bool,
-        input: Box<dyn Any + Send>
+        /// Trace id of the message, shared by all the messages of a chain of requests
+        trace: u64,
+        input: Box<dyn Any + Send>,
+        /// Expected types of the demon, verified before delivery if present
+        types: Option<DemonTypes>
+    },
+    /// Requests the cancellation of the messages that a demon did not handle yet
+    CancelPending {
+        address: usize,
+        tx: Sender<Result<usize, Error>>
+    },
+    /// Installs an observer of the outputs of a demon
+    Tap {
+        address: usize,
+        observer: Box<dyn Any + Send>,
+        tx: Sender<Result<(), Error>>
+    },
+    /// Replaces a single replica of a demon spawned with replicas
+    RestartReplica {
+        address: usize,
+        replica: usize,
+        demon: Box<dyn Any + Send>,
+        tx: Sender<Result<(), Error>>
+    },
+    /// Subscribes a demon to a topic
+    Subscribe {
+        address: usize,
+        topic: String,
+        tx: Sender<Result<(), Error>>
+    },
+    /// Removes the subscription of a demon to a topic
+    Unsubscribe {
+        address: usize,
+        topic: String,
+        tx: Sender<Result<(), Error>>
+    },
+    /// Delivers a copy of a message to all the subscribers of a topic that accept its type
+    Publish {
+        topic: String,
+        input: Box<dyn Any + Send>,
+        /// Copies the message for each subscriber
+        clone: fn(&(dyn Any + Send)) -> Box<dyn Any + Send>,
+        trace: u64,
+        /// Amount of subscribers that got the message
+        tx: Sender<usize>
+    },
+    /// Declares that a demon sends requests to another demon
+    DeclareDependency {
+        from: usize,
+        to: usize,
+        tx: Sender<Result<(), Error>>
+    },
+    /// Requests a cycle check over the declared dependencies
+    CheckCycles {
+        tx: Sender<Result<(), Error>>
+    },
+    /// Pings all demons, reporting which ones answered in time
+    HealthSweep {
+        timeout: Duration,
+        tx: Sender<Vec<(usize, bool)>>
+    },
+    /// Requests the addresses of all demons of a given type
+    LocationsOfType {
+        demon: TypeId,
+        tx: Sender<Vec<usize>>
+    },
+    /// Requests the type tag of the demon at an address
+    DemonType {
+        address: usize,
+        tx: Sender<Result<&'static str, Error>>
+    },
+    /// Requests the address of the demon registered under a name, which has to be of the given type
+    Named {
+        name: String,
+        demon: TypeId,
+        expected: &'static str,
+        tx: Sender<Result<usize, Error>>
+    },
+    /// Requests the generation of the demon at an address
+    Generation {
+        address: usize,
+        tx: Sender<Result<usize, Error>>
+    },
+    /// Requests the message history of a demon spawned with recording
+    #[cfg(feature = "record")]
+    History {
+        address: usize,
+        tx: Sender<Result<Arc<dyn Recorder>, Error>>
+    },
+    /// Replaces the default timeout for vanquish and extinguish calls
+    SetTimeout {
+        timeout: Option<Duration>,
+        tx: Sender<()>
     },
     /// Requests the stats structure
     Stats {
         tx: Sender<HellStats>
     },
+    /// Requests the counter of messages in flight, once all previous instructions were processed
+    InFlight {
+        tx: Sender<Arc<InFlight>>
+    },
+    /// Requests a snapshot of all the demons
+    DumpRegistry {
+        tx: Sender<RegistrySnapshot>
+    },
     /// Asks for termination
     Extinguish {
-        tx: Sender<Result<(), Error>>,
-        timeout: Option<Option<Duration>>
+        tx: Sender<Result<ExtinguishReport, Error>>,
+        timeout: Option<Option<Duration>>,
+        /// Overall deadline, after which demons that did not leave yet are abandoned
+        deadline: Option<Instant>
+    }
+}
+
+/// Amount of instructions processed by the broker, by type
+#[derive(Default)]
+pub(crate) struct InstructionCounts {
+    pub(crate) message: usize,
+    pub(crate) create_address: usize,
+    pub(crate) spawn_demon: usize,
+    pub(crate) register_demon: usize,
+    pub(crate) remove_demon: usize,
+    pub(crate) stats: usize,
+    pub(crate) other: usize
+}
+
+impl InstructionCounts {
+    /// Counts an instruction about to be processed
+    pub(crate) fn count(&mut self, instruction: &HellInstruction) {
+        let counter = match instruction {
+            HellInstruction::Message{..} => &mut self.message,
+            HellInstruction::CreateAddress{..} => &mut self.create_address,
+            HellInstruction::SpawnDemon{..} => &mut self.spawn_demon,
+            HellInstruction::RegisterDemon{..} => &mut self.register_demon,
+            HellInstruction::RemoveDemon{..} => &mut self.remove_demon,
+            HellInstruction::Stats{..} => &mut self.stats,
+            _ => &mut self.other
+        };
+        *counter += 1;
     }
 }
\ No newline at end of file
@@ -1,11 +1,15 @@
 use chrono::{DateTime, Utc};
+use std::collections::HashMap;
+use std::time::Duration;
 #[cfg(feature = "serde")]
 use serde::{Serialize, Deserialize};
 
 /// Basic statistics structure
-#[derive(Clone, Copy, Debug)]
+#[derive(Clone, Debug)]
 #[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub struct HellStats {
+    /// Name of the hell instance
+    pub name: String,
     /// Amount of spawned demons through the lifetime of this hell instance
     pub spawned_demons: usize,
     /// Amount of active demons at the time of the call
@@ -16,6 +20,34 @@ pub struct HellStats {
     pub successful_messages: usize,
     /// Total number of messages whose deivery failed
     pub failed_messages: usize,
+    /// Total number of messages rejected for exceeding the maximum message size of a demon
+    pub rejected_messages: usize,
+    /// Amount of active demons, by their [type tag](crate::Demon::TYPE_TAG)
+    pub demons_by_tag: HashMap<String, usize>,
+    /// Amount of instructions waiting to be processed by the broker, a large value means that the broker is saturated
+    pub broker_backlog: usize,
+    /// Amount of messages waiting in the internal queues of demons spawned with replicas, because all replicas were busy
+    pub queued_messages: usize,
+    /// Longest internal queue that a single demon spawned with replicas ever reached
+    pub queued_messages_high_water: usize,
+    /// Message instructions processed by the broker, one per message sent, whether it could be delivered or not
+    pub message_instructions: usize,
+    /// Address reservations processed by the broker, one per demon spawned at a reserved address, like the ones of a [WiringBuilder](crate::WiringBuilder)
+    pub create_address_instructions: usize,
+    /// Demon spawns processed by the broker, each one picking an address and registering the demon at it
+    pub spawn_demon_instructions: usize,
+    /// Demon registrations at a reserved address processed by the broker
+    pub register_demon_instructions: usize,
+    /// Demon removals processed by the broker, one per vanquish
+    pub remove_demon_instructions: usize,
+    /// Stats requests processed by the broker, including the one that produced these stats
+    pub stats_instructions: usize,
+    /// Any other instructions processed by the broker, like subscriptions or pings
+    pub other_instructions: usize,
+    /// Time spent by demons inside their handle functions, through the lifetime of this hell instance
+    ///
+    /// Every mini hell measures each call to the handle function of its demon, and adds it to a counter shared by all of hell as soon as the call finishes, so the total is up to date whenever stats are requested, and includes demons that already left. Calls that panic, or that the killswitch cuts short, are not counted, and neither are [follow-ups](crate::Demon::handle_with_followup). Replicas of a pool handle messages in parallel, so the total can grow faster than time passes. Divided by the uptime and the amount of demons, it tells how busy demons are on average.
+    pub total_handle_time: Duration,
     /// Time of ignition of the hell instance
     pub ignition_time: DateTime<Utc>
 }
\ No newline at end of file
@@ -0,0 +1,50 @@
+use std::any::Any;
+use std::collections::VecDeque;
+use std::sync::Mutex;
+
+/// Type-erased access to the [History](History) of a demon, for the broker
+pub(crate) trait Recorder: Send + Sync {
+    /// Records a message about to be delivered, ignoring messages of the wrong type
+    fn record(&self, input: &(dyn Any + Send));
+
+    fn as_any(&self) -> &dyn Any;
+}
+
+/// Last messages delivered to a demon, in delivery order
+pub(crate) struct History<I> {
+    /// Maximum amount of messages kept, older ones are discarded
+    capacity: usize,
+    /// Recorded messages
+    inputs: Mutex<VecDeque<I>>
+}
+
+impl<I: Clone> History<I> {
+    /// Creates an empty history, that keeps at least one message
+    pub(crate) fn new(capacity: usize) -> History<I> {
+        History {
+            capacity: capacity.max(1),
+            inputs: Mutex::new(VecDeque::new())
+        }
+    }
+
+    /// Copy of the recorded messages, oldest first
+    pub(crate) fn inputs(&self) -> Vec<I> {
+        self.inputs.lock().expect("history is never poisoned").iter().cloned().collect()
+    }
+}
+
+impl<I: 'static + Clone + Send> Recorder for History<I> {
+    fn record(&self, input: &(dyn Any + Send)) {
+        if let Some(input) = input.downcast_ref::<I>() {
+            let mut inputs = self.inputs.lock().expect("history is never poisoned");
+            if inputs.len() == self.capacity {
+                inputs.pop_front();
+            }
+            inputs.push_back(input.clone());
+        }
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+}
@@ -0,0 +1,44 @@
+use std::sync::{Arc, atomic::{AtomicUsize, Ordering}};
+use tokio::sync::Notify;
+
+/// Messages delivered to demons whose handling did not finish yet, across all of hell
+#[derive(Default)]
+pub(crate) struct InFlight {
+    count: AtomicUsize,
+    idle: Notify
+}
+
+impl InFlight {
+    /// Counts one more message, until the returned guard gets dropped
+    pub(crate) fn track(self: &Arc<Self>) -> InFlightGuard {
+        self.count.fetch_add(1, Ordering::SeqCst);
+        InFlightGuard(self.clone())
+    }
+
+    /// Waits until no message is in flight
+    pub(crate) async fn idle(&self) {
+        loop {
+            // Registered before checking the count, so that no notification gets lost
+            let notified = self.idle.notified();
+            tokio::pin!(notified);
+            notified.as_mut().enable();
+            if self.count.load(Ordering::SeqCst) == 0 {
+                return;
+            }
+            notified.await;
+        }
+    }
+}
+
+/// Marks a message as in flight while alive
+///
+/// The guard travels with the reply channel of the message, so it gets dropped once the message is answered, rejected, cancelled, or lost together with its demon.
+pub(crate) struct InFlightGuard(Arc<InFlight>);
+
+impl Drop for InFlightGuard {
+    fn drop(&mut self) {
+        if self.0.count.fetch_sub(1, Ordering::SeqCst) == 1 {
+            self.0.idle.notify_waiters();
+        }
+    }
+}
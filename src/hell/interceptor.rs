@@ -0,0 +1,28 @@
+use std::time::Duration;
+
+/// Function that sees every message before the broker routes it, see [intercept](crate::HellBuilder::intercept)
+pub(crate) type Interceptor = Box<dyn Fn(&BrokerContext) -> InterceptDecision + Send + Sync>;
+
+/// Message about to be routed by the broker, as seen by an [interceptor](crate::HellBuilder::intercept)
+#[derive(Clone, Debug)]
+pub struct BrokerContext {
+    /// Address of the demon that the message is for
+    pub address: usize,
+    /// [Type tag](crate::Demon::TYPE_TAG) of the demon that the message is for, if there is a demon at the address
+    pub tag: Option<&'static str>,
+    /// Trace id of the message, see [current_trace](crate::current_trace)
+    pub trace: u64,
+    /// Whether the sender is not waiting for the reply
+    pub ignore: bool
+}
+
+/// What the broker does with an intercepted message
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum InterceptDecision {
+    /// The message is routed as usual
+    Allow,
+    /// The message is dropped, and the sender gets `Error::Denied`
+    Deny,
+    /// The message is routed once the duration elapses, without being intercepted again
+    Delay(Duration)
+}
@@ -1,54 +1,142 @@
-use crate::{Error, Demon, Location, hell::{MiniHellInstruction, DemonChannels}};
-use std::any::Any;
-use tokio::sync::{oneshot::{Sender}, mpsc::{self, UnboundedReceiver}};
+use crate::{Error, Demon, DemonContext, MessageOrder, ShutdownReason, trace, hell::{AbortGuard, MiniHellInstruction, PendingMessage, cancel_pending, drain_pending, reject_pending, Taps, DemonChannels, DemonKind, DemonTypes, ExitNotice}};
+use std::any::{Any, TypeId};
+use tokio::{sync::{oneshot::{Sender, Receiver}, mpsc::{self, UnboundedReceiver, UnboundedSender}}, time::Instant};
 
 /// Structure that holds a single demon, and asynchronously deals with the messages that this demon receives.
-pub(crate) struct MiniHell<D> {
+pub(crate) struct MiniHell<D: Demon> {
     /// Demon contained inside this minihell instance
-    demon: D,
-    /// Address of this demon
-    location: Location<D>,
+    demon: AbortGuard<D>,
+    /// Context of this demon
+    context: DemonContext<D>,
     /// Channel where instructions are sent to the minihell
     instructions: UnboundedReceiver<MiniHellInstruction>,
     /// Killswitch endpoint
-    killswitch: UnboundedReceiver<Sender<()>>
+    killswitch: UnboundedReceiver<Sender<()>>,
+    /// Confirmation of the registration of this demon in hell, with its address and generation
+    registered: Receiver<(usize, usize)>,
+    /// Channel to give the demon back if the registration fails
+    give_back: Option<Sender<D>>,
+    /// Messages inherited from the demon that this one replaces, handled before any other
+    inherited: Option<Receiver<Vec<PendingMessage>>>,
+    /// Order in which waiting messages reach the demon
+    order: MessageOrder,
+    /// Notification for hell once this mini hell finishes
+    exit_notice: ExitNotice
 }
 
 impl<I: 'static + Send, O: 'static + Send, D: 'static + Demon<Input = I, Output = O>> MiniHell<D> {
-    pub fn spawn(demon: D, location: Location<D>) -> DemonChannels {
+    pub fn spawn(demon: D, context: DemonContext<D>, registered: Receiver<(usize, usize)>, give_back: Option<Sender<D>>, inherited: Option<Receiver<Vec<PendingMessage>>>, order: MessageOrder, on_close_tx: UnboundedSender<(usize, usize)>) -> DemonChannels {
+        let (mini_hell, demon_channels) = MiniHell::new(demon, context, registered, give_back, inherited, order, on_close_tx);
+        tokio::spawn(async move {
+            mini_hell.ignite().await;
+        });
+        demon_channels
+    }
+
+    /// Same as [spawn](MiniHell::spawn), but the mini hell runs on its own single threaded runtime, in a dedicated thread
+    ///
+    /// The thread, and the runtime with any task spawned in it, finish together with the mini hell.
+    pub fn spawn_pinned(demon: D, context: DemonContext<D>, registered: Receiver<(usize, usize)>, on_close_tx: UnboundedSender<(usize, usize)>) -> Result<DemonChannels, Error> {
+        let runtime = tokio::runtime::Builder::new_current_thread().enable_all().build().map_err(Error::IO)?;
+        let name = format!("apocalypse-d-{}", context.location.address);
+        let (mini_hell, demon_channels) = MiniHell::new(demon, context, registered, None, None, MessageOrder::Fifo, on_close_tx);
+        std::thread::Builder::new().name(name).spawn(move || {
+            runtime.block_on(mini_hell.ignite());
+        }).map_err(Error::IO)?;
+        Ok(demon_channels)
+    }
+
+    fn new(demon: D, context: DemonContext<D>, registered: Receiver<(usize, usize)>, give_back: Option<Sender<D>>, inherited: Option<Receiver<Vec<PendingMessage>>>, order: MessageOrder, on_close_tx: UnboundedSender<(usize, usize)>) -> (MiniHell<D>, DemonChannels) {
         // Main instruction channel
         let (mailbox, instructions) = mpsc::unbounded_channel();
         // Killswitch channel
         let (killswitch_tx, killswitch) = mpsc::unbounded_channel();
+        // Backlog counter, shared with the context
+        let backlog = context.mailbox.clone();
         let mini_hell = MiniHell {
-            demon,
-            location,
+            exit_notice: ExitNotice::new(on_close_tx),
+            demon: AbortGuard::new(demon),
+            context,
             instructions,
-            killswitch
+            killswitch,
+            registered,
+            give_back,
+            inherited,
+            order
         };
-        tokio::spawn(async move {
-            mini_hell.ignite().await;
-        });
 
-        DemonChannels {
+        (mini_hell, DemonChannels {
             instructions: mailbox,
-            killswitch: killswitch_tx
-        }
+            killswitch: killswitch_tx,
+            size_guard: None,
+            demon: TypeId::of::<D>(),
+            kind: DemonKind::Single,
+            types: DemonTypes::of::<D, I, O>(),
+            mailbox: backlog,
+            delivered: 0,
+            generation: 0,
+            name: None,
+            queue: None,
+            #[cfg(feature = "record")]
+            history: None
+        })
     }
 
     async fn ignite(mut self) {
         #[cfg(feature = "full_log")]
         log::trace!("[{}] demon thread starting", self.demon.id());
-        let (mailbox, mut messages) = mpsc::unbounded_channel::<(Sender<Result<Box<dyn Any + Send>, Error>>, Box<dyn Any + Send>)>();
+
+        // Nothing happens until hell confirms that this demon was registered, and tells its address and generation
+        let (address, generation) = match self.registered.await {
+            Ok(registration) => registration,
+            Err(_) => {
+                #[cfg(feature = "full_log")]
+                log::debug!("[{}] demon registration failed, leaving", self.demon.id());
+                // The demon was never spawned, so it is not aborted either
+                let demon = self.demon.into_inner();
+                if let Some(give_back) = self.give_back {
+                    let _ = give_back.send(demon);
+                }
+                return;
+            }
+        };
+        self.context.location.address = address;
+        self.context.generation = generation;
+        self.exit_notice.registered(address, generation);
+
+        let (mailbox, mut messages) = mpsc::unbounded_channel::<PendingMessage>();
+        // Observers of the outputs
+        let mut taps = Taps::<O>::new();
+
+        // Messages of the replaced demon go first, if it is still around to hand them over
+        if let Some(inherited) = self.inherited.take() {
+            for pending_message in inherited.await.unwrap_or_default() {
+                self.context.put_in_mailbox();
+                if mailbox.send(pending_message).is_err() {
+                    #[cfg(feature = "full_log")]
+                    log::warn!("[{}] impossible error happened, could not send inherited message to itself!", self.demon.id());
+                }
+            }
+        }
 
         // We call the spawned function from this demon
-        let other_loc = self.location.clone();
+        let context = self.context.clone();
         #[cfg(feature = "full_log")]
         log::trace!("[{}] calling spawn function", self.demon.id());
-        self.demon.spawned(other_loc).await;
+        self.demon.spawned_with_context(context).await;
         #[cfg(feature = "full_log")]
         log::trace!("[{}] spawn function called", self.demon.id());
 
+        // Demons spawned with a warm-up only take messages once they are ready
+        let mut warm_up = self.context.ready.as_ref().map(|ready| ready.subscribe());
+        let mut warm = warm_up.is_none();
+
+        // Demons that take the newest message first keep their backlog in a stack
+        let lifo = self.order == MessageOrder::Lifo;
+        let mut stack = Vec::new();
+
+        // Unless something else happens, the demon leaves because it was asked to
+        let mut reason = ShutdownReason::Vanquished;
         let (mut vanquish_mailbox, killswitched) = loop {
             tokio::select! {
                 res = self.killswitch.recv() => if let Some(vanquish_mailbox) = res {
@@ -60,36 +148,76 @@ impl<I: 'static + Send, O: 'static + Send, D: 'static + Demon<Input = I, Output
                     log::trace!("[{}] all incoming killswitch channels closed (impossible)", self.demon.id());
                     break (None, true);
                 },
-                res = messages.recv() => if let Some((tx, input)) = res {
+                _ = async {
+                    if let Some(warm_up) = warm_up.as_mut() {
+                        // The context keeps the sender alive
+                        let _ = warm_up.wait_for(|ready| *ready).await;
+                    }
+                }, if !warm => {
+                    #[cfg(feature = "full_log")]
+                    log::trace!("[{}] demon is ready, taking messages", self.demon.id());
+                    warm = true;
+                },
+                // With a LIFO order, instructions are taken first, so that the newest message is in the stack
+                res = next_message(&mut messages, &mut stack, lifo), if warm && (!lifo || self.instructions.is_empty()) => if let Some((tx, trace, input)) = res {
+                    self.context.take_from_mailbox();
                     if let Ok(input) = input.downcast::<I>() {
                         #[cfg(feature = "full_log")]
-                        log::trace!("[{}] calling handle function", self.demon.id());
-                        let output = tokio::select!{
-                            output = self.demon.handle(*input) => {
+                        log::trace!("[{}] calling handle function, trace {}", self.demon.id(), trace);
+                        let started = Instant::now();
+                        let (output, followup) = tokio::select!{
+                            handled = trace::scope(trace, trace::handling(self.context.location.address, self.demon.handle_with_followup(*input))) => {
                                 #[cfg(feature = "full_log")]
                                 log::trace!("[{}] handle function called", self.demon.id());
-                                output
+                                handled
                             },
                             res = self.killswitch.recv() => if let Some(vanquish_mailbox) = res {
                                 #[cfg(feature = "full_log")]
                                 log::trace!("[{}] killswitch signal received, aborting current handle execution!", self.demon.id());
+                                // The message might be half handled, so the sender cannot assume either way
+                                let _ = tx.send(Err(Error::DemonCommunication));
                                 break (Some(vanquish_mailbox), true);
                             } else {
                                 #[cfg(feature = "full_log")]
                                 log::trace!("[{}] all incoming killswitch channels closed (impossible), aborting current handle execution", self.demon.id());
+                                let _ = tx.send(Err(Error::DemonCommunication));
                                 break (None, true);
                             }
                         };
+                        let elapsed = started.elapsed();
+                        self.context.handle_time.add(elapsed);
                         #[cfg(feature = "full_log")]
                         log::trace!("[{}] demon processed message!", self.demon.id());
-                        if tx.send(Ok(Box::new(output))).is_err() {
+                        // The follow-up keeps working while the demon takes its next message
+                        if let Some(followup) = followup {
+                            self.context.spawn_linked(trace::scope(trace, followup));
+                        }
+                        taps.observe(&output);
+                        let fatal = D::is_fatal(&output);
+                        // Declined messages get no reply
+                        let reply = if D::is_declined(&output) {
+                            Err(Error::Declined)
+                        } else {
+                            Ok(Box::new(output) as Box<dyn Any + Send>)
+                        };
+                        if tx.send_handled(reply, elapsed).is_err() {
                             #[cfg(feature = "full_log")]
                             log::error!("[{}] demon processed message could not be sent back", self.demon.id());   
                         }
+                        if fatal {
+                            #[cfg(feature = "full_log")]
+                            log::trace!("[{}] fatal reply, demon leaves on its own", self.demon.id());
+                            reason = ShutdownReason::Fatal;
+                            let (rejected, shutdown) = reject_pending(&mut messages, &mut self.instructions);
+                            self.context.take_many_from_mailbox(rejected);
+                            break (shutdown, false);
+                        }
                     } else {
-                        if tx.send(Err(Error::WrongType)).is_err() {
+                        #[cfg(feature = "full_log")]
+                        log::error!("[{}] demon received wrong message type, expected {}", self.demon.id(), std::any::type_name::<I>());
+                        if tx.send(Err(Error::WrongInputType{expected: std::any::type_name::<I>()})).is_err() {
                             #[cfg(feature = "full_log")]
-                            log::error!("[{}] somehow, demon received wrong message type", self.demon.id());   
+                            log::error!("[{}] wrong message type error could not be sent back", self.demon.id());
                         }
                     }
                 } else {
@@ -99,32 +227,75 @@ impl<I: 'static + Send, O: 'static + Send, D: 'static + Demon<Input = I, Output
                 },
                 res = self.instructions.recv() => match res {
                     Some(instruction) => match instruction {
-                        MiniHellInstruction::Shutdown(vanquish_mailbox) => {
+                        MiniHellInstruction::Shutdown(vanquish_mailbox, shutdown_reason) => {
+                            reason = shutdown_reason;
                             #[cfg(feature = "full_log")]
                             log::trace!("[{}] shutdown signal received", self.demon.id());
                             break (Some(vanquish_mailbox), false);
                         },
-                        MiniHellInstruction::Message(result_mailbox, message) => {
+                        MiniHellInstruction::Message(result_mailbox, trace, message) => {
                             #[cfg(feature = "full_log")]
                             log::trace!("[{}] received instruction, adding to the processing queue", self.demon.id());
-                            if mailbox.send((result_mailbox, message)).is_err() {
+                            if mailbox.send((result_mailbox, trace, message)).is_err() {
                                 #[cfg(feature = "full_log")]
                                 log::warn!("[{}] impossible error happened, could not send back message to itself!", self.demon.id());
                             }
+                        },
+                        MiniHellInstruction::CancelPending(tx) => {
+                            let cancelled = cancel_pending(&mut messages) + reject_stacked(&mut stack, || Error::Cancelled);
+                            self.context.take_many_from_mailbox(cancelled);
+                            #[cfg(feature = "full_log")]
+                            log::trace!("[{}] cancelled {} pending messages", self.demon.id(), cancelled);
+                            let _ = tx.send(cancelled);
+                        },
+                        MiniHellInstruction::Ping(tx) => {
+                            #[cfg(feature = "full_log")]
+                            log::trace!("[{}] ping received", self.demon.id());
+                            let _ = tx.send(());
+                        },
+                        MiniHellInstruction::Handoff(tx) => {
+                            // The stack only holds messages older than the ones in the queue
+                            let mut drained = std::mem::take(&mut stack);
+                            drained.extend(drain_pending(&mut messages));
+                            self.context.take_many_from_mailbox(drained.len());
+                            #[cfg(feature = "full_log")]
+                            log::trace!("[{}] handing {} pending messages over", self.demon.id(), drained.len());
+                            // The address now belongs to the replacement
+                            reason = ShutdownReason::Replaced;
+                            self.exit_notice.dismiss();
+                            let _ = tx.send(drained);
+                            break (None, false);
+                        },
+                        MiniHellInstruction::Tap(observer, tx) => {
+                            #[cfg(feature = "full_log")]
+                            log::trace!("[{}] tap received", self.demon.id());
+                            let _ = tx.send(taps.install(observer));
+                        },
+                        // Without replicas, there is nothing to restart
+                        MiniHellInstruction::RestartReplica(_, _, tx) => {
+                            let _ = tx.send(Err(Error::InvalidLocation));
                         }
                     },
                     None => {
+                        // Only happens if hell itself is gone, so there is no one left to wait for the vanquished function
                         #[cfg(feature = "full_log")]
-                        log::info!("[{}] all channels to this demon are now closed", self.demon.id());
-                        break (None, false);
+                        log::info!("[{}] all channels to this demon are now closed, aborting", self.demon.id());
+                        break (None, true);
                     }
                 }
             }
         };
 
+        // Messages still waiting never reach the demon
+        let (rejected, _) = reject_pending(&mut messages, &mut self.instructions);
+        self.context.take_many_from_mailbox(rejected + reject_stacked(&mut stack, || Error::InvalidLocation));
+
         #[cfg(feature = "full_log")]
         let demon_id = self.demon.id();
 
+        // Tasks linked to this demon cannot outlive it
+        self.context.abort_linked();
+
         // We call the vanquished function from this demon
         #[cfg(feature = "full_log")]
         log::trace!("[{}] calling vanquish function", demon_id);
@@ -138,7 +309,7 @@ impl<I: 'static + Send, O: 'static + Send, D: 'static + Demon<Input = I, Output
                     #[cfg(feature = "full_log")]
                     log::trace!("[{}] all incoming killswitch channels closed (impossible)", demon_id);
                 },
-                _ = self.demon.vanquished() => {
+                _ = self.demon.into_inner().vanquished_with_reason(self.context.clone(), reason) => {
                     #[cfg(feature = "full_log")]
                     log::trace!("[{}] vanquish function called", demon_id);
                 }
@@ -158,4 +329,28 @@ impl<I: 'static + Send, O: 'static + Send, D: 'static + Demon<Input = I, Output
             log::trace!("[{}] it seems that there are no channels to anounce departure", demon_id);
         }
     }
-}
\ No newline at end of file
+}
+/// Next message for the demon, the newest one if it takes them in LIFO order
+///
+/// Messages are moved to the stack before any of them is taken, so no message is lost if the future is dropped.
+async fn next_message(messages: &mut UnboundedReceiver<PendingMessage>, stack: &mut Vec<PendingMessage>, lifo: bool) -> Option<PendingMessage> {
+    if lifo {
+        while let Ok(pending_message) = messages.try_recv() {
+            stack.push(pending_message);
+        }
+        if let Some(pending_message) = stack.pop() {
+            return Some(pending_message);
+        }
+    }
+    messages.recv().await
+}
+
+/// Replies an error to all the messages waiting in the stack of a LIFO demon, returning how many there were
+fn reject_stacked(stack: &mut Vec<PendingMessage>, error: fn() -> Error) -> usize {
+    let rejected = stack.len();
+    for (tx, _trace, _input) in stack.drain(..) {
+        // The caller might not be waiting for the reply anymore
+        let _ = tx.send(Err(error()));
+    }
+    rejected
+}
@@ -1,11 +1,143 @@
 use std::any::Any;
-use crate::{Error};
-use tokio::sync::oneshot::Sender;
+use std::time::Duration;
+use crate::{Error, ShutdownReason, hell::InFlightGuard};
+use tokio::sync::{oneshot::Sender, mpsc::UnboundedReceiver};
+
+/// Message waiting to be handled, together with its reply channel and its trace id
+pub(crate) type PendingMessage = (ReplyTo, u64, Box<dyn Any + Send>);
+
+/// Channel where the reply to a message goes, missing for messages whose reply is ignored
+///
+/// Ignored messages skip both the channel and the task that would wait on it, which makes them two allocations cheaper. The message counts as in flight until the reply is sent, or dropped.
+pub(crate) struct ReplyTo {
+    tx: Option<Sender<Result<Box<dyn Any + Send>, Error>>>,
+    /// Whether the reply carries how long the demon took to handle the message
+    timed: bool,
+    /// Only held, to be dropped together with the reply channel
+    _in_flight: InFlightGuard
+}
+
+impl ReplyTo {
+    pub(crate) fn new(tx: Sender<Result<Box<dyn Any + Send>, Error>>, in_flight: InFlightGuard) -> ReplyTo {
+        ReplyTo {
+            tx: Some(tx),
+            timed: false,
+            _in_flight: in_flight
+        }
+    }
+
+    pub(crate) fn ignored(in_flight: InFlightGuard) -> ReplyTo {
+        ReplyTo {
+            tx: None,
+            timed: false,
+            _in_flight: in_flight
+        }
+    }
+
+    /// Sends the reply, which never fails for ignored messages
+    pub(crate) fn send(self, reply: Result<Box<dyn Any + Send>, Error>) -> Result<(), Result<Box<dyn Any + Send>, Error>> {
+        match self.tx {
+            Some(tx) => tx.send(reply),
+            None => Ok(())
+        }
+    }
+
+    /// Makes the reply carry how long the demon took to handle the message, see [Timed](Timed)
+    pub(crate) fn timed(mut self, timed: bool) -> ReplyTo {
+        self.timed = timed;
+        self
+    }
+
+    /// Sends the reply of a handled message, together with the handling time if the reply is timed
+    pub(crate) fn send_handled(self, reply: Result<Box<dyn Any + Send>, Error>, handled: Duration) -> Result<(), Result<Box<dyn Any + Send>, Error>> {
+        let reply = match reply {
+            Ok(output) if self.timed => Ok(Box::new((output, handled)) as Box<dyn Any + Send>),
+            reply => reply
+        };
+        self.send(reply)
+    }
+}
+
+/// Input of a message whose reply includes how long the demon took to handle it
+///
+/// The broker takes the input out before delivering it, so demons never see the wrapper, and the reply becomes a `(Box<dyn Any + Send>, Duration)` tuple.
+pub(crate) struct Timed(pub(crate) Box<dyn Any + Send>);
+
+impl Timed {
+    /// Takes the input out of the wrapper, if there is one, indicating whether there was
+    pub(crate) fn open(input: Box<dyn Any + Send>) -> (Box<dyn Any + Send>, bool) {
+        match input.downcast::<Timed>() {
+            Ok(timed) => (timed.0, true),
+            Err(input) => (input, false)
+        }
+    }
+}
 
 /// Message passing for the thread runner of each demon
 pub(crate) enum MiniHellInstruction {
     /// Requests a graceful shutdown
-    Shutdown(Sender<()>),
-    /// Delivers a message to the demon
-    Message(Sender<Result<Box<dyn Any + Send>, Error>>, Box<dyn Any + Send>)
-}
\ No newline at end of file
+    Shutdown(Sender<()>, ShutdownReason),
+    /// Delivers a message to the demon, with its trace id
+    Message(ReplyTo, u64, Box<dyn Any + Send>),
+    /// Drops all the messages that were not handled yet, replying with the amount of them
+    CancelPending(Sender<usize>),
+    /// Liveness check, answered as soon as the demon is not busy
+    Ping(Sender<()>),
+    /// Hands all the messages that were not handled yet over to a replacement demon, and shuts down
+    Handoff(Sender<Vec<PendingMessage>>),
+    /// Installs a type erased observer of the outputs of the demon
+    Tap(Box<dyn Any + Send>, Sender<Result<(), Error>>),
+    /// Replaces the replica with the given index by a type erased demon, once it is not busy
+    RestartReplica(usize, Box<dyn Any + Send>, Sender<Result<(), Error>>)
+}
+
+/// Replies `Error::Cancelled` to all the messages waiting in the queue, returning how many there were
+pub(crate) fn cancel_pending(messages: &mut UnboundedReceiver<PendingMessage>) -> usize {
+    let mut cancelled = 0;
+    while let Ok((tx, _trace, _input)) = messages.try_recv() {
+        // The caller might not be waiting for the reply anymore
+        let _ = tx.send(Err(Error::Cancelled));
+        cancelled += 1;
+    }
+    cancelled
+}
+
+/// Replies `Error::InvalidLocation` to all the messages waiting for a demon that is leaving
+///
+/// No more instructions are accepted afterwards. Returns how many messages were rejected, and the shutdown request among the pending instructions, if any, which still expects an answer.
+pub(crate) fn reject_pending(messages: &mut UnboundedReceiver<PendingMessage>, instructions: &mut UnboundedReceiver<MiniHellInstruction>) -> (usize, Option<Sender<()>>) {
+    instructions.close();
+    let mut rejected = 0;
+    while let Ok((tx, _trace, _input)) = messages.try_recv() {
+        let _ = tx.send(Err(Error::InvalidLocation));
+        rejected += 1;
+    }
+    let mut shutdown = None;
+    while let Ok(instruction) = instructions.try_recv() {
+        match instruction {
+            MiniHellInstruction::Message(tx, _trace, _input) => {
+                let _ = tx.send(Err(Error::InvalidLocation));
+                rejected += 1;
+            },
+            MiniHellInstruction::Shutdown(tx, _reason) => shutdown = Some(tx),
+            MiniHellInstruction::CancelPending(tx) => {
+                let _ = tx.send(0);
+            },
+            MiniHellInstruction::Tap(_observer, tx) | MiniHellInstruction::RestartReplica(_, _observer, tx) => {
+                let _ = tx.send(Err(Error::InvalidLocation));
+            },
+            // The replacement starts without inherited messages
+            MiniHellInstruction::Ping(_) | MiniHellInstruction::Handoff(_) => ()
+        }
+    }
+    (rejected, shutdown)
+}
+
+/// Takes all the messages waiting in the queue, without replying to them
+pub(crate) fn drain_pending(messages: &mut UnboundedReceiver<PendingMessage>) -> Vec<PendingMessage> {
+    let mut drained = Vec::new();
+    while let Ok(pending_message) = messages.try_recv() {
+        drained.push(pending_message);
+    }
+    drained
+}
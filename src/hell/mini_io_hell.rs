@@ -0,0 +1,293 @@
+use crate::{Error, Demon, DemonContext, ShutdownReason, IoThread, trace, hell::{AbortGuard, MiniHellInstruction, PendingMessage, cancel_pending, reject_pending, Taps, DemonChannels, DemonKind, DemonTypes, ExitNotice}};
+use std::any::{Any, TypeId};
+use tokio::{
+    io::{AsyncRead, AsyncReadExt},
+    sync::{oneshot::{Sender, Receiver}, mpsc::{self, UnboundedReceiver, UnboundedSender}},
+    time::Instant
+};
+
+/// Size of the buffer for each read from the stream
+const READ_BUFFER_SIZE: usize = 8 * 1024;
+
+/// Structure that holds a single demon, and asynchronously deals with both the messages that this demon receives and the bytes of its stream.
+pub(crate) struct MiniIoHell<D: Demon, R> {
+    /// Demon contained inside this minihell instance
+    demon: AbortGuard<D>,
+    /// Context of this demon
+    context: DemonContext<D>,
+    /// Channel where instructions are sent to the minihell
+    instructions: UnboundedReceiver<MiniHellInstruction>,
+    /// Killswitch endpoint
+    killswitch: UnboundedReceiver<Sender<()>>,
+    /// Confirmation of the registration of this demon in hell, with its address and generation
+    registered: Receiver<(usize, usize)>,
+    /// Notification for hell once this mini hell finishes
+    exit_notice: ExitNotice,
+    /// Stream where the bytes arrive
+    reader: R
+}
+
+impl<I: 'static + Send, O: 'static + Send, D: 'static + Demon<Input = I, Output = O> + IoThread, R: 'static + AsyncRead + Unpin + Send> MiniIoHell<D, R> {
+    pub(crate) fn spawn(demon: D, context: DemonContext<D>, registered: Receiver<(usize, usize)>, on_close_tx: UnboundedSender<(usize, usize)>, reader: R) -> DemonChannels {
+        // Main instruction channel
+        let (mailbox, instructions) = mpsc::unbounded_channel();
+        // Killswitch channel
+        let (killswitch_tx, killswitch) = mpsc::unbounded_channel();
+        // Backlog counter, shared with the context
+        let backlog = context.mailbox.clone();
+
+        let mini_hell = MiniIoHell {
+            exit_notice: ExitNotice::new(on_close_tx),
+            demon: AbortGuard::new(demon),
+            context,
+            instructions,
+            killswitch,
+            registered,
+            reader
+        };
+        tokio::spawn(async move {
+            mini_hell.ignite().await;
+        });
+
+        DemonChannels {
+            instructions: mailbox,
+            killswitch: killswitch_tx,
+            size_guard: None,
+            demon: TypeId::of::<D>(),
+            kind: DemonKind::Io,
+            types: DemonTypes::of::<D, I, O>(),
+            mailbox: backlog,
+            delivered: 0,
+            generation: 0,
+            name: None,
+            queue: None,
+            #[cfg(feature = "record")]
+            history: None
+        }
+    }
+
+    async fn ignite(mut self) {
+        #[cfg(feature = "full_log")]
+        log::debug!("[{}] demon thread starting", self.demon.id());
+
+        // Nothing happens until hell confirms that this demon was registered, and tells its address and generation
+        let (address, generation) = match self.registered.await {
+            Ok(registration) => registration,
+            Err(_) => {
+                #[cfg(feature = "full_log")]
+                log::debug!("[{}] demon registration failed, leaving", self.demon.id());
+                // The demon was never spawned, so it is not aborted either
+                drop(self.demon.into_inner());
+                return;
+            }
+        };
+        self.context.location.address = address;
+        self.context.generation = generation;
+        self.exit_notice.registered(address, generation);
+
+        // Inner message passing
+        let (mailbox, mut messages) = mpsc::unbounded_channel::<PendingMessage>();
+        // Observers of the outputs
+        let mut taps = Taps::<O>::new();
+        // Bytes of the last read
+        let mut buffer = vec![0; READ_BUFFER_SIZE];
+
+        let context = self.context.clone();
+        #[cfg(feature = "full_log")]
+        log::debug!("[{}] calling spawn function", self.demon.id());
+        self.demon.spawned_with_context(context).await;
+        #[cfg(feature = "full_log")]
+        log::debug!("[{}] spawn function called", self.demon.id());
+
+        // Unless something else happens, the demon leaves because it was asked to
+        let mut reason = ShutdownReason::Vanquished;
+        let (mut vanquish_mailbox, killswitched) = loop {
+            tokio::select! {
+                res = self.killswitch.recv() => if let Some(vanquish_mailbox) = res {
+                    #[cfg(feature = "full_log")]
+                    log::debug!("[{}] killswitch message received, forced demon shutdown", self.demon.id());
+                    break (Some(vanquish_mailbox), true);
+                } else {
+                    #[cfg(feature = "full_log")]
+                    log::debug!("[{}] all incoming killswitch channels closed (impossible)", self.demon.id());
+                    break (None, true);
+                },
+                res = messages.recv() => if let Some((tx, trace, input)) = res {
+                    self.context.take_from_mailbox();
+                    if let Ok(input) = input.downcast::<I>() {
+                        #[cfg(feature = "full_log")]
+                        log::debug!("[{}] calling handle function, trace {}", self.demon.id(), trace);
+                        let started = Instant::now();
+                        let (output, followup) = tokio::select!{
+                            handled = trace::scope(trace, trace::handling(self.context.location.address, self.demon.handle_with_followup(*input))) => {
+                                #[cfg(feature = "full_log")]
+                                log::debug!("[{}] handle function called", self.demon.id());
+                                handled
+                            },
+                            res = self.killswitch.recv() => if let Some(vanquish_mailbox) = res {
+                                #[cfg(feature = "full_log")]
+                                log::debug!("[{}] killswitch signal received, aborting current handle execution!", self.demon.id());
+                                // The message might be half handled, so the sender cannot assume either way
+                                let _ = tx.send(Err(Error::DemonCommunication));
+                                break (Some(vanquish_mailbox), true);
+                            } else {
+                                #[cfg(feature = "full_log")]
+                                log::debug!("[{}] all incoming killswitch channels closed (impossible), aborting current handle execution", self.demon.id());
+                                let _ = tx.send(Err(Error::DemonCommunication));
+                                break (None, true);
+                            }
+                        };
+                        let elapsed = started.elapsed();
+                        self.context.handle_time.add(elapsed);
+                        #[cfg(feature = "full_log")]
+                        log::debug!("[{}] demon processed message!", self.demon.id());
+                        // The follow-up keeps working while the demon takes its next message
+                        if let Some(followup) = followup {
+                            self.context.spawn_linked(trace::scope(trace, followup));
+                        }
+                        taps.observe(&output);
+                        let fatal = D::is_fatal(&output);
+                        // Declined messages get no reply
+                        let reply = if D::is_declined(&output) {
+                            Err(Error::Declined)
+                        } else {
+                            Ok(Box::new(output) as Box<dyn Any + Send>)
+                        };
+                        if tx.send_handled(reply, elapsed).is_err() {
+                            #[cfg(feature = "full_log")]
+                            log::error!("[{}] demon processed message could not be sent back", self.demon.id());
+                        }
+                        if fatal {
+                            #[cfg(feature = "full_log")]
+                            log::debug!("[{}] fatal reply, demon leaves on its own", self.demon.id());
+                            reason = ShutdownReason::Fatal;
+                            let (rejected, shutdown) = reject_pending(&mut messages, &mut self.instructions);
+                            self.context.take_many_from_mailbox(rejected);
+                            break (shutdown, false);
+                        }
+                    } else {
+                        #[cfg(feature = "full_log")]
+                        log::error!("[{}] demon received wrong message type, expected {}", self.demon.id(), std::any::type_name::<I>());
+                        if tx.send(Err(Error::WrongInputType{expected: std::any::type_name::<I>()})).is_err() {
+                            #[cfg(feature = "full_log")]
+                            log::error!("[{}] wrong message type error could not be sent back", self.demon.id());
+                        }
+                    }
+                } else {
+                    #[cfg(feature = "full_log")]
+                    log::debug!("[{}] all incoming channels closed (impossible)", self.demon.id());
+                    break (None, false);
+                },
+                // Reading is cancel safe, no bytes get lost when another branch wins
+                read = self.reader.read(&mut buffer) => match read {
+                    Ok(0) => {
+                        #[cfg(feature = "full_log")]
+                        log::debug!("[{}] stream closed, executing on_close", self.demon.id());
+                        self.demon.on_close(true).await;
+                        self.exit_notice.notify();
+                        reason = ShutdownReason::ConnectionLost;
+                        break (None, false);
+                    },
+                    Ok(read) => {
+                        self.demon.on_bytes(&buffer[..read]).await;
+                    },
+                    Err(_e) => {
+                        #[cfg(feature = "full_log")]
+                        log::debug!("[{}] stream read failed, {}", self.demon.id(), _e);
+                        self.demon.on_close(false).await;
+                        self.exit_notice.notify();
+                        reason = ShutdownReason::ConnectionLost;
+                        break (None, false);
+                    }
+                },
+                res = self.instructions.recv() => match res {
+                    Some(instruction) => match instruction {
+                        MiniHellInstruction::Shutdown(tx, shutdown_reason) => {
+                            reason = shutdown_reason;
+                            #[cfg(feature = "full_log")]
+                            log::debug!("[{}] shutdown signal received", self.demon.id());
+                            break (Some(tx), false);
+                        },
+                        MiniHellInstruction::Message(result_mailbox, trace, message) => {
+                            #[cfg(feature = "full_log")]
+                            log::debug!("[{}] received instruction, adding to the processing queue", self.demon.id());
+                            if mailbox.send((result_mailbox, trace, message)).is_err() {
+                                #[cfg(feature = "full_log")]
+                                log::warn!("[{}] impossible error happened, could not send back message to itself!", self.demon.id());
+                            }
+                        },
+                        MiniHellInstruction::CancelPending(tx) => {
+                            let cancelled = cancel_pending(&mut messages);
+                            self.context.take_many_from_mailbox(cancelled);
+                            #[cfg(feature = "full_log")]
+                            log::debug!("[{}] cancelled {} pending messages", self.demon.id(), cancelled);
+                            let _ = tx.send(cancelled);
+                        },
+                        MiniHellInstruction::Ping(tx) => {
+                            #[cfg(feature = "full_log")]
+                            log::debug!("[{}] ping received", self.demon.id());
+                            let _ = tx.send(());
+                        },
+                        // Only single demons are handed off, the broker never asks anyone else
+                        MiniHellInstruction::Handoff(_) => (),
+                        MiniHellInstruction::Tap(observer, tx) => {
+                            #[cfg(feature = "full_log")]
+                            log::debug!("[{}] tap received", self.demon.id());
+                            let _ = tx.send(taps.install(observer));
+                        },
+                        // Without replicas, there is nothing to restart
+                        MiniHellInstruction::RestartReplica(_, _, tx) => {
+                            let _ = tx.send(Err(Error::InvalidLocation));
+                        }
+                    },
+                    None => {
+                        // Only happens if hell itself is gone, so there is no one left to wait for the vanquished function
+                        #[cfg(feature = "full_log")]
+                        log::info!("[{}] all channels to this demon are now closed, aborting", self.demon.id());
+                        break (None, true);
+                    }
+                }
+            }
+        };
+
+        // Messages still waiting never reach the demon
+        let (rejected, _) = reject_pending(&mut messages, &mut self.instructions);
+        self.context.take_many_from_mailbox(rejected);
+
+        #[cfg(feature = "full_log")]
+        let demon_id = self.demon.id();
+
+        // Tasks linked to this demon cannot outlive it
+        self.context.abort_linked();
+
+        // We call the vanquished function from this demon
+        #[cfg(feature = "full_log")]
+        log::trace!("[{}] calling vanquish function", demon_id);
+        if !killswitched {
+            tokio::select!{
+                res = self.killswitch.recv() => if let Some(vm) = res {
+                    #[cfg(feature = "full_log")]
+                    log::trace!("[{}] killswitch message received, canceling vanquished function", demon_id);
+                    vanquish_mailbox = Some(vm);
+                } else {
+                    #[cfg(feature = "full_log")]
+                    log::trace!("[{}] all incoming killswitch channels closed (impossible)", demon_id);
+                },
+                _ = self.demon.into_inner().vanquished_with_reason(self.context.clone(), reason) => {
+                    #[cfg(feature = "full_log")]
+                    log::trace!("[{}] vanquish function called", demon_id);
+                }
+            }
+        } else {
+            #[cfg(feature = "full_log")]
+            log::trace!("[{}] skipping vanquish function due to killswitch detection", demon_id);
+        }
+
+        if let Some(vanquish_mailbox) = vanquish_mailbox {
+            if vanquish_mailbox.send(()).is_err() {
+                #[cfg(feature = "full_log")]
+                log::warn!("[{}] could not notify back hell about shutdown!", demon_id);
+            }
+        }
+    }
+}
@@ -1,41 +1,128 @@
-use crate::{Error, Demon, Location, hell::{MiniHellInstruction, DemonChannels}};
-use std::any::Any;
+use crate::{Error, Demon, DemonContext, ShutdownReason, trace, ReconnectPolicy, WsOptions, WsPriority, demon::ReconnectHook, gate::Connection, hell::{AbortGuard, MiniHellInstruction, PendingMessage, cancel_pending, reject_pending, Taps, DemonChannels, DemonKind, DemonTypes, ExitNotice}};
+use futures::future::BoxFuture;
+use std::{any::{Any, TypeId}, time::Duration};
 
 use tokio::{
-    sync::{oneshot::Sender, mpsc::{self, UnboundedReceiver, UnboundedSender}}
+    net::TcpStream,
+    sync::{oneshot::{Sender, Receiver}, mpsc::{self, UnboundedReceiver, UnboundedSender}},
+    time::Instant
 };
-use cataclysm::ws::{WebSocketReader, WebSocketThread};
+use cataclysm::ws::{WSError, Frame, WebSocketReader, WebSocketThread};
 
 /// Structure that holds a single demon, and asynchronously deals with the messages that this demon receives.
-pub(crate) struct MiniWSHell<D> {
+pub(crate) struct MiniWSHell<D: Demon> {
     /// Demon contained inside this minihell instance
-    demon: D,
-    /// Address of this demon
-    location: Location<D>,
+    demon: AbortGuard<D>,
+    /// Context of this demon
+    context: DemonContext<D>,
     /// Channel where instructions are sent to the minihell
     instructions: UnboundedReceiver<MiniHellInstruction>,
     /// Killswitch endpoint
     killswitch: UnboundedReceiver<Sender<()>>,
-    /// Endpoint to let know that a demon was vanquished
-    on_close_tx: UnboundedSender<usize>,
+    /// Confirmation of the registration of this demon in hell, with its address and generation
+    registered: Receiver<(usize, usize)>,
+    /// Notification for hell once this mini hell finishes
+    exit_notice: ExitNotice,
     /// Read stream where ws messages arrive
-    wsr: WebSocketReader
+    wsr: WebSocketReader,
+    /// Optional reconnection policy, only missing while reconnecting
+    reconnect: Option<ReconnectPolicy>,
+    /// Reconnection hook of the demon
+    on_reconnect: Option<ReconnectHook<D>>,
+    /// Maximum wait for the close frame of the other side, when this demon closes the connection
+    close_timeout: Duration,
+    /// Priority between incoming frames and messages
+    priority: WsPriority
+}
+
+/// Reconnection attempt in progress
+type Reconnection = BoxFuture<'static, (ReconnectPolicy, Option<Connection>)>;
+
+/// Waits for the reconnection in progress, if any
+async fn reconnected(reconnection: &mut Option<Reconnection>) -> (ReconnectPolicy, Option<Connection>) {
+    match reconnection {
+        Some(reconnection) => reconnection.await,
+        None => std::future::pending().await
+    }
+}
+
+/// Next frame or message for the demon
+enum Incoming {
+    Frame(Result<Frame, WSError>),
+    Message(Option<PendingMessage>)
+}
+
+/// Waits for the next frame or message, following the priority between them
+///
+/// Frames are only read when `reading` is set, that is, while the connection is up.
+async fn incoming(wsr: &WebSocketReader, reading: bool, messages: &mut UnboundedReceiver<PendingMessage>, priority: WsPriority) -> Incoming {
+    match priority {
+        WsPriority::Fair => tokio::select! {
+            frame = wsr.try_read_frame(), if reading => Incoming::Frame(frame),
+            message = messages.recv() => Incoming::Message(message)
+        },
+        WsPriority::Frames => tokio::select! {
+            biased;
+            frame = wsr.try_read_frame(), if reading => Incoming::Frame(frame),
+            message = messages.recv() => Incoming::Message(message)
+        },
+        WsPriority::Messages => tokio::select! {
+            biased;
+            message = messages.recv() => Incoming::Message(message),
+            frame = wsr.try_read_frame(), if reading => Incoming::Frame(frame)
+        }
+    }
+}
+
+/// Sends a close frame, and waits for the one of the other side for at most `close_timeout`
+///
+/// The writing half belongs to the demon, but both halves share the same socket.
+async fn close_handshake(wsr: &WebSocketReader, close_timeout: Duration) {
+    let handshake = async {
+        let stream: &TcpStream = wsr.as_ref();
+        let close: Vec<u8> = Frame::close().into();
+        let mut written = 0;
+        while written < close.len() {
+            if stream.writable().await.is_err() {
+                return;
+            }
+            match stream.try_write(&close[written..]) {
+                Ok(n) => written += n,
+                Err(ref e) if e.kind() == std::io::ErrorKind::WouldBlock => continue,
+                Err(_) => return
+            }
+        }
+        // Anything but the close frame is discarded at this point
+        while let Ok(frame) = wsr.try_read_frame().await {
+            if frame.message.is_close() {
+                break;
+            }
+        }
+    };
+    let _ = tokio::time::timeout(close_timeout, handshake).await;
 }
 
 impl<I: 'static + Send, O: 'static + Send, D: 'static + Demon<Input = I, Output = O> + WebSocketThread> MiniWSHell<D> {
-    pub(crate) fn spawn(demon: D, location: Location<D>, on_close_tx: UnboundedSender<usize>, wsr: WebSocketReader) -> DemonChannels {
+    pub(crate) fn spawn(demon: D, context: DemonContext<D>, registered: Receiver<(usize, usize)>, on_close_tx: UnboundedSender<(usize, usize)>, wsr: WebSocketReader, options: WsOptions, on_reconnect: Option<ReconnectHook<D>>) -> DemonChannels {
         // Main instruction channel
         let (mailbox, instructions) = mpsc::unbounded_channel();
         // Killswitch channel
         let (killswitch_tx, killswitch) = mpsc::unbounded_channel();
-
+        // Backlog counter, shared with the context
+        let backlog = context.mailbox.clone();
         let mini_hell = MiniWSHell {
-            demon,
-            location,
+            exit_notice: ExitNotice::new(on_close_tx),
+            demon: AbortGuard::new(demon),
+            context,
             instructions,
             killswitch,
-            on_close_tx,
-            wsr
+            registered,
+            wsr,
+            // The policy gets moved around while reconnecting, the hook stays
+            reconnect: options.reconnect,
+            on_reconnect,
+            close_timeout: options.close_timeout,
+            priority: options.priority
         };
         tokio::spawn(async move {
             mini_hell.ignite().await;
@@ -43,15 +130,44 @@ impl<I: 'static + Send, O: 'static + Send, D: 'static + Demon<Input = I, Output
 
         DemonChannels {
             instructions: mailbox,
-            killswitch: killswitch_tx
+            killswitch: killswitch_tx,
+            size_guard: None,
+            demon: TypeId::of::<D>(),
+            kind: DemonKind::WebSocket,
+            types: DemonTypes::of::<D, I, O>(),
+            mailbox: backlog,
+            delivered: 0,
+            generation: 0,
+            name: None,
+            queue: None,
+            #[cfg(feature = "record")]
+            history: None
         }
     }
 
     async fn ignite(mut self) {
         #[cfg(feature = "full_log")]
         log::debug!("[{}] demon thread starting", self.demon.id());
+
+        // Nothing happens until hell confirms that this demon was registered, and tells its address and generation
+        let (address, generation) = match self.registered.await {
+            Ok(registration) => registration,
+            Err(_) => {
+                #[cfg(feature = "full_log")]
+                log::debug!("[{}] demon registration failed, leaving", self.demon.id());
+                // The demon was never spawned, so it is not aborted either
+                drop(self.demon.into_inner());
+                return;
+            }
+        };
+        self.context.location.address = address;
+        self.context.generation = generation;
+        self.exit_notice.registered(address, generation);
+
         // Inner message passing
-        let (mailbox, mut messages) = mpsc::unbounded_channel::<(Sender<Result<Box<dyn Any + Send>, Error>>, Box<dyn Any + Send>)>();
+        let (mailbox, mut messages) = mpsc::unbounded_channel::<PendingMessage>();
+        // Observers of the outputs
+        let mut taps = Taps::<O>::new();
 
         // We call both opening callbacks, starting by the websockets one
         #[cfg(feature = "full_log")]
@@ -60,13 +176,20 @@ impl<I: 'static + Send, O: 'static + Send, D: 'static + Demon<Input = I, Output
         #[cfg(feature = "full_log")]
         log::debug!("[{}] on_open function called", self.demon.id());
 
-        let other_loc = self.location.clone();
+        let context = self.context.clone();
         #[cfg(feature = "full_log")]
         log::debug!("[{}] calling spawn function", self.demon.id());
-        self.demon.spawned(other_loc).await;
+        self.demon.spawned_with_context(context).await;
         #[cfg(feature = "full_log")]
         log::debug!("[{}] spawn function called", self.demon.id());
 
+        // Pending reconnection, while the connection is down
+        let mut reconnection: Option<Reconnection> = None;
+        // Whether the connection is still up, and so needs to be closed by this side
+        let mut open = true;
+
+        // Unless something else happens, the demon leaves because it was asked to
+        let mut reason = ShutdownReason::Vanquished;
         let (mut vanquish_mailbox, killswitched) = loop {
             tokio::select! {
                 res = self.killswitch.recv() => if let Some(vanquish_mailbox) = res {
@@ -78,96 +201,198 @@ impl<I: 'static + Send, O: 'static + Send, D: 'static + Demon<Input = I, Output
                     log::debug!("[{}] all incoming killswitch channels closed (impossible)", self.demon.id());
                     break (None, true);
                 },
-                res = messages.recv() => if let Some((tx, input)) = res {
-                    if let Ok(input) = input.downcast::<I>() {
-                        #[cfg(feature = "full_log")]
-                        log::debug!("[{}] calling handle function", self.demon.id());
-                        let output = tokio::select!{
-                            output = self.demon.handle(*input) => {
-                                #[cfg(feature = "full_log")]
-                                log::debug!("[{}] handle function called", self.demon.id());
-                                output
-                            },
-                            res = self.killswitch.recv() => if let Some(vanquish_mailbox) = res {
-                                #[cfg(feature = "full_log")]
-                                log::debug!("[{}] killswitch signal received, aborting current handle execution!", self.demon.id());
-                                break (Some(vanquish_mailbox), true);
+                next = incoming(&self.wsr, reconnection.is_none(), &mut messages, self.priority) => match next {
+                    Incoming::Message(res) => if let Some((tx, trace, input)) = res {
+                        self.context.take_from_mailbox();
+                        if let Ok(input) = input.downcast::<I>() {
+                            #[cfg(feature = "full_log")]
+                            log::debug!("[{}] calling handle function, trace {}", self.demon.id(), trace);
+                            let started = Instant::now();
+                            let (output, followup) = tokio::select!{
+                                handled = trace::scope(trace, trace::handling(self.context.location.address, self.demon.handle_with_followup(*input))) => {
+                                    #[cfg(feature = "full_log")]
+                                    log::debug!("[{}] handle function called", self.demon.id());
+                                    handled
+                                },
+                                res = self.killswitch.recv() => if let Some(vanquish_mailbox) = res {
+                                    #[cfg(feature = "full_log")]
+                                    log::debug!("[{}] killswitch signal received, aborting current handle execution!", self.demon.id());
+                                    // The message might be half handled, so the sender cannot assume either way
+                                    let _ = tx.send(Err(Error::DemonCommunication));
+                                    break (Some(vanquish_mailbox), true);
+                                } else {
+                                    #[cfg(feature = "full_log")]
+                                    log::debug!("[{}] all incoming killswitch channels closed (impossible), aborting current handle execution", self.demon.id());
+                                    let _ = tx.send(Err(Error::DemonCommunication));
+                                    break (None, true);
+                                }
+                            };
+                            let elapsed = started.elapsed();
+                            self.context.handle_time.add(elapsed);
+                            #[cfg(feature = "full_log")]
+                            log::debug!("[{}] demon processed message!", self.demon.id());
+                            // The follow-up keeps working while the demon takes its next message
+                            if let Some(followup) = followup {
+                                self.context.spawn_linked(trace::scope(trace, followup));
+                            }
+                            taps.observe(&output);
+                            let fatal = D::is_fatal(&output);
+                            // Declined messages get no reply
+                            let reply = if D::is_declined(&output) {
+                                Err(Error::Declined)
                             } else {
+                                Ok(Box::new(output) as Box<dyn Any + Send>)
+                            };
+                            if tx.send_handled(reply, elapsed).is_err() {
                                 #[cfg(feature = "full_log")]
-                                log::debug!("[{}] all incoming killswitch channels closed (impossible), aborting current handle execution", self.demon.id());
-                                break (None, true);
+                                log::error!("[{}] demon processed message could not be sent back", self.demon.id());  
                             }
-                        };
-                        #[cfg(feature = "full_log")]
-                        log::debug!("[{}] demon processed message!", self.demon.id());
-                        if tx.send(Ok(Box::new(output))).is_err() {
+                            if fatal {
+                                #[cfg(feature = "full_log")]
+                                log::debug!("[{}] fatal reply, demon leaves on its own", self.demon.id());
+                                reason = ShutdownReason::Fatal;
+                                let (rejected, shutdown) = reject_pending(&mut messages, &mut self.instructions);
+                                self.context.take_many_from_mailbox(rejected);
+                                break (shutdown, false);
+                            }
+                        } else {
                             #[cfg(feature = "full_log")]
-                            log::error!("[{}] demon processed message could not be sent back", self.demon.id());  
+                            log::error!("[{}] demon received wrong message type, expected {}", self.demon.id(), std::any::type_name::<I>());
+                            if tx.send(Err(Error::WrongInputType{expected: std::any::type_name::<I>()})).is_err() {
+                                #[cfg(feature = "full_log")]
+                                log::error!("[{}] wrong message type error could not be sent back", self.demon.id());
+                            }
                         }
                     } else {
-                        if tx.send(Err(Error::WrongType)).is_err() {
-                            #[cfg(feature = "full_log")]
-                            log::error!("[{}] somehow, demon received wrong message type", self.demon.id());   
-                        }
-                    }
-                } else {
-                    #[cfg(feature = "full_log")]
-                    log::debug!("[{}] all incoming channels closed (impossible)", self.demon.id());
-                    break (None, false);
-                },
-                frame = self.wsr.try_read_frame() => match frame {
-                    Ok(frame) => {
-                        if frame.message.is_close() {
-                            #[cfg(feature = "full_log")]
-                            log::debug!("[{}] close message received, executing on_close", self.demon.id(), );
-                            self.demon.on_close(true).await;
+                        #[cfg(feature = "full_log")]
+                        log::debug!("[{}] all incoming channels closed (impossible)", self.demon.id());
+                        break (None, false);
+                    },
+                    Incoming::Frame(frame) => match frame {
+                        Ok(frame) => {
+                            if frame.message.is_close() {
+                                #[cfg(feature = "full_log")]
+                                log::debug!("[{}] close message received, executing on_close", self.demon.id(), );
+                                self.demon.on_close(true).await;
+                                #[cfg(feature = "full_log")]
+                                log::debug!("[{}] on_close executed", self.demon.id(), );
+
+                                open = false;
+                                self.exit_notice.notify();
+                                reason = ShutdownReason::ConnectionLost;
+                                break (None, false);
+                            }
+
+                            self.demon.on_message(frame.message).await;
+                        },
+                        Err(_e) => {
                             #[cfg(feature = "full_log")]
-                            log::debug!("[{}] on_close executed", self.demon.id(), );
+                            log::debug!("[{}] {}", self.demon.id(), _e);
 
-                            let _ = self.on_close_tx.send(self.location.address);
-                            break (None, false);
-                        }
+                            // If possible, the connection gets reestablished while messages keep being processed
+                            if let Some(policy) = self.reconnect.take() {
+                                #[cfg(feature = "full_log")]
+                                log::debug!("[{}] connection lost, reconnecting", self.demon.id());
+                                reconnection = Some(policy.reconnection());
+                            } else {
+                                self.demon.on_close(false).await;
+                                open = false;
+                                self.exit_notice.notify();
+                                reason = ShutdownReason::ConnectionLost;
 
-                        self.demon.on_message(frame.message).await;
+                                break (None, false);
+                            }
+                        }
                     },
-                    Err(_e) => {
-                        self.demon.on_close(false).await;
+                },
+                (policy, connection) = reconnected(&mut reconnection) => {
+                    reconnection = None;
+                    self.reconnect = Some(policy);
+                    if let Some((writer, wsr)) = connection {
                         #[cfg(feature = "full_log")]
-                        log::debug!("[{}] {}", self.demon.id(), _e);
-
-                        let _ = self.on_close_tx.send(self.location.address);
+                        log::debug!("[{}] connection reestablished, calling on_reconnect function", self.demon.id());
+                        self.wsr = wsr;
+                        if let Some(on_reconnect) = self.on_reconnect {
+                            on_reconnect(&mut self.demon, writer).await;
+                        }
+                    } else {
+                        #[cfg(feature = "full_log")]
+                        log::debug!("[{}] reconnection attempts exhausted", self.demon.id());
+                        self.demon.on_close(false).await;
+                        open = false;
+                        self.exit_notice.notify();
+                        reason = ShutdownReason::ConnectionLost;
 
                         break (None, false);
                     }
                 },
                 res = self.instructions.recv() => match res {
                     Some(instruction) => match instruction {
-                        MiniHellInstruction::Shutdown(tx) => {
+                        MiniHellInstruction::Shutdown(tx, shutdown_reason) => {
+                            reason = shutdown_reason;
                             #[cfg(feature = "full_log")]
                             log::debug!("[{}] shutdown signal received", self.demon.id());
                             break (Some(tx), false);
                         },
-                        MiniHellInstruction::Message(result_mailbox, message) => {
+                        MiniHellInstruction::Message(result_mailbox, trace, message) => {
                             #[cfg(feature = "full_log")]
                             log::debug!("[{}] received instruction, adding to the processing queue", self.demon.id());
-                            if mailbox.send((result_mailbox, message)).is_err() {
+                            if mailbox.send((result_mailbox, trace, message)).is_err() {
                                 #[cfg(feature = "full_log")]
                                 log::warn!("[{}] impossible error happened, could not send back message to itself!", self.demon.id());   
                             }
+                        },
+                        MiniHellInstruction::CancelPending(tx) => {
+                            let cancelled = cancel_pending(&mut messages);
+                            self.context.take_many_from_mailbox(cancelled);
+                            #[cfg(feature = "full_log")]
+                            log::debug!("[{}] cancelled {} pending messages", self.demon.id(), cancelled);
+                            let _ = tx.send(cancelled);
+                        },
+                        MiniHellInstruction::Ping(tx) => {
+                            #[cfg(feature = "full_log")]
+                            log::debug!("[{}] ping received", self.demon.id());
+                            let _ = tx.send(());
+                        },
+                        // Only single demons are handed off, the broker never asks anyone else
+                        MiniHellInstruction::Handoff(_) => (),
+                        MiniHellInstruction::Tap(observer, tx) => {
+                            #[cfg(feature = "full_log")]
+                            log::debug!("[{}] tap received", self.demon.id());
+                            let _ = tx.send(taps.install(observer));
+                        },
+                        // Without replicas, there is nothing to restart
+                        MiniHellInstruction::RestartReplica(_, _, tx) => {
+                            let _ = tx.send(Err(Error::InvalidLocation));
                         }
                     },
                     None => {
+                        // Only happens if hell itself is gone, so there is no one left to wait for the vanquished function
                         #[cfg(feature = "full_log")]
-                        log::info!("[{}] all channels to this demon are now closed", self.demon.id());
-                        break (None, false);
+                        log::info!("[{}] all channels to this demon are now closed, aborting", self.demon.id());
+                        break (None, true);
                     }
                 }
             }
         };
 
+        // Messages still waiting never reach the demon
+        let (rejected, _) = reject_pending(&mut messages, &mut self.instructions);
+        self.context.take_many_from_mailbox(rejected);
+
         #[cfg(feature = "full_log")]
         let demon_id = self.demon.id();
 
+        // Tasks linked to this demon cannot outlive it
+        self.context.abort_linked();
+
+        // Broken connections, and those of forced shutdowns, are just dropped. The handshake goes before vanquishing, as the writing half leaves with the demon
+        if open && reconnection.is_none() && !killswitched && !self.close_timeout.is_zero() {
+            #[cfg(feature = "full_log")]
+            log::trace!("[{}] performing close handshake", demon_id);
+            close_handshake(&self.wsr, self.close_timeout).await;
+        }
+
         // We call the vanquished function from this demon
         #[cfg(feature = "full_log")]
         log::trace!("[{}] calling vanquish function", demon_id);
@@ -181,7 +406,7 @@ impl<I: 'static + Send, O: 'static + Send, D: 'static + Demon<Input = I, Output
                     #[cfg(feature = "full_log")]
                     log::trace!("[{}] all incoming killswitch channels closed (impossible)", demon_id);
                 },
-                _ = self.demon.vanquished() => {
+                _ = self.demon.into_inner().vanquished_with_reason(self.context.clone(), reason) => {
                     #[cfg(feature = "full_log")]
                     log::trace!("[{}] vanquish function called", demon_id);
                 }
@@ -191,6 +416,7 @@ impl<I: 'static + Send, O: 'static + Send, D: 'static + Demon<Input = I, Output
             log::trace!("[{}] skipping vanquish function due to killswitch detection", demon_id);
         }
 
+
         if let Some(vanquish_mailbox) = vanquish_mailbox {
             if vanquish_mailbox.send(()).is_err() {
                 #[cfg(feature = "full_log")]
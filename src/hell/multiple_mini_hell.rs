@@ -1,96 +1,438 @@
-use crate::{Error, Demon, Location, hell::{MiniHellInstruction, DemonChannels}};
-use std::any::Any;
+use crate::{Error, Demon, DemonContext, OverflowPolicy, ShutdownReason, trace, hell::{AbortGuard, MiniHellInstruction, PendingMessage, cancel_pending, reject_pending, ReplyTo, Taps, DemonChannels, DemonKind, DemonTypes, ExitNotice, QueueGauge, HandleTime}};
+use std::any::{Any, TypeId};
+use std::panic::AssertUnwindSafe;
+use futures::{FutureExt, future::BoxFuture};
 use std::collections::{VecDeque, HashMap};
-use tokio::sync::{oneshot::{Sender}, mpsc::{self, UnboundedReceiver}};
+use std::sync::Arc;
+use tokio::sync::{oneshot::{Sender, Receiver}, mpsc::{self, UnboundedReceiver, UnboundedSender}};
+use tokio::{task::JoinHandle, time::Instant};
+
+/// Factory of new replicas, for pools that can scale
+type Factory<D> = Box<dyn FnMut(usize) -> D + Send>;
+
+/// Message waiting for a replica, together with its reply channel and its trace id
+type Request<I> = (ReplyTo, u64, I);
+
+/// Replica coming back from handling a message, with its index, whether its reply was fatal or it panicked, and the follow-up it left behind
+type Answer<D> = (usize, AbortGuard<D>, bool, bool, Option<BoxFuture<'static, ()>>);
+
+/// Reply of a message handed to a replica, answered with `Error::DemonCommunication` if the replica is aborted before replying
+struct PendingReply(Option<ReplyTo>);
+
+impl PendingReply {
+    fn take(&mut self) -> ReplyTo {
+        self.0.take().expect("a reply is only taken once")
+    }
+}
+
+impl Drop for PendingReply {
+    fn drop(&mut self) {
+        if let Some(tx) = self.0.take() {
+            let _ = tx.send(Err(Error::DemonCommunication));
+        }
+    }
+}
+
+/// Message that always goes to the same replica as all other messages with the same key
+pub(crate) struct Keyed {
+    pub(crate) key: u64,
+    pub(crate) input: Box<dyn Any + Send>
+}
+
+/// Messages waiting for a specific busy replica because of their key, which is kept next to them
+struct KeyedQueues<I> {
+    queues: HashMap<usize, VecDeque<(u64, Request<I>)>>,
+    len: usize
+}
+
+impl<I> KeyedQueues<I> {
+    fn new() -> KeyedQueues<I> {
+        KeyedQueues {
+            queues: HashMap::new(),
+            len: 0
+        }
+    }
+
+    /// Total amount of messages waiting, for all replicas
+    fn len(&self) -> usize {
+        self.len
+    }
+
+    fn push(&mut self, idx: usize, key: u64, request: Request<I>) {
+        self.queues.entry(idx).or_default().push_back((key, request));
+        self.len += 1;
+    }
+
+    /// Next message for the given replica
+    fn pop(&mut self, idx: usize) -> Option<Request<I>> {
+        let queue = self.queues.get_mut(&idx)?;
+        let (_key, request) = queue.pop_front()?;
+        if queue.is_empty() {
+            self.queues.remove(&idx);
+        }
+        self.len -= 1;
+        Some(request)
+    }
+
+    /// All the messages for the given replica, in arrival order
+    fn remove(&mut self, idx: usize) -> VecDeque<(u64, Request<I>)> {
+        let queue = self.queues.remove(&idx).unwrap_or_default();
+        self.len -= queue.len();
+        queue
+    }
+
+    /// All the messages, for all replicas
+    fn drain(&mut self) -> Vec<(u64, Request<I>)> {
+        self.len = 0;
+        self.queues.drain().flat_map(|(_idx, queue)| queue).collect()
+    }
+}
 
 /// Structure that holds a single demon, and asynchronously deals with the messages that this demon receives.
-pub(crate) struct MultipleMiniHell<D> {
+pub(crate) struct MultipleMiniHell<D: Demon> {
     /// Demon contained inside this minihell instance
-    demons: VecDeque<(usize, D)>,
-    /// Address of this demon
-    location: Location<D>,
+    demons: VecDeque<(usize, AbortGuard<D>)>,
+    /// Context of this demon
+    context: DemonContext<D>,
     /// Channel where instructions are sent to the minihell
     instructions: UnboundedReceiver<MiniHellInstruction>,
     /// Killswitch endpoint
-    killswitch: UnboundedReceiver<Sender<()>>
+    killswitch: UnboundedReceiver<Sender<()>>,
+    /// Confirmation of the registration of this demon in hell, with its address and generation
+    registered: Receiver<(usize, usize)>,
+    /// Optional limit for the internal queue, and what happens once it is reached
+    bound: Option<(usize, OverflowPolicy)>,
+    /// Length of the internal queue, shared with hell
+    queue: Arc<QueueGauge>,
+    /// Factory for new replicas, only present if the pool can scale
+    factory: Option<Factory<D>>,
+    /// Scale requests coming from the demons themselves
+    scale: UnboundedReceiver<i32>,
+    /// Amount of replicas, not counting the ones about to be removed
+    replicas: usize,
+    /// Index for the next replica to be created
+    next_index: usize,
+    /// Notification for hell once this mini hell finishes
+    exit_notice: ExitNotice
 }
 
 impl<I: 'static + Send, O: 'static + Send, D: 'static + Demon<Input = I, Output = O>> MultipleMiniHell<D> {
-    pub fn spawn<F: FnMut() -> D>(mut demon_factory: F, replicas: usize, location: Location<D>) -> Result<DemonChannels, Error> {
+    pub fn spawn<F: FnMut(usize) -> D>(mut demon_factory: F, replicas: usize, bound: Option<(usize, OverflowPolicy)>, context: DemonContext<D>, registered: Receiver<(usize, usize)>, on_close_tx: UnboundedSender<(usize, usize)>) -> Result<DemonChannels, Error> {
+        let demons = (0..replicas).map(|idx| (idx, demon_factory(idx))).collect();
+        MultipleMiniHell::spawn_replicas(demons, bound, context, registered, on_close_tx)
+    }
+
+    /// Same as [spawn](MultipleMiniHell::spawn), but with the replicas already built, together with their indices
+    pub fn spawn_replicas(demons: VecDeque<(usize, D)>, bound: Option<(usize, OverflowPolicy)>, context: DemonContext<D>, registered: Receiver<(usize, usize)>, on_close_tx: UnboundedSender<(usize, usize)>) -> Result<DemonChannels, Error> {
+        if demons.is_empty() {
+            return Err(Error::WrongReplicas);
+        }
+
+        // Nobody can request a scale for this pool
+        let (_, scale) = mpsc::unbounded_channel();
+
+        Ok(MultipleMiniHell::start(demons, None, scale, bound, context, registered, on_close_tx))
+    }
+
+    /// Same as [spawn](MultipleMiniHell::spawn), but the factory is kept so that the demons can scale the pool
+    pub fn spawn_scalable<F: 'static + FnMut(usize) -> D + Send>(mut demon_factory: F, replicas: usize, mut context: DemonContext<D>, registered: Receiver<(usize, usize)>, on_close_tx: UnboundedSender<(usize, usize)>) -> Result<DemonChannels, Error> {
+        if replicas == 0 {
+            return Err(Error::WrongReplicas);
+        }
+
+        let demons = (0..replicas).map(|idx| (idx, demon_factory(idx))).collect();
+        // Scale requests come through the context
+        let (scale_tx, scale) = mpsc::unbounded_channel();
+        context.scale = Some(scale_tx);
+
+        Ok(MultipleMiniHell::start(demons, Some(Box::new(demon_factory)), scale, None, context, registered, on_close_tx))
+    }
+
+    fn start(demons: VecDeque<(usize, D)>, factory: Option<Factory<D>>, scale: UnboundedReceiver<i32>, bound: Option<(usize, OverflowPolicy)>, context: DemonContext<D>, registered: Receiver<(usize, usize)>, on_close_tx: UnboundedSender<(usize, usize)>) -> DemonChannels {
         // Main instruction channel
         let (mailbox, instructions) = mpsc::unbounded_channel();
         // Killswitch channel
         let (killswitch_tx, killswitch) = mpsc::unbounded_channel();
-
-        let demons: VecDeque<(usize, D)> = (0..replicas).map(|idx| (idx, demon_factory())).collect();
+        // Backlog counter, shared with the context
+        let backlog = context.mailbox.clone();
+        // Internal queue gauge, shared with hell
+        let queue = Arc::new(QueueGauge::default());
 
         let multiple_mini_hell = MultipleMiniHell {
-            demons,
-            location,
+            exit_notice: ExitNotice::new(on_close_tx),
+            replicas: demons.len(),
+            // Indices can have gaps, when some replicas could not be built
+            next_index: demons.iter().map(|(idx, _)| idx + 1).max().unwrap_or(0),
+            demons: demons.into_iter().map(|(idx, demon)| (idx, AbortGuard::new(demon))).collect(),
+            context,
             instructions,
-            killswitch
+            killswitch,
+            registered,
+            bound,
+            queue: queue.clone(),
+            factory,
+            scale
         };
 
         tokio::spawn(async move {
             multiple_mini_hell.ignite().await;
         });
 
-        Ok(DemonChannels {
+        DemonChannels {
             instructions: mailbox,
-            killswitch: killswitch_tx
+            killswitch: killswitch_tx,
+            size_guard: None,
+            demon: TypeId::of::<D>(),
+            kind: DemonKind::Pool,
+            types: DemonTypes::of::<D, I, O>(),
+            mailbox: backlog,
+            delivered: 0,
+            generation: 0,
+            name: None,
+            queue: Some(queue),
+            #[cfg(feature = "record")]
+            history: None
+        }
+    }
+
+    /// Indicates if a new message can be taken from the mailbox, given the length of the internal queue
+    fn accepts_messages(&self, queued: usize) -> bool {
+        match self.bound {
+            Some((max_queued, OverflowPolicy::Wait)) => !self.demons.is_empty() || queued < max_queued,
+            _ => true
+        }
+    }
+
+    /// Indicates if a new message has to be rejected, given the length of the internal queue
+    fn overflows(&self, queued: usize) -> bool {
+        match self.bound {
+            Some((max_queued, OverflowPolicy::Reject)) => queued >= max_queued,
+            _ => false
+        }
+    }
+
+    /// Replica for the given key, among the replicas that are alive, sorted by index
+    fn replica_for(&self, key: u64, handles: &HashMap<usize, JoinHandle<()>>) -> usize {
+        let mut replicas: Vec<usize> = self.demons.iter().map(|(idx, _)| *idx).chain(handles.keys().copied()).collect();
+        replicas.sort_unstable();
+        replicas[(key % replicas.len() as u64) as usize]
+    }
+
+    /// Takes the replica with the given index out of the pool, if it is idle
+    fn take_idle(&mut self, idx: usize) -> Option<(usize, AbortGuard<D>)> {
+        let position = self.demons.iter().position(|(idle, _)| *idle == idx)?;
+        self.demons.remove(position)
+    }
+
+    /// Hands a keyed message to its replica, or gives it back together with the replica if it is busy
+    fn route(&mut self, key: u64, request: Request<I>, handles: &mut HashMap<usize, JoinHandle<()>>, taps: &Taps<O>, answers_tx: &UnboundedSender<Answer<D>>) -> Result<(), (usize, Request<I>)> {
+        let idx = self.replica_for(key, handles);
+        match self.take_idle(idx) {
+            Some((idx, demon)) => {
+                self.context.take_from_mailbox();
+                handles.insert(idx, MultipleMiniHell::dispatch(idx, demon, request, taps.clone(), self.context.handle_time.clone(), answers_tx.clone()));
+                Ok(())
+            },
+            None => Err((idx, request))
+        }
+    }
+
+    /// Moves a demon to its own task to handle a message, sending the demon back once it finishes, together with the fatality of its reply
+    ///
+    /// Panics are caught, so that the pool learns about them, and the sender gets `Error::Panicked`.
+    fn dispatch(idx: usize, mut demon: AbortGuard<D>, request: Request<I>, taps: Taps<O>, handle_time: Arc<HandleTime>, answers_tx: UnboundedSender<Answer<D>>) -> JoinHandle<()> {
+        let (tx, trace, input) = request;
+        // Guarded before the task starts, as it can be aborted before its first poll
+        let mut tx = PendingReply(Some(tx));
+        tokio::spawn(async move {
+            #[cfg(feature = "full_log")]
+            log::debug!("[{}] calling handle function, trace {}", demon.id(), trace);
+            let started = Instant::now();
+            let (output, followup) = match AssertUnwindSafe(trace::scope(trace, demon.handle_with_followup(input))).catch_unwind().await {
+                Ok(handled) => handled,
+                Err(_) => {
+                    #[cfg(feature = "full_log")]
+                    log::error!("[{}] handle function panicked", demon.id());
+                    let _ = tx.take().send(Err(Error::Panicked));
+                    let _ = answers_tx.send((idx, demon, true, true, None));
+                    return;
+                }
+            };
+            let elapsed = started.elapsed();
+            handle_time.add(elapsed);
+            #[cfg(feature = "full_log")]
+            log::debug!("[{}] handle function called", demon.id());
+
+            // We first send the reply
+            taps.observe(&output);
+            let fatal = D::is_fatal(&output);
+            // Declined messages get no reply
+            let reply = if D::is_declined(&output) {
+                Err(Error::Declined)
+            } else {
+                Ok(Box::new(output) as Box<dyn Any + Send>)
+            };
+            if tx.take().send_handled(reply, elapsed).is_err() {
+                #[cfg(feature = "full_log")]
+                log::error!("[{}] demon processed message could not be sent back", demon.id());
+            }
+
+            // Now the demon back, together with its follow-up, which the pool links to itself
+            #[cfg(feature = "full_log")]
+            let demon_id = demon.id();
+            let followup = followup.map(|followup| Box::pin(trace::scope(trace, followup)) as BoxFuture<'static, ()>);
+            if answers_tx.send((idx, demon, fatal, false, followup)).is_err() {
+                #[cfg(feature = "full_log")]
+                log::error!("[{}] demon could not be sent back for reuse", demon_id);
+            }
         })
     }
 
+    /// Vanquishes a replica, unless it panicked, and starts its replacement, which takes the same index
+    async fn replace(demon: AbortGuard<D>, panicked: bool, replacement: D, tx: Sender<Result<(), Error>>, context: DemonContext<D>) -> AbortGuard<D> {
+        if panicked {
+            // Its state cannot be trusted, so it is not aborted either
+            drop(demon.into_inner());
+        } else {
+            MultipleMiniHell::retire(demon, context.clone(), ShutdownReason::Replaced).await;
+        }
+        let mut replacement = AbortGuard::new(replacement);
+        replacement.spawned_with_context(context).await;
+        let _ = tx.send(Ok(()));
+        replacement
+    }
+
+    /// Removes a single replica from the pool
+    async fn retire(demon: AbortGuard<D>, context: DemonContext<D>, reason: ShutdownReason) {
+        #[cfg(feature = "full_log")]
+        log::debug!("[{}] removing replica from the pool", demon.id());
+        demon.into_inner().vanquished_with_reason(context, reason).await;
+    }
+
     async fn ignite(mut self) {
         #[cfg(feature = "full_log")]
         log::debug!("[{}] multiple demon thread starting", <D as Demon>::multiple_id());
-        let (mailbox, mut messages) = mpsc::unbounded_channel::<(Sender<Result<Box<dyn Any + Send>, Error>>, Box<dyn Any + Send>)>();
+
+        // Nothing happens until hell confirms that this demon was registered, and tells its address and generation
+        let (address, generation) = match (&mut self.registered).await {
+            Ok(registration) => registration,
+            Err(_) => {
+                #[cfg(feature = "full_log")]
+                log::debug!("[{}] demon registration failed, leaving", <D as Demon>::multiple_id());
+                // The demons were never spawned, so they are not aborted either
+                for (_, demon) in self.demons.drain(..) {
+                    drop(demon.into_inner());
+                }
+                return;
+            }
+        };
+        self.context.location.address = address;
+        self.context.generation = generation;
+        self.exit_notice.registered(address, generation);
+
+        let (mailbox, mut messages) = mpsc::unbounded_channel::<PendingMessage>();
 
         // Answers channel
-        let (answers_tx, mut answers) = mpsc::unbounded_channel::<(usize, D)>();
-        let mut requests: VecDeque<(
-            Sender<Result<Box<dyn Any + Send>, Error>>,
-            I
-        )> = VecDeque::new();
+        let (answers_tx, mut answers) = mpsc::unbounded_channel::<Answer<D>>();
+        let mut requests: VecDeque<Request<I>> = VecDeque::new();
+        // Messages with a key, waiting for the replica that their key belongs to
+        let mut keyed = KeyedQueues::<I>::new();
 
-        let mut handles: HashMap<usize, tokio::task::JoinHandle<()>> = HashMap::new();
+        let mut handles: HashMap<usize, JoinHandle<()>> = HashMap::new();
+        // Observers of the outputs, shared by all the replicas
+        let mut taps = Taps::<O>::new();
+        // Busy replicas to be removed as soon as they finish
+        let mut retiring: usize = 0;
+        // Replacements for busy replicas, waiting for them to finish
+        let mut replacing: HashMap<usize, (D, Sender<Result<(), Error>>)> = HashMap::new();
 
         // We call the spawned function from this demon
         for (_, demon) in &mut self.demons {
             #[cfg(feature = "full_log")]
             log::debug!("[{}] calling spawn function", demon.id());
-            demon.spawned(self.location.clone()).await;
+            demon.spawned_with_context(self.context.clone()).await;
             #[cfg(feature = "full_log")]
             log::debug!("[{}] spawn function called", demon.id());
         }
 
+        // Unless something else happens, the pool leaves because it was asked to
+        let mut reason = ShutdownReason::Vanquished;
         let vanquish_mailbox = loop {
             tokio::select! {
-                answer = answers.recv() => if let Some((idx, mut demon)) = answer {
-                    // if we have pending requests, we pop them here
-                    if let Some((tx, request)) = requests.pop_front() {
-                        let answers_tx_clone = answers_tx.clone();
-                        handles.insert(idx, tokio::spawn(async move {
+                answer = answers.recv() => if let Some((idx, demon, fatal, panicked, followup)) = answer {
+                    // The follow-up keeps working while the replica takes its next message
+                    if let Some(followup) = followup {
+                        self.context.spawn_linked(followup);
+                    }
+                    // A waiting replacement takes the place of the replica, even after a fatal reply or a panic
+                    let (demon, fatal, panicked) = match replacing.remove(&idx) {
+                        Some((replacement, tx)) => (MultipleMiniHell::replace(demon, panicked, replacement, tx, self.context.clone()).await, false, false),
+                        None => (demon, fatal, panicked)
+                    };
+                    // Scalable pools rebuild replicas that panicked, and other pools lose them
+                    let (demon, fatal, panicked) = match self.factory.as_mut() {
+                        Some(factory) if panicked => {
                             #[cfg(feature = "full_log")]
-                            log::debug!("[{}] calling handle function", demon.id());
-                            let output = demon.handle(request).await;
+                            log::warn!("[{}] replica {} panicked, replacing it with a new one", <D as Demon>::multiple_id(), idx);
+                            let mut rebuilt = AbortGuard::new(factory(idx));
+                            drop(demon.into_inner());
+                            rebuilt.spawned_with_context(self.context.clone()).await;
+                            (rebuilt, false, false)
+                        },
+                        _ => (demon, fatal, panicked)
+                    };
+                    if fatal {
+                        // Replicas pending removal are covered by this one
+                        handles.remove(&idx);
+                        if retiring > 0 {
+                            retiring -= 1;
+                        } else {
+                            self.replicas -= 1;
+                        }
+                        if panicked {
                             #[cfg(feature = "full_log")]
-                            log::debug!("[{}] handle function called", demon.id());
-                            
-                            // We first send the reply
-                            if tx.send(Ok(Box::new(output))).is_err() {
-                                #[cfg(feature = "full_log")]
-                                log::error!("[{}] demon processed message could not be sent back", demon.id());
-                            }
-
-                            // Now the demon back
+                            log::warn!("[{}] replica {} panicked and leaves the pool. remaining replicas: {}", <D as Demon>::multiple_id(), idx, self.replicas);
+                            // Its state cannot be trusted, so it is neither vanquished nor aborted
+                            drop(demon.into_inner());
+                        } else {
                             #[cfg(feature = "full_log")]
-                            let demon_id = demon.id();
-                            if answers_tx_clone.send((idx, demon)).is_err() {
-                                #[cfg(feature = "full_log")]
-                                log::error!("[{}] demon could not be sent back", demon_id);
+                            log::debug!("[{}] fatal reply, replica leaves on its own. remaining replicas: {}", <D as Demon>::multiple_id(), self.replicas);
+                            MultipleMiniHell::retire(demon, self.context.clone(), ShutdownReason::Fatal).await;
+                        }
+                        if self.replicas == 0 {
+                            let mut rejected = 0;
+                            for (request_tx, _, _) in requests.drain(..).chain(keyed.drain().into_iter().map(|(_key, request)| request)) {
+                                let _ = request_tx.send(Err(Error::InvalidLocation));
+                                rejected += 1;
+                            }
+                            self.queue.set(0);
+                            let (rejected_pending, shutdown) = reject_pending(&mut messages, &mut self.instructions);
+                            self.context.take_many_from_mailbox(rejected + rejected_pending);
+                            break shutdown;
+                        }
+                        // The keys of the replica now belong to the remaining ones
+                        for (key, request) in keyed.remove(idx) {
+                            if let Err((idx, request)) = self.route(key, request, &mut handles, &taps, &answers_tx) {
+                                keyed.push(idx, key, request);
                             }
-                        }));
+                        }
+                        self.queue.set(requests.len() + keyed.len());
+                    } else if retiring > 0 {
+                        // The pool was scaled down while this replica was busy
+                        retiring -= 1;
+                        handles.remove(&idx);
+                        MultipleMiniHell::retire(demon, self.context.clone(), ShutdownReason::Retired).await;
+                        for (key, request) in keyed.remove(idx) {
+                            if let Err((idx, request)) = self.route(key, request, &mut handles, &taps, &answers_tx) {
+                                keyed.push(idx, key, request);
+                            }
+                        }
+                        self.queue.set(requests.len() + keyed.len());
+                    } else if let Some(request) = keyed.pop(idx).or_else(|| requests.pop_front()) {
+                        // Messages for the key of this replica go first, then the ones for any replica
+                        self.queue.set(requests.len() + keyed.len());
+                        self.context.take_from_mailbox();
+                        handles.insert(idx, MultipleMiniHell::dispatch(idx, demon, request, taps.clone(), self.context.handle_time.clone(), answers_tx.clone()));
                     } else {
                         handles.remove(&idx);
                         self.demons.push_back((idx, demon));
@@ -112,43 +454,58 @@ impl<I: 'static + Send, O: 'static + Send, D: 'static + Demon<Input = I, Output
                     log::debug!("[{}] all incoming killswitch channels closed (impossible)", <D as Demon>::multiple_id());
                     break None;
                 },
-                res = messages.recv() => if let Some((tx, input)) = res {
-                    if let Ok(input) = input.downcast::<I>() {
-                        if let Some((idx, mut demon)) = self.demons.pop_front() {
-                            #[cfg(feature = "full_log")]
-                            log::debug!("[{}] available demon, sending to thread to process message. remaining demons: {}", demon.id(), self.demons.len());
-                            // We move the demon to a thread
-                            let answers_tx_clone = answers_tx.clone();
-                            handles.insert(idx.clone(), tokio::spawn(async move {
+                // When waiting on overflow, messages are only taken while there is room for them
+                res = messages.recv(), if self.accepts_messages(requests.len() + keyed.len()) => if let Some((tx, trace, input)) = res {
+                    // Keyed messages carry the input inside
+                    let input = match input.downcast::<Keyed>() {
+                        Ok(keyed_input) => keyed_input.input.downcast::<I>().map(|input| (Some(keyed_input.key), input)),
+                        Err(input) => input.downcast::<I>().map(|input| (None, input))
+                    };
+                    if let Ok((Some(key), input)) = input {
+                        if let Err((idx, request)) = self.route(key, (tx, trace, *input), &mut handles, &taps, &answers_tx) {
+                            if self.overflows(requests.len() + keyed.len()) {
+                                self.context.take_from_mailbox();
                                 #[cfg(feature = "full_log")]
-                                log::debug!("[{}] calling handle function", demon.id());
-                                let output = demon.handle(*input).await;
-                                #[cfg(feature = "full_log")]
-                                log::debug!("[{}] handle function called", demon.id());
-
-                                // We first send the reply
-                                if tx.send(Ok(Box::new(output))).is_err() {
+                                log::warn!("[{}] replica {} is busy and the inner queue is full, rejecting keyed message", <D as Demon>::multiple_id(), idx);
+                                if request.0.send(Err(Error::Overloaded)).is_err() {
                                     #[cfg(feature = "full_log")]
-                                    log::error!("[{}] demon processed message could not be sent back", demon.id());
+                                    log::error!("[{}] overload error could not be sent back", <D as Demon>::multiple_id());
                                 }
-
-                                // Now the demon back
+                            } else {
                                 #[cfg(feature = "full_log")]
-                                let demon_id = demon.id();
-                                if answers_tx_clone.send((idx, demon)).is_err() {
-                                    #[cfg(feature = "full_log")]
-                                    log::error!("[{}] demon could not be sent back for reuse", demon_id);
-                                }
-                            }));
+                                log::debug!("[{}] replica {} is busy, puting keyed message in its queue", <D as Demon>::multiple_id(), idx);
+                                keyed.push(idx, key, request);
+                                self.queue.set(requests.len() + keyed.len());
+                            }
+                        }
+                    } else if let Ok((None, input)) = input {
+                        if let Some((idx, demon)) = self.demons.pop_front() {
+                            self.context.take_from_mailbox();
+                            #[cfg(feature = "full_log")]
+                            log::debug!("[{}] available demon, sending to thread to process message. remaining demons: {}", demon.id(), self.demons.len());
+                            // We move the demon to a thread
+                            handles.insert(idx, MultipleMiniHell::dispatch(idx, demon, (tx, trace, *input), taps.clone(), self.context.handle_time.clone(), answers_tx.clone()));
+                        } else if self.overflows(requests.len() + keyed.len()) {
+                            self.context.take_from_mailbox();
+                            #[cfg(feature = "full_log")]
+                            log::warn!("[{}] all demons are busy and the inner queue is full, rejecting message", <D as Demon>::multiple_id());
+                            if tx.send(Err(Error::Overloaded)).is_err() {
+                                #[cfg(feature = "full_log")]
+                                log::error!("[{}] overload error could not be sent back", <D as Demon>::multiple_id());
+                            }
                         } else {
                             #[cfg(feature = "full_log")]
-                            log::debug!("[{}] all demons are busy, puting message in inner queue. Total pending messages: {}", <D as Demon>::multiple_id(), requests.len() + 1);
-                            requests.push_back((tx, *input));
+                            log::debug!("[{}] all demons are busy, puting message in inner queue. Total pending messages: {}", <D as Demon>::multiple_id(), requests.len() + keyed.len() + 1);
+                            requests.push_back((tx, trace, *input));
+                            self.queue.set(requests.len() + keyed.len());
                         }
                     } else {
-                        if tx.send(Err(Error::WrongType)).is_err() {
+                        self.context.take_from_mailbox();
+                        #[cfg(feature = "full_log")]
+                        log::error!("[{}] demon received wrong message type, expected {}", <D as Demon>::multiple_id(), std::any::type_name::<I>());
+                        if tx.send(Err(Error::WrongInputType{expected: std::any::type_name::<I>()})).is_err() {
                             #[cfg(feature = "full_log")]
-                            log::error!("[{}] somehow, demon received wrong message type", <D as Demon>::multiple_id());   
+                            log::error!("[{}] wrong message type error could not be sent back", <D as Demon>::multiple_id());
                         }
                     }
                 } else {
@@ -156,20 +513,109 @@ impl<I: 'static + Send, O: 'static + Send, D: 'static + Demon<Input = I, Output
                     log::debug!("[{}] all incoming channels closed (impossible)", <D as Demon>::multiple_id());
                     break None;
                 },
+                res = self.scale.recv(), if self.factory.is_some() => if let Some(delta) = res {
+                    if delta >= 0 {
+                        // Replicas about to be removed are kept instead of creating new ones
+                        let kept = retiring.min(delta as usize);
+                        retiring -= kept;
+                        self.replicas += kept;
+                        for _ in kept..(delta as usize) {
+                            let idx = self.next_index;
+                            let mut demon = match self.factory.as_mut() {
+                                Some(factory) => AbortGuard::new(factory(idx)),
+                                None => break
+                            };
+                            self.next_index += 1;
+                            self.replicas += 1;
+                            demon.spawned_with_context(self.context.clone()).await;
+                            if let Some(request) = requests.pop_front() {
+                                self.queue.set(requests.len() + keyed.len());
+                                self.context.take_from_mailbox();
+                                handles.insert(idx, MultipleMiniHell::dispatch(idx, demon, request, taps.clone(), self.context.handle_time.clone(), answers_tx.clone()));
+                            } else {
+                                self.demons.push_back((idx, demon));
+                            }
+                        }
+                    } else {
+                        // At least one replica always remains
+                        let removed = (delta.unsigned_abs() as usize).min(self.replicas - 1);
+                        self.replicas -= removed;
+                        for _ in 0..removed {
+                            if let Some((_, demon)) = self.demons.pop_back() {
+                                MultipleMiniHell::retire(demon, self.context.clone(), ShutdownReason::Retired).await;
+                            } else {
+                                retiring += 1;
+                            }
+                        }
+                    }
+                    #[cfg(feature = "full_log")]
+                    log::debug!("[{}] pool scaled by {}, now with {} replicas", <D as Demon>::multiple_id(), delta, self.replicas);
+                } else {
+                    #[cfg(feature = "full_log")]
+                    log::debug!("[{}] all incoming scale channels closed (impossible)", <D as Demon>::multiple_id());
+                    break None;
+                },
                 res = self.instructions.recv() => match res {
                     Some(instruction) => match instruction {
-                        MiniHellInstruction::Shutdown(vanquish_mailbox) => {
+                        MiniHellInstruction::Shutdown(vanquish_mailbox, shutdown_reason) => {
+                            reason = shutdown_reason;
                             #[cfg(feature = "full_log")]
                             log::debug!("[{}] shutdown signal received", <D as Demon>::multiple_id());
                             break Some(vanquish_mailbox);
                         },
-                        MiniHellInstruction::Message(result_mailbox, message) => {
+                        MiniHellInstruction::Message(result_mailbox, trace, message) => {
                             #[cfg(feature = "full_log")]
                             log::debug!("[{}] received instruction, adding to the processing queue", <D as Demon>::multiple_id());
-                            if mailbox.send((result_mailbox, message)).is_err() {
+                            if mailbox.send((result_mailbox, trace, message)).is_err() {
                                 #[cfg(feature = "full_log")]
                                 log::warn!("[{}] impossible error happened, could not send back message to itself!", <D as Demon>::multiple_id());   
                             }
+                        },
+                        MiniHellInstruction::CancelPending(tx) => {
+                            // Both the messages waiting for a free replica, and the ones not even looked at
+                            let mut cancelled = cancel_pending(&mut messages);
+                            for (request_tx, _, _) in requests.drain(..).chain(keyed.drain().into_iter().map(|(_key, request)| request)) {
+                                let _ = request_tx.send(Err(Error::Cancelled));
+                                cancelled += 1;
+                            }
+                            self.queue.set(0);
+                            self.context.take_many_from_mailbox(cancelled);
+                            #[cfg(feature = "full_log")]
+                            log::debug!("[{}] cancelled {} pending messages", <D as Demon>::multiple_id(), cancelled);
+                            let _ = tx.send(cancelled);
+                        },
+                        MiniHellInstruction::Ping(tx) => {
+                            #[cfg(feature = "full_log")]
+                            log::debug!("[{}] ping received", <D as Demon>::multiple_id());
+                            let _ = tx.send(());
+                        },
+                        // Only single demons are handed off, the broker never asks anyone else
+                        MiniHellInstruction::Handoff(_) => (),
+                        MiniHellInstruction::Tap(observer, tx) => {
+                            #[cfg(feature = "full_log")]
+                            log::debug!("[{}] tap received", <D as Demon>::multiple_id());
+                            let _ = tx.send(taps.install(observer));
+                        },
+                        MiniHellInstruction::RestartReplica(idx, replacement, tx) => match replacement.downcast::<D>() {
+                            Ok(replacement) => if let Some((idx, demon)) = self.take_idle(idx) {
+                                #[cfg(feature = "full_log")]
+                                log::debug!("[{}] restarting idle replica {}", <D as Demon>::multiple_id(), idx);
+                                // With an idle replica around, no message is waiting
+                                let replacement = MultipleMiniHell::replace(demon, false, *replacement, tx, self.context.clone()).await;
+                                self.demons.push_back((idx, replacement));
+                            } else if handles.contains_key(&idx) {
+                                #[cfg(feature = "full_log")]
+                                log::debug!("[{}] replica {} is busy, restarting it once it finishes", <D as Demon>::multiple_id(), idx);
+                                // Only the latest replacement is kept
+                                if let Some((_, tx)) = replacing.insert(idx, (*replacement, tx)) {
+                                    let _ = tx.send(Err(Error::Cancelled));
+                                }
+                            } else {
+                                let _ = tx.send(Err(Error::InvalidLocation));
+                            },
+                            Err(_) => {
+                                let _ = tx.send(Err(Error::WrongType));
+                            }
                         }
                     },
                     None => {
@@ -181,13 +627,30 @@ impl<I: 'static + Send, O: 'static + Send, D: 'static + Demon<Input = I, Output
             }
         };
 
+        // Messages still waiting never reach a replica
+        let mut rejected = reject_pending(&mut messages, &mut self.instructions).0;
+        for (request_tx, _, _) in requests.drain(..).chain(keyed.drain().into_iter().map(|(_key, request)| request)) {
+            let _ = request_tx.send(Err(Error::InvalidLocation));
+            rejected += 1;
+        }
+        self.queue.set(0);
+        self.context.take_many_from_mailbox(rejected);
+
+        // Tasks linked to this demon cannot outlive it
+        self.context.abort_linked();
+
+        // Replacements never got to be spawned
+        for (_, (_replacement, tx)) in replacing {
+            let _ = tx.send(Err(Error::InvalidLocation));
+        }
+
         // We call the vanquished function from this demon
         for (_, demon) in self.demons {
             #[cfg(feature = "full_log")]
             let demon_id = demon.id();
             #[cfg(feature = "full_log")]
             log::debug!("[{}] calling vanquish function", demon_id);
-            demon.vanquished().await;
+            demon.into_inner().vanquished_with_reason(self.context.clone(), reason).await;
             #[cfg(feature = "full_log")]
             log::debug!("[{}] vanquish function called", demon_id);
         }
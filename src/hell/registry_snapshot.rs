@@ -0,0 +1,47 @@
+use chrono::{DateTime, Utc};
+#[cfg(feature = "serde")]
+use serde::{Serialize, Deserialize};
+
+/// State of all the demons in hell at a given moment, for diagnostics
+///
+/// The snapshot is assembled by the broker in a single step, so it is consistent. With the `serde` feature it can be serialized, for example to attach it to a bug report.
+#[derive(Clone, Debug)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct RegistrySnapshot {
+    /// Name of the hell instance
+    pub name: String,
+    /// Time at which the snapshot was taken
+    pub taken_at: DateTime<Utc>,
+    /// All the demons in hell, sorted by address
+    pub demons: Vec<DemonSnapshot>
+}
+
+/// State of a single demon, see [RegistrySnapshot](RegistrySnapshot)
+#[derive(Clone, Debug)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct DemonSnapshot {
+    /// Address of the demon
+    pub address: usize,
+    /// Number of handoffs that happened at the address, see [generation](crate::Gate::generation)
+    pub generation: usize,
+    /// Unique name of the demon, see [try_spawn_named](crate::Gate::try_spawn_named)
+    pub name: Option<String>,
+    /// [Type tag](crate::Demon::TYPE_TAG) of the demon
+    pub tag: String,
+    /// Name of the input type of the demon
+    pub input: String,
+    /// Name of the output type of the demon
+    pub output: String,
+    /// Total number of messages delivered to the demon
+    pub delivered_messages: usize,
+    /// Amount of messages waiting to be handled
+    pub mailbox: usize,
+    /// Amount of messages waiting for a free replica, only for demons spawned with replicas
+    pub queued_messages: Option<usize>,
+    /// Address of the parent demon, if any
+    pub parent: Option<usize>,
+    /// Addresses of the children, in spawn order
+    pub children: Vec<usize>,
+    /// Topics that the demon is subscribed to, sorted
+    pub topics: Vec<String>
+}
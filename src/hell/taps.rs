@@ -0,0 +1,43 @@
+use std::any::Any;
+use std::sync::Arc;
+use crate::Error;
+
+/// Observer of the outputs of a demon
+pub(crate) type Observer<O> = Arc<dyn Fn(&O) + Send + Sync>;
+
+/// Observers installed in a mini hell, called with each output before it is sent back
+///
+/// Cloning is cheap, so that demons spawned with replicas can take the observers to the task that handles each message.
+pub(crate) struct Taps<O> {
+    observers: Arc<Vec<Observer<O>>>
+}
+
+impl<O: 'static> Taps<O> {
+    pub(crate) fn new() -> Taps<O> {
+        Taps {
+            observers: Arc::new(Vec::new())
+        }
+    }
+
+    /// Installs a type erased observer, which fails only if it does not observe outputs of type `O`
+    pub(crate) fn install(&mut self, observer: Box<dyn Any + Send>) -> Result<(), Error> {
+        let observer = observer.downcast::<Observer<O>>().map_err(|_| Error::WrongType)?;
+        Arc::make_mut(&mut self.observers).push(*observer);
+        Ok(())
+    }
+
+    /// Calls all the observers with the output, in installation order
+    pub(crate) fn observe(&self, output: &O) {
+        for observer in self.observers.iter() {
+            observer(output);
+        }
+    }
+}
+
+impl<O> Clone for Taps<O> {
+    fn clone(&self) -> Taps<O> {
+        Taps {
+            observers: self.observers.clone()
+        }
+    }
+}
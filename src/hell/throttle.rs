@@ -0,0 +1,86 @@
+use std::collections::VecDeque;
+use tokio::time::{Duration, Instant};
+use super::HellInstruction;
+
+/// Limit for the rate at which the broker delivers messages, for the whole hell
+///
+/// The limit is a token bucket that holds up to one second worth of tokens, and starts full. Messages that find the bucket empty are parked, in arrival order, and released by the broker as tokens become available. Other instructions are never held back, except the ones that wait for the delivered messages to be handled.
+pub(crate) struct Throttle {
+    /// Tokens added per second, and maximum amount of tokens
+    rate: f64,
+    /// Tokens currently available
+    tokens: f64,
+    /// Last time tokens were added
+    refilled: Instant,
+    /// Instructions waiting for a token
+    parked: VecDeque<HellInstruction>
+}
+
+impl Throttle {
+    pub(crate) fn new(per_second: u32) -> Throttle {
+        let rate = per_second.max(1) as f64;
+        Throttle {
+            rate,
+            tokens: rate,
+            refilled: Instant::now(),
+            parked: VecDeque::new()
+        }
+    }
+
+    /// Adds the tokens accumulated since the last refill
+    fn refill(&mut self) {
+        let now = Instant::now();
+        self.tokens = (self.tokens + now.duration_since(self.refilled).as_secs_f64() * self.rate).min(self.rate);
+        self.refilled = now;
+    }
+
+    /// Takes a token from the bucket, if there is any
+    fn take(&mut self) -> bool {
+        self.refill();
+        if self.tokens >= 1.0 {
+            self.tokens -= 1.0;
+            true
+        } else {
+            false
+        }
+    }
+
+    /// Gives the instruction back if it can be processed right away, and parks it otherwise
+    pub(crate) fn admit(&mut self, instruction: HellInstruction) -> Option<HellInstruction> {
+        match instruction {
+            // Parked messages go first, so that the delivery order is kept
+            HellInstruction::Message{..} if self.parked.is_empty() && self.take() => Some(instruction),
+            HellInstruction::Message{..} => {
+                self.parked.push_back(instruction);
+                None
+            },
+            // Waiting for idleness includes the parked messages
+            HellInstruction::InFlight{..} if !self.parked.is_empty() => {
+                self.parked.push_back(instruction);
+                None
+            },
+            _ => Some(instruction)
+        }
+    }
+
+    /// Moment at which the next parked instruction can be released, if there is any
+    pub(crate) fn release_at(&self) -> Option<Instant> {
+        match self.parked.front()? {
+            HellInstruction::Message{..} => {
+                let missing = (1.0 - self.tokens).max(0.0);
+                Some(self.refilled + Duration::from_secs_f64(missing / self.rate))
+            },
+            _ => Some(self.refilled)
+        }
+    }
+
+    /// Takes the next parked instruction, if it can be released already
+    pub(crate) fn release(&mut self) -> Option<HellInstruction> {
+        let is_message = matches!(self.parked.front()?, HellInstruction::Message{..});
+        if is_message && !self.take() {
+            None
+        } else {
+            self.parked.pop_front()
+        }
+    }
+}
@@ -18,7 +18,7 @@
 //!         format!("Hey, {} here: {}", self.name, &message)
 //!     }
 //! }
-//! 
+//!
 //! #[tokio::main]
 //! async fn main() {
 //!     // We create one demon
@@ -53,12 +53,26 @@
 //! }
 //! ```
 
-pub use self::demon::{Demon, Location};
-pub use self::hell::{Hell, HellBuilder, HellStats};
-pub use self::gate::{Gate};
+pub use self::demon::{Demon, DemonContext, Location, DemonMap, RequestResponseDemon, BlockingDemon, Blocking, MessageSize, HandlerOutcome, RateLimited, RateLimitError, Broadcaster, BroadcasterInput, IoThread, ShutdownReason};
+#[cfg(feature = "ws")]
+pub use self::demon::{WebSocketStreamThread, WebSocketFrames, WebSocketReconnect};
+pub use self::hell::{Hell, HellBuilder, HellStats, RegistrySnapshot, DemonSnapshot, ExtinguishReport, BrokerContext, InterceptDecision};
+pub use self::gate::{Gate, WeakGate, SpawnOptions, MessageOrder, OverflowPolicy, RetryPolicy, MultiSpawnResult, SendFuture, ReplyReceiver, CancellationToken, WiringBuilder, Wire};
+#[cfg(feature = "ws")]
+pub use self::gate::{WsOptions, WsPriority, ReconnectPolicy};
+#[cfg(feature = "remote")]
+pub use self::demon::RemoteDemon;
+#[cfg(feature = "remote")]
+pub use self::gate::{RemoteGate, Envelope};
 pub use self::error::Error;
+pub use self::trace::current_trace;
+#[cfg(feature = "record")]
+pub use self::demon::replay;
+pub use self::clock::{Clock, SystemClock, MockClock};
 
 mod demon;
 mod hell;
 mod gate;
-mod error;
\ No newline at end of file
+mod error;
+mod trace;
+mod clock;
\ No newline at end of file
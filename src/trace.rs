@@ -0,0 +1,85 @@
+use crate::Error;
+use std::future::Future;
+use std::sync::atomic::{AtomicU64, Ordering};
+
+tokio::task_local! {
+    /// Trace id of the message that the current task is handling
+    static TRACE: u64;
+    /// Address of the demon that cannot take more messages until the current task finishes its handle function
+    static HANDLING: usize;
+}
+
+/// Source of trace ids for messages sent from outside of any demon
+static NEXT_TRACE: AtomicU64 = AtomicU64::new(1);
+
+/// Trace id of the message being handled
+///
+/// Every message gets a trace id when it is sent. Messages sent from within the [handle](crate::Demon::handle) function of a demon inherit the trace id of the message being handled, so that a whole chain of requests shares the same id. With the `full_log` feature, the trace id is part of the logs of the message delivery and handling. Outside of a handle function, `None` is returned.
+///
+/// ```rust
+/// use apocalypse::{Hell, Demon, Gate, Location};
+///
+/// struct Tracer;
+///
+/// impl Demon for Tracer {
+///     type Input = ();
+///     type Output = Option<u64>;
+///     async fn handle(&mut self, _message: Self::Input) -> Self::Output {
+///         apocalypse::current_trace()
+///     }
+/// }
+///
+/// struct Forwarder {
+///     gate: Gate,
+///     tracer: Location<Tracer>
+/// }
+///
+/// impl Demon for Forwarder {
+///     type Input = ();
+///     type Output = (Option<u64>, Option<u64>);
+///     async fn handle(&mut self, message: Self::Input) -> Self::Output {
+///         let inner = self.gate.send(&self.tracer, message).await.unwrap();
+///         (apocalypse::current_trace(), inner)
+///     }
+/// }
+///
+/// # #[tokio::main]
+/// # async fn main() {
+/// let (gate, join_handle) = Hell::new().ignite().await.unwrap();
+/// let tracer = gate.spawn(Tracer).await.unwrap();
+/// let forwarder = gate.spawn(Forwarder{gate: gate.clone(), tracer}).await.unwrap();
+/// assert_eq!(None, apocalypse::current_trace());
+/// let (outer, inner) = gate.send(&forwarder, ()).await.unwrap();
+/// assert!(outer.is_some());
+/// assert_eq!(outer, inner);
+/// // Each top-level send starts a new trace
+/// let (next_outer, _) = gate.send(&forwarder, ()).await.unwrap();
+/// assert_ne!(outer, next_outer);
+/// # }
+/// ```
+pub fn current_trace() -> Option<u64> {
+    TRACE.try_with(|trace| *trace).ok()
+}
+
+/// Trace id for a new message, inherited from the message being handled, if any
+pub(crate) fn inherit_or_new() -> u64 {
+    current_trace().unwrap_or_else(|| NEXT_TRACE.fetch_add(1, Ordering::Relaxed))
+}
+
+/// Runs the handling of a message with its trace id
+pub(crate) fn scope<F: Future>(trace: u64, f: F) -> impl Future<Output = F::Output> {
+    TRACE.scope(trace, f)
+}
+
+/// Runs the handling of a message by a demon that takes no other message meanwhile
+pub(crate) fn handling<F: Future>(address: usize, f: F) -> impl Future<Output = F::Output> {
+    HANDLING.scope(address, f)
+}
+
+/// Rejects requests that would wait for the demon whose handle function is running in this task
+pub(crate) fn check_self_send(address: usize) -> Result<(), Error> {
+    match HANDLING.try_with(|handling| *handling) {
+        Ok(handling) if handling == address => Err(Error::SelfSend),
+        _ => Ok(())
+    }
+}
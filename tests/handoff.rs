@@ -0,0 +1,39 @@
+#![cfg(feature = "ws")]
+use apocalypse::{Hell, Demon, Error};
+use cataclysm::ws::{WebSocketThread, WebSocketReader, Message};
+use tokio::net::{TcpListener, TcpStream};
+
+// Demon that only tells which one it is
+struct Listener {
+    version: usize
+}
+
+impl Demon for Listener {
+    type Input = ();
+    type Output = usize;
+    async fn handle(&mut self, _message: Self::Input) -> Self::Output {
+        self.version
+    }
+}
+
+impl WebSocketThread for Listener {
+    type Output = ();
+    async fn on_message(&mut self, _message: Message) {}
+    async fn on_close(&mut self, _clean: bool) -> Self::Output {}
+}
+
+#[tokio::test]
+async fn handoff_rejects_websocket_demons() {
+    let (gate, _join_handle) = Hell::new().ignite().await.unwrap();
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    // The client side stays open, so that the demon keeps its connection
+    let _client = TcpStream::connect(listener.local_addr().unwrap()).await.unwrap();
+    let (tcp_stream, _) = listener.accept().await.unwrap();
+    let (read_stream, _write_stream) = tcp_stream.into_split();
+    let location = gate.spawn_ws(Listener{version: 1}, WebSocketReader::new_unchecked(read_stream)).await.unwrap();
+
+    assert!(matches!(gate.handoff(&location, Listener{version: 2}).await, Err(Error::HandoffUnsupported)));
+    // The websocket demon keeps its address, and its connection
+    assert_eq!(1, gate.send(&location, ()).await.unwrap());
+    assert_eq!(0, gate.generation(&location).await.unwrap());
+}
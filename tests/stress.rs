@@ -0,0 +1,134 @@
+use apocalypse::{Hell, Demon, Gate, Location, Error};
+use std::collections::hash_map::RandomState;
+use std::hash::{BuildHasher, Hasher};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+// Demon that answers right away, or after a short wait, depending on the message
+struct Worker;
+
+impl Demon for Worker {
+    type Input = u64;
+    type Output = u64;
+    async fn handle(&mut self, message: Self::Input) -> Self::Output {
+        if message % 50 == 0 {
+            tokio::time::sleep(Duration::from_millis(5)).await;
+        } else if message % 4 == 0 {
+            tokio::task::yield_now().await;
+        }
+        message
+    }
+}
+
+// Small generator, so that every task follows its own interleaving
+struct Dice(u64);
+
+impl Dice {
+    fn new() -> Dice {
+        let mut hasher = RandomState::new().build_hasher();
+        hasher.write_u64(0);
+        Dice(hasher.finish() | 1)
+    }
+
+    fn roll(&mut self, sides: u64) -> u64 {
+        // xorshift64
+        self.0 ^= self.0 << 13;
+        self.0 ^= self.0 >> 7;
+        self.0 ^= self.0 << 17;
+        self.0 % sides
+    }
+}
+
+// Errors that a send can give when the demon leaves around the same time
+fn expected(error: &Error) -> bool {
+    matches!(error, Error::InvalidLocation | Error::DemonCommunication)
+}
+
+async fn churn(gate: Gate, shared: Arc<Mutex<Vec<Location<Worker>>>>, iterations: usize) {
+    let mut dice = Dice::new();
+    let mut own = Vec::new();
+    for _ in 0..iterations {
+        match dice.roll(6) {
+            0 | 1 => {
+                let location = if dice.roll(4) == 0 {
+                    gate.spawn_multiple(|| Worker, 2).await.unwrap()
+                } else {
+                    gate.spawn(Worker).await.unwrap()
+                };
+                shared.lock().unwrap().push(location.clone());
+                own.push(location);
+            },
+            2 | 3 => {
+                // Demons of other tasks might be leaving at the same time
+                let location = {
+                    let shared = shared.lock().unwrap();
+                    if shared.is_empty() {
+                        continue;
+                    }
+                    shared[dice.roll(shared.len() as u64) as usize].clone()
+                };
+                let message = dice.roll(1000);
+                match gate.send(&location, message).await {
+                    Ok(reply) => assert_eq!(message, reply),
+                    Err(e) => assert!(expected(&e), "unexpected send error {:?}", e)
+                }
+            },
+            4 => {
+                let location = {
+                    let shared = shared.lock().unwrap();
+                    if shared.is_empty() {
+                        continue;
+                    }
+                    shared[dice.roll(shared.len() as u64) as usize].clone()
+                };
+                if let Err(e) = gate.send_and_ignore(&location, dice.roll(1000)).await {
+                    assert!(expected(&e), "unexpected send_and_ignore error {:?}", e);
+                }
+            },
+            _ => {
+                if own.is_empty() {
+                    continue;
+                }
+                let location = own.swap_remove(dice.roll(own.len() as u64) as usize);
+                shared.lock().unwrap().retain(|shared| shared != &location);
+                match dice.roll(3) {
+                    0 => gate.vanquish(&location).await.unwrap(),
+                    // Slow messages get cut short by the killswitch
+                    1 => gate.vanquish_with_timeout(&location, Some(Duration::from_millis(1))).await.unwrap(),
+                    _ => gate.vanquish_and_ignore(&location).await.unwrap()
+                }
+                // The demon is gone, or about to be
+                if let Err(e) = gate.send(&location, 0).await {
+                    assert!(expected(&e), "unexpected error after vanquish {:?}", e);
+                }
+            }
+        }
+    }
+    for location in own {
+        shared.lock().unwrap().retain(|shared| shared != &location);
+        gate.vanquish(&location).await.unwrap();
+    }
+}
+
+async fn stress(tasks: usize, iterations: usize) {
+    let (gate, join_handle) = Hell::new().ignite().await.unwrap();
+    let shared = Arc::new(Mutex::new(Vec::new()));
+    let handles: Vec<_> = (0..tasks).map(|_| tokio::spawn(churn(gate.clone(), shared.clone(), iterations))).collect();
+    for handle in handles {
+        handle.await.unwrap();
+    }
+    let stats = gate.stats().await.unwrap();
+    assert_eq!(0, stats.active_demons);
+    gate.extinguish().await.unwrap();
+    join_handle.await.unwrap();
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+async fn concurrent_spawn_send_vanquish() {
+    tokio::time::timeout(Duration::from_secs(60), stress(32, 500)).await.expect("hell hung under load");
+}
+
+#[tokio::test(flavor = "current_thread")]
+async fn concurrent_spawn_send_vanquish_single_thread() {
+    tokio::time::timeout(Duration::from_secs(60), stress(16, 500)).await.expect("hell hung under load");
+}